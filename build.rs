@@ -0,0 +1,27 @@
+use std::path::Path;
+use std::process::Command;
+
+// The commit built, for `clouddns_build_info`: `CLOUDDNS_GIT_SHA` when set, e.g. by an image build
+// without the repository, otherwise what git says. Builds outside a checkout get "unknown".
+fn main() {
+    println!("cargo:rerun-if-env-changed=CLOUDDNS_GIT_SHA");
+    for path in [".git/HEAD", ".git/refs/heads", ".git/packed-refs"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    let sha = std::env::var("CLOUDDNS_GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| {
+            Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| String::from_utf8(output.stdout).ok())
+                .map(|sha| sha.trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=CLOUDDNS_GIT_SHA={}", sha);
+}
@@ -0,0 +1,33 @@
+/* C interface to clouddns, built with
+ *   cargo rustc --release --lib --features ffi --crate-type cdylib
+ * and linked with -lclouddns. Calls block until done. */
+
+#ifndef CLOUDDNS_H
+#define CLOUDDNS_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct clouddns clouddns;
+
+/* Loads the configuration file and reads the API token. state_file may be NULL to keep the state
+ * in memory only. Returns NULL on failure, see clouddns_last_error(). */
+clouddns *clouddns_new(const char *config_file, const char *state_file);
+
+/* One detection and update pass, as `clouddns once`. Returns 0 on success, otherwise the exit code
+ * `clouddns once` would have, e.g. 4 when the token was rejected. */
+int clouddns_update_once(clouddns *ddns);
+
+/* Releases an updater, NULL is ignored. */
+void clouddns_free(clouddns *ddns);
+
+/* The message of the last call that failed on this thread, or NULL. It stays valid until the next
+ * failing call on the same thread. */
+const char *clouddns_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
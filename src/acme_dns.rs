@@ -0,0 +1,361 @@
+// The acme-dns API (https://github.com/joohoi/acme-dns): `POST /register` hands out an account
+// and a subdomain, `POST /update` puts a DNS-01 challenge token there as a TXT record. The records
+// are written through the configured provider, the DNS itself staying with it. Bodies are JSON
+// whatever their content type, clients not always giving one.
+
+use crate::admin::constant_time_eq;
+use crate::api::{DnsApiClient, RecordContent};
+use crate::config::{names, AcmeDnsConfig};
+use anyhow::{Context, Result};
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use ipnet::IpNet;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+    fs,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{net::TcpListener, sync::Mutex, task::JoinHandle};
+
+// Challenge tokens are base64url SHA-256 digests, always this long
+const TXT_LENGTH: usize = 43;
+
+// As short as Cloudflare allows, challenges being short-lived
+const TXT_TTL: u32 = 60;
+
+// Like acme-dns, the last two tokens are kept, for certificates covering a name and its wildcard
+const TXT_KEPT: usize = 2;
+
+// Answers ACME clients, stopped when dropped
+pub(crate) struct AcmeDnsServer {
+    task: JoinHandle<()>,
+}
+
+struct AcmeDns {
+    client: Arc<dyn DnsApiClient + Send + Sync>,
+    zone_id: String,
+    domain: String,
+    register_from: Vec<IpNet>,
+    path: PathBuf,
+    // Held across the writes to the provider too, so updates don't interleave
+    accounts: Mutex<Vec<Account>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Account {
+    username: String,
+    // SHA-256 of the password, which is random and long enough not to need a slow hash
+    password_hash: String,
+    subdomain: String,
+    #[serde(default)]
+    allowfrom: Vec<String>,
+    // Oldest first
+    #[serde(default)]
+    txt: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct Registration {
+    #[serde(default)]
+    allowfrom: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Update {
+    subdomain: String,
+    txt: String,
+}
+
+impl AcmeDnsServer {
+    pub async fn start(
+        config: &AcmeDnsConfig,
+        zone_id: String,
+        path: PathBuf,
+        client: Arc<dyn DnsApiClient + Send + Sync>,
+    ) -> Result<Self> {
+        let accounts = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid acme-dns accounts file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let listener = TcpListener::bind(config.listen)
+            .await
+            .with_context(|| format!("Failed to listen on {} for acme-dns", config.listen))?;
+        info!(
+            "Serving the acme-dns API on http://{} for {}",
+            listener.local_addr()?,
+            config.domain
+        );
+
+        let acme_dns = Arc::new(AcmeDns {
+            client,
+            zone_id,
+            domain: names::normalize(&config.domain),
+            // Checked when the configuration was validated
+            register_from: config
+                .register_from
+                .iter()
+                .filter_map(|network| network.parse().ok())
+                .collect(),
+            path,
+            accounts: Mutex::new(accounts),
+        });
+        let app = Router::new()
+            .route("/register", post(register))
+            .route("/update", post(update))
+            .route("/health", get(|| async { StatusCode::OK }))
+            .with_state(acme_dns);
+        let task = tokio::spawn(async move {
+            let app = app.into_make_service_with_connect_info::<SocketAddr>();
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("acme-dns API stopped: {}", e);
+            }
+        });
+        Ok(Self { task })
+    }
+}
+
+impl Drop for AcmeDnsServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn register(
+    State(acme_dns): State<Arc<AcmeDns>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    body: Bytes,
+) -> Response {
+    let allowed = acme_dns.register_from.is_empty()
+        || acme_dns
+            .register_from
+            .iter()
+            .any(|network| network.contains(&peer.ip()));
+    if !allowed {
+        return failure(StatusCode::UNAUTHORIZED, "forbidden");
+    }
+    let registration = match body.iter().all(u8::is_ascii_whitespace) {
+        true => Registration::default(),
+        false => match serde_json::from_slice::<Registration>(&body) {
+            Ok(registration) => registration,
+            Err(_) => return failure(StatusCode::BAD_REQUEST, "malformed_json_payload"),
+        },
+    };
+    if registration
+        .allowfrom
+        .iter()
+        .any(|network| network.parse::<IpNet>().is_err())
+    {
+        return failure(StatusCode::BAD_REQUEST, "invalid_allowfrom_cidr");
+    }
+
+    let password = random_password();
+    let account = Account {
+        username: random_uuid(),
+        password_hash: hash(&password),
+        subdomain: random_uuid(),
+        allowfrom: registration.allowfrom,
+        txt: Vec::new(),
+    };
+    let mut accounts = acme_dns.accounts.lock().await;
+    accounts.push(account.clone());
+    if let Err(e) = save(&acme_dns.path, &accounts) {
+        accounts.pop();
+        error!("{:#}", e);
+        return failure(StatusCode::INTERNAL_SERVER_ERROR, "db_error");
+    }
+    info!(
+        "Registered acme-dns account {} from {}",
+        account.subdomain,
+        peer.ip()
+    );
+    let body = json!({
+        "username": account.username,
+        "password": password,
+        "fulldomain": acme_dns.fulldomain(&account.subdomain),
+        "subdomain": account.subdomain,
+        "allowfrom": account.allowfrom,
+    });
+    (StatusCode::CREATED, Json(body)).into_response()
+}
+
+async fn update(
+    State(acme_dns): State<Arc<AcmeDns>>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let Ok(update) = serde_json::from_slice::<Update>(&body) else {
+        return failure(StatusCode::BAD_REQUEST, "malformed_json_payload");
+    };
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let (username, password) = (header("X-Api-User"), header("X-Api-Key"));
+
+    let mut accounts = acme_dns.accounts.lock().await;
+    let Some(index) = accounts.iter().position(|account| {
+        account.username == username
+            && constant_time_eq(hash(&password).as_bytes(), account.password_hash.as_bytes())
+    }) else {
+        return failure(StatusCode::UNAUTHORIZED, "forbidden");
+    };
+    let account = &accounts[index];
+    let allowed = account.allowfrom.is_empty()
+        || account
+            .allowfrom
+            .iter()
+            .filter_map(|network| network.parse::<IpNet>().ok())
+            .any(|network| network.contains(&peer.ip()));
+    if !allowed {
+        return failure(StatusCode::UNAUTHORIZED, "forbidden");
+    }
+    if update.subdomain != account.subdomain {
+        return failure(StatusCode::UNAUTHORIZED, "forbidden");
+    }
+    if update.txt.len() != TXT_LENGTH {
+        return failure(StatusCode::BAD_REQUEST, "bad_txt");
+    }
+
+    let mut account = account.clone();
+    match acme_dns.put_txt(&mut account, &update.txt).await {
+        Ok(()) => {
+            accounts[index] = account;
+            if let Err(e) = save(&acme_dns.path, &accounts) {
+                warn!("{:#}", e);
+            }
+            (StatusCode::OK, Json(json!({ "txt": update.txt }))).into_response()
+        }
+        Err(e) => {
+            error!(
+                "Failed to put the challenge of {}: {:#}",
+                acme_dns.fulldomain(&account.subdomain),
+                e
+            );
+            failure(StatusCode::INTERNAL_SERVER_ERROR, "db_error")
+        }
+    }
+}
+
+impl AcmeDns {
+    fn fulldomain(&self, subdomain: &str) -> String {
+        format!("{}.{}", subdomain, self.domain)
+    }
+
+    // Creates the TXT record, then deletes those of tokens older than the last two
+    async fn put_txt(&self, account: &mut Account, txt: &str) -> Result<()> {
+        let name = self.fulldomain(&account.subdomain);
+        let content = RecordContent::Txt(txt.to_string());
+        self.client
+            .create_record(&self.zone_id, &name, &content, TXT_TTL, false)
+            .await?;
+        info!("Put a challenge token on {}", name);
+        account.txt.retain(|kept| kept != txt);
+        account.txt.push(txt.to_string());
+        if account.txt.len() <= TXT_KEPT {
+            return Ok(());
+        }
+
+        let kept = account.txt.split_off(account.txt.len() - TXT_KEPT);
+        let records = self.client.list_records(&self.zone_id).await?;
+        let stale = records.iter().filter(|record| {
+            record.r#type == "TXT"
+                && record.name == name
+                && !kept
+                    .iter()
+                    .any(|txt| txt == record.content.trim_matches('"'))
+        });
+        for record in stale {
+            if let Err(e) = self.client.delete_record(&self.zone_id, &record.id).await {
+                warn!("Failed to delete an old challenge of {}: {:#}", name, e);
+            }
+        }
+        account.txt = kept;
+        Ok(())
+    }
+}
+
+// Written whole and renamed over the old one, readable by its owner only
+fn save(path: &Path, accounts: &[Account]) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let temporary = path.with_extension("json.tmp");
+    let contents = serde_json::to_string_pretty(accounts)?;
+    #[cfg(unix)]
+    let written = {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(&temporary)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+    };
+    #[cfg(not(unix))]
+    let written = fs::write(&temporary, contents);
+    written.with_context(|| format!("Failed to write {}", temporary.display()))?;
+    fs::rename(&temporary, path)
+        .with_context(|| format!("Failed to write the acme-dns accounts {}", path.display()))
+}
+
+// Errors as acme-dns words them, which some clients look for
+fn failure(status: StatusCode, error: &str) -> Response {
+    (status, Json(json!({ "error": error }))).into_response()
+}
+
+fn hash(password: &str) -> String {
+    hex::encode(Sha256::digest(password.as_bytes()))
+}
+
+fn random_bytes<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    getrandom::getrandom(&mut bytes).expect("the system has no random number generator");
+    bytes
+}
+
+// A version 4 UUID
+fn random_uuid() -> String {
+    let mut bytes = random_bytes::<16>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+// 40 characters, like acme-dns's
+fn random_password() -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    random_bytes::<40>()
+        .iter()
+        .map(|byte| ALPHABET[usize::from(byte % 64)] as char)
+        .collect()
+}
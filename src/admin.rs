@@ -0,0 +1,191 @@
+use crate::ddns::Control;
+use crate::snapshot::{Report, Snapshot};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use log::{error, info};
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use std::net::{IpAddr, SocketAddr};
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc::UnboundedSender, watch},
+    task::JoinHandle,
+};
+
+// Lets tools check on and steer the daemon without signals, stopped when dropped
+pub struct AdminServer {
+    task: JoinHandle<()>,
+}
+
+/// The tokens requests may carry
+#[derive(Clone)]
+pub struct Tokens {
+    pub admin: SecretString,
+    // Only good for /trigger
+    pub trigger: Option<SecretString>,
+}
+
+#[derive(Clone)]
+struct Admin {
+    tokens: Tokens,
+    control: UnboundedSender<Control>,
+    snapshot: watch::Receiver<Snapshot>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TriggerQuery {
+    token: Option<String>,
+    // One address or two, one per family, separated by a comma
+    ip: Option<String>,
+}
+
+impl AdminServer {
+    pub async fn start(
+        listen: SocketAddr,
+        tokens: Tokens,
+        control: UnboundedSender<Control>,
+        snapshot: watch::Receiver<Snapshot>,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("Failed to listen on {} for the admin API", listen))?;
+        info!("Serving the admin API on http://{}", listener.local_addr()?);
+
+        let admin = Admin {
+            tokens,
+            control,
+            snapshot,
+        };
+        let app = Router::new()
+            .route("/status", get(status))
+            .route("/update", post(|state| request(state, Control::UpdateNow)))
+            .route(
+                "/force-update",
+                post(|state| request(state, Control::ForceUpdate)),
+            )
+            .route("/pause", post(|state| request(state, Control::Pause)))
+            .route("/resume", post(|state| request(state, Control::Resume)))
+            .route("/reload", post(|state| request(state, Control::Reload)))
+            .route_layer(middleware::from_fn_with_state(admin.clone(), authorize))
+            // Checks its own tokens, which may also come in the query
+            .route("/trigger", post(trigger))
+            .with_state(admin);
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Admin API stopped: {}", e);
+            }
+        });
+        Ok(Self { task })
+    }
+}
+
+impl Drop for AdminServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn authorize(
+    State(admin): State<Admin>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    match bearer(&headers) {
+        Some(token) if matches(token, &admin.tokens.admin) => next.run(request).await,
+        _ => unauthorized(),
+    }
+}
+
+fn bearer(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+}
+
+fn matches(token: &str, expected: &SecretString) -> bool {
+    constant_time_eq(token.as_bytes(), expected.expose_secret().as_bytes())
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Bearer")],
+        "unauthorized\n",
+    )
+        .into_response()
+}
+
+// Doesn't tell how much of a wrong token was right
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// Queued for the daemon, which picks it up between cycles
+async fn request(State(admin): State<Admin>, control: Control) -> (StatusCode, &'static str) {
+    match admin.control.send(control) {
+        Ok(()) => (StatusCode::ACCEPTED, "accepted\n"),
+        Err(_) => (StatusCode::SERVICE_UNAVAILABLE, "shutting down\n"),
+    }
+}
+
+// A check asked for from elsewhere, e.g. a router's ip-up script, optionally with the addresses
+// to use rather than detecting them
+async fn trigger(
+    State(admin): State<Admin>,
+    headers: HeaderMap,
+    Query(query): Query<TriggerQuery>,
+) -> Response {
+    let tokens = &admin.tokens;
+    let allowed = bearer(&headers)
+        .or(query.token.as_deref())
+        .is_some_and(|token| {
+            matches(token, &tokens.admin)
+                || tokens
+                    .trigger
+                    .as_ref()
+                    .is_some_and(|trigger| matches(token, trigger))
+        });
+    if !allowed {
+        return unauthorized();
+    }
+
+    let mut addresses: Vec<IpAddr> = Vec::new();
+    for address in query.ip.iter().flat_map(|ip| ip.split(',')) {
+        let Ok(address) = address.trim().parse::<IpAddr>() else {
+            let message = format!("invalid address {:?}\n", address.trim());
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        };
+        if addresses
+            .iter()
+            .any(|other| other.is_ipv4() == address.is_ipv4())
+        {
+            let message = "one address per family at most\n";
+            return (StatusCode::BAD_REQUEST, message).into_response();
+        }
+        addresses.push(address);
+    }
+    let requests = addresses
+        .into_iter()
+        .map(Control::PushAddress)
+        .chain([Control::UpdateNow]);
+    for control in requests {
+        if admin.control.send(control).is_err() {
+            return (StatusCode::SERVICE_UNAVAILABLE, "shutting down\n").into_response();
+        }
+    }
+    (StatusCode::ACCEPTED, "accepted\n").into_response()
+}
+
+async fn status(State(admin): State<Admin>) -> Json<Report> {
+    let snapshot = admin.snapshot.borrow().clone();
+    Json(Report::from(snapshot))
+}
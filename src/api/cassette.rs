@@ -0,0 +1,302 @@
+//! Records the requests made to the DNS provider and its answers to a cassette file, or plays a
+//! cassette back instead of reaching the provider. A cassette recorded on a user's machine
+//! reproduces their bug offline, and one kept with the tests runs the whole update without network.
+//!
+//! Only HTTP providers go through it. Secrets are masked as in the logs, and request headers, the
+//! API token's among them, are never recorded.
+
+use crate::redact;
+use anyhow::{Context as _, Result};
+use futures::future::BoxFuture;
+use log::warn;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Request, Response, ResponseBuilderExt, StatusCode, Url,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    task::{Context, Poll},
+};
+use tower::{Layer, Service};
+
+const VERSION: u32 = 1;
+
+// Response headers worth keeping, the others are noise in a bug report
+const KEPT_HEADERS: [&str; 2] = ["content-type", "retry-after"];
+
+// Set before the provider's client is created, which then goes through it
+static CASSETTE: RwLock<Option<Arc<Cassette>>> = RwLock::new(None);
+
+/// Saves every request to the provider and its answer to `path`, the file being rewritten after
+/// each one
+pub fn record(path: impl Into<PathBuf>) {
+    set(Cassette::new(path.into(), Mode::Record, Vec::new()));
+}
+
+/// Answers the requests to the provider from a cassette saved by [`record`]. A request matches a
+/// recorded one with the same method, URL and body, and each recording is used once before the
+/// last one for that request is repeated.
+pub fn replay(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let contents = std::fs::read(path)
+        .with_context(|| format!("Failed to read the cassette {}", path.display()))?;
+    let tape: Tape = serde_json::from_slice(&contents)
+        .with_context(|| format!("Invalid cassette {}", path.display()))?;
+    set(Cassette::new(
+        path.to_path_buf(),
+        Mode::Replay,
+        tape.interactions,
+    ));
+    Ok(())
+}
+
+fn set(cassette: Cassette) {
+    *CASSETTE.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(cassette));
+}
+
+pub(crate) fn current() -> Option<Arc<Cassette>> {
+    CASSETTE.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Record,
+    Replay,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Tape {
+    version: u32,
+    interactions: Vec<Interaction>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Interaction {
+    request: RecordedRequest,
+    response: RecordedResponse,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+struct RecordedRequest {
+    method: String,
+    url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    body: Option<Value>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct RecordedResponse {
+    status: u16,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    body: Value,
+}
+
+pub(crate) struct Cassette {
+    path: PathBuf,
+    mode: Mode,
+    interactions: Mutex<Vec<Interaction>>,
+    // Which interactions were played back already
+    played: Mutex<Vec<bool>>,
+}
+
+impl Cassette {
+    fn new(path: PathBuf, mode: Mode, interactions: Vec<Interaction>) -> Self {
+        let played = vec![false; interactions.len()];
+        Self {
+            path,
+            mode,
+            interactions: Mutex::new(interactions),
+            played: Mutex::new(played),
+        }
+    }
+
+    fn record(&self, interaction: Interaction) {
+        let mut interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        interactions.push(interaction);
+        let tape = Tape {
+            version: VERSION,
+            interactions: interactions.clone(),
+        };
+        let written = serde_json::to_vec_pretty(&tape)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(std::fs::write(&self.path, contents)?));
+        if let Err(e) = written {
+            warn!(
+                "Failed to write the cassette {}: {:#}",
+                self.path.display(),
+                e
+            );
+        }
+    }
+
+    fn play(&self, request: &RecordedRequest) -> Option<RecordedResponse> {
+        let interactions = self.interactions.lock().unwrap_or_else(|e| e.into_inner());
+        let mut played = self.played.lock().unwrap_or_else(|e| e.into_inner());
+        let matching: Vec<usize> = (0..interactions.len())
+            .filter(|&index| interactions[index].request == *request)
+            .collect();
+        let index = matching
+            .iter()
+            .find(|&&index| !played[index])
+            .or(matching.last())?;
+        played[*index] = true;
+        Some(interactions[*index].response.clone())
+    }
+}
+
+/// Puts a cassette between the provider's client and the network
+#[derive(Clone)]
+pub(crate) struct CassetteLayer {
+    cassette: Arc<Cassette>,
+}
+
+impl CassetteLayer {
+    pub fn new(cassette: Arc<Cassette>) -> Self {
+        Self { cassette }
+    }
+}
+
+impl<S> Layer<S> for CassetteLayer {
+    type Service = Recorder<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Recorder {
+            inner,
+            cassette: self.cassette.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Recorder<S> {
+    inner: S,
+    cassette: Arc<Cassette>,
+}
+
+impl<S> Service<Request> for Recorder<S>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = BoxFuture<'static, reqwest::Result<Response>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<reqwest::Result<()>> {
+        match self.cassette.mode {
+            Mode::Record => self.inner.poll_ready(cx),
+            Mode::Replay => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let recorded = RecordedRequest {
+            method: request.method().to_string(),
+            url: redact::scrub(request.url().as_str()),
+            body: request
+                .body()
+                .and_then(|body| body.as_bytes())
+                .map(scrubbed),
+        };
+        let url = request.url().clone();
+        let cassette = self.cassette.clone();
+
+        if cassette.mode == Mode::Replay {
+            let response = cassette.play(&recorded).unwrap_or_else(|| {
+                let message = format!(
+                    "No answer recorded in {} for {} {}",
+                    cassette.path.display(),
+                    recorded.method,
+                    recorded.url
+                );
+                warn!("{}", message);
+                RecordedResponse {
+                    status: StatusCode::NOT_IMPLEMENTED.as_u16(),
+                    headers: BTreeMap::new(),
+                    body: json!({
+                        "success": false,
+                        "errors": [{ "code": 0, "message": message }],
+                        "result": null,
+                    }),
+                }
+            });
+            return Box::pin(async move { Ok(response.into_response(url)) });
+        }
+
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let response = response.await?;
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.bytes().await?;
+            let response = RecordedResponse {
+                status: status.as_u16(),
+                headers: KEPT_HEADERS
+                    .iter()
+                    .filter_map(|&name| {
+                        let value = headers.get(name)?.to_str().ok()?;
+                        Some((name.to_string(), value.to_string()))
+                    })
+                    .collect(),
+                body: scrubbed(&body),
+            };
+            cassette.record(Interaction {
+                request: recorded,
+                response: response.clone(),
+            });
+            // The answer itself goes on untouched
+            let mut answer = http::Response::builder().status(status).url(url);
+            if let Some(answer_headers) = answer.headers_mut() {
+                *answer_headers = headers;
+            }
+            Ok(Response::from(
+                answer
+                    .body(body)
+                    .unwrap_or_else(|_| http::Response::default()),
+            ))
+        })
+    }
+}
+
+impl RecordedResponse {
+    fn into_response(self, url: Url) -> Response {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) =
+                (name.parse::<HeaderName>(), HeaderValue::from_str(value))
+            {
+                headers.insert(name, value);
+            }
+        }
+        // Bodies that weren't JSON were kept as a string
+        let body = match self.body {
+            Value::String(text) => text.into_bytes(),
+            body => {
+                headers
+                    .entry(CONTENT_TYPE)
+                    .or_insert(HeaderValue::from_static("application/json"));
+                body.to_string().into_bytes()
+            }
+        };
+        let mut response = http::Response::builder().status(self.status).url(url);
+        if let Some(response_headers) = response.headers_mut() {
+            *response_headers = headers;
+        }
+        Response::from(
+            response
+                .body(body)
+                .unwrap_or_else(|_| http::Response::default()),
+        )
+    }
+}
+
+// A body as JSON if it is, its text otherwise, with the secrets masked
+fn scrubbed(body: &[u8]) -> Value {
+    let text = redact::scrub(&String::from_utf8_lossy(body));
+    serde_json::from_str(&text).unwrap_or(Value::String(text))
+}
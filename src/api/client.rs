@@ -1,16 +1,39 @@
-use super::models::*;
+use super::{content::RecordContent, models::*};
 use anyhow::Result;
 use async_trait::async_trait;
-use std::net::Ipv4Addr;
 
+/// What the updater and the commands need from a DNS provider
 #[async_trait]
 pub trait DnsApiClient {
-    async fn get_record(&self, zone_id: &str, domain: &str) -> Result<DnsRecordUpdate>;
+    /// The zones the credentials can see
+    async fn list_zones(&self) -> Result<Vec<ApiZone>>;
+    /// Every record of a zone, across all the pages the provider splits them in
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>>;
+    /// The record of a name and type, an [`error::NotFound`] error when there is none
+    ///
+    /// [`error::NotFound`]: crate::error::NotFound
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate>;
+    /// Points an existing record at `content`, whose type must be the record's
     async fn update_record(
         &self,
         zone_id: &str,
         record: &DnsRecordUpdate,
-        content: &Ipv4Addr,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord>;
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
         ttl: u32,
+        proxied: bool,
     ) -> Result<ApiDnsRecord>;
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()>;
 }
@@ -1,34 +1,73 @@
-use std::net::Ipv4Addr;
+use std::fmt;
+use std::time::{Duration, SystemTime};
 
-use super::{client::DnsApiClient, models::*};
-use anyhow::Result;
+use super::middleware::{self, HttpService};
+use super::zones;
+use super::{client::DnsApiClient, content::RecordContent, limiter::Limiter, models::*};
+use crate::config::RetryPolicy;
+use crate::error::{ErrorKind, NotFound, ResultExt};
+use crate::http;
+use anyhow::{Context, Result};
 use async_trait::async_trait;
-use log::error;
+use log::info;
+use reqwest::StatusCode;
+use secrecy::SecretString;
+use serde::de::DeserializeOwned;
 use serde_json::json;
+use tower::ServiceExt;
 
 const API_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
 
+// For each attempt, a retry gets a fresh one
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Cloudflare's v4 API, authenticated with an API token
 pub struct CloudflareClient {
     client: reqwest::Client,
-    api_token: String,
+    service: HttpService,
+    base_url: String,
 }
 
 #[async_trait]
 impl DnsApiClient for CloudflareClient {
-    async fn get_record(&self, zone_id: &str, domain: &str) -> Result<DnsRecordUpdate> {
-        let response = self
-            .client
-            .get(&format!("{}/zones/{}/dns_records", API_BASE_URL, zone_id))
-            .headers(self.build_headers())
-            .send()
+    async fn list_zones(&self) -> Result<Vec<ApiZone>> {
+        self.get_all(&format!("{}/zones", self.base_url), &[])
+            .await
+            .context("Failed to list zones")
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>> {
+        self.get_all(
+            &format!("{}/zones/{}/dns_records", self.base_url, zone_id),
+            &[],
+        )
+        .await
+        .with_context(|| format!("Failed to list records of zone {}", zone_id))
+    }
+
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate> {
+        let response: ApiResponse<Option<Vec<DnsRecordUpdate>>> = self
+            .send(
+                self.client
+                    .get(format!("{}/zones/{}/dns_records", self.base_url, zone_id))
+                    .query(&[("name", domain), ("type", record_type)]),
+            )
             .await?;
 
-        let response_json: ApiResponse<Vec<DnsRecordUpdate>> = response.json().await?;
-        let record = response_json
+        let record = response
             .result
+            .unwrap_or_default()
             .into_iter()
-            .find(|record| record.name == domain)
-            .ok_or_else(|| anyhow::anyhow!("DNS record not found for domain: {}", domain))?;
+            .find(|record| record.name == domain && record.r#type == record_type)
+            .ok_or_else(|| NotFound {
+                record_type: record_type.to_string(),
+                domain: domain.to_string(),
+            })?;
 
         Ok(record)
     }
@@ -37,57 +76,338 @@ impl DnsApiClient for CloudflareClient {
         &self,
         zone_id: &str,
         record: &DnsRecordUpdate,
-        content: &Ipv4Addr,
+        content: &RecordContent,
         ttl: u32,
+        proxied: bool,
     ) -> Result<ApiDnsRecord> {
-        let response = self
-            .client
-            .patch(&format!(
-                "{}/zones/{}/dns_records/{}",
-                API_BASE_URL, zone_id, record.id
-            ))
-            .bearer_auth(&self.api_token)
-            .header("Content-Type", "application/json")
-            .body(serde_json::to_string(&json!({
-                "type": record.r#type,
-                "name": record.name,
-                "content": content.to_string(),
-                "ttl": ttl,
-                "proxied": record.proxied,
-            }))?)
-            .send()
-            .await?;
+        let body = json!({
+            "type": record.r#type,
+            "name": record.name,
+            "content": content.to_string(),
+            "ttl": ttl,
+            "proxied": proxied,
+        });
+        let response: ApiResponse<Option<ApiDnsRecord>> = self
+            .send(
+                self.client
+                    .patch(format!(
+                        "{}/zones/{}/dns_records/{}",
+                        self.base_url, zone_id, record.id
+                    ))
+                    .json(&body),
+            )
+            .await
+            .with_context(|| {
+                format!("Failed to update {} record {}", record.r#type, record.name)
+            })?;
 
-        // Handle response
-        let update_response: ApiResponse<ApiDnsRecord> = response.json().await?;
+        response.result.with_context(|| {
+            format!(
+                "Cloudflare didn't return the updated {} record {}",
+                record.r#type, record.name
+            )
+        })
+    }
 
-        if !update_response.success {
-            error!("Failed to update DNS record: {:?}", &update_response.errors);
-            return Err(anyhow::anyhow!(
-                "Failed to update DNS record: {:?}",
-                &update_response.errors
-            ));
-        }
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        let record_type = content.record_type();
+        let body = json!({
+            "type": record_type,
+            "name": name,
+            "content": content.to_string(),
+            "ttl": ttl,
+            "proxied": proxied,
+        });
+        let url = format!("{}/zones/{}/dns_records", self.base_url, zone_id);
+        let response: ApiResponse<Option<ApiDnsRecord>> =
+            match self.send(self.client.post(&url).json(&body)).await {
+                // Already what was asked for, e.g. created by a previous attempt that timed out
+                Err(e) if reported(&e, CloudflareError::IDENTICAL_RECORD) => {
+                    info!("{} record {} already exists", record_type, name);
+                    let content = content.to_string();
+                    let query = [("name", name), ("type", record_type), ("content", &content)];
+                    return self
+                        .get_all(&url, &query)
+                        .await?
+                        .into_iter()
+                        .next()
+                        .context(e);
+                }
+                response => response
+                    .with_context(|| format!("Failed to create {} record {}", record_type, name))?,
+            };
 
-        Ok(update_response.result)
+        response.result.with_context(|| {
+            format!(
+                "Cloudflare didn't return the created {} record {}",
+                record_type, name
+            )
+        })
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.send::<serde_json::Value>(self.client.delete(format!(
+            "{}/zones/{}/dns_records/{}",
+            self.base_url, zone_id, record_id
+        )))
+        .await
+        .with_context(|| format!("Failed to delete record {}", record_id))?;
+        Ok(())
     }
 }
 
 impl CloudflareClient {
-    pub fn new(api_token: &str) -> Self {
+    pub fn new(api_token: &SecretString, retry: &RetryPolicy, limiter: Limiter) -> Self {
+        let client = http::client();
+        let service = middleware::stack(client.clone(), api_token, retry, limiter, REQUEST_TIMEOUT);
         Self {
-            client: reqwest::Client::new(),
-            api_token: api_token.to_string(),
+            client,
+            service,
+            base_url: API_BASE_URL.to_string(),
+        }
+    }
+
+    /// Talks to another endpoint than Cloudflare's, e.g. a test server standing in for it. The URL
+    /// is that of the API's root, such as `http://127.0.0.1:8080/client/v4`.
+    pub fn base_url(mut self, url: &str) -> Self {
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    // Check that the token is valid and active
+    pub async fn verify_token(&self) -> Result<TokenStatus> {
+        self.send(
+            self.client
+                .get(format!("{}/user/tokens/verify", self.base_url)),
+        )
+        .await
+        .context("Token verification failed")?
+        .result
+        .context("Token verification failed: no status in the response")
+    }
+
+    // Whether the API answers at all, an unauthenticated request being enough to tell
+    pub async fn reachable() -> bool {
+        http::client()
+            .head(API_BASE_URL)
+            .timeout(Duration::from_secs(5))
+            .send()
+            .await
+            .is_ok()
+    }
+
+    // The API's own clock, from the Date header of an unauthenticated request
+    pub async fn server_time() -> Result<SystemTime> {
+        let response = http::client().head(API_BASE_URL).send().await?;
+        let date = response
+            .headers()
+            .get(reqwest::header::DATE)
+            .context("No Date header in the API response")?
+            .to_str()?;
+        httpdate::parse_http_date(date).with_context(|| format!("Invalid Date header {:?}", date))
+    }
+
+    pub async fn get_zone(&self, zone_id: &str) -> Result<ApiZone> {
+        // A zone given by name, see ZoneCache
+        if zones::is_name(zone_id) {
+            let name = zone_id.trim_end_matches('.');
+            let found: Vec<ApiZone> = self
+                .get_all(&format!("{}/zones", self.base_url), &[("name", name)])
+                .await
+                .with_context(|| format!("Zone {} is not accessible", name))?;
+            return found
+                .into_iter()
+                .next()
+                .with_context(|| format!("The API token can't see any zone named {}", name));
+        }
+        self.send(
+            self.client
+                .get(format!("{}/zones/{}", self.base_url, zone_id)),
+        )
+        .await
+        .with_context(|| format!("Zone {} is not accessible", zone_id))?
+        .result
+        .with_context(|| format!("Zone {} is not accessible", zone_id))
+    }
+
+    // Follow Cloudflare's page based pagination until every item is fetched
+    async fn get_all<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let mut page = 1;
+
+        loop {
+            let page_number = page.to_string();
+            let response: ApiResponse<Option<Vec<T>>> = self
+                .send(
+                    self.client
+                        .get(url)
+                        .query(query)
+                        .query(&[("page", page_number.as_str()), ("per_page", "50")]),
+                )
+                .await?;
+            items.extend(response.result.unwrap_or_default());
+
+            match response.result_info {
+                Some(info) if info.page < info.total_pages => page = info.page + 1,
+                _ => break,
+            }
+        }
+
+        Ok(items)
+    }
+
+    // Through the middleware, which authenticates, rate limits, retries and logs it
+    async fn send<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<ApiResponse<Option<T>>> {
+        let response = self.service.clone().oneshot(request.build()?).await?;
+        read(response).await
+    }
+}
+
+/// The errors Cloudflare reported for a request, each with its code. The codes clouddns knows
+/// about come with a hint on what to do, e.g. `Invalid access token (9109): the API token is
+/// malformed, expired or was revoked, …`, followed by the ray ID to quote to Cloudflare's support.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub struct CloudflareError {
+    pub errors: Vec<ApiError>,
+    /// The `cf-ray` header of the answer
+    pub ray: Option<String>,
+}
+
+impl CloudflareError {
+    /// The API token is malformed, expired or was revoked
+    pub const INVALID_TOKEN: u32 = 9109;
+    /// The API token isn't allowed to do what was asked, e.g. edit the records of that zone
+    pub const AUTHENTICATION_ERROR: u32 = 10000;
+    /// A record with the same name, type and content already exists
+    pub const IDENTICAL_RECORD: u32 = 81057;
+
+    /// Whether Cloudflare reported this code
+    pub fn has(&self, code: u32) -> bool {
+        self.errors.iter().any(|error| error.code == code)
+    }
+
+    fn hint(code: u32) -> Option<&'static str> {
+        match code {
+            Self::INVALID_TOKEN => Some(
+                "the API token is malformed, expired or was revoked, create a new one and update \
+                 api_token",
+            ),
+            Self::AUTHENTICATION_ERROR => Some(
+                "the API token isn't allowed to do this, it needs the Zone Read and DNS Edit \
+                 permissions on the zone",
+            ),
+            Self::IDENTICAL_RECORD => {
+                Some("a record with the same name, type and content already exists")
+            }
+            _ => None,
         }
     }
+}
+
+impl fmt::Display for CloudflareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, error) in self.errors.iter().enumerate() {
+            if index > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{} ({})", error.message, error.code)?;
+            if let Some(hint) = Self::hint(error.code) {
+                write!(f, ": {}", hint)?;
+            }
+        }
+        if let Some(ray) = &self.ray {
+            write!(f, " (ray {})", ray)?;
+        }
+        Ok(())
+    }
+}
+
+// Whether Cloudflare reported that code, wherever the error was given context
+fn reported(error: &anyhow::Error, code: u32) -> bool {
+    error
+        .downcast_ref::<CloudflareError>()
+        .is_some_and(|e| e.has(code))
+}
+
+// Every answer comes in the same envelope, whose errors say more than the status. 401 and 403 mean
+// the token is invalid or lacks a permission, 429 that the retries couldn't wait out the rate limit.
+pub(super) async fn read<T: DeserializeOwned>(
+    response: reqwest::Response,
+) -> Result<ApiResponse<Option<T>>> {
+    let status = response.status();
+    // Kept in the chain, so the status still classifies the failure
+    let failed = response
+        .error_for_status_ref()
+        .err()
+        .map(reqwest::Error::without_url);
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        if let Some(e) = failed {
+            return Err(e).context("Cloudflare's rate limit was reached");
+        }
+    }
+    let rejected = matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN);
+    let ray = middleware::ray_id(&response);
+    let answer = |status: StatusCode| match &ray {
+        Some(ray) => format!("{}, ray {}", status, ray),
+        None => status.to_string(),
+    };
+    let body = response.bytes().await?;
+
+    let response = match serde_json::from_slice::<ApiResponse<Option<T>>>(&body) {
+        Ok(response) if response.success => return Ok(response),
+        Ok(response) if !response.errors.is_empty() => response,
+        _ if rejected => {
+            return Err(anyhow::anyhow!(
+                "Cloudflare rejected the API token ({})",
+                answer(status)
+            ))
+            .kind(ErrorKind::Auth)
+        }
+        Ok(_) => {
+            return Err(anyhow::anyhow!(
+                "Cloudflare reported a failure ({})",
+                answer(status)
+            ))
+        }
+        Err(e) => {
+            let error = match failed {
+                Some(failed) => anyhow::Error::new(failed),
+                None => anyhow::Error::new(e),
+            };
+            return Err(error.context(format!(
+                "Invalid response from Cloudflare ({})",
+                answer(status)
+            )));
+        }
+    };
 
-    fn build_headers(&self) -> reqwest::header::HeaderMap {
-        let mut headers = reqwest::header::HeaderMap::new();
-        headers.insert(
-            "Authorization",
-            format!("Bearer {}", &self.api_token).parse().unwrap(),
-        );
-        headers.insert("Content-Type", "application/json".parse().unwrap());
-        headers
+    let error = CloudflareError {
+        errors: response.errors,
+        ray,
+    };
+    let auth = rejected
+        || error.has(CloudflareError::INVALID_TOKEN)
+        || error.has(CloudflareError::AUTHENTICATION_ERROR);
+    let error = match failed {
+        Some(failed) => anyhow::Error::new(failed).context(error),
+        None => anyhow::Error::new(error),
+    };
+    match auth {
+        true => Err(error).kind(ErrorKind::Auth),
+        false => Err(error),
     }
 }
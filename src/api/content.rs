@@ -0,0 +1,68 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+/// What a record points at, its variant deciding the record type. Serialized as the type and the
+/// content, e.g. `{"type": "A", "content": "192.0.2.7"}`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", content = "content", rename_all = "UPPERCASE")]
+pub enum RecordContent {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// Text, without the quotes the zone file syntax wraps it in
+    Txt(String),
+    /// The name the record is an alias of
+    Cname(String),
+}
+
+impl RecordContent {
+    /// The record type, as the provider names it
+    pub fn record_type(&self) -> &'static str {
+        match self {
+            RecordContent::A(_) => "A",
+            RecordContent::Aaaa(_) => "AAAA",
+            RecordContent::Txt(_) => "TXT",
+            RecordContent::Cname(_) => "CNAME",
+        }
+    }
+
+    /// The address of an A or AAAA record
+    pub fn address(&self) -> Option<IpAddr> {
+        match self {
+            RecordContent::A(address) => Some(IpAddr::V4(*address)),
+            RecordContent::Aaaa(address) => Some(IpAddr::V6(*address)),
+            RecordContent::Txt(_) | RecordContent::Cname(_) => None,
+        }
+    }
+
+    /// Whether a record's content as the provider reports it is this one. Addresses are compared
+    /// as addresses, `2001:db8:0::1` being `2001:db8::1`.
+    pub fn matches(&self, content: &str) -> bool {
+        match self.address() {
+            Some(address) => content.parse::<IpAddr>() == Ok(address),
+            None => self.to_string() == content,
+        }
+    }
+}
+
+impl From<IpAddr> for RecordContent {
+    fn from(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(address) => RecordContent::A(address),
+            IpAddr::V6(address) => RecordContent::Aaaa(address),
+        }
+    }
+}
+
+// The content as the provider stores it, and as records are compared
+impl fmt::Display for RecordContent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RecordContent::A(address) => address.fmt(f),
+            RecordContent::Aaaa(address) => address.fmt(f),
+            RecordContent::Txt(text) | RecordContent::Cname(text) => f.write_str(text),
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use super::{client::DnsApiClient, content::RecordContent, models::*};
+use crate::config::ProviderConfig;
+use crate::error::{ErrorKind, NotFound};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use log::debug;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::{json, Value};
+use std::{path::PathBuf, process::Stdio};
+use tokio::{io::AsyncWriteExt, process::Command, time::Duration};
+
+const PROTOCOL_VERSION: u32 = 1;
+
+/// A DNS provider implemented by an external program, run once per call. The program reads one
+/// JSON request on its standard input, e.g.
+/// `{"version": 1, "operation": "get_record", "zone_id": "…", "name": "home.example.com", "type": "A"}`,
+/// and writes one JSON response on its standard output: `{"result": …}`, or
+/// `{"error": {"message": "…", "kind": "not_found"}}` where `kind` is optional and may also be
+/// `auth`. Records are objects with the fields `id`, `name`, `type`, `content`, `ttl` and
+/// `proxied`, zones with `id` and `name`.
+pub struct ExecClient {
+    command: PathBuf,
+    args: Vec<String>,
+    timeout: Duration,
+    api_token: SecretString,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    result: Value,
+    #[serde(default)]
+    error: Option<PluginError>,
+}
+
+#[derive(Deserialize)]
+struct PluginError {
+    message: String,
+    #[serde(default)]
+    kind: Option<String>,
+}
+
+impl ExecClient {
+    /// Runs the provider's command, which the configuration must have
+    pub fn new(provider: &ProviderConfig, api_token: &SecretString) -> Result<Self> {
+        let command = provider
+            .command
+            .clone()
+            .context("An exec provider needs a command")?;
+        Ok(Self {
+            command,
+            args: provider.args.clone(),
+            timeout: Duration::from_secs(provider.timeout),
+            api_token: api_token.clone(),
+        })
+    }
+
+    async fn call<T: DeserializeOwned>(&self, operation: &str, mut request: Value) -> Result<T> {
+        request["version"] = json!(PROTOCOL_VERSION);
+        request["operation"] = json!(operation);
+        let program = self.command.display();
+        debug!("Running {} for {}", program, operation);
+
+        let mut child = Command::new(&self.command)
+            .args(&self.args)
+            .env("CLOUDDNS_API_TOKEN", self.api_token.expose_secret())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run the provider {}", program))?;
+        let mut stdin = child.stdin.take().context("No stdin for the provider")?;
+        let mut input = serde_json::to_vec(&request)?;
+        input.push(b'\n');
+        let exchange = async {
+            // A program that answers without reading its input is fine
+            let _ = stdin.write_all(&input).await;
+            drop(stdin);
+            child.wait_with_output().await
+        };
+        let output = tokio::time::timeout(self.timeout, exchange)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "The provider {} didn't answer {} within {}s",
+                    program,
+                    operation,
+                    self.timeout.as_secs()
+                )
+            })?
+            .with_context(|| format!("Failed to run the provider {}", program))?;
+
+        // An error the program reports itself says more than its exit status
+        let response = match serde_json::from_slice::<Response>(&output.stdout) {
+            Ok(Response {
+                error: Some(error), ..
+            }) => return Err(plugin_error(error, &request)),
+            response => response,
+        };
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return match stderr.trim() {
+                "" => Err(anyhow::anyhow!(
+                    "The provider {} failed ({})",
+                    program,
+                    output.status
+                )),
+                stderr => Err(anyhow::anyhow!(
+                    "The provider {} failed ({}): {}",
+                    program,
+                    output.status,
+                    stderr
+                )),
+            };
+        }
+        let response = response.with_context(|| format!("Invalid response from {}", program))?;
+        serde_json::from_value(response.result)
+            .with_context(|| format!("Unexpected {} result from {}", operation, program))
+    }
+}
+
+// Classified like the errors of the Cloudflare client, so retries and exit codes work the same
+fn plugin_error(error: PluginError, request: &Value) -> anyhow::Error {
+    match error.kind.as_deref() {
+        Some("not_found") => anyhow::Error::new(NotFound {
+            record_type: request["type"].as_str().unwrap_or_default().to_string(),
+            domain: request["name"].as_str().unwrap_or_default().to_string(),
+        })
+        .context(error.message),
+        Some("auth") => anyhow::anyhow!(error.message).context(ErrorKind::Auth),
+        _ => anyhow::anyhow!(error.message),
+    }
+}
+
+#[async_trait]
+impl DnsApiClient for ExecClient {
+    async fn list_zones(&self) -> Result<Vec<ApiZone>> {
+        self.call("list_zones", json!({})).await
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>> {
+        self.call("list_records", json!({ "zone_id": zone_id }))
+            .await
+    }
+
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate> {
+        let request = json!({ "zone_id": zone_id, "name": domain, "type": record_type });
+        self.call("get_record", request).await
+    }
+
+    async fn update_record(
+        &self,
+        zone_id: &str,
+        record: &DnsRecordUpdate,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        let request = json!({
+            "zone_id": zone_id,
+            "record": record,
+            "name": record.name,
+            "type": content.record_type(),
+            "content": content.to_string(),
+            "ttl": ttl,
+            "proxied": proxied,
+        });
+        self.call("update_record", request).await
+    }
+
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        let request = json!({
+            "zone_id": zone_id,
+            "name": name,
+            "type": content.record_type(),
+            "content": content.to_string(),
+            "ttl": ttl,
+            "proxied": proxied,
+        });
+        self.call("create_record", request).await
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let request = json!({ "zone_id": zone_id, "record_id": record_id });
+        self.call("delete_record", request).await
+    }
+}
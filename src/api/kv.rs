@@ -0,0 +1,93 @@
+use std::time::Duration;
+
+use super::cloudflare::read;
+use super::limiter::Limiter;
+use super::middleware::{self, HttpService};
+use crate::config::RetryPolicy;
+use crate::http;
+use anyhow::{Context, Result};
+use reqwest::{header::CONTENT_TYPE, StatusCode, Url};
+use secrecy::SecretString;
+use serde_json::Value;
+use tower::ServiceExt;
+
+const API_BASE_URL: &str = "https://api.cloudflare.com/client/v4";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A Cloudflare Workers KV namespace, reached with the same API token as the records
+pub struct KvClient {
+    client: reqwest::Client,
+    service: HttpService,
+    base_url: String,
+    account_id: String,
+    namespace_id: String,
+}
+
+impl KvClient {
+    pub fn new(
+        api_token: &SecretString,
+        retry: &RetryPolicy,
+        limiter: Limiter,
+        account_id: &str,
+        namespace_id: &str,
+    ) -> Self {
+        let client = http::client();
+        let service = middleware::stack(client.clone(), api_token, retry, limiter, REQUEST_TIMEOUT);
+        Self {
+            client,
+            service,
+            base_url: API_BASE_URL.to_string(),
+            account_id: account_id.to_string(),
+            namespace_id: namespace_id.to_string(),
+        }
+    }
+
+    /// Talks to another endpoint than Cloudflare's, see [`CloudflareClient::base_url`]
+    ///
+    /// [`CloudflareClient::base_url`]: super::CloudflareClient::base_url
+    pub fn base_url(mut self, url: &str) -> Self {
+        self.base_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// The value of a key, None when the namespace doesn't hold it
+    pub async fn get(&self, key: &str) -> Result<Option<String>> {
+        let request = self.client.get(self.url(key)?).build()?;
+        let response = self.service.clone().oneshot(request).await?;
+        match response.status() {
+            // The value comes as it was written, outside the usual envelope
+            status if status.is_success() => Ok(Some(response.text().await?)),
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => read::<Value>(response).await.map(|_| None),
+        }
+        .with_context(|| format!("Failed to read {} from the KV namespace", key))
+    }
+
+    pub async fn put(&self, key: &str, value: String) -> Result<()> {
+        let request = self
+            .client
+            .put(self.url(key)?)
+            .header(CONTENT_TYPE, "text/plain")
+            .body(value)
+            .build()?;
+        let response = self.service.clone().oneshot(request).await?;
+        read::<Value>(response)
+            .await
+            .with_context(|| format!("Failed to write {} to the KV namespace", key))?;
+        Ok(())
+    }
+
+    // The key is one segment of the path, escaped as such
+    fn url(&self, key: &str) -> Result<Url> {
+        let mut url = Url::parse(&format!(
+            "{}/accounts/{}/storage/kv/namespaces/{}/values",
+            self.base_url, self.account_id, self.namespace_id
+        ))
+        .context("Invalid KV namespace URL")?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("Invalid KV namespace URL"))?
+            .push(key);
+        Ok(url)
+    }
+}
@@ -0,0 +1,49 @@
+use crate::config::RateLimit;
+use governor::{DefaultDirectRateLimiter, Quota};
+use log::{info, warn};
+use std::num::NonZeroU32;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+// Token bucket shared by every request to the provider, the whole allowance can go out at once
+// and then refills evenly over the period
+#[derive(Clone)]
+pub struct Limiter {
+    limiter: Arc<DefaultDirectRateLimiter>,
+    limit: RateLimit,
+    // Set while requests are held back, so that is logged once
+    throttled: Arc<AtomicBool>,
+}
+
+impl Limiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let burst = NonZeroU32::new(limit.requests).unwrap_or(NonZeroU32::MIN);
+        let quota = Quota::with_period(limit.period() / burst.get())
+            .unwrap_or_else(|| Quota::per_second(NonZeroU32::MAX))
+            .allow_burst(burst);
+        Self {
+            limiter: Arc::new(DefaultDirectRateLimiter::direct(quota)),
+            limit,
+            throttled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    // Waits until the next request fits in the limit
+    pub async fn acquire(&self) {
+        if self.limiter.check().is_ok() {
+            if self.throttled.swap(false, Ordering::Relaxed) {
+                info!("Back under the API rate limit");
+            }
+            return;
+        }
+        if !self.throttled.swap(true, Ordering::Relaxed) {
+            warn!(
+                "Reached the API rate limit of {} requests per {}s, slowing down",
+                self.limit.requests, self.limit.period
+            );
+        }
+        self.limiter.until_ready().await;
+    }
+}
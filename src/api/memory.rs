@@ -0,0 +1,288 @@
+use super::{client::DnsApiClient, content::RecordContent, models::*};
+use crate::error::{ErrorKind, NotFound, ResultExt, Unreachable};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// A call to the provider, to slow down, fail or count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operation {
+    ListZones,
+    ListRecords,
+    GetRecord,
+    UpdateRecord,
+    CreateRecord,
+    DeleteRecord,
+}
+
+/// How an injected failure fails
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// As if the provider couldn't be reached
+    Unavailable,
+    /// As if the API token was rejected
+    Unauthorized,
+}
+
+/// A DNS provider kept in memory, for testing an integration without network access. Clones share
+/// the same zones, so one can be given to the updater and another kept to look at the records:
+///
+/// ```
+/// use clouddns::api::{MemoryDnsProvider, RecordContent};
+///
+/// let provider = MemoryDnsProvider::new()
+///     .zone("zone-id", "example.com")
+///     .record("zone-id", "home.example.com", RecordContent::A([192, 0, 2, 1].into()));
+/// assert_eq!(provider.records("zone-id")[0].content, "192.0.2.1");
+/// ```
+#[derive(Clone, Default)]
+pub struct MemoryDnsProvider {
+    inner: Arc<Mutex<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    zones: BTreeMap<String, Zone>,
+    next_id: u64,
+    latency: Duration,
+    latencies: HashMap<Operation, Duration>,
+    // Calls left to fail, per operation
+    failures: HashMap<Operation, (Failure, usize)>,
+    calls: HashMap<Operation, usize>,
+}
+
+struct Zone {
+    name: String,
+    records: Vec<ApiDnsRecord>,
+}
+
+impl MemoryDnsProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an empty zone
+    pub fn zone(self, id: &str, name: &str) -> Self {
+        self.lock().zones.insert(
+            id.to_string(),
+            Zone {
+                name: name.to_string(),
+                records: Vec::new(),
+            },
+        );
+        self
+    }
+
+    /// Adds a record to a zone added before, with an automatic TTL and not proxied
+    pub fn record(self, zone_id: &str, name: &str, content: RecordContent) -> Self {
+        let mut inner = self.lock();
+        let record = inner.new_record(name, &content, 1, false);
+        match inner.zones.get_mut(zone_id) {
+            Some(zone) => zone.records.push(record),
+            None => panic!("Zone {} must be added before its records", zone_id),
+        }
+        drop(inner);
+        self
+    }
+
+    /// How long every call takes
+    pub fn latency(self, latency: Duration) -> Self {
+        self.lock().latency = latency;
+        self
+    }
+
+    /// How long calls of one kind take, instead of the latency of every call
+    pub fn operation_latency(self, operation: Operation, latency: Duration) -> Self {
+        self.lock().latencies.insert(operation, latency);
+        self
+    }
+
+    /// Makes the next `times` calls of one kind fail, without changing anything
+    pub fn fail(&self, operation: Operation, failure: Failure, times: usize) {
+        self.lock().failures.insert(operation, (failure, times));
+    }
+
+    /// The records of a zone as they are now, empty for an unknown zone
+    pub fn records(&self, zone_id: &str) -> Vec<ApiDnsRecord> {
+        self.lock()
+            .zones
+            .get(zone_id)
+            .map(|zone| zone.records.clone())
+            .unwrap_or_default()
+    }
+
+    /// How many calls of one kind were made, failed ones included
+    pub fn calls(&self, operation: Operation) -> usize {
+        self.lock().calls.get(&operation).copied().unwrap_or(0)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Counts the call, waits out its latency, then fails it if a failure is pending
+    async fn call(&self, operation: Operation) -> Result<()> {
+        let (latency, failure) = {
+            let mut inner = self.lock();
+            *inner.calls.entry(operation).or_default() += 1;
+            let latency = inner
+                .latencies
+                .get(&operation)
+                .copied()
+                .unwrap_or(inner.latency);
+            let failure = match inner.failures.get_mut(&operation) {
+                Some((failure, times)) if *times > 0 => {
+                    *times -= 1;
+                    Some(*failure)
+                }
+                _ => None,
+            };
+            (latency, failure)
+        };
+        if !latency.is_zero() {
+            tokio::time::sleep(latency).await;
+        }
+        match failure {
+            None => Ok(()),
+            Some(Failure::Unavailable) => Err(Unreachable(
+                "The provider is unavailable (injected failure)".to_string(),
+            )
+            .into()),
+            Some(Failure::Unauthorized) => Err(anyhow::anyhow!(
+                "The provider rejected the API token (injected failure)"
+            ))
+            .kind(ErrorKind::Auth),
+        }
+    }
+}
+
+impl Inner {
+    fn new_record(
+        &mut self,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> ApiDnsRecord {
+        self.next_id += 1;
+        ApiDnsRecord {
+            id: format!("record-{}", self.next_id),
+            name: name.to_string(),
+            content: content.to_string(),
+            r#type: content.record_type().to_string(),
+            proxied,
+            ttl,
+        }
+    }
+
+    fn zone(&mut self, zone_id: &str) -> Result<&mut Zone> {
+        self.zones
+            .get_mut(zone_id)
+            .ok_or_else(|| anyhow::anyhow!("Zone {} not found", zone_id))
+    }
+}
+
+#[async_trait]
+impl DnsApiClient for MemoryDnsProvider {
+    async fn list_zones(&self) -> Result<Vec<ApiZone>> {
+        self.call(Operation::ListZones).await?;
+        Ok(self
+            .lock()
+            .zones
+            .iter()
+            .map(|(id, zone)| ApiZone {
+                id: id.clone(),
+                name: zone.name.clone(),
+                status: "active".to_string(),
+            })
+            .collect())
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>> {
+        self.call(Operation::ListRecords).await?;
+        Ok(self.lock().zone(zone_id)?.records.clone())
+    }
+
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate> {
+        self.call(Operation::GetRecord).await?;
+        let mut inner = self.lock();
+        let record = inner
+            .zone(zone_id)?
+            .records
+            .iter()
+            .find(|record| record.name == domain && record.r#type == record_type)
+            .ok_or_else(|| NotFound {
+                record_type: record_type.to_string(),
+                domain: domain.to_string(),
+            })?;
+        Ok(DnsRecordUpdate {
+            id: record.id.clone(),
+            name: record.name.clone(),
+            content: record.content.clone(),
+            ttl: record.ttl,
+            proxied: record.proxied,
+            r#type: record.r#type.clone(),
+            modified_on: None,
+        })
+    }
+
+    async fn update_record(
+        &self,
+        zone_id: &str,
+        record: &DnsRecordUpdate,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        self.call(Operation::UpdateRecord).await?;
+        let mut inner = self.lock();
+        let stored = inner
+            .zone(zone_id)?
+            .records
+            .iter_mut()
+            .find(|stored| stored.id == record.id)
+            .ok_or_else(|| anyhow::anyhow!("Record {} not found", record.id))?;
+        stored.content = content.to_string();
+        stored.ttl = ttl;
+        stored.proxied = proxied;
+        Ok(stored.clone())
+    }
+
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        self.call(Operation::CreateRecord).await?;
+        let mut inner = self.lock();
+        inner.zone(zone_id)?;
+        let record = inner.new_record(name, content, ttl, proxied);
+        inner.zone(zone_id)?.records.push(record.clone());
+        Ok(record)
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.call(Operation::DeleteRecord).await?;
+        let mut inner = self.lock();
+        let records = &mut inner.zone(zone_id)?.records;
+        match records.iter().position(|record| record.id == record_id) {
+            Some(index) => {
+                records.remove(index);
+                Ok(())
+            }
+            None => Err(anyhow::anyhow!("Record {} not found", record_id)),
+        }
+    }
+}
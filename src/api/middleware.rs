@@ -0,0 +1,272 @@
+use super::cassette::{self, CassetteLayer};
+use super::limiter::Limiter;
+use crate::config::RetryPolicy;
+use crate::retry::RetryLayer;
+use crate::{correlation, redact};
+use futures::future::BoxFuture;
+use log::debug;
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Client, Request, Response,
+};
+use secrecy::{ExposeSecret, SecretString};
+use std::{
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+use tower::{util::BoxCloneSyncService, Layer, Service, ServiceBuilder, ServiceExt};
+
+// What a provider's requests go through on their way to the HTTP client
+pub type HttpService = BoxCloneSyncService<Request, Response, reqwest::Error>;
+
+// The stack shared by the HTTP providers: the token is added once, the rate limit counts a request
+// and its retries as one, and every attempt gets the full timeout and a line in the debug log. A
+// cassette, when one is set, records the answers or stands in for the provider.
+pub fn stack(
+    client: Client,
+    api_token: &SecretString,
+    retry: &RetryPolicy,
+    limiter: Limiter,
+    timeout: Duration,
+) -> HttpService {
+    BoxCloneSyncService::new(
+        ServiceBuilder::new()
+            .layer(AuthLayer::bearer(api_token))
+            .layer(RateLimitLayer::new(limiter))
+            .layer(RetryLayer::new(retry.clone()))
+            .layer(TimeoutLayer::new(timeout))
+            .layer(LogLayer)
+            .option_layer(cassette::current().map(CassetteLayer::new))
+            .service(client),
+    )
+}
+
+// Adds the Authorization header, left out of the Debug output of the request
+#[derive(Clone)]
+pub struct AuthLayer {
+    authorization: HeaderValue,
+}
+
+impl AuthLayer {
+    pub fn bearer(api_token: &SecretString) -> Self {
+        let mut authorization =
+            HeaderValue::from_str(&format!("Bearer {}", api_token.expose_secret()))
+                .unwrap_or_else(|_| HeaderValue::from_static(""));
+        authorization.set_sensitive(true);
+        Self { authorization }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Auth<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Auth {
+            inner,
+            authorization: self.authorization.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Auth<S> {
+    inner: S,
+    authorization: HeaderValue,
+}
+
+impl<S: Service<Request>> Service<Request> for Auth<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        request
+            .headers_mut()
+            .insert(AUTHORIZATION, self.authorization.clone());
+        self.inner.call(request)
+    }
+}
+
+// Waits for the shared limiter before letting a request through
+#[derive(Clone)]
+pub struct RateLimitLayer {
+    limiter: Limiter,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: Limiter) -> Self {
+        Self { limiter }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RateLimit<S> {
+    inner: S,
+    limiter: Limiter,
+}
+
+impl<S> Service<Request> for RateLimit<S>
+where
+    S: Service<Request> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<S::Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let limiter = self.limiter.clone();
+        let clone = self.inner.clone();
+        let inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            limiter.acquire().await;
+            inner.oneshot(request).await
+        })
+    }
+}
+
+// Gives up on requests the server doesn't answer in time, unless they set a timeout of their own
+#[derive(Clone)]
+pub struct TimeoutLayer {
+    timeout: Duration,
+}
+
+impl TimeoutLayer {
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout }
+    }
+}
+
+impl<S> Layer<S> for TimeoutLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            timeout: self.timeout,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Timeout<S> {
+    inner: S,
+    timeout: Duration,
+}
+
+impl<S: Service<Request>> Service<Request> for Timeout<S> {
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), S::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        // reqwest enforces it, so a timeout is reported and retried like any network error
+        request.timeout_mut().get_or_insert(self.timeout);
+        self.inner.call(request)
+    }
+}
+
+// Logs every request with its status and duration, under an ID sent along as `X-Request-Id`,
+// and the ray ID Cloudflare gives its answers
+#[derive(Clone)]
+pub struct LogLayer;
+
+impl<S> Layer<S> for LogLayer {
+    type Service = Log<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Log { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct Log<S> {
+    inner: S,
+}
+
+impl<S> Service<Request> for Log<S>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error>,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = BoxFuture<'static, reqwest::Result<Response>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<reqwest::Result<()>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request) -> Self::Future {
+        let method = request.method().clone();
+        let url = redact::url(request.url().as_str());
+        let id = correlation::new_id();
+        if let Ok(value) = HeaderValue::from_str(&id) {
+            request.headers_mut().insert(REQUEST_ID, value);
+        }
+        let span = tracing::Span::current();
+        span.record("http.request.id", id.as_str());
+        let start = Instant::now();
+        let response = self.inner.call(request);
+        Box::pin(async move {
+            let result = response.await;
+            let elapsed = start.elapsed().as_millis();
+            match &result {
+                Ok(response) => {
+                    let ray = ray_id(response);
+                    if let Some(ray) = &ray {
+                        span.record("cloudflare.ray", ray.as_str());
+                    }
+                    debug!(
+                        "{} {}: {} in {}ms (request {}{})",
+                        method,
+                        url,
+                        response.status(),
+                        elapsed,
+                        id,
+                        ray.map(|ray| format!(", ray {}", ray)).unwrap_or_default()
+                    )
+                }
+                Err(e) => debug!(
+                    "{} {}: failed after {}ms (request {}): {}",
+                    method, url, elapsed, id, e
+                ),
+            }
+            result
+        })
+    }
+}
+
+const REQUEST_ID: &str = "x-request-id";
+
+// What Cloudflare's support asks for about a request
+pub fn ray_id(response: &Response) -> Option<String> {
+    response
+        .headers()
+        .get("cf-ray")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
@@ -1,6 +1,102 @@
+pub mod cassette;
 pub mod client;
 pub mod cloudflare;
+pub mod content;
+pub mod exec;
+pub mod kv;
+pub mod limiter;
+pub mod memory;
+mod middleware;
 pub mod models;
+pub mod tenants;
+pub mod zones;
+
+use crate::config::{Config, ProviderKind};
+use crate::secrets;
+use anyhow::{Context, Result};
+use secrecy::SecretString;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
 
 pub use client::DnsApiClient;
-pub use cloudflare::CloudflareClient;
+pub use cloudflare::{CloudflareClient, CloudflareError};
+pub use content::RecordContent;
+pub use exec::ExecClient;
+pub use kv::KvClient;
+pub use limiter::Limiter;
+pub use memory::MemoryDnsProvider;
+pub use tenants::TenantRouter;
+pub use zones::ZoneCache;
+
+/// The client of the configured provider. Cloudflare's shares `limiter`, so that recreating it
+/// with a new token doesn't reset the allowance, and looks zones given by name up through a
+/// [`ZoneCache`].
+pub fn from_config(
+    config: &Config,
+    api_token: &SecretString,
+    limiter: Limiter,
+) -> Result<Box<dyn DnsApiClient>> {
+    Ok(match config.provider.r#type {
+        ProviderKind::Cloudflare => Box::new(cloudflare_client(config, api_token, limiter)),
+        ProviderKind::Exec => Box::new(ExecClient::new(&config.provider, api_token)?),
+    })
+}
+
+// The same, for servers answering requests from tasks of their own
+pub(crate) fn shared_from_config(
+    config: &Config,
+    api_token: &SecretString,
+    limiter: Limiter,
+) -> Result<Arc<dyn DnsApiClient + Send + Sync>> {
+    Ok(match config.provider.r#type {
+        ProviderKind::Cloudflare => Arc::new(cloudflare_client(config, api_token, limiter)),
+        ProviderKind::Exec => Arc::new(ExecClient::new(&config.provider, api_token)?),
+    })
+}
+
+fn cloudflare_client(config: &Config, api_token: &SecretString, limiter: Limiter) -> ZoneCache {
+    let client = CloudflareClient::new(api_token, &config.retry, limiter);
+    let ttl = Duration::from_secs(config.zone_cache_ttl * 60);
+    ZoneCache::new(Arc::new(client), ttl)
+}
+
+/// [`from_config`], with the zones of each tenant handled by a client of the tenant's own token.
+/// The tenants share `limiter`, the rate limit being one for the whole process.
+pub fn with_tenants(
+    config: &Config,
+    api_token: &SecretString,
+    tenant_tokens: &BTreeMap<String, SecretString>,
+    limiter: Limiter,
+) -> Result<Box<dyn DnsApiClient>> {
+    if config.tenants.is_empty() {
+        return from_config(config, api_token, limiter);
+    }
+    let default = shared_from_config(config, api_token, limiter.clone())?;
+    let mut tenants = HashMap::new();
+    for (tenant, token) in tenant_tokens {
+        tenants.insert(
+            tenant.clone(),
+            shared_from_config(config, token, limiter.clone())?,
+        );
+    }
+    Ok(Box::new(TenantRouter::new(
+        default,
+        tenants,
+        config.tenant_zones(),
+    )))
+}
+
+/// The API token of every tenant, from wherever it is kept
+pub async fn tenant_tokens(config: &Config) -> Result<BTreeMap<String, SecretString>> {
+    let mut tokens = BTreeMap::new();
+    for (name, tenant) in &config.tenants {
+        let token = secrets::resolve(&tenant.api_token)
+            .await
+            .with_context(|| format!("Failed to read the API token of tenant {}", name))?;
+        tokens.insert(name.clone(), token);
+    }
+    Ok(tokens)
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ApiDnsRecord {
     pub id: String,
     pub name: String,
@@ -11,7 +11,7 @@ pub struct ApiDnsRecord {
     pub ttl: u32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct DnsRecordUpdate {
     pub id: String,
     pub name: String,
@@ -19,12 +19,59 @@ pub struct DnsRecordUpdate {
     pub ttl: u32,
     pub proxied: bool,
     pub r#type: String,
+    // When the record last changed on the provider side
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified_on: Option<String>,
+}
+
+impl From<ApiDnsRecord> for DnsRecordUpdate {
+    fn from(record: ApiDnsRecord) -> Self {
+        Self {
+            id: record.id,
+            name: record.name,
+            content: record.content,
+            ttl: record.ttl,
+            proxied: record.proxied,
+            r#type: record.r#type,
+            modified_on: None,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize)]
-pub struct ApiResponse<T> {
+pub(crate) struct ApiResponse<T> {
     pub result: T,
     pub success: bool,
     #[serde(default)]
-    pub errors: Vec<serde_json::Value>,
+    pub errors: Vec<ApiError>,
+    #[serde(default)]
+    pub result_info: Option<ResultInfo>,
+}
+
+/// One of the errors Cloudflare reports in a response, see [`CloudflareError`]
+///
+/// [`CloudflareError`]: super::CloudflareError
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ApiError {
+    pub code: u32,
+    pub message: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct ResultInfo {
+    pub page: u32,
+    pub total_pages: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiZone {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenStatus {
+    pub status: String,
 }
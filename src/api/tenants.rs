@@ -0,0 +1,99 @@
+use super::{client::DnsApiClient, content::RecordContent, models::*};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::{collections::HashMap, sync::Arc};
+
+type SharedClient = Arc<dyn DnsApiClient + Send + Sync>;
+
+/// Sends what concerns a tenant's zones through the client of the tenant's token and everything
+/// else through the default one, so that no token is ever used on another customer's zones
+pub struct TenantRouter {
+    default: SharedClient,
+    // By zone ID
+    zones: HashMap<String, SharedClient>,
+    // One per tenant, for listing the zones
+    tenants: Vec<SharedClient>,
+}
+
+impl TenantRouter {
+    pub fn new(
+        default: SharedClient,
+        tenants: HashMap<String, SharedClient>,
+        zone_tenants: impl IntoIterator<Item = (String, String)>,
+    ) -> Self {
+        let zones = zone_tenants
+            .into_iter()
+            .filter_map(|(zone, tenant)| Some((zone, tenants.get(&tenant)?.clone())))
+            .collect();
+        Self {
+            default,
+            zones,
+            tenants: tenants.into_values().collect(),
+        }
+    }
+
+    fn client(&self, zone_id: &str) -> &(dyn DnsApiClient + Send + Sync) {
+        &**self.zones.get(zone_id).unwrap_or(&self.default)
+    }
+}
+
+#[async_trait]
+impl DnsApiClient for TenantRouter {
+    // Every zone any of the tokens can see, once
+    async fn list_zones(&self) -> Result<Vec<ApiZone>> {
+        let mut zones = self.default.list_zones().await?;
+        for client in &self.tenants {
+            for zone in client.list_zones().await? {
+                if !zones.iter().any(|known| known.id == zone.id) {
+                    zones.push(zone);
+                }
+            }
+        }
+        Ok(zones)
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>> {
+        self.client(zone_id).list_records(zone_id).await
+    }
+
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate> {
+        self.client(zone_id)
+            .get_record(zone_id, domain, record_type)
+            .await
+    }
+
+    async fn update_record(
+        &self,
+        zone_id: &str,
+        record: &DnsRecordUpdate,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        self.client(zone_id)
+            .update_record(zone_id, record, content, ttl, proxied)
+            .await
+    }
+
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        self.client(zone_id)
+            .create_record(zone_id, name, content, ttl, proxied)
+            .await
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        self.client(zone_id).delete_record(zone_id, record_id).await
+    }
+}
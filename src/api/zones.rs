@@ -0,0 +1,197 @@
+use super::{client::DnsApiClient, content::RecordContent, models::*};
+use crate::error::ErrorKind;
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{debug, warn};
+use reqwest::StatusCode;
+use std::{
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+type SharedClient = Arc<dyn DnsApiClient + Send + Sync>;
+
+/// Lets zones be given by name rather than ID: a zone ID with a dot in it is taken for the zone's
+/// name and looked up among the zones the token can see. Those are listed once and kept for `ttl`,
+/// then listed again in the background while the previous list still answers. An answer that a
+/// zone doesn't exist or that the token may not use it has them listed again at the next request.
+pub struct ZoneCache {
+    client: SharedClient,
+    ttl: Duration,
+    cached: Arc<Mutex<Cached>>,
+    listing: tokio::sync::Mutex<()>,
+}
+
+#[derive(Default)]
+struct Cached {
+    zones: Vec<ApiZone>,
+    // None until listed, and once invalidated
+    listed: Option<Instant>,
+    refreshing: bool,
+}
+
+impl ZoneCache {
+    pub fn new(client: SharedClient, ttl: Duration) -> Self {
+        Self {
+            client,
+            ttl,
+            cached: Arc::default(),
+            listing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Cached> {
+        self.cached.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    async fn zones(&self) -> Result<Vec<ApiZone>> {
+        if let Some(zones) = self.cached() {
+            return Ok(zones);
+        }
+        // Requests made together wait on one listing
+        let _listing = self.listing.lock().await;
+        if let Some(zones) = self.cached() {
+            return Ok(zones);
+        }
+        let zones = self.client.list_zones().await?;
+        let mut cached = self.lock();
+        cached.zones = zones.clone();
+        cached.listed = Some(Instant::now());
+        Ok(zones)
+    }
+
+    // The zones listed, refreshed in the background once they have been kept for the TTL
+    fn cached(&self) -> Option<Vec<ApiZone>> {
+        let mut cached = self.lock();
+        let listed = cached.listed?;
+        if listed.elapsed() >= self.ttl && !cached.refreshing {
+            cached.refreshing = true;
+            self.refresh();
+        }
+        Some(cached.zones.clone())
+    }
+
+    fn refresh(&self) {
+        let (client, cached) = (self.client.clone(), self.cached.clone());
+        tokio::spawn(async move {
+            let zones = client.list_zones().await;
+            let mut cached = cached.lock().unwrap_or_else(|e| e.into_inner());
+            cached.refreshing = false;
+            match zones {
+                Ok(zones) => {
+                    debug!("Refreshed the {} zones the API token can see", zones.len());
+                    cached.zones = zones;
+                    cached.listed = Some(Instant::now());
+                }
+                Err(e) if is_stale(&e) => cached.listed = None,
+                Err(e) => warn!("Failed to refresh the zones, keeping those listed: {:#}", e),
+            }
+        });
+    }
+
+    // The ID of a zone given by name, any other ID as it is
+    async fn id(&self, zone: &str) -> Result<String> {
+        if !is_name(zone) {
+            return Ok(zone.to_string());
+        }
+        let name = zone.trim_end_matches('.');
+        let zones = self.zones().await?;
+        match zones.into_iter().find(|listed| listed.name == name) {
+            Some(listed) => Ok(listed.id),
+            None => {
+                // Perhaps added since
+                self.lock().listed = None;
+                anyhow::bail!("The API token can't see any zone named {}", name)
+            }
+        }
+    }
+
+    fn check<T>(&self, zone: &str, result: Result<T>) -> Result<T> {
+        if let Err(e) = &result {
+            if is_name(zone) && is_stale(e) {
+                debug!("Looking zone {} up again at the next request", zone);
+                self.lock().listed = None;
+            }
+        }
+        result
+    }
+}
+
+// Zone IDs are hexadecimal
+pub(crate) fn is_name(zone: &str) -> bool {
+    zone.contains('.')
+}
+
+// The zone is gone, or was moved out of the token's reach
+fn is_stale(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<ErrorKind>() == Some(&ErrorKind::Auth)
+        || error
+            .downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status)
+            == Some(StatusCode::NOT_FOUND)
+}
+
+#[async_trait]
+impl DnsApiClient for ZoneCache {
+    async fn list_zones(&self) -> Result<Vec<ApiZone>> {
+        self.zones().await
+    }
+
+    async fn list_records(&self, zone_id: &str) -> Result<Vec<ApiDnsRecord>> {
+        let id = self.id(zone_id).await?;
+        self.check(zone_id, self.client.list_records(&id).await)
+    }
+
+    async fn get_record(
+        &self,
+        zone_id: &str,
+        domain: &str,
+        record_type: &str,
+    ) -> Result<DnsRecordUpdate> {
+        let id = self.id(zone_id).await?;
+        self.check(
+            zone_id,
+            self.client.get_record(&id, domain, record_type).await,
+        )
+    }
+
+    async fn update_record(
+        &self,
+        zone_id: &str,
+        record: &DnsRecordUpdate,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        let id = self.id(zone_id).await?;
+        self.check(
+            zone_id,
+            self.client
+                .update_record(&id, record, content, ttl, proxied)
+                .await,
+        )
+    }
+
+    async fn create_record(
+        &self,
+        zone_id: &str,
+        name: &str,
+        content: &RecordContent,
+        ttl: u32,
+        proxied: bool,
+    ) -> Result<ApiDnsRecord> {
+        let id = self.id(zone_id).await?;
+        self.check(
+            zone_id,
+            self.client
+                .create_record(&id, name, content, ttl, proxied)
+                .await,
+        )
+    }
+
+    async fn delete_record(&self, zone_id: &str, record_id: &str) -> Result<()> {
+        let id = self.id(zone_id).await?;
+        self.check(zone_id, self.client.delete_record(&id, record_id).await)
+    }
+}
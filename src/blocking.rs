@@ -0,0 +1,77 @@
+//! A synchronous face on the updater, for programs that don't run an async runtime themselves.
+//!
+//! ```no_run
+//! # fn main() -> Result<(), clouddns::DdnsError> {
+//! let mut ddns = clouddns::blocking::Ddns::new("/etc/clouddns/config.toml", None)?
+//!     .state(Some("/var/lib/clouddns/state.json"))?;
+//! ddns.run_once()?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::config::Config;
+use crate::error::DdnsError;
+use crate::CloudflareDdns;
+use anyhow::Context;
+use tokio::runtime::{Builder, Runtime};
+
+/// Wraps a [`CloudflareDdns`] with a runtime of its own, each call blocking until done. Calling it
+/// from within an async runtime panics, async code should use [`CloudflareDdns`] directly.
+pub struct Ddns {
+    runtime: Runtime,
+    inner: CloudflareDdns,
+}
+
+impl Ddns {
+    /// Loads the configuration, with the profile if given, and reads the API token
+    pub fn new(config_file: &str, profile: Option<&str>) -> Result<Self, DdnsError> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(CloudflareDdns::new(config_file, profile))?;
+        Ok(Self { runtime, inner })
+    }
+
+    /// Starts from a configuration already in memory
+    pub fn from_config(config: Config) -> Result<Self, DdnsError> {
+        let runtime = runtime()?;
+        let inner = runtime.block_on(CloudflareDdns::builder(config).build())?;
+        Ok(Self { runtime, inner })
+    }
+
+    /// Keeps the state in a file, see [`CloudflareDdns::state`]
+    pub fn state(mut self, state_file: Option<&str>) -> Result<Self, DdnsError> {
+        self.inner = self.inner.state(state_file)?;
+        Ok(self)
+    }
+
+    /// Reads the records but never updates them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.inner = self.inner.dry_run(dry_run);
+        self
+    }
+
+    /// A single detection and update pass over every enabled domain, updating the records that
+    /// changed, as `clouddns once` does
+    pub fn run_once(&mut self) -> Result<(), DdnsError> {
+        self.runtime.block_on(self.inner.run_once(false))
+    }
+
+    /// Pushes every enabled record now, even those already up to date, as `clouddns force-update`
+    /// does
+    pub fn update(&mut self) -> Result<(), DdnsError> {
+        self.runtime.block_on(self.inner.run_once(true))
+    }
+
+    /// The async updater, for what this facade doesn't cover
+    pub fn inner(&mut self) -> &mut CloudflareDdns {
+        &mut self.inner
+    }
+}
+
+// One thread is plenty, records are checked concurrently on it
+fn runtime() -> Result<Runtime, DdnsError> {
+    Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to start a runtime")
+        .map_err(DdnsError::from)
+}
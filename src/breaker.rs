@@ -0,0 +1,60 @@
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use tokio::time::{Duration, Instant};
+
+// Consecutive failures before an endpoint is left alone, and for how long
+const FAILURE_THRESHOLD: u32 = 3;
+const OPEN_FOR: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Default)]
+struct Circuit {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+// Endpoints that keep failing are skipped for a while, instead of being queried (and
+// logged about) on every cycle
+#[derive(Debug, Default)]
+pub struct Breakers {
+    circuits: HashMap<String, Circuit>,
+}
+
+impl Breakers {
+    // Once the pause is over a single attempt goes through, and its outcome decides
+    // whether the circuit closes or stays open
+    pub fn allows(&self, endpoint: &str, now: Instant) -> bool {
+        self.circuits
+            .get(endpoint)
+            .and_then(|circuit| circuit.open_until)
+            .is_none_or(|open_until| now >= open_until)
+    }
+
+    pub fn success(&mut self, endpoint: &str) {
+        if let Some(Circuit {
+            open_until: Some(_),
+            ..
+        }) = self.circuits.remove(endpoint)
+        {
+            info!("{} is responding again", endpoint);
+        }
+    }
+
+    pub fn failure(&mut self, endpoint: &str, now: Instant) {
+        let circuit = self.circuits.entry(endpoint.to_string()).or_default();
+        circuit.failures += 1;
+        if circuit.failures < FAILURE_THRESHOLD {
+            return;
+        }
+
+        match circuit.open_until {
+            None => warn!(
+                "{} failed {} times in a row, leaving it alone for {} minutes",
+                endpoint,
+                circuit.failures,
+                OPEN_FOR.as_secs() / 60
+            ),
+            Some(_) => debug!("{} is still failing", endpoint),
+        }
+        circuit.open_until = Some(now + OPEN_FOR);
+    }
+}
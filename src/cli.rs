@@ -0,0 +1,384 @@
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use clouddns::config::{ConfigFormat, IpSource, RecordTypes, DEFAULT_KEYRING_ACCOUNT};
+use std::{net::IpAddr, path::PathBuf};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Dynamic DNS updater for Cloudflare",
+    long_about = "Dynamic DNS updater for Cloudflare\n\n\
+        Keeps A and AAAA records pointed at the public address of this machine. \
+        Without a command, runs the updater in the foreground.",
+    after_help = "Examples:\n  \
+        clouddns init                        Create config.toml interactively\n  \
+        clouddns -c /etc/clouddns.yaml       Run with another configuration file\n  \
+        clouddns validate --online           Check the configuration and the API token\n  \
+        clouddns list --profile travel       Show the records of a profile"
+)]
+pub struct Cli {
+    /// Path to the configuration file (TOML, YAML or JSON)
+    #[arg(
+        short,
+        long,
+        global = true,
+        env = "CLOUDDNS_CONFIG",
+        default_value = "config.toml"
+    )]
+    pub config: String,
+
+    /// Profile from the configuration file to activate
+    #[arg(short, long, global = true, env = "CLOUDDNS_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Log more: -v for info, -vv for debug, -vvv for trace
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    pub verbose: u8,
+
+    /// Log less: -q for errors only, -qq for nothing
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    pub quiet: u8,
+
+    /// Log filter such as `debug` or `clouddns::ddns=debug,reqwest=off`, overrides -q/-v per module
+    #[arg(long, global = true, env = "CLOUDDNS_LOG", value_name = "FILTER")]
+    pub log_level: Option<String>,
+
+    /// Read the records and print what would change, without updating anything. `once` then exits
+    /// with 7 if something would change, 0 if everything is in sync
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Address to use instead of detecting it, for drills or a known upcoming address. Once per
+    /// family, families without one are detected as usual
+    #[arg(long = "ip", global = true, value_name = "ADDRESS")]
+    pub ip_override: Vec<IpAddr>,
+
+    /// Lock file keeping a second instance from managing the same configuration
+    #[arg(long, global = true, env = "CLOUDDNS_LOCK_FILE", value_name = "PATH")]
+    pub lock_file: Option<String>,
+
+    /// File remembering addresses and record history across restarts
+    #[arg(long, global = true, env = "CLOUDDNS_STATE_FILE", value_name = "PATH")]
+    pub state_file: Option<String>,
+
+    /// Save the requests to the DNS provider and its answers to this cassette file, secrets
+    /// masked, e.g. to attach to a bug report
+    #[arg(
+        long,
+        global = true,
+        value_name = "PATH",
+        conflicts_with = "replay_api"
+    )]
+    pub record_api: Option<String>,
+
+    /// Answer the requests to the DNS provider from a cassette saved with --record-api, without
+    /// reaching it. With --ip, nothing goes over the network
+    #[arg(long, global = true, value_name = "PATH")]
+    pub replay_api: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Keep the configured records up to date (default)
+    Run,
+    /// Update the records once and exit, non-zero if anything failed
+    Once,
+    /// Push every enabled record now, even those already up to date, and exit
+    ForceUpdate,
+    /// Keep the records up to date with a live dashboard of addresses, records and events
+    Watch,
+    /// Check on or steer the running daemon through its control socket
+    #[cfg(unix)]
+    Ctl {
+        /// Socket to connect to, defaults to control_socket from the configuration
+        #[arg(long, value_name = "PATH")]
+        socket: Option<String>,
+        #[command(subcommand)]
+        action: CtlCommand,
+    },
+    /// Create a configuration file interactively
+    Init,
+    /// List the configured records and their effective settings
+    List {
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Show the detected addresses and whether each record is in sync
+    Status {
+        /// Only the records of this tenant
+        #[arg(long)]
+        tenant: Option<String>,
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Compare the configured records with what the provider serves, without updating them
+    Diff {
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Show what `apply` would create, change and delete, optionally saving it for `apply`
+    Plan {
+        /// Save the plan to this file, for `apply` to carry out exactly
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+        /// Exit with 7 when there are changes, 0 when there are none
+        #[arg(long)]
+        detailed_exitcode: bool,
+    },
+    /// Create, change and delete records to match the configuration, or carry out a saved plan
+    Apply {
+        /// Plan saved with `plan --out`, refused if the records changed since
+        plan: Option<PathBuf>,
+        /// Apply without asking for confirmation
+        #[arg(long)]
+        auto_approve: bool,
+    },
+    /// Show the changes made to the records and the failed updates: when, from what to what,
+    /// and why
+    #[command(args_conflicts_with_subcommands = true)]
+    History {
+        #[command(subcommand)]
+        action: Option<HistoryCommand>,
+        #[command(flatten)]
+        query: HistoryQueryArgs,
+    },
+    /// Detect the public addresses and print them, without a configuration
+    Ip {
+        /// Service to ask: ipify, icanhazip, cloudflare or an http(s) URL
+        #[arg(short, long)]
+        source: Option<IpSource>,
+        /// Only detect the IPv4 address
+        #[arg(short = '4', long, conflicts_with = "ipv6")]
+        ipv4: bool,
+        /// Only detect the IPv6 address
+        #[arg(short = '6', long)]
+        ipv6: bool,
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Add a record to the configuration file
+    Add {
+        /// Zone the record belongs to, by name or ID
+        zone: String,
+        /// Record name, relative to the zone (`home`, `@`) or fully qualified
+        record: String,
+        /// Zone ID, needed when the zone is not in the configuration yet
+        #[arg(long)]
+        zone_id: Option<String>,
+        /// Record type: A, AAAA or both
+        #[arg(short = 't', long = "type")]
+        record_type: Option<RecordTypes>,
+        /// Route the record through Cloudflare's proxy
+        #[arg(long)]
+        proxied: bool,
+        /// Record TTL in seconds
+        #[arg(long)]
+        ttl: Option<u32>,
+        /// Update the records right after editing the file
+        #[arg(long)]
+        update: bool,
+        /// Rewrite a YAML file even though its comments are lost
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove a record from the configuration file
+    Remove {
+        /// Zone the record belongs to, by name or ID
+        zone: String,
+        /// Record name, relative to the zone (`home`, `@`) or fully qualified
+        record: String,
+        /// Rewrite a YAML file even though its comments are lost
+        #[arg(long)]
+        force: bool,
+    },
+    /// Check the configuration file and report every problem found
+    Validate {
+        /// Also check the token, zones and records against the API
+        #[arg(long)]
+        online: bool,
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Check the configuration, address detection, token, zones, records and clock
+    Doctor,
+    /// Print the JSON Schema of the configuration file
+    Schema,
+    /// Work with configuration files
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Convert a config file from the legacy zone_id/domain_list layout
+    MigrateConfig {
+        /// Config file to convert, defaults to --config
+        input: Option<String>,
+        /// Where to write the converted config, printed to stdout when omitted
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Output format (toml, yaml or json), defaults to the output extension or the input format
+        #[arg(short, long)]
+        format: Option<ConfigFormat>,
+    },
+    /// Convert a ddclient configuration, for its hosts using the cloudflare protocol
+    ImportDdclient {
+        /// ddclient configuration file
+        #[arg(default_value = "/etc/ddclient.conf")]
+        input: String,
+        /// Where to write the converted config, printed to stdout when omitted
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Output format (toml, yaml or json), defaults to the output extension or toml
+        #[arg(short, long)]
+        format: Option<ConfigFormat>,
+        /// Look the zone IDs up with the imported API token
+        #[arg(long)]
+        online: bool,
+    },
+    /// Convert an inadyn configuration, for its cloudflare.com providers
+    ImportInadyn {
+        /// inadyn configuration file
+        #[arg(default_value = "/etc/inadyn.conf")]
+        input: String,
+        /// Where to write the converted config, printed to stdout when omitted
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Output format (toml, yaml or json), defaults to the output extension or toml
+        #[arg(short, long)]
+        format: Option<ConfigFormat>,
+        /// Look the zone IDs up with the imported API token
+        #[arg(long)]
+        online: bool,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to generate the script for
+        shell: Shell,
+    },
+    /// Print a hardened systemd service unit for this binary and configuration
+    SystemdUnit {
+        /// File holding the API token, passed to the service as a credential
+        #[arg(long, value_name = "PATH")]
+        token_file: Option<String>,
+    },
+    /// Manage the API token stored in the platform keyring
+    Token {
+        #[command(subcommand)]
+        action: TokenCommand,
+    },
+    /// Run as a Windows service
+    #[cfg(windows)]
+    Service {
+        #[command(subcommand)]
+        action: ServiceCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print a commented configuration covering every option
+    Example,
+}
+
+#[cfg(unix)]
+#[derive(Debug, Subcommand)]
+pub enum CtlCommand {
+    /// Show the addresses and record states the daemon knows about
+    Status {
+        /// Output format: text or json
+        #[arg(short, long, value_enum, default_value_t = OutputFormat::Text)]
+        output: OutputFormat,
+    },
+    /// Check every record now
+    Update,
+    /// Push every enabled record now, even those already up to date
+    ForceUpdate,
+    /// Stop the scheduled checks until resumed
+    Pause,
+    /// Resume the scheduled checks
+    Resume,
+    /// Reload the configuration file
+    Reload,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// Store a token, prompting for it (or reading it from stdin when not a terminal)
+    Set {
+        /// Keyring entry to store the token under
+        #[arg(long, default_value = DEFAULT_KEYRING_ACCOUNT)]
+        account: String,
+    },
+    /// Remove a stored token
+    Delete {
+        /// Keyring entry to remove
+        #[arg(long, default_value = DEFAULT_KEYRING_ACCOUNT)]
+        account: String,
+    },
+}
+
+// Read-only commands print a table by default; json output is a stable
+// structure meant for scripts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+    /// Look up the history by domain and time range, what `history` alone does too
+    Query(HistoryQueryArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct HistoryQueryArgs {
+    /// Only the records of this name and the names under it, e.g. a zone
+    #[arg(long)]
+    pub domain: Option<String>,
+    /// Only since then: a span back such as 7d, 12h or 30m, or a date such as 2024-05-01
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Only before then, in the same forms as --since
+    #[arg(long)]
+    pub until: Option<String>,
+    /// Only the failed updates
+    #[arg(long)]
+    pub failed: bool,
+    /// Only the last N entries
+    #[arg(short = 'n', long, value_name = "N")]
+    pub limit: Option<usize>,
+    /// Output format: text, csv or json
+    #[arg(short, long, value_enum, default_value_t = HistoryFormat::Text)]
+    pub output: HistoryFormat,
+}
+
+// The history also goes to spreadsheets
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum HistoryFormat {
+    Text,
+    Csv,
+    Json,
+}
+
+#[cfg(windows)]
+#[derive(Debug, Subcommand)]
+pub enum ServiceCommand {
+    /// Register clouddns as a service started at boot, with the current --config and --profile
+    Install,
+    /// Stop and remove the service
+    Uninstall,
+    /// Entry point used by the service control manager
+    #[command(hide = true)]
+    Run,
+}
@@ -0,0 +1,17 @@
+use chrono::{DateTime, Local};
+
+/// The wall clock the updater reads maintenance windows and timestamps from, which embedders and
+/// tests can replace with their own
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// The system's clock, in the local time zone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
@@ -0,0 +1,137 @@
+use super::{print_json, print_table};
+use crate::cli::{CtlCommand, OutputFormat};
+use anyhow::{anyhow, bail, Context, Result};
+use clouddns::config;
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::snapshot::Report;
+use std::path::PathBuf;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+// Sends one request to the daemon and prints its answer
+pub async fn ctl(
+    config_file: &str,
+    profile: Option<&str>,
+    socket: Option<&str>,
+    action: CtlCommand,
+) -> Result<()> {
+    let path = match socket {
+        Some(socket) => PathBuf::from(socket),
+        None => config::load(config_file, profile)
+            .kind(ErrorKind::Config)?
+            .control_socket
+            .ok_or_else(|| anyhow!("No control_socket in {}, pass --socket", config_file))
+            .kind(ErrorKind::Config)?,
+    };
+
+    let (request, output) = match action {
+        CtlCommand::Status { output } => ("status", Some(output)),
+        CtlCommand::Update => ("update", None),
+        CtlCommand::ForceUpdate => ("force-update", None),
+        CtlCommand::Pause => ("pause", None),
+        CtlCommand::Resume => ("resume", None),
+        CtlCommand::Reload => ("reload", None),
+    };
+
+    let mut stream = UnixStream::connect(&path).await.with_context(|| {
+        format!(
+            "Failed to connect to {}, is the daemon running?",
+            path.display()
+        )
+    })?;
+    stream
+        .write_all(format!("{}\n", request).as_bytes())
+        .await?;
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await?;
+    let response = response.trim_end();
+    if let Some(error) = response.strip_prefix("error: ") {
+        bail!("The daemon refused the request: {}", error);
+    }
+
+    match output {
+        Some(output) => {
+            let report: Report =
+                serde_json::from_str(response).context("Unexpected answer from the daemon")?;
+            print_report(&report, output)
+        }
+        None => {
+            println!("Request sent, the daemon handles it between checks");
+            Ok(())
+        }
+    }
+}
+
+fn print_report(report: &Report, output: OutputFormat) -> Result<()> {
+    if output == OutputFormat::Json {
+        return print_json(report);
+    }
+
+    let updates = match (report.paused, report.in_maintenance) {
+        (true, _) => "paused",
+        (false, true) => "held by a maintenance window",
+        (false, false) => "running",
+    };
+    println!("Updates:      {}", updates);
+    println!(
+        "Next check:   {}",
+        report
+            .next_check
+            .map_or_else(|| "-".to_string(), |secs| format!("in {}s", secs))
+    );
+    println!(
+        "Last success: {}",
+        report
+            .last_success
+            .map_or_else(|| "never".to_string(), |secs| format!("{}s ago", secs))
+    );
+    println!();
+
+    println!("Detected addresses:");
+    for address in &report.addresses {
+        match (&address.address, &address.error) {
+            (Some(ip), _) => println!("  {:<4} {:<12} {}", address.r#type, address.source, ip),
+            (None, error) => println!(
+                "  {:<4} {:<12} failed: {}",
+                address.r#type,
+                address.source,
+                error.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+    println!();
+
+    let tenants = report.records.iter().any(|record| record.tenant.is_some());
+    let mut header = vec![
+        "RECORD".to_string(),
+        "TYPE".to_string(),
+        "CONTENT".to_string(),
+        "STATE".to_string(),
+        "CHECKED".to_string(),
+    ];
+    if tenants {
+        header.insert(0, "TENANT".to_string());
+    }
+    let mut rows = vec![header];
+    for record in &report.records {
+        let state = match &record.error {
+            Some(error) => format!("{}: {}", record.state, error),
+            None => record.state.replace('_', " "),
+        };
+        let tenant = tenants.then(|| record.tenant.clone().unwrap_or_else(|| "-".to_string()));
+        rows.push(
+            tenant
+                .into_iter()
+                .chain([
+                    record.record.clone(),
+                    record.r#type.clone(),
+                    record.content.clone().unwrap_or_else(|| "-".to_string()),
+                    state,
+                    record.checked.format("%Y-%m-%d %H:%M:%S").to_string(),
+                ])
+                .collect(),
+        );
+    }
+    print_table(&rows)?;
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use super::print_json;
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use clouddns::api::{self, Limiter, RecordContent};
+use clouddns::config::{self, IpSource, RecordType};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::secrets;
+use clouddns::{http, ip};
+use ratatui::crossterm::style::Stylize;
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    env,
+    io::{self, IsTerminal},
+    net::IpAddr,
+};
+
+#[derive(Debug, Serialize)]
+struct RecordDiff {
+    record: String,
+    r#type: &'static str,
+    status: DiffStatus,
+    changes: Vec<Change>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum DiffStatus {
+    InSync,
+    Different,
+    // The provider has no such record
+    Missing,
+    Error,
+}
+
+#[derive(Debug, Serialize)]
+struct Change {
+    field: &'static str,
+    provider: String,
+    desired: String,
+}
+
+// Compare what the configuration asks for with what the provider serves, without updating anything
+pub async fn diff(config_file: &str, profile: Option<&str>, output: OutputFormat) -> Result<()> {
+    let config = config::load(config_file, profile)
+        .and_then(|config| config::validate(&config).map(|()| config))
+        .kind(ErrorKind::Config)?;
+    http::configure(&config).await.kind(ErrorKind::Config)?;
+    let api_token = secrets::resolve(&config.api_token)
+        .await
+        .kind(ErrorKind::Auth)?;
+    let tenant_tokens = api::tenant_tokens(&config).await.kind(ErrorKind::Auth)?;
+    let limiter = Limiter::new(config.api_rate_limit());
+    let client =
+        api::with_tenants(&config, &api_token, &tenant_tokens, limiter).kind(ErrorKind::Config)?;
+
+    let mut detected: HashMap<(IpSource, RecordType), Result<IpAddr, String>> = HashMap::new();
+    let mut diffs = Vec::new();
+
+    for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+        for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+            let settings = config.settings(zone, domain);
+
+            for &record_type in settings.record_types.types() {
+                let source = (settings.ip_source.clone(), record_type);
+                if !detected.contains_key(&source) {
+                    let ip = ip::detect(&settings.ip_source, record_type, &config.retry)
+                        .await
+                        .map_err(|e| format!("{:#}", e));
+                    detected.insert(source.clone(), ip);
+                }
+                let desired_ip = &detected[&source];
+
+                for name in domain.record_names(zone) {
+                    let mut diff = RecordDiff {
+                        record: name,
+                        r#type: record_type.as_str(),
+                        status: DiffStatus::InSync,
+                        changes: Vec::new(),
+                        error: None,
+                    };
+
+                    let record = match client
+                        .get_record(&zone.id, &diff.record, record_type.as_str())
+                        .await
+                    {
+                        Ok(record) => record,
+                        Err(e) => {
+                            diff.status = match e.to_string().contains("not found") {
+                                true => DiffStatus::Missing,
+                                false => DiffStatus::Error,
+                            };
+                            diff.error = Some(format!("{:#}", e));
+                            diffs.push(diff);
+                            continue;
+                        }
+                    };
+
+                    match desired_ip {
+                        Ok(ip) if !RecordContent::from(*ip).matches(&record.content) => {
+                            diff.changes.push(Change {
+                                field: "content",
+                                provider: record.content.clone(),
+                                desired: ip.to_string(),
+                            })
+                        }
+                        Ok(_) => {}
+                        // The other fields can still be compared
+                        Err(e) => diff.error = Some(e.clone()),
+                    }
+                    // Cloudflare reports an automatic TTL for proxied records, whatever was asked
+                    let proxied = settings.proxied.unwrap_or(record.proxied);
+                    let ttl = settings
+                        .ttl
+                        .filter(|&ttl| ttl != record.ttl && !(record.proxied && proxied));
+                    if let Some(ttl) = ttl {
+                        diff.changes.push(Change {
+                            field: "ttl",
+                            provider: record.ttl.to_string(),
+                            desired: ttl.to_string(),
+                        });
+                    }
+                    if let Some(proxied) = settings.proxied.filter(|&p| p != record.proxied) {
+                        diff.changes.push(Change {
+                            field: "proxied",
+                            provider: record.proxied.to_string(),
+                            desired: proxied.to_string(),
+                        });
+                    }
+
+                    diff.status = match (diff.changes.is_empty(), &diff.error) {
+                        (false, _) => DiffStatus::Different,
+                        (true, Some(_)) => DiffStatus::Error,
+                        (true, None) => DiffStatus::InSync,
+                    };
+                    diffs.push(diff);
+                }
+            }
+        }
+    }
+
+    match output {
+        OutputFormat::Json => print_json(&diffs)?,
+        OutputFormat::Text => print_diff(&diffs),
+    }
+
+    let out_of_sync = diffs
+        .iter()
+        .filter(|diff| matches!(diff.status, DiffStatus::Different | DiffStatus::Missing))
+        .count();
+    if out_of_sync > 0 {
+        return Err(anyhow::anyhow!(
+            "{} record(s) differ from the configuration",
+            out_of_sync
+        ))
+        .kind(ErrorKind::PendingChanges);
+    }
+    if diffs.iter().any(|diff| diff.status == DiffStatus::Error) {
+        return Err(anyhow::anyhow!("Some records couldn't be compared"));
+    }
+    Ok(())
+}
+
+fn print_diff(diffs: &[RecordDiff]) {
+    // Colors only for people, and not for those who opted out
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let paint = |line: String, sign: char| match (color, sign) {
+        (true, '-') => line.red().to_string(),
+        (true, '+') => line.green().to_string(),
+        (true, '!') => line.yellow().to_string(),
+        _ => line,
+    };
+
+    for diff in diffs {
+        let name = format!("{} {}", diff.record, diff.r#type);
+        match diff.status {
+            DiffStatus::InSync => println!("  {}", name),
+            DiffStatus::Missing => println!("{}", paint(format!("+ {} (missing)", name), '+')),
+            DiffStatus::Error if diff.changes.is_empty() => println!(
+                "{}",
+                paint(
+                    format!("! {}: {}", name, diff.error.as_deref().unwrap_or_default()),
+                    '!'
+                )
+            ),
+            _ => {
+                println!("~ {}", name);
+                for change in &diff.changes {
+                    println!(
+                        "{}",
+                        paint(format!("-   {} {}", change.field, change.provider), '-')
+                    );
+                    println!(
+                        "{}",
+                        paint(format!("+   {} {}", change.field, change.desired), '+')
+                    );
+                }
+                if let Some(error) = &diff.error {
+                    println!("{}", paint(format!("!   {}", error), '!'));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::Result;
+use clouddns::api::{CloudflareClient, DnsApiClient, ExecClient, Limiter};
+use clouddns::config::{self, Config, IpSource, ProviderKind, RecordType};
+use clouddns::secrets;
+use clouddns::{http, ip};
+use secrecy::SecretString;
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+// Beyond this, TLS and token checks start failing in confusing ways
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(300);
+
+enum Outcome {
+    Pass(String),
+    Fail { problem: String, fix: String },
+    Skip(String),
+}
+
+struct Report {
+    failures: usize,
+}
+
+impl Report {
+    fn check(&mut self, name: &str, outcome: Outcome) {
+        match outcome {
+            Outcome::Pass(detail) => println!("[ ok ] {}: {}", name, detail),
+            Outcome::Skip(reason) => println!("[skip] {}: {}", name, reason),
+            Outcome::Fail { problem, fix } => {
+                self.failures += 1;
+                println!("[FAIL] {}: {}", name, problem);
+                println!("       fix: {}", fix);
+            }
+        }
+    }
+}
+
+// reqwest errors already repeat their causes in their message, the root one says it all
+fn describe(e: &anyhow::Error) -> String {
+    let request = e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<reqwest::Error>());
+    match request {
+        Some(request) if !request.is_status() => match request.url() {
+            Some(url) => format!("request to {} failed: {}", url, e.root_cause()),
+            None => e.root_cause().to_string(),
+        },
+        _ => format!("{:#}", e),
+    }
+}
+
+fn network_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| cause.is::<reqwest::Error>())
+}
+
+fn fail(problem: impl ToString, fix: impl ToString) -> Outcome {
+    Outcome::Fail {
+        problem: problem.to_string(),
+        fix: fix.to_string(),
+    }
+}
+
+// Run every check in turn and print a pass/fail line for each, with a suggested fix for failures
+pub async fn doctor(config_file: &str, profile: Option<&str>) -> Result<()> {
+    let mut report = Report { failures: 0 };
+
+    let config = load_config(&mut report, config_file, profile);
+    if let Some(config) = &config {
+        check_http(&mut report, config).await;
+    }
+    check_sources(&mut report, config.as_ref()).await;
+    check_clock(&mut report).await;
+
+    match &config {
+        Some(config) => check_api(&mut report, config).await,
+        None => report.check(
+            "API",
+            Outcome::Skip("the configuration must be valid first".to_string()),
+        ),
+    }
+
+    if report.failures > 0 {
+        return Err(anyhow::anyhow!("{} check(s) failed", report.failures));
+    }
+    println!("All checks passed");
+    Ok(())
+}
+
+fn load_config(report: &mut Report, config_file: &str, profile: Option<&str>) -> Option<Config> {
+    let mut document = match config::read(config_file) {
+        Ok(document) => document,
+        Err(e) => {
+            let fix = match Path::new(config_file).exists() {
+                true => "fix the reported field, `clouddns config example` shows every option",
+                false => "run `clouddns init` to create one, or pass --config",
+            };
+            report.check("Configuration", fail(format!("{:#}", e), fix));
+            return None;
+        }
+    };
+    if let Some(profile) = profile {
+        if let Err(e) = document.config.apply_profile(profile) {
+            report.check("Configuration", fail(e, "pick one of the listed profiles"));
+            return None;
+        }
+    }
+
+    let problems = config::problems(&document.config);
+    if !problems.is_empty() {
+        report.check(
+            "Configuration",
+            fail(
+                format!("{} problem(s): {}", problems.len(), problems.join("; ")),
+                "run `clouddns validate` and fix the reported fields",
+            ),
+        );
+        return None;
+    }
+
+    let detail = match document.legacy {
+        true => format!("{} is valid (legacy layout)", config_file),
+        false => format!("{} is valid", config_file),
+    };
+    report.check("Configuration", Outcome::Pass(detail));
+    Some(document.config)
+}
+
+// Every source the enabled records use, or the default one without a configuration
+fn sources(config: Option<&Config>) -> Vec<(IpSource, RecordType)> {
+    let mut sources = Vec::new();
+    let Some(config) = config else {
+        return vec![
+            (IpSource::default(), RecordType::A),
+            (IpSource::default(), RecordType::Aaaa),
+        ];
+    };
+
+    for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+        for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+            let settings = config.settings(zone, domain);
+            for &record_type in settings.record_types.types() {
+                let source = (settings.ip_source.clone(), record_type);
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+    }
+    sources
+}
+
+async fn check_http(report: &mut Report, config: &Config) {
+    if config.proxy.is_none() && config.ca_certificates.is_empty() && config.dns.is_empty() {
+        return;
+    }
+    let outcome = match http::configure(config).await {
+        Ok(()) => {
+            let mut details = Vec::new();
+            if let Some(proxy) = &config.proxy {
+                details.push(format!("requests go through {}", proxy.url));
+            }
+            if !config.ca_certificates.is_empty() {
+                details.push(format!(
+                    "trusting {} extra CA file(s)",
+                    config.ca_certificates.len()
+                ));
+            }
+            if !config.dns.is_empty() {
+                details.push("names looked up as set in [dns]".to_string());
+            }
+            Outcome::Pass(details.join(", "))
+        }
+        Err(e) => fail(
+            format!("{:#}", e),
+            "check the proxy settings and the ca_certificates files",
+        ),
+    };
+    report.check("HTTP client", outcome);
+}
+
+async fn check_sources(report: &mut Report, config: Option<&Config>) {
+    let retry = config
+        .map(|config| config.retry.clone())
+        .unwrap_or_default();
+    for (source, record_type) in sources(config) {
+        let name = format!("IP source {} ({})", source, record_type.as_str());
+        let url = ip::source_url(&source, record_type);
+
+        if let Err(e) = resolve(url, config).await {
+            report.check(
+                &name,
+                fail(
+                    format!("{} does not resolve: {}", url, describe(&e)),
+                    "check the resolvers in /etc/resolv.conf, or pick another ip_source",
+                ),
+            );
+            continue;
+        }
+
+        let outcome = match ip::detect(&source, record_type, &retry).await {
+            Ok(ip) => Outcome::Pass(format!("detected {}", ip)),
+            Err(e) if record_type == RecordType::Aaaa => fail(
+                describe(&e),
+                "if this host has no IPv6 connectivity, set record_type to A",
+            ),
+            Err(e) => fail(
+                describe(&e),
+                "check outbound HTTPS (firewall, proxy), or pick another ip_source",
+            ),
+        };
+        report.check(&name, outcome);
+    }
+}
+
+async fn resolve(url: &str, config: Option<&Config>) -> Result<()> {
+    let url = reqwest::Url::parse(url)?;
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow::anyhow!("no host in {}", url))?;
+    // Literal addresses need no lookup
+    if host.starts_with('[') || host.parse::<std::net::IpAddr>().is_ok() {
+        return Ok(());
+    }
+    // Nor do names a proxy or the dns settings take care of, the requests themselves will tell
+    if config.is_some_and(|config| config.proxy.is_some() || config.dns.overrides(host)) {
+        return Ok(());
+    }
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    match tokio::net::lookup_host(format!("{}:{}", host, port))
+        .await?
+        .next()
+    {
+        Some(_) => Ok(()),
+        None => Err(anyhow::anyhow!("no addresses for {}", host)),
+    }
+}
+
+async fn check_clock(report: &mut Report) {
+    let outcome = match CloudflareClient::server_time().await {
+        Ok(server) => {
+            let now = SystemTime::now();
+            let skew = now
+                .duration_since(server)
+                .or_else(|_| server.duration_since(now))
+                .unwrap_or_default();
+            match skew > MAX_CLOCK_SKEW {
+                true => fail(
+                    format!(
+                        "the local clock is {}s away from Cloudflare's",
+                        skew.as_secs()
+                    ),
+                    "enable time synchronisation, e.g. `timedatectl set-ntp true`",
+                ),
+                false => Outcome::Pass(format!("within {}s of Cloudflare's", skew.as_secs())),
+            }
+        }
+        Err(e) => fail(describe(&e), "check outbound HTTPS to api.cloudflare.com"),
+    };
+    report.check("Clock", outcome);
+}
+
+async fn check_api(report: &mut Report, config: &Config) {
+    let token = match secrets::resolve(&config.api_token).await {
+        Ok(token) => token,
+        Err(e) => {
+            report.check(
+                "API token",
+                fail(
+                    format!("{:#}", e),
+                    "check the api_token setting and the secret it points to",
+                ),
+            );
+            return;
+        }
+    };
+
+    if config.provider.r#type == ProviderKind::Exec {
+        check_plugin(report, config, &token).await;
+        return;
+    }
+
+    let client =
+        CloudflareClient::new(&token, &config.retry, Limiter::new(config.api_rate_limit()));
+    let outcome = match client.verify_token().await {
+        Ok(status) if status.status == "active" => Outcome::Pass("active".to_string()),
+        Ok(status) => fail(
+            format!("the token is {}", status.status),
+            "create a new token with the Zone / DNS / Edit permission",
+        ),
+        Err(e) if network_error(&e) => {
+            fail(describe(&e), "check outbound HTTPS to api.cloudflare.com")
+        }
+        Err(e) => fail(
+            describe(&e),
+            "create a new token with the Zone / DNS / Edit permission",
+        ),
+    };
+    let token_ok = matches!(outcome, Outcome::Pass(_));
+    report.check("API token", outcome);
+    if !token_ok {
+        return;
+    }
+
+    for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+        let label = zone.name.as_deref().unwrap_or(&zone.id);
+        let outcome = match client.get_zone(&zone.id).await {
+            Ok(api_zone) => match &zone.name {
+                Some(name) if name.trim_end_matches('.') != api_zone.name => fail(
+                    format!("zone {} is named {}", &zone.id, &api_zone.name),
+                    "fix the zone name or ID in the configuration",
+                ),
+                _ => Outcome::Pass(format!("{} is accessible", &api_zone.name)),
+            },
+            Err(e) => fail(
+                describe(&e),
+                "check the zone ID, and that the token's Zone Resources include this zone",
+            ),
+        };
+        let zone_ok = matches!(outcome, Outcome::Pass(_));
+        report.check(&format!("Zone {}", label), outcome);
+        if !zone_ok {
+            continue;
+        }
+
+        for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+            let settings = config.settings(zone, domain);
+            for record_type in settings.record_types.types() {
+                for record in domain.record_names(zone) {
+                    let outcome = match client
+                        .get_record(&zone.id, &record, record_type.as_str())
+                        .await
+                    {
+                        Ok(found) => Outcome::Pass(format!("exists ({})", found.content)),
+                        Err(e) if e.to_string().contains("not found") => fail(
+                            "does not exist",
+                            "create the record in the Cloudflare dashboard, existing records are updated but never created",
+                        ),
+                        Err(e) => fail(describe(&e), "check the token's DNS permissions"),
+                    };
+                    report.check(
+                        &format!("Record {} {}", record, record_type.as_str()),
+                        outcome,
+                    );
+                }
+            }
+        }
+    }
+}
+
+// A plugin provider is asked for its zones, which tells whether it runs and answers sensibly
+async fn check_plugin(report: &mut Report, config: &Config, token: &SecretString) {
+    let outcome = match ExecClient::new(&config.provider, token) {
+        Ok(client) => match client.list_zones().await {
+            Ok(zones) => Outcome::Pass(format!("answered with {} zone(s)", zones.len())),
+            Err(e) => fail(
+                describe(&e),
+                "check the provider command, and that it follows the plugin protocol",
+            ),
+        },
+        Err(e) => fail(describe(&e), "set provider.command"),
+    };
+    report.check("Provider", outcome);
+}
@@ -0,0 +1,383 @@
+use anyhow::{Context, Result};
+use clouddns::config::{self, names, Config, ConfigFormat, Domain, RecordTypes, Zone};
+use clouddns::ddns::CloudflareDdns;
+use clouddns::error::{ErrorKind, ResultExt};
+use log::{info, warn};
+use std::{borrow::Cow, fs};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table, TableLike};
+
+pub struct NewRecord {
+    pub zone: String,
+    pub record: String,
+    pub zone_id: Option<String>,
+    pub record_type: Option<RecordTypes>,
+    pub proxied: bool,
+    pub ttl: Option<u32>,
+}
+
+// A change to the zones list, applied to both the typed config and the TOML document
+enum Edit {
+    AppendRecord {
+        zone: usize,
+        domain: usize,
+    },
+    AddDomain {
+        zone: usize,
+        domain: Domain,
+    },
+    AddZone {
+        zone: Zone,
+    },
+    RemoveRecord {
+        zone: usize,
+        domain: usize,
+        record: usize,
+    },
+    RemoveDomain {
+        zone: usize,
+        domain: usize,
+    },
+}
+
+pub async fn add(config_file: &str, new: NewRecord, update: bool, force: bool) -> Result<()> {
+    let (mut document, contents) = read(config_file, force)?;
+    let config = &document.config;
+    let record = Cow::Owned(new.record.clone());
+
+    let edit = match find_zone(config, &new.zone) {
+        Some(index) => {
+            let zone = &config.zones[index];
+            if let Some(name) = &zone.name {
+                let fqdn = names::expand(&new.record, name);
+                let types = new.record_type.unwrap_or_default();
+                if zone.domains.iter().any(|domain| {
+                    domain.record_names(zone).contains(&fqdn)
+                        && config.settings(zone, domain).record_types == types
+                }) {
+                    return Err(anyhow::anyhow!("{} is already managed", fqdn))
+                        .kind(ErrorKind::Config);
+                }
+            }
+
+            // Records join an apex entry giving them the settings asked for, if any, rather than
+            // a new entry of their own
+            let fqdn = names::expand(&new.record, zone.name.as_deref().unwrap_or_default());
+            match zone.domains.iter().position(|domain| {
+                let settings = config.settings(zone, domain);
+                zone.name.as_deref().is_some_and(|name| {
+                    names::expand(&domain.name, name) == name.trim_end_matches('.')
+                }) && domain.is_enabled()
+                    && !domain.record_names(zone).contains(&fqdn)
+                    && new
+                        .record_type
+                        .is_none_or(|types| settings.record_types == types)
+                    && (!new.proxied || settings.proxied == Some(true))
+                    && new.ttl.is_none_or(|ttl| settings.ttl == Some(ttl))
+            }) {
+                Some(domain) => Edit::AppendRecord {
+                    zone: index,
+                    domain,
+                },
+                None => Edit::AddDomain {
+                    zone: index,
+                    domain: new_domain(zone.name.is_some(), record, &new),
+                },
+            }
+        }
+        None => {
+            let zone_id = new.zone_id.clone().with_context(|| {
+                format!(
+                    "Zone {} is not in the configuration, pass --zone-id to add it",
+                    &new.zone
+                )
+            })?;
+            Edit::AddZone {
+                zone: Zone {
+                    id: Cow::Owned(zone_id),
+                    name: Some(Cow::Owned(new.zone.clone())),
+                    domains: vec![new_domain(true, record, &new)],
+                    ..Zone::default()
+                },
+            }
+        }
+    };
+
+    apply(
+        &mut document.config,
+        &[edit],
+        &new.record,
+        &contents,
+        document.format,
+        config_file,
+    )?;
+    println!("Added {} to {}", &new.record, &new.zone);
+
+    if update {
+        let mut ddns = CloudflareDdns::new(config_file, None).await?;
+        ddns.run_once(false).await?;
+    }
+    Ok(())
+}
+
+pub fn remove(config_file: &str, zone_name: &str, record: &str, force: bool) -> Result<()> {
+    let (mut document, contents) = read(config_file, force)?;
+    let config = &document.config;
+
+    let zone_index = find_zone(config, zone_name)
+        .with_context(|| format!("Zone {} is not in the configuration", zone_name))
+        .kind(ErrorKind::Config)?;
+    let zone = &config.zones[zone_index];
+    let fqdn = names::expand(record, zone.name.as_deref().unwrap_or_default());
+
+    let mut edits = Vec::new();
+    for (domain_index, domain) in zone.domains.iter().enumerate() {
+        let names = domain.record_names(zone);
+        let matching: Vec<usize> = (0..names.len()).filter(|&i| names[i] == fqdn).collect();
+        if matching.is_empty() {
+            continue;
+        }
+
+        if matching.len() == domain.records.len() {
+            edits.push(Edit::RemoveDomain {
+                zone: zone_index,
+                domain: domain_index,
+            });
+        } else {
+            edits.extend(matching.into_iter().map(|record_index| Edit::RemoveRecord {
+                zone: zone_index,
+                domain: domain_index,
+                record: record_index,
+            }));
+        }
+    }
+
+    if edits.is_empty() {
+        return Err(anyhow::anyhow!("{} is not managed in {}", fqdn, zone_name))
+            .kind(ErrorKind::Config);
+    }
+    // Highest indices first so the remaining ones stay valid
+    edits.reverse();
+
+    apply(
+        &mut document.config,
+        &edits,
+        record,
+        &contents,
+        document.format,
+        config_file,
+    )?;
+    println!("Removed {} from {}", fqdn, zone_name);
+    Ok(())
+}
+
+// YAML files are serialized again, which loses their comments: only when forced
+fn read(config_file: &str, force: bool) -> Result<(config::Document, String)> {
+    let document = config::read_raw(config_file).kind(ErrorKind::Config)?;
+    if document.legacy {
+        return Err(anyhow::anyhow!(
+            "{} uses the legacy layout, run `clouddns migrate-config` first",
+            config_file
+        ))
+        .kind(ErrorKind::Config);
+    }
+    if document.format == ConfigFormat::Yaml && !force {
+        return Err(anyhow::anyhow!(
+            "Editing {} would rewrite it without its comments, pass --force to do so anyway",
+            config_file
+        ))
+        .kind(ErrorKind::Config);
+    }
+    let contents = fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to read config file: {}", config_file))?;
+    Ok((document, contents))
+}
+
+fn find_zone(config: &Config, zone: &str) -> Option<usize> {
+    let wanted = names::normalize(zone.trim_end_matches('.'));
+    config.zones.iter().position(|candidate| {
+        candidate.id == zone
+            || candidate
+                .name
+                .as_deref()
+                .is_some_and(|name| names::normalize(name.trim_end_matches('.')) == wanted)
+    })
+}
+
+fn new_domain(zone_named: bool, record: Cow<'static, str>, new: &NewRecord) -> Domain {
+    // Without a zone name there is nothing to be relative to, so the record becomes the domain
+    let (name, records) = match zone_named {
+        true => (Cow::Borrowed("@"), vec![record]),
+        false => (record, vec![Cow::Borrowed("@")]),
+    };
+    Domain {
+        name,
+        records,
+        record_type: new.record_type,
+        proxied: new.proxied.then_some(true),
+        ttl: new.ttl,
+        ..Domain::default()
+    }
+}
+
+// Check the edited config, then write it back. TOML files are edited in place so
+// comments and layout survive; YAML and JSON, which has none, are serialized again.
+fn apply(
+    config: &mut Config,
+    edits: &[Edit],
+    record: &str,
+    contents: &str,
+    format: ConfigFormat,
+    config_file: &str,
+) -> Result<()> {
+    let mut toml = match format {
+        ConfigFormat::Toml => Some(
+            contents
+                .parse::<DocumentMut>()
+                .with_context(|| format!("Failed to parse config file: {}", config_file))?,
+        ),
+        _ => None,
+    };
+
+    for edit in edits {
+        apply_typed(config, edit, record);
+        if let Some(toml) = &mut toml {
+            apply_toml(toml, edit, record)?;
+        }
+    }
+
+    let mut resolved = config.clone();
+    config::resolve(&mut resolved)
+        .and_then(|()| config::validate(&resolved))
+        .context("The edited configuration is not valid, nothing was written")
+        .kind(ErrorKind::Config)?;
+
+    let output = match toml {
+        Some(toml) => toml.to_string(),
+        None => {
+            if format == ConfigFormat::Yaml {
+                warn!("Comments in {} were not preserved", config_file);
+            }
+            format.serialize(config)?
+        }
+    };
+    fs::write(config_file, output)
+        .with_context(|| format!("Failed to write config file: {}", config_file))?;
+    info!("Updated {}", config_file);
+    Ok(())
+}
+
+fn apply_typed(config: &mut Config, edit: &Edit, record: &str) {
+    match edit {
+        Edit::AppendRecord { zone, domain } => config.zones[*zone].domains[*domain]
+            .records
+            .push(Cow::Owned(record.to_string())),
+        Edit::AddDomain { zone, domain } => config.zones[*zone].domains.push(domain.clone()),
+        Edit::AddZone { zone } => config.zones.push(zone.clone()),
+        Edit::RemoveRecord {
+            zone,
+            domain,
+            record,
+        } => {
+            config.zones[*zone].domains[*domain].records.remove(*record);
+        }
+        Edit::RemoveDomain { zone, domain } => {
+            config.zones[*zone].domains.remove(*domain);
+        }
+    }
+}
+
+fn apply_toml(document: &mut DocumentMut, edit: &Edit, record: &str) -> Result<()> {
+    let zones = document
+        .get_mut("zones")
+        .context("No zones list found in the TOML document")?;
+
+    match edit {
+        Edit::AddZone { zone } => push_table(zones, to_table(zone)?),
+        Edit::AppendRecord { zone, domain } => records(zones, *zone, *domain)?.push(record),
+        Edit::RemoveRecord {
+            zone,
+            domain,
+            record,
+        } => {
+            records(zones, *zone, *domain)?.remove(*record);
+        }
+        Edit::AddDomain { zone, domain } => {
+            let domains = nth_table(zones, *zone)?
+                .get_mut("domains")
+                .context("Zone has no domains list")?;
+            push_table(domains, to_table(domain)?);
+        }
+        Edit::RemoveDomain { zone, domain } => {
+            let domains = nth_table(zones, *zone)?
+                .get_mut("domains")
+                .context("Zone has no domains list")?;
+            match domains {
+                Item::ArrayOfTables(tables) => tables.remove(*domain),
+                item => {
+                    item.as_array_mut()
+                        .context("domains is not a list")?
+                        .remove(*domain);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// Lists of tables may be written as `[[zones]]` or as inline `zones = [{ ... }]`
+fn nth_table(item: &mut Item, index: usize) -> Result<&mut dyn TableLike> {
+    let table: Option<&mut dyn TableLike> = match item {
+        Item::ArrayOfTables(tables) => tables.get_mut(index).map(|t| t as &mut dyn TableLike),
+        item => item
+            .as_array_mut()
+            .and_then(|array| array.get_mut(index))
+            .and_then(|value| value.as_inline_table_mut())
+            .map(|t| t as &mut dyn TableLike),
+    };
+    table.with_context(|| format!("Entry {} not found in the TOML document", index))
+}
+
+fn records(zones: &mut Item, zone: usize, domain: usize) -> Result<&mut toml_edit::Array> {
+    let domains = nth_table(zones, zone)?
+        .get_mut("domains")
+        .context("Zone has no domains list")?;
+    nth_table(domains, domain)?
+        .get_mut("records")
+        .and_then(Item::as_array_mut)
+        .context("Domain has no records list")
+}
+
+fn push_table(item: &mut Item, table: Table) {
+    match item {
+        Item::ArrayOfTables(tables) => tables.push(table),
+        item => {
+            if let Some(array) = item.as_array_mut() {
+                array.push(table.into_inline_table());
+            }
+        }
+    }
+}
+
+fn to_table<T: serde::Serialize>(value: &T) -> Result<Table> {
+    let document: DocumentMut = toml::to_string(value)?.parse()?;
+    Ok(detach(document.as_table()))
+}
+
+// Copy a parsed table without its document positions, which would otherwise
+// place nested tables wherever they were in the scratch document
+fn detach(table: &Table) -> Table {
+    let mut detached = Table::new();
+    for (key, item) in table.iter() {
+        let item = match item {
+            Item::Table(table) => Item::Table(detach(table)),
+            Item::ArrayOfTables(tables) => {
+                let mut copy = ArrayOfTables::new();
+                tables.iter().for_each(|table| copy.push(detach(table)));
+                Item::ArrayOfTables(copy)
+            }
+            item => item.clone(),
+        };
+        detached.insert(key, item);
+    }
+    detached
+}
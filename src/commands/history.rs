@@ -0,0 +1,152 @@
+use super::{print_json, print_table};
+use crate::cli::{HistoryFormat, HistoryQueryArgs};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use clouddns::config::{self, names};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::state::{self, HistoryEntry, HistoryQuery};
+use std::path::PathBuf;
+
+// Print the changes the daemon made to the records and the updates that failed, as kept in its
+// state store
+pub fn history(
+    config_file: &str,
+    profile: Option<&str>,
+    state_file: Option<&str>,
+    args: &HistoryQueryArgs,
+) -> Result<()> {
+    let config = config::load(config_file, profile)
+        .and_then(|config| config::validate(&config).map(|()| config))
+        .kind(ErrorKind::Config)?;
+    let time = |flag: &str, value: &Option<String>| {
+        value
+            .as_deref()
+            .map(|value| parse_time(flag, value))
+            .transpose()
+            .kind(ErrorKind::Config)
+    };
+    let query = HistoryQuery {
+        // As the records are named
+        domain: args
+            .domain
+            .as_deref()
+            .map(|domain| names::normalize(domain).trim_end_matches('.').to_string()),
+        since: time("--since", &args.since)?,
+        until: time("--until", &args.until)?,
+        failed: args.failed,
+        limit: args.limit,
+    };
+
+    let path = state_file
+        .map(PathBuf::from)
+        .or_else(|| config.state.path.clone());
+    let store = state::open(&config, path, Some(config_file))
+        .kind(ErrorKind::Config)?
+        .context("No state store to read the history from")?;
+    let entries = store.history(&query)?;
+
+    match args.output {
+        HistoryFormat::Json => print_json(&entries),
+        HistoryFormat::Csv => {
+            print_csv(&entries);
+            Ok(())
+        }
+        HistoryFormat::Text => {
+            if entries.is_empty() {
+                println!("Nothing recorded");
+                return Ok(());
+            }
+            let mut rows = vec![vec![
+                "TIME".to_string(),
+                "RECORD".to_string(),
+                "TYPE".to_string(),
+                "CHANGE".to_string(),
+                "REASON".to_string(),
+                "TRIGGER".to_string(),
+                "RESULT".to_string(),
+            ]];
+            for entry in entries {
+                rows.push(vec![
+                    entry.time.format("%Y-%m-%d %H:%M:%S").to_string(),
+                    entry.record,
+                    entry.r#type.to_string(),
+                    format!(
+                        "{} → {}",
+                        entry.previous.as_deref().unwrap_or("none"),
+                        entry.content.as_deref().unwrap_or("none")
+                    ),
+                    entry.reason.to_string(),
+                    entry.trigger.to_string(),
+                    // Last, errors being long
+                    match entry.error {
+                        Some(error) => format!("failed: {}", error),
+                        None => "ok".to_string(),
+                    },
+                ]);
+            }
+            print_table(&rows)?;
+            Ok(())
+        }
+    }
+}
+
+fn print_csv(entries: &[HistoryEntry]) {
+    println!("time,record,type,previous,content,reason,trigger,error");
+    for entry in entries {
+        let fields = [
+            entry.time.to_rfc3339(),
+            entry.record.clone(),
+            entry.r#type.to_string(),
+            entry.previous.clone().unwrap_or_default(),
+            entry.content.clone().unwrap_or_default(),
+            entry.reason.to_string(),
+            entry.trigger.to_string(),
+            entry.error.clone().unwrap_or_default(),
+        ];
+        let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        println!("{}", fields.join(","));
+    }
+}
+
+// Quoted only when it has to be
+fn csv_field(field: &str) -> String {
+    match field.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", field.replace('"', "\"\"")),
+        false => field.to_string(),
+    }
+}
+
+// A span back from now such as `7d`, `12h`, `30m` or `2w`, or a date or time such as `2024-05-01`
+// or `2024-05-01T08:00:00Z`
+fn parse_time(flag: &str, value: &str) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+    }
+    let split = value.len() - value.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = value.split_at(split);
+    let Ok(count) = count.parse::<i64>() else {
+        bail!(
+            "Invalid {} {:?}, expected e.g. 7d, 12h, 30m, 2w or a date such as 2024-05-01",
+            flag,
+            value
+        );
+    };
+    let span = match unit {
+        "s" => Duration::try_seconds(count),
+        "m" => Duration::try_minutes(count),
+        "h" => Duration::try_hours(count),
+        "d" => Duration::try_days(count),
+        "w" => Duration::try_weeks(count),
+        _ => bail!(
+            "Invalid {} {:?}, the unit must be one of s, m, h, d or w",
+            flag,
+            value
+        ),
+    };
+    span.and_then(|span| Utc::now().checked_sub_signed(span))
+        .with_context(|| format!("{} {:?} is too far back", flag, value))
+}
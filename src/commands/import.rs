@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use clouddns::api::{self, Limiter};
+use clouddns::config::{self, import, ConfigFormat};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::secrets;
+use log::{info, warn};
+use std::{fs, path::Path};
+
+// Convert a ddclient configuration, printing it or writing it to a file
+pub async fn import_ddclient(
+    input: &str,
+    output: Option<&str>,
+    format: Option<ConfigFormat>,
+    online: bool,
+) -> Result<()> {
+    let contents = fs::read_to_string(input)
+        .with_context(|| format!("Failed to read ddclient configuration: {}", input))?;
+    let imported = import::ddclient::parse(&contents)
+        .with_context(|| format!("Failed to import {}", input))?;
+    write(imported, output, format, online).await
+}
+
+// Convert an inadyn configuration, printing it or writing it to a file
+pub async fn import_inadyn(
+    input: &str,
+    output: Option<&str>,
+    format: Option<ConfigFormat>,
+    online: bool,
+) -> Result<()> {
+    let contents = fs::read_to_string(input)
+        .with_context(|| format!("Failed to read inadyn configuration: {}", input))?;
+    let imported =
+        import::inadyn::parse(&contents).with_context(|| format!("Failed to import {}", input))?;
+    write(imported, output, format, online).await
+}
+
+async fn write(
+    imported: import::Imported,
+    output: Option<&str>,
+    format: Option<ConfigFormat>,
+    online: bool,
+) -> Result<()> {
+    let import::Imported {
+        mut config,
+        warnings,
+    } = imported;
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+
+    if online {
+        // The other clients only know the zones by name
+        let api_token = secrets::resolve(&config.api_token)
+            .await
+            .kind(ErrorKind::Auth)?;
+        let client = api::from_config(&config, &api_token, Limiter::new(config.api_rate_limit()))
+            .kind(ErrorKind::Config)?;
+        let api_zones = client.list_zones().await?;
+        for zone in &mut config.zones {
+            let name = zone.name.as_deref().unwrap_or_default();
+            match api_zones.iter().find(|api_zone| api_zone.name == name) {
+                Some(api_zone) => zone.id = api_zone.id.clone().into(),
+                None => warn!("The API token doesn't give access to zone {}", name),
+            }
+        }
+    } else {
+        warn!(
+            "Replace the {} placeholders with the zone IDs, or run again with --online to look \
+             them up",
+            import::placeholder_id("…")
+        );
+    }
+
+    config::validate(&config).context("The imported configuration is invalid")?;
+
+    let format = format
+        .or_else(|| output.and_then(|output| ConfigFormat::from_path(Path::new(output))))
+        .unwrap_or(ConfigFormat::Toml);
+    let contents = format.serialize(&config)?;
+
+    match output {
+        Some(output) => {
+            super::write_private(output, &contents)
+                .with_context(|| format!("Failed to write config file: {}", output))?;
+            info!("Wrote {} configuration to {}", format.name(), output);
+        }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
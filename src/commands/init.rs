@@ -0,0 +1,230 @@
+use anyhow::{Context, Result};
+use clouddns::api::{CloudflareClient, DnsApiClient, Limiter};
+use clouddns::config::{
+    self, names, Config, ConfigFormat, Defaults, Domain, RateLimit, RecordTypes, RetryPolicy,
+    SecretSource, Zone,
+};
+use secrecy::{zeroize::Zeroize, ExposeSecret, SecretString};
+use std::{
+    borrow::Cow,
+    collections::BTreeMap,
+    io::{self, BufRead, Write},
+    path::Path,
+};
+
+fn prompt(question: &str, default: Option<&str>) -> Result<String> {
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        return Err(anyhow::anyhow!("Setup aborted"));
+    }
+
+    let answer = answer.trim();
+    Ok(match (answer.is_empty(), default) {
+        (true, Some(default)) => default.to_string(),
+        _ => answer.to_string(),
+    })
+}
+
+fn confirm(question: &str, default: bool) -> Result<bool> {
+    let answer = prompt(question, Some(if default { "Y/n" } else { "y/N" }))?;
+    Ok(match answer.to_ascii_lowercase().as_str() {
+        "y" | "yes" => true,
+        "n" | "no" => false,
+        _ => default,
+    })
+}
+
+// Parse "1,3-5" or "all" into zero-based indices
+fn parse_selection(answer: &str, count: usize) -> Option<Vec<usize>> {
+    if answer.eq_ignore_ascii_case("all") {
+        return Some((0..count).collect());
+    }
+
+    let mut selection = Vec::new();
+    for part in answer
+        .split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+    {
+        let (start, end): (usize, usize) = match part.split_once('-') {
+            Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+            None => {
+                let index = part.parse().ok()?;
+                (index, index)
+            }
+        };
+        if start == 0 || end > count || start > end {
+            return None;
+        }
+        selection.extend((start - 1)..end);
+    }
+
+    selection.sort_unstable();
+    selection.dedup();
+    (!selection.is_empty()).then_some(selection)
+}
+
+fn select(question: &str, items: &[String]) -> Result<Vec<usize>> {
+    for (index, item) in items.iter().enumerate() {
+        println!("  {:>3}) {}", index + 1, item);
+    }
+
+    loop {
+        let answer = prompt(question, Some("all"))?;
+        match parse_selection(&answer, items.len()) {
+            Some(selection) => return Ok(selection),
+            None => println!(
+                "Enter numbers between 1 and {}, e.g. 1,3-5, or `all`",
+                items.len()
+            ),
+        }
+    }
+}
+
+async fn ask_token() -> Result<(SecretString, CloudflareClient)> {
+    loop {
+        let mut input = rpassword::prompt_password("Cloudflare API token: ")?;
+        let token = SecretString::from(input.trim());
+        input.zeroize();
+        if token.expose_secret().is_empty() {
+            continue;
+        }
+
+        let client = CloudflareClient::new(
+            &token,
+            &RetryPolicy::default(),
+            Limiter::new(RateLimit::CLOUDFLARE),
+        );
+        match client.verify_token().await {
+            Ok(status) if status.status == "active" => {
+                println!("Token is valid");
+                return Ok((token, client));
+            }
+            Ok(status) => println!("Token is {}, try another one", status.status),
+            Err(e) => println!("Token verification failed: {:#}", e),
+        }
+    }
+}
+
+pub async fn init(config_file: &str) -> Result<()> {
+    println!("This will create {}\n", config_file);
+
+    if Path::new(config_file).exists()
+        && !confirm(&format!("{} exists, overwrite it?", config_file), false)?
+    {
+        return Err(anyhow::anyhow!("Setup aborted"));
+    }
+
+    loop {
+        let provider = prompt("DNS provider", Some("cloudflare"))?;
+        if provider == "cloudflare" {
+            break;
+        }
+        println!("Only cloudflare is supported for now");
+    }
+
+    let (token, client) = ask_token().await?;
+
+    let api_zones = client.list_zones().await?;
+    if api_zones.is_empty() {
+        return Err(anyhow::anyhow!("The token doesn't give access to any zone"));
+    }
+
+    println!("\nZones available to this token:");
+    let zone_names: Vec<String> = api_zones.iter().map(|zone| zone.name.clone()).collect();
+    let selected_zones = select("Zones to manage", &zone_names)?;
+
+    let mut zones = Vec::new();
+    for index in selected_zones {
+        let api_zone = &api_zones[index];
+        let records: Vec<_> = client
+            .list_records(&api_zone.id)
+            .await?
+            .into_iter()
+            .filter(|record| record.r#type == "A" || record.r#type == "AAAA")
+            .collect();
+
+        if records.is_empty() {
+            println!("\n{} has no A or AAAA records, skipping it", api_zone.name);
+            continue;
+        }
+
+        println!("\nAddress records in {}:", api_zone.name);
+        let descriptions: Vec<String> = records
+            .iter()
+            .map(|record| {
+                format!(
+                    "{:<40} {:<4} {}",
+                    record.name, record.r#type, record.content
+                )
+            })
+            .collect();
+        let selected_records = select("Records to keep up to date", &descriptions)?;
+
+        // One domain entry per record type, with names relative to the zone
+        let mut by_type: BTreeMap<&str, Vec<Cow<'static, str>>> = BTreeMap::new();
+        for index in selected_records {
+            let record = &records[index];
+            by_type
+                .entry(record.r#type.as_str())
+                .or_default()
+                .push(Cow::Owned(
+                    names::relative(&record.name, &api_zone.name)
+                        .unwrap_or_else(|| record.name.clone()),
+                ));
+        }
+
+        let domains = by_type
+            .into_iter()
+            .map(|(record_type, records)| Domain {
+                name: Cow::Borrowed("@"),
+                records,
+                record_type: (record_type == "AAAA").then_some(RecordTypes::Aaaa),
+                ..Domain::default()
+            })
+            .collect();
+
+        zones.push(Zone {
+            id: Cow::Owned(api_zone.id.clone()),
+            name: Some(Cow::Owned(api_zone.name.clone())),
+            domains,
+            ..Zone::default()
+        });
+    }
+
+    if zones.is_empty() {
+        return Err(anyhow::anyhow!("No records selected, nothing to write"));
+    }
+
+    let update_interval = loop {
+        match prompt("\nMinutes between checks", Some("5"))?.parse::<u64>() {
+            Ok(minutes) if minutes > 0 => break minutes,
+            _ => println!("Enter a number of minutes greater than 0"),
+        }
+    };
+
+    let config = Config {
+        defaults: Defaults {
+            update_interval: Some(update_interval),
+            ..Defaults::default()
+        },
+        ..Config::new(SecretSource::Plain(token.into()), zones)
+    };
+    config::validate(&config).context("Generated configuration is invalid")?;
+
+    let format = ConfigFormat::from_path(Path::new(config_file)).unwrap_or(ConfigFormat::Toml);
+    super::write_private(config_file, &format.serialize(&config)?)
+        .with_context(|| format!("Failed to write config file: {}", config_file))?;
+
+    println!(
+        "\nWrote {}, start the updater with `clouddns -c {}`",
+        config_file, config_file
+    );
+    Ok(())
+}
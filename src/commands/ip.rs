@@ -0,0 +1,71 @@
+use super::print_json;
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use clouddns::config::{IpSource, RecordType, RetryPolicy};
+use clouddns::ip;
+use serde::Serialize;
+use std::net::IpAddr;
+
+#[derive(Debug, Serialize)]
+struct Detection {
+    family: &'static str,
+    source: String,
+    address: Option<IpAddr>,
+    error: Option<String>,
+}
+
+// Run address detection alone, no configuration or credentials involved
+pub async fn ip(
+    source: Option<IpSource>,
+    ipv4: bool,
+    ipv6: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let source = source.unwrap_or_default();
+    let record_types = match (ipv4, ipv6) {
+        (true, false) => vec![RecordType::A],
+        (false, true) => vec![RecordType::Aaaa],
+        _ => vec![RecordType::A, RecordType::Aaaa],
+    };
+
+    let mut detections = Vec::new();
+    for record_type in record_types {
+        let detected = ip::detect(&source, record_type, &RetryPolicy::default()).await;
+        detections.push(Detection {
+            family: match record_type {
+                RecordType::A => "ipv4",
+                RecordType::Aaaa => "ipv6",
+            },
+            source: source.to_string(),
+            address: detected.as_ref().ok().copied(),
+            error: detected.err().map(|e| e.to_string()),
+        });
+    }
+
+    if output == OutputFormat::Json {
+        print_json(&detections)?;
+    } else {
+        for detection in &detections {
+            let family = match detection.family {
+                "ipv4" => "IPv4",
+                _ => "IPv6",
+            };
+            match (&detection.address, &detection.error) {
+                (Some(ip), _) => println!("{}: {}", family, ip),
+                (None, error) => println!(
+                    "{}: detection failed: {}",
+                    family,
+                    error.as_deref().unwrap_or_default()
+                ),
+            }
+        }
+    }
+
+    if detections
+        .iter()
+        .all(|detection| detection.address.is_none())
+    {
+        return Err(anyhow::anyhow!("No address detected from {}", source));
+    }
+    Ok(())
+}
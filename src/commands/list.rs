@@ -0,0 +1,85 @@
+use super::{print_json, print_table};
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use clouddns::config;
+use clouddns::error::{ErrorKind, ResultExt};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+struct ListedRecord {
+    record: String,
+    types: Vec<&'static str>,
+    // None keeps whatever the provider has
+    ttl: Option<u32>,
+    proxied: Option<bool>,
+    ip_source: String,
+    // Minutes
+    update_interval: u64,
+    enabled: bool,
+}
+
+// Print every configured record with its effective settings, without touching the API
+pub fn list(config_file: &str, profile: Option<&str>, output: OutputFormat) -> Result<()> {
+    let config = config::load(config_file, profile)
+        .and_then(|config| config::validate(&config).map(|()| config))
+        .kind(ErrorKind::Config)?;
+
+    let mut records = Vec::new();
+    for zone in &config.zones {
+        for domain in &zone.domains {
+            let settings = config.settings(zone, domain);
+            let types: Vec<&str> = settings
+                .record_types
+                .types()
+                .iter()
+                .map(|record_type| record_type.as_str())
+                .collect();
+
+            for record in domain.record_names(zone) {
+                records.push(ListedRecord {
+                    record,
+                    types: types.clone(),
+                    ttl: settings.ttl,
+                    proxied: settings.proxied,
+                    ip_source: settings.ip_source.to_string(),
+                    update_interval: settings.update_interval,
+                    enabled: zone.is_enabled() && domain.is_enabled(),
+                });
+            }
+        }
+    }
+
+    if output == OutputFormat::Json {
+        return print_json(&records);
+    }
+
+    let mut rows = vec![vec![
+        "RECORD".to_string(),
+        "TYPE".to_string(),
+        "TTL".to_string(),
+        "PROXIED".to_string(),
+        "IP SOURCE".to_string(),
+        "INTERVAL".to_string(),
+        "STATE".to_string(),
+    ]];
+    for record in records {
+        rows.push(vec![
+            record.record,
+            record.types.join("+"),
+            record
+                .ttl
+                .map_or_else(|| "keep".to_string(), |ttl| ttl.to_string()),
+            record
+                .proxied
+                .map_or_else(|| "keep".to_string(), |proxied| proxied.to_string()),
+            record.ip_source,
+            format!("{}m", record.update_interval),
+            match record.enabled {
+                true => "enabled".to_string(),
+                false => "disabled".to_string(),
+            },
+        ]);
+    }
+    print_table(&rows)?;
+    Ok(())
+}
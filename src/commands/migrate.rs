@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use clouddns::config::{self, ConfigFormat};
+use log::info;
+use std::{fs, path::Path};
+
+// Rewrite a config file in the current format, converting the legacy layout if needed
+pub fn migrate_config(
+    input: &str,
+    output: Option<&str>,
+    format: Option<ConfigFormat>,
+) -> Result<()> {
+    // Placeholders are kept in the output, only the check runs on the expanded names
+    let document = config::read_raw(input)?;
+    let mut resolved = document.config.clone();
+    config::resolve(&mut resolved)
+        .and_then(|()| config::validate(&resolved))
+        .context("The converted configuration is not valid, fix the input file first")?;
+
+    if document.legacy {
+        info!("Converted legacy zone_id/domain_list configuration");
+    } else {
+        info!("{} already uses the current format", input);
+    }
+
+    let format = format
+        .or_else(|| output.and_then(|output| ConfigFormat::from_path(Path::new(output))))
+        .unwrap_or(document.format);
+    let contents = format.serialize(&document.config)?;
+
+    match output {
+        Some(output) => {
+            fs::write(output, contents)
+                .with_context(|| format!("Failed to write config file: {}", output))?;
+            info!("Wrote {} configuration to {}", format.name(), output);
+        }
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
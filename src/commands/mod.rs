@@ -0,0 +1,96 @@
+#[cfg(unix)]
+pub mod ctl;
+pub mod diff;
+pub mod doctor;
+pub mod edit;
+pub mod history;
+pub mod import;
+pub mod init;
+pub mod ip;
+pub mod list;
+pub mod migrate;
+pub mod plan;
+pub mod status;
+pub mod systemd;
+pub mod token;
+pub mod validate;
+pub mod watch;
+
+use crate::cli::Cli;
+use anyhow::Result;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use clouddns::config;
+use serde::Serialize;
+use std::{
+    fs,
+    io::{self, Write},
+};
+
+pub fn completions(shell: Shell) -> Result<()> {
+    clap_complete::generate(shell, &mut Cli::command(), "clouddns", &mut io::stdout());
+    Ok(())
+}
+
+pub fn example() -> Result<()> {
+    print!("{}", config::example());
+    Ok(())
+}
+
+pub fn schema() -> Result<()> {
+    print_json(&config::schema())
+}
+
+// Print rows as left-aligned columns, the first row being the header
+fn print_table(rows: &[Vec<String>]) -> Result<()> {
+    let mut widths = Vec::new();
+    for row in rows {
+        widths.resize(widths.len().max(row.len()), 0);
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    let written = rows.iter().try_for_each(|row| {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        writeln!(stdout, "{}", line.join("  ").trim_end())
+    });
+    unless_closed(written)
+}
+
+fn print_json<T: Serialize>(value: &T) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)?;
+    unless_closed(writeln!(io::stdout().lock(), "{}", json))
+}
+
+// Output piped into e.g. `head` stops being read once it has seen enough, which is no error
+fn unless_closed(written: io::Result<()>) -> Result<()> {
+    match written {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        written => Ok(written?),
+    }
+}
+
+// The file holds the API token, keep it readable by its owner only
+fn write_private(path: &str, contents: &str) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let mut file = fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)?;
+        file.write_all(contents.as_bytes())
+    }
+
+    #[cfg(not(unix))]
+    fs::write(path, contents)
+}
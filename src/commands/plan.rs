@@ -0,0 +1,342 @@
+use super::print_json;
+use crate::cli::OutputFormat;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clouddns::api::{self, models::DnsRecordUpdate, DnsApiClient, Limiter, RecordContent};
+use clouddns::config::{self, Config, IpSource, RecordType};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::plan::{Action, DesiredRecord, UpdatePlanner};
+use clouddns::secrets;
+use clouddns::{http, ip};
+use ratatui::crossterm::style::Stylize;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::{self, BufRead, IsTerminal, Write},
+    net::IpAddr,
+    path::Path,
+};
+
+// Bumped whenever saved plans change in a way older ones can't be read as
+const PLAN_VERSION: u32 = 1;
+
+// What `plan --out` writes and `apply` reads back
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedPlan {
+    version: u32,
+    created: DateTime<Utc>,
+    zones: Vec<ZonePlan>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ZonePlan {
+    zone_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    // Only those changing something, each with the record as it was when planned
+    actions: Vec<Action>,
+}
+
+impl SavedPlan {
+    fn actions(&self) -> impl Iterator<Item = (&str, &Action)> {
+        self.zones.iter().flat_map(|zone| {
+            zone.actions
+                .iter()
+                .map(move |action| (zone.zone_id.as_str(), action))
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.actions().next().is_none()
+    }
+}
+
+pub struct PlanOptions<'a> {
+    pub ip_override: &'a [IpAddr],
+    pub out: Option<&'a Path>,
+    pub output: OutputFormat,
+    pub detailed_exitcode: bool,
+}
+
+// Work out what applying the configuration would change, and save it to apply it later
+pub async fn plan(
+    config_file: &str,
+    profile: Option<&str>,
+    options: PlanOptions<'_>,
+) -> Result<()> {
+    let (config, client) = connect(config_file, profile).await?;
+    let plan = make_plan(&config, &*client, options.ip_override).await?;
+
+    match options.output {
+        OutputFormat::Json => print_json(&plan)?,
+        OutputFormat::Text => print_plan(&plan),
+    }
+    if let Some(out) = options.out {
+        let contents = serde_json::to_string_pretty(&plan)?;
+        fs::write(out, contents + "\n")
+            .with_context(|| format!("Failed to write the plan to {}", out.display()))?;
+        if options.output == OutputFormat::Text {
+            println!("\nSaved the plan to: {}", out.display());
+            println!("\nTo perform exactly these actions, run:");
+            println!("    clouddns apply {}", out.display());
+        }
+    }
+
+    if options.detailed_exitcode && !plan.is_empty() {
+        return Err(anyhow::anyhow!("The records differ from the configuration"))
+            .kind(ErrorKind::PendingChanges);
+    }
+    Ok(())
+}
+
+// Carry out a saved plan, unless the records changed since it was made, or plan and carry out the
+// changes once confirmed
+pub async fn apply(
+    config_file: &str,
+    profile: Option<&str>,
+    plan_file: Option<&Path>,
+    ip_override: &[IpAddr],
+    auto_approve: bool,
+) -> Result<()> {
+    let (config, client) = connect(config_file, profile).await?;
+
+    let plan = match plan_file {
+        Some(plan_file) => {
+            let contents = fs::read_to_string(plan_file)
+                .with_context(|| format!("Failed to read the plan {}", plan_file.display()))?;
+            let plan: SavedPlan = serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid plan {}", plan_file.display()))?;
+            if plan.version != PLAN_VERSION {
+                bail!(
+                    "The plan {} was made by another version of clouddns, run `clouddns plan` again",
+                    plan_file.display()
+                );
+            }
+            check_stale(&*client, &plan).await?;
+            plan
+        }
+        None => {
+            let plan = make_plan(&config, &*client, ip_override).await?;
+            print_plan(&plan);
+            if plan.is_empty() {
+                return Ok(());
+            }
+            if !auto_approve && !approved()? {
+                bail!("Apply cancelled");
+            }
+            println!();
+            plan
+        }
+    };
+
+    let (mut added, mut changed, mut destroyed, mut failed) = (0, 0, 0, 0);
+    for (zone_id, action) in plan.actions() {
+        let (name, record_type) = action.target();
+        match action.apply(&*client, zone_id).await {
+            Ok(_) => {
+                let done = match action {
+                    Action::Create { .. } => {
+                        added += 1;
+                        "Created"
+                    }
+                    Action::Delete { .. } => {
+                        destroyed += 1;
+                        "Destroyed"
+                    }
+                    _ => {
+                        changed += 1;
+                        "Modified"
+                    }
+                };
+                println!("{} {}: {}", name, record_type, done);
+            }
+            Err(e) => {
+                failed += 1;
+                eprintln!("{} {}: failed: {:#}", name, record_type, e);
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} change(s) failed, {} added, {} changed, {} destroyed",
+            failed,
+            added + changed + destroyed + failed,
+            added,
+            changed,
+            destroyed
+        ))
+        .kind(ErrorKind::Update);
+    }
+    println!(
+        "\nApply complete! Records: {} added, {} changed, {} destroyed.",
+        added, changed, destroyed
+    );
+    Ok(())
+}
+
+async fn connect(
+    config_file: &str,
+    profile: Option<&str>,
+) -> Result<(Config, Box<dyn DnsApiClient>)> {
+    let config = config::load(config_file, profile)
+        .and_then(|config| config::validate(&config).map(|()| config))
+        .kind(ErrorKind::Config)?;
+    http::configure(&config).await.kind(ErrorKind::Config)?;
+    let api_token = secrets::resolve(&config.api_token)
+        .await
+        .kind(ErrorKind::Auth)?;
+    let tenant_tokens = api::tenant_tokens(&config).await.kind(ErrorKind::Auth)?;
+    let limiter = Limiter::new(config.api_rate_limit());
+    let client =
+        api::with_tenants(&config, &api_token, &tenant_tokens, limiter).kind(ErrorKind::Config)?;
+    Ok((config, client))
+}
+
+// Every enabled record of a zone is planned against all the zone's records, so extra records of
+// the same name and type show up as deletions
+async fn make_plan(
+    config: &Config,
+    client: &dyn DnsApiClient,
+    ip_override: &[IpAddr],
+) -> Result<SavedPlan> {
+    let mut detected: HashMap<(IpSource, RecordType), IpAddr> = HashMap::new();
+    let mut zones = Vec::new();
+
+    for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+        let mut desired = Vec::new();
+        for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+            let settings = config.settings(zone, domain);
+            for &record_type in settings.record_types.types() {
+                let source = (settings.ip_source.clone(), record_type);
+                let overridden = ip_override
+                    .iter()
+                    .find(|ip| ip.is_ipv4() == (record_type == RecordType::A));
+                let address = match (overridden, detected.get(&source)) {
+                    (Some(ip), _) | (None, Some(ip)) => *ip,
+                    (None, None) => {
+                        let ip = ip::detect(&settings.ip_source, record_type, &config.retry)
+                            .await
+                            .kind(ErrorKind::Detection)?;
+                        detected.insert(source, ip);
+                        ip
+                    }
+                };
+                for name in domain.record_names(zone) {
+                    desired.push(
+                        DesiredRecord::new(&name, RecordContent::from(address))
+                            .ttl(settings.ttl)
+                            .proxied(settings.proxied),
+                    );
+                }
+            }
+        }
+        if desired.is_empty() {
+            continue;
+        }
+
+        let current: Vec<DnsRecordUpdate> = client
+            .list_records(&zone.id)
+            .await
+            .with_context(|| format!("Failed to list the records of zone {}", zone.id))?
+            .into_iter()
+            .map(DnsRecordUpdate::from)
+            .collect();
+        let plan = UpdatePlanner::new().plan(&desired, &current);
+        zones.push(ZonePlan {
+            zone_id: zone.id.to_string(),
+            name: zone.name.as_deref().map(str::to_string),
+            actions: plan.changes().cloned().collect(),
+        });
+    }
+
+    Ok(SavedPlan {
+        version: PLAN_VERSION,
+        created: Utc::now(),
+        zones,
+    })
+}
+
+// A plan only holds for the records it was made from: one created, changed or deleted since by
+// anyone else makes it stale
+async fn check_stale(client: &dyn DnsApiClient, plan: &SavedPlan) -> Result<()> {
+    let mut stale = Vec::new();
+    for zone in &plan.zones {
+        if zone.actions.is_empty() {
+            continue;
+        }
+        let current: Vec<DnsRecordUpdate> = client
+            .list_records(&zone.zone_id)
+            .await
+            .with_context(|| format!("Failed to list the records of zone {}", zone.zone_id))?
+            .into_iter()
+            .map(DnsRecordUpdate::from)
+            .collect();
+        for action in &zone.actions {
+            let (name, record_type) = action.target();
+            let unchanged = match action {
+                Action::Create { .. } => !current
+                    .iter()
+                    .any(|record| record.name == name && record.r#type == record_type),
+                Action::Update { record, .. }
+                | Action::Delete { record }
+                | Action::NoOp { record } => current.contains(record),
+            };
+            if !unchanged {
+                stale.push(format!("{} {}", name, record_type));
+            }
+        }
+    }
+    if !stale.is_empty() {
+        bail!(
+            "Saved plan is stale, {} changed since it was made. Run `clouddns plan` again.",
+            stale.join(", ")
+        );
+    }
+    Ok(())
+}
+
+fn print_plan(plan: &SavedPlan) {
+    if plan.is_empty() {
+        println!("No changes. The records match the configuration.");
+        return;
+    }
+    // Colors only for people, and not for those who opted out
+    let color = io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none();
+    let paint = |action: &Action| {
+        let line = format!("  {}", action);
+        match (color, action) {
+            (true, Action::Create { .. }) => line.green().to_string(),
+            (true, Action::Update { .. }) => line.yellow().to_string(),
+            (true, Action::Delete { .. }) => line.red().to_string(),
+            _ => line,
+        }
+    };
+
+    println!("clouddns will perform the following actions:\n");
+    let (mut add, mut change, mut destroy) = (0, 0, 0);
+    for (_, action) in plan.actions() {
+        println!("{}", paint(action));
+        match action {
+            Action::Create { .. } => add += 1,
+            Action::Update { .. } => change += 1,
+            Action::Delete { .. } => destroy += 1,
+            Action::NoOp { .. } => {}
+        }
+    }
+    println!(
+        "\nPlan: {} to add, {} to change, {} to destroy.",
+        add, change, destroy
+    );
+}
+
+fn approved() -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!("Not asking for confirmation without a terminal, pass --auto-approve to apply");
+    }
+    print!("\nDo you want to perform these actions? Only 'yes' will be accepted: ");
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim() == "yes")
+}
@@ -0,0 +1,226 @@
+use super::{print_json, print_table};
+use crate::cli::OutputFormat;
+use anyhow::{bail, Result};
+use clouddns::api::{self, Limiter, RecordContent};
+use clouddns::config::{self, IpSource, RecordType};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::secrets;
+use clouddns::{http, ip};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, net::IpAddr};
+
+#[derive(Debug, Serialize)]
+struct Status {
+    addresses: Vec<DetectedAddress>,
+    records: Vec<RecordStatus>,
+}
+
+#[derive(Debug, Serialize)]
+struct DetectedAddress {
+    r#type: &'static str,
+    source: String,
+    address: Option<IpAddr>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecordStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tenant: Option<String>,
+    record: String,
+    r#type: &'static str,
+    // What the provider currently serves
+    content: Option<String>,
+    detected: Option<IpAddr>,
+    state: RecordState,
+    modified_on: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum RecordState {
+    InSync,
+    OutOfSync,
+    // The address couldn't be detected
+    Unknown,
+    NotFound,
+    Error,
+    Disabled,
+}
+
+impl fmt::Display for RecordState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RecordState::InSync => "in sync",
+            RecordState::OutOfSync => "out of sync",
+            RecordState::Unknown => "unknown",
+            RecordState::NotFound => "not found",
+            RecordState::Error => "error",
+            RecordState::Disabled => "disabled",
+        })
+    }
+}
+
+// Compare every record with the address detected right now, querying the provider live. Records
+// are grouped by tenant, or limited to one.
+pub async fn status(
+    config_file: &str,
+    profile: Option<&str>,
+    tenant: Option<&str>,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut config = config::load(config_file, profile)
+        .and_then(|config| config::validate(&config).map(|()| config))
+        .kind(ErrorKind::Config)?;
+    if let Some(tenant) = tenant {
+        if !config.tenants.contains_key(tenant) {
+            bail!("No tenant named {:?} in the configuration", tenant);
+        }
+        config
+            .zones
+            .retain(|zone| zone.tenant.as_deref() == Some(tenant));
+    }
+    config.zones.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+    http::configure(&config).await.kind(ErrorKind::Config)?;
+    let api_token = secrets::resolve(&config.api_token)
+        .await
+        .kind(ErrorKind::Auth)?;
+    let tenant_tokens = api::tenant_tokens(&config).await.kind(ErrorKind::Auth)?;
+    let limiter = Limiter::new(config.api_rate_limit());
+    let client =
+        api::with_tenants(&config, &api_token, &tenant_tokens, limiter).kind(ErrorKind::Config)?;
+
+    let mut detected: HashMap<(IpSource, RecordType), Result<IpAddr, String>> = HashMap::new();
+    let mut records = Vec::new();
+
+    for zone in &config.zones {
+        for domain in &zone.domains {
+            let settings = config.settings(zone, domain);
+            let enabled = zone.is_enabled() && domain.is_enabled();
+
+            for &record_type in settings.record_types.types() {
+                let source = (settings.ip_source.clone(), record_type);
+                if enabled && !detected.contains_key(&source) {
+                    let ip = ip::detect(&settings.ip_source, record_type, &config.retry)
+                        .await
+                        .map_err(|e| e.to_string());
+                    detected.insert(source.clone(), ip);
+                }
+                let current_ip = detected
+                    .get(&source)
+                    .and_then(|ip| ip.as_ref().ok())
+                    .copied();
+
+                for name in domain.record_names(zone) {
+                    let mut status = RecordStatus {
+                        tenant: zone.tenant.clone(),
+                        record: name,
+                        r#type: record_type.as_str(),
+                        content: None,
+                        detected: current_ip,
+                        state: RecordState::Disabled,
+                        modified_on: None,
+                    };
+                    if !enabled {
+                        status.detected = None;
+                        records.push(status);
+                        continue;
+                    }
+
+                    status.state = match client
+                        .get_record(&zone.id, &status.record, record_type.as_str())
+                        .await
+                    {
+                        Ok(record) => {
+                            let state = match current_ip {
+                                Some(ip) if RecordContent::from(ip).matches(&record.content) => {
+                                    RecordState::InSync
+                                }
+                                Some(_) => RecordState::OutOfSync,
+                                None => RecordState::Unknown,
+                            };
+                            status.content = Some(record.content);
+                            status.modified_on = record.modified_on;
+                            state
+                        }
+                        Err(e) if e.to_string().contains("not found") => RecordState::NotFound,
+                        Err(_) => RecordState::Error,
+                    };
+                    records.push(status);
+                }
+            }
+        }
+    }
+
+    let mut addresses: Vec<DetectedAddress> = detected
+        .into_iter()
+        .map(|((source, record_type), ip)| DetectedAddress {
+            r#type: record_type.as_str(),
+            source: source.to_string(),
+            address: ip.as_ref().ok().copied(),
+            error: ip.err(),
+        })
+        .collect();
+    addresses.sort_by(|a, b| (&a.source, a.r#type).cmp(&(&b.source, b.r#type)));
+    let status = Status { addresses, records };
+
+    if output == OutputFormat::Json {
+        return print_json(&status);
+    }
+
+    println!("Detected addresses:");
+    for address in &status.addresses {
+        match (&address.address, &address.error) {
+            (Some(ip), _) => println!("  {:<4} {:<12} {}", address.r#type, address.source, ip),
+            (None, error) => println!(
+                "  {:<4} {:<12} failed: {}",
+                address.r#type,
+                address.source,
+                error.as_deref().unwrap_or_default()
+            ),
+        }
+    }
+    println!();
+
+    let tenants = !config.tenants.is_empty();
+    let mut header = vec![
+        "RECORD".to_string(),
+        "TYPE".to_string(),
+        "PROVIDER".to_string(),
+        "DETECTED".to_string(),
+        "STATE".to_string(),
+        "UPDATED".to_string(),
+    ];
+    if tenants {
+        header.insert(0, "TENANT".to_string());
+    }
+    let mut rows = vec![header];
+    for record in status.records {
+        let tenant = tenants.then(|| record.tenant.unwrap_or_else(|| "-".to_string()));
+        rows.push(
+            tenant
+                .into_iter()
+                .chain([
+                    record.record,
+                    record.r#type.to_string(),
+                    record.content.unwrap_or_else(|| "-".to_string()),
+                    record
+                        .detected
+                        .map_or_else(|| "-".to_string(), |ip| ip.to_string()),
+                    record.state.to_string(),
+                    record
+                        .modified_on
+                        .as_deref()
+                        .map_or_else(|| "-".to_string(), timestamp),
+                ])
+                .collect(),
+        );
+    }
+    print_table(&rows)?;
+    Ok(())
+}
+
+// `2024-05-01T12:34:56.789Z` as `2024-05-01 12:34:56`
+fn timestamp(value: &str) -> String {
+    value.get(..19).unwrap_or(value).replacen('T', " ", 1)
+}
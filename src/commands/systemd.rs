@@ -0,0 +1,144 @@
+use anyhow::{Context, Result};
+use clouddns::config::{self, Config, StateStoreKind};
+use std::{env, fs};
+
+// Print a hardened service unit running this binary with the given configuration. The unit runs
+// under a throwaway user, so the configuration must be world-readable and the token, which
+// shouldn't be, is handed over as a credential.
+pub fn unit(config_file: &str, profile: Option<&str>, token_file: Option<&str>) -> Result<()> {
+    let binary = env::current_exe().context("Failed to locate the clouddns binary")?;
+    let config = fs::canonicalize(config_file)
+        .with_context(|| format!("Failed to locate config file: {}", config_file))?;
+    // Placeholders are left alone, the variables they name may only be set for the service
+    let mut settings = config::read_raw(config_file)?.config;
+    if let Some(profile) = profile {
+        settings.apply_profile(profile)?;
+    }
+
+    let mut command = vec![
+        binary.to_string_lossy().to_string(),
+        "--config".to_string(),
+        config.to_string_lossy().to_string(),
+    ];
+    if let Some(profile) = profile {
+        command.extend(["--profile".to_string(), profile.to_string()]);
+    }
+    command.push("run".to_string());
+    let command: Vec<String> = command.iter().map(|arg| quote(arg)).collect();
+
+    let mut unit = format!(
+        "[Unit]
+Description=Dynamic DNS updater for Cloudflare
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=simple
+ExecStart={}
+ExecReload=/bin/kill -HUP $MAINPID
+# Retrying won't fix a broken configuration (3) or a rejected token (4)
+Restart=on-failure
+RestartSec=30s
+RestartPreventExitStatus=3 4
+",
+        command.join(" ")
+    );
+
+    if let Some(token_file) = token_file {
+        let token_file = fs::canonicalize(token_file)
+            .with_context(|| format!("Failed to locate token file: {}", token_file))?;
+        unit.push_str(&format!(
+            "# Read by clouddns with api_token = \"secret://api_token\"\nLoadCredential=api_token:{}\n",
+            quote(&token_file.to_string_lossy())
+        ));
+    }
+
+    // A configuration in a home directory has to stay readable
+    let protect_home = match config.starts_with("/home") || config.starts_with("/root") {
+        true => "read-only",
+        false => "yes",
+    };
+    unit.push_str("\nDynamicUser=yes\n");
+    if uses_state_directory(&settings) {
+        unit.push_str(
+            "# Keeps the state file across restarts, see --state-file\nStateDirectory=clouddns\n",
+        );
+    }
+    if uses_runtime_directory(&settings) {
+        unit.push_str("# Holds the control socket\nRuntimeDirectory=clouddns\n");
+    }
+    unit.push_str(&format!(
+        "UMask=0077
+NoNewPrivileges=yes
+CapabilityBoundingSet=
+ProtectSystem=strict
+ProtectHome={}
+PrivateTmp=yes
+PrivateDevices=yes
+ProtectClock=yes
+ProtectHostname=yes
+ProtectKernelLogs=yes
+ProtectKernelModules=yes
+ProtectKernelTunables=yes
+ProtectControlGroups=yes
+ProtectProc=invisible
+RestrictAddressFamilies=AF_INET AF_INET6 AF_UNIX
+RestrictNamespaces=yes
+RestrictRealtime=yes
+RestrictSUIDSGID=yes
+LockPersonality=yes
+MemoryDenyWriteExecute=yes
+SystemCallArchitectures=native
+SystemCallFilter=@system-service
+SystemCallFilter=~@privileged @resources
+
+[Install]
+WantedBy=multi-user.target
+",
+        protect_home
+    ));
+
+    print!("{}", unit);
+    Ok(())
+}
+
+// Files kept in the state directory, by default or because the configuration says so
+fn uses_state_directory(config: &Config) -> bool {
+    let default_state = matches!(
+        config.state.store,
+        StateStoreKind::File | StateStoreKind::Sqlite
+    ) && config.state.path.is_none();
+    let default_accounts = config
+        .acme_dns
+        .as_ref()
+        .is_some_and(|acme_dns| acme_dns.accounts.is_none());
+    let configured = [
+        config.state.path.as_deref(),
+        config.status_file.as_deref(),
+        config
+            .acme_dns
+            .as_ref()
+            .and_then(|acme_dns| acme_dns.accounts.as_deref()),
+    ];
+    default_state
+        || default_accounts
+        || configured
+            .into_iter()
+            .flatten()
+            .any(|path| path.starts_with("/var/lib/clouddns"))
+}
+
+fn uses_runtime_directory(config: &Config) -> bool {
+    config.control_socket.as_deref().is_some_and(|path| {
+        path.starts_with("/run/clouddns") || path.starts_with("/var/run/clouddns")
+    })
+}
+
+// systemd splits on whitespace, honours double quotes and expands `%` specifiers
+fn quote(arg: &str) -> String {
+    let arg = arg.replace('%', "%%");
+    match arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"' || c == '\\') {
+        true => format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\"")),
+        false => arg,
+    }
+}
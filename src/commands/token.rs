@@ -0,0 +1,39 @@
+use crate::cli::TokenCommand;
+use anyhow::{Context, Result};
+use clouddns::secrets::keyring;
+use std::io::{self, BufRead, IsTerminal};
+
+fn read_token() -> Result<String> {
+    let token = if io::stdin().is_terminal() {
+        rpassword::prompt_password("API token: ")?
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        line
+    };
+
+    let token = token.trim().to_string();
+    if token.is_empty() {
+        return Err(anyhow::anyhow!("No token given"));
+    }
+    Ok(token)
+}
+
+pub async fn token(action: TokenCommand) -> Result<()> {
+    match action {
+        TokenCommand::Set { account } => {
+            let token = read_token()?;
+            keyring::set(&account, &token)
+                .await
+                .context("Failed to store the token")?;
+            println!("Token stored in the keyring as `{}`", account);
+        }
+        TokenCommand::Delete { account } => {
+            keyring::delete(&account)
+                .await
+                .context("Failed to delete the token")?;
+            println!("Token `{}` removed from the keyring", account);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use super::print_json;
+use crate::cli::OutputFormat;
+use anyhow::Result;
+use clouddns::api::{self, CloudflareClient, Limiter};
+use clouddns::config::{self, Config, ProviderKind};
+use clouddns::error::{ErrorKind, ResultExt};
+use clouddns::{http, secrets};
+use serde::Serialize;
+use std::{collections::BTreeMap, iter};
+
+#[derive(Debug, Serialize)]
+struct Validation {
+    file: String,
+    valid: bool,
+    legacy: bool,
+    problems: Vec<String>,
+    warnings: Vec<String>,
+    disabled: Vec<String>,
+}
+
+// Check the token, zones and records against the live API
+async fn online_problems(config: &Config) -> Vec<String> {
+    if let Err(e) = http::configure(config).await {
+        return vec![format!("{:#}", e)];
+    }
+    let token = match secrets::resolve(&config.api_token).await {
+        Ok(token) => token,
+        Err(e) => return vec![format!("api_token: {:#}", e)],
+    };
+
+    let tenant_tokens = match api::tenant_tokens(config).await {
+        Ok(tokens) => tokens,
+        Err(e) => return vec![format!("tenants: {:#}", e)],
+    };
+
+    let limiter = Limiter::new(config.api_rate_limit());
+    // A plugin provider's token and zones are its own business, only the records are checked.
+    // Each tenant's token is checked against the tenant's zones.
+    let cloudflare = match config.provider.r#type {
+        ProviderKind::Cloudflare => {
+            let tokens = iter::once((None, &token)).chain(
+                tenant_tokens
+                    .iter()
+                    .map(|(tenant, token)| (Some(tenant.clone()), token)),
+            );
+            let mut clients = BTreeMap::new();
+            for (tenant, token) in tokens {
+                let field = match &tenant {
+                    Some(tenant) => format!("tenants.{}.api_token", tenant),
+                    None => "api_token".to_string(),
+                };
+                let client = CloudflareClient::new(token, &config.retry, limiter.clone());
+                match client.verify_token().await {
+                    Ok(status) if status.status == "active" => {}
+                    Ok(status) => return vec![format!("{}: token is {}", field, status.status)],
+                    Err(e) => return vec![format!("{}: {:#}", field, e)],
+                }
+                clients.insert(tenant, client);
+            }
+            Some(clients)
+        }
+        ProviderKind::Exec => None,
+    };
+    let client = match api::with_tenants(config, &token, &tenant_tokens, limiter) {
+        Ok(client) => client,
+        Err(e) => return vec![format!("provider: {:#}", e)],
+    };
+
+    let mut problems = Vec::new();
+    for (index, zone) in config.zones.iter().enumerate() {
+        // Parked entries may well point at zones that are gone for now
+        if !zone.is_enabled() {
+            continue;
+        }
+        if let Some(cloudflare) = cloudflare
+            .as_ref()
+            .and_then(|clients| clients.get(&zone.tenant))
+        {
+            match cloudflare.get_zone(&zone.id).await {
+                Ok(api_zone) => {
+                    if let Some(name) = &zone.name {
+                        if name.trim_end_matches('.') != api_zone.name {
+                            problems.push(format!(
+                                "zones[{}].name: zone {} is named {}, not {}",
+                                index, &zone.id, &api_zone.name, name
+                            ));
+                        }
+                    }
+                }
+                Err(e) => {
+                    problems.push(format!("zones[{}].id: {:#}", index, e));
+                    continue;
+                }
+            }
+        }
+
+        for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+            let settings = config.settings(zone, domain);
+            for record_type in settings.record_types.types() {
+                for record in domain.record_names(zone) {
+                    if let Err(e) = client
+                        .get_record(&zone.id, &record, record_type.as_str())
+                        .await
+                    {
+                        problems.push(format!("zones[{}]: {:#}", index, e));
+                    }
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+pub async fn validate(
+    config_file: &str,
+    profile: Option<&str>,
+    online: bool,
+    output: OutputFormat,
+) -> Result<()> {
+    let mut document = config::read(config_file).kind(ErrorKind::Config)?;
+    if let Some(profile) = profile {
+        document
+            .config
+            .apply_profile(profile)
+            .kind(ErrorKind::Config)?;
+    }
+
+    let mut problems = config::problems(&document.config);
+    // Online checks on a broken config would only add noise
+    if online && problems.is_empty() {
+        problems.extend(online_problems(&document.config).await);
+    }
+    let validation = Validation {
+        file: config_file.to_string(),
+        valid: problems.is_empty(),
+        legacy: document.legacy,
+        disabled: document.config.disabled_entries(),
+        warnings: document.config.ttl_warnings(),
+        problems,
+    };
+
+    if output == OutputFormat::Json {
+        print_json(&validation)?;
+    } else {
+        if validation.legacy {
+            println!("warning: legacy zone_id/domain_list format, run `clouddns migrate-config` to convert it");
+        }
+        for warning in &validation.warnings {
+            println!("warning: {}", warning);
+        }
+        if validation.valid {
+            println!("{} is valid", config_file);
+            for entry in &validation.disabled {
+                println!("  - {} is disabled", entry);
+            }
+        }
+        for problem in &validation.problems {
+            println!("  - {}", problem);
+        }
+    }
+
+    if validation.valid {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "{} has {} problem(s)",
+        config_file,
+        validation.problems.len()
+    ))
+    .kind(ErrorKind::Config)
+}
@@ -0,0 +1,259 @@
+use anyhow::Result;
+use clouddns::ddns::{CloudflareDdns, Control};
+use clouddns::logging;
+use clouddns::snapshot::{RecordState, Snapshot};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Paragraph, Row, Table},
+    DefaultTerminal, Frame,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+use tokio::sync::{mpsc, oneshot, watch};
+use tokio::time::Instant;
+
+const LOG_LINES: usize = 500;
+const REFRESH: Duration = Duration::from_millis(250);
+
+// Log lines kept for the event pane, fed by the logger
+#[derive(Clone, Default)]
+struct LogBuffer {
+    lines: Arc<Mutex<VecDeque<String>>>,
+}
+
+impl Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut lines = self.lines.lock().unwrap();
+        for line in String::from_utf8_lossy(buf).lines() {
+            if lines.len() == LOG_LINES {
+                lines.pop_front();
+            }
+            lines.push_back(line.to_string());
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct Options<'a> {
+    pub config_file: &'a str,
+    pub profile: Option<&'a str>,
+    pub dry_run: bool,
+    pub lock_file: Option<&'a str>,
+    pub state_file: Option<&'a str>,
+    pub ip_override: &'a [IpAddr],
+    pub verbose: u8,
+    pub quiet: u8,
+    pub log_level: Option<&'a str>,
+}
+
+// Run the updater with a live view of its state, taking over the terminal until `q` is pressed
+pub async fn watch(options: Options<'_>) -> Result<()> {
+    let logs = LogBuffer::default();
+    logging::init_pipe(
+        options.verbose,
+        options.quiet,
+        options.log_level,
+        Box::new(logs.clone()),
+    );
+
+    let (snapshot_tx, snapshot_rx) = watch::channel(Snapshot::default());
+    let (control_tx, control_rx) = mpsc::unbounded_channel();
+    let (stop_tx, stop_rx) = oneshot::channel::<()>();
+
+    let mut ddns = CloudflareDdns::new(options.config_file, options.profile)
+        .await?
+        .dry_run(options.dry_run)
+        .ip_override(options.ip_override)?
+        .lock(options.lock_file)?
+        .state(options.state_file)?
+        .control(control_rx)
+        .snapshots(snapshot_tx);
+
+    let mut terminal = ratatui::init();
+    let daemon = ddns.run(async {
+        let _ = stop_rx.await;
+    });
+    tokio::pin!(daemon);
+
+    let result = tokio::select! {
+        // The updater only stops on its own when it fails
+        result = &mut daemon => result.map_err(Into::into),
+        result = interact(&mut terminal, snapshot_rx, &logs, control_tx) => {
+            let _ = stop_tx.send(());
+            result.and(daemon.await.map_err(Into::into))
+        }
+    };
+    ratatui::restore();
+    result
+}
+
+async fn interact(
+    terminal: &mut DefaultTerminal,
+    snapshot: watch::Receiver<Snapshot>,
+    logs: &LogBuffer,
+    control: mpsc::UnboundedSender<Control>,
+) -> Result<()> {
+    let mut keys = read_keys();
+    let mut refresh = tokio::time::interval(REFRESH);
+
+    loop {
+        {
+            let snapshot = snapshot.borrow();
+            let logs = logs.lines.lock().unwrap();
+            terminal.draw(|frame| draw(frame, &snapshot, &logs))?;
+        }
+
+        tokio::select! {
+            _ = refresh.tick() => {}
+            Some(key) = keys.recv() => match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(()),
+                KeyCode::Char('f') => {
+                    let _ = control.send(Control::ForceUpdate);
+                }
+                KeyCode::Char('p') => {
+                    let request = match snapshot.borrow().paused {
+                        true => Control::Resume,
+                        false => Control::Pause,
+                    };
+                    let _ = control.send(request);
+                }
+                _ => {}
+            },
+        }
+    }
+}
+
+// crossterm only offers blocking reads, so they happen on their own thread
+fn read_keys() -> mpsc::UnboundedReceiver<KeyEvent> {
+    let (sender, keys) = mpsc::unbounded_channel();
+    thread::spawn(move || {
+        while !sender.is_closed() {
+            match event::poll(REFRESH) {
+                Ok(true) => {
+                    if let Ok(Event::Key(key)) = event::read() {
+                        if key.kind == KeyEventKind::Press && sender.send(key).is_err() {
+                            break;
+                        }
+                    }
+                }
+                Ok(false) => {}
+                Err(_) => break,
+            }
+        }
+    });
+    keys
+}
+
+fn draw(frame: &mut Frame, snapshot: &Snapshot, logs: &VecDeque<String>) {
+    let [addresses_area, records_area, status_area, log_area] = Layout::vertical([
+        Constraint::Length(snapshot.addresses.len().max(1) as u16 + 2),
+        Constraint::Min(snapshot.records.len().min(10) as u16 + 3),
+        Constraint::Length(1),
+        Constraint::Percentage(35),
+    ])
+    .areas(frame.area());
+
+    let addresses: Vec<Line> = match snapshot.addresses.is_empty() {
+        true => vec![Line::from("Not detected yet")],
+        false => snapshot
+            .addresses
+            .iter()
+            .map(|((source, record_type), ip)| match ip {
+                Ok(ip) => Line::from(format!("{:<4} {:<12} {}", record_type, source, ip)),
+                Err(e) => Line::styled(
+                    format!("{:<4} {:<12} failed: {}", record_type, source, e),
+                    Style::new().fg(Color::Red),
+                ),
+            })
+            .collect(),
+    };
+    frame.render_widget(
+        Paragraph::new(addresses).block(Block::bordered().title(" Addresses ")),
+        addresses_area,
+    );
+
+    let header = Row::new(["RECORD", "TYPE", "PROVIDER", "STATE", "CHECKED"])
+        .style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = snapshot
+        .records
+        .iter()
+        .map(|((record, record_type), status)| {
+            let (state, color) = match &status.state {
+                RecordState::InSync => ("in sync".to_string(), Color::Green),
+                RecordState::Updated => ("updated".to_string(), Color::Cyan),
+                RecordState::Pending => ("would update".to_string(), Color::Yellow),
+                RecordState::Deferred => ("deferred".to_string(), Color::Yellow),
+                RecordState::Failed(e) => (format!("failed: {}", e), Color::Red),
+            };
+            Row::new([
+                record.clone(),
+                record_type.to_string(),
+                status.content.clone().unwrap_or_else(|| "-".to_string()),
+                state,
+                status.checked.format("%H:%M:%S").to_string(),
+            ])
+            .style(Style::new().fg(color))
+        });
+    let table = Table::new(
+        rows,
+        [
+            Constraint::Min(20),
+            Constraint::Length(4),
+            Constraint::Length(39),
+            Constraint::Min(12),
+            Constraint::Length(8),
+        ],
+    )
+    .header(header)
+    .block(Block::bordered().title(" Records "));
+    frame.render_widget(table, records_area);
+
+    let state = if snapshot.paused {
+        Span::styled("Paused", Style::new().fg(Color::Yellow))
+    } else if snapshot.in_maintenance {
+        Span::styled("Maintenance window", Style::new().fg(Color::Yellow))
+    } else {
+        match snapshot.next_run {
+            Some(next_run) => {
+                let seconds = next_run.saturating_duration_since(Instant::now()).as_secs();
+                Span::raw(format!(
+                    "Next check in {}:{:02}",
+                    seconds / 60,
+                    seconds % 60
+                ))
+            }
+            None => Span::raw("Nothing scheduled"),
+        }
+    };
+    let keys = Span::styled(
+        "   f force update · p pause/resume · q quit",
+        Style::new().fg(Color::DarkGray),
+    );
+    frame.render_widget(Paragraph::new(Line::from(vec![state, keys])), status_area);
+
+    // Newest at the bottom, as many as fit
+    let height = log_area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = logs
+        .iter()
+        .skip(logs.len().saturating_sub(height))
+        .map(|line| Line::from(line.as_str()))
+        .collect();
+    frame.render_widget(
+        Paragraph::new(lines).block(Block::bordered().title(" Events ")),
+        log_area,
+    );
+}
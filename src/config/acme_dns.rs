@@ -0,0 +1,53 @@
+use super::names;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::{net::SocketAddr, path::PathBuf};
+use validator::{Validate, ValidationError};
+
+/// An acme-dns compatible API, so ACME clients across the network can answer DNS-01 challenges
+/// through clouddns rather than each holding an API token. Clients register an account, get a
+/// subdomain of `domain`, and put their challenge tokens there; `_acme-challenge` names are then
+/// pointed at it with a CNAME.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AcmeDnsConfig {
+    /// Address and port to listen on. Anyone reaching it may register, see `register_from`.
+    #[schemars(example = &"0.0.0.0:8053")]
+    pub listen: SocketAddr,
+
+    /// Name the accounts' subdomains are created under, inside one of the named zones
+    #[validate(custom(function = "validate_domain"))]
+    #[schemars(example = &"acme.example.com")]
+    pub domain: String,
+
+    /// Networks allowed to register accounts, such as `192.168.1.0/24`. Anyone when empty.
+    #[validate(custom(function = "validate_networks"))]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub register_from: Vec<String>,
+
+    /// File keeping the registered accounts, named after the configuration file in the state
+    /// directory by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub accounts: Option<PathBuf>,
+}
+
+fn validate_domain(domain: &str) -> Result<(), ValidationError> {
+    names::validate_fqdn(&names::normalize(domain))
+        .map_err(|e| ValidationError::new("acme_dns_domain").with_message(Cow::Owned(e)))
+}
+
+fn validate_networks(networks: &[String]) -> Result<(), ValidationError> {
+    match networks
+        .iter()
+        .find(|network| network.parse::<ipnet::IpNet>().is_err())
+    {
+        Some(network) => Err(
+            ValidationError::new("network").with_message(Cow::Owned(format!(
+                "{:?} is not a network such as 192.168.1.0/24",
+                network
+            ))),
+        ),
+        None => Ok(()),
+    }
+}
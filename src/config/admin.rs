@@ -0,0 +1,24 @@
+use super::secret::{validate_secret, SecretSource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use validator::Validate;
+
+/// HTTP API to look at and steer the running daemon, e.g. from orchestration tools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    /// Address and port to listen on. The API can trigger updates, keep it on a trusted network
+    #[schemars(example = &"127.0.0.1:8081")]
+    pub listen: SocketAddr,
+
+    /// Bearer token every request must carry
+    #[validate(custom(function = "validate_secret"))]
+    pub token: SecretSource,
+
+    /// Token accepted by `POST /trigger` alone, for routers and scripts that shouldn't be able to
+    /// do more than ask for a check. Given as a bearer token or as `?token=`.
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trigger_token: Option<SecretSource>,
+}
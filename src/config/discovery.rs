@@ -0,0 +1,110 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use validator::Validate;
+
+const DEFAULT_DOCKER_SOCKET: &str = "/var/run/docker.sock";
+const DEFAULT_PROXY_INTERVAL: u64 = 60;
+
+/// Hostnames learned from other software running on this host, managed on top of the configured
+/// domains. Each one belongs to the configured zone whose `name` it ends with.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// Running containers with a `clouddns.hostname` label
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub docker: Option<DockerDiscovery>,
+
+    /// Ingresses and Services with a `clouddns.io/hostname` annotation, with the `k8s` feature
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kubernetes: Option<KubernetesDiscovery>,
+
+    /// The hostnames Traefik routes, read from its API
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub traefik: Option<ProxyDiscovery>,
+
+    /// The hostnames Caddy serves, read from its admin API
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub caddy: Option<ProxyDiscovery>,
+
+    /// Keep the records of a hostname nothing asks for anymore, e.g. once its container stopped,
+    /// rather than deleting them
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub keep_records: bool,
+}
+
+impl DiscoveryConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == DiscoveryConfig::default()
+    }
+}
+
+/// Containers asking for records through their labels: `clouddns.hostname` (several names
+/// separated by commas), and optionally `clouddns.type`, `clouddns.ttl` and `clouddns.proxied`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DockerDiscovery {
+    /// Docker's API socket
+    #[serde(default = "default_docker_socket")]
+    pub socket: PathBuf,
+}
+
+impl Default for DockerDiscovery {
+    fn default() -> Self {
+        Self {
+            socket: default_docker_socket(),
+        }
+    }
+}
+
+fn default_docker_socket() -> PathBuf {
+    PathBuf::from(DEFAULT_DOCKER_SOCKET)
+}
+
+/// Kubernetes objects asking for records through their annotations: `clouddns.io/hostname`
+/// (several names separated by commas), and optionally `clouddns.io/type`, `clouddns.io/ttl` and
+/// `clouddns.io/proxied`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct KubernetesDiscovery {
+    /// The API server, e.g. `http://127.0.0.1:8001` behind `kubectl proxy`. Inside a pod, the
+    /// cluster's own, reached with the pod's service account.
+    #[validate(url(message = "Kubernetes API server must be a valid URL"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_server: Option<String>,
+
+    /// Only the objects of this namespace, rather than those of all namespaces
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+}
+
+/// A reverse proxy whose routes are read again and again, each of their hostnames getting records
+/// with the settings of its zone
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyDiscovery {
+    /// The API, e.g. `http://127.0.0.1:8080` for Traefik or `http://127.0.0.1:2019` for Caddy
+    #[validate(url(message = "Proxy API URL must be a valid URL"))]
+    pub url: String,
+
+    /// Seconds between reads
+    #[validate(range(min = 1, message = "Proxy discovery interval must be greater than 0"))]
+    #[serde(
+        default = "default_proxy_interval",
+        skip_serializing_if = "is_default_proxy_interval"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub interval: u64,
+}
+
+fn default_proxy_interval() -> u64 {
+    DEFAULT_PROXY_INTERVAL
+}
+
+fn is_default_proxy_interval(interval: &u64) -> bool {
+    *interval == DEFAULT_PROXY_INTERVAL
+}
@@ -0,0 +1,58 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, net::IpAddr};
+use validator::{Validate, ValidationError};
+
+/// How the host names of the API, IP sources and notification services are looked up, so
+/// updates keep working while the local DNS is broken
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct DnsConfig {
+    /// Fixed addresses for host names, used without any lookup
+    #[validate(custom(function = "validate_hosts"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(example = example_hosts())]
+    pub hosts: BTreeMap<String, Vec<IpAddr>>,
+
+    /// Resolvers asked for every other name instead of the system's
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(example = &["1.1.1.1", "9.9.9.9"])]
+    pub nameservers: Vec<IpAddr>,
+
+    /// Look each record up before asking the API about it, and leave those already answering
+    /// with the current address alone until the next check. Never for proxied records, whose
+    /// public answers are Cloudflare's edge addresses rather than their content.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub precheck: bool,
+}
+
+impl DnsConfig {
+    // Whether names are all looked up the usual way
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty() && self.nameservers.is_empty()
+    }
+
+    pub fn is_default(&self) -> bool {
+        *self == DnsConfig::default()
+    }
+
+    // Whether names aren't all looked up by the system
+    pub fn overrides(&self, host: &str) -> bool {
+        !self.nameservers.is_empty() || self.hosts.contains_key(host)
+    }
+}
+
+fn example_hosts() -> BTreeMap<String, Vec<IpAddr>> {
+    BTreeMap::from([(
+        "api.cloudflare.com".to_string(),
+        vec![[104, 16, 132, 229].into(), [104, 16, 133, 229].into()],
+    )])
+}
+
+fn validate_hosts(hosts: &BTreeMap<String, Vec<IpAddr>>) -> Result<(), ValidationError> {
+    match hosts.iter().find(|(_, addresses)| addresses.is_empty()) {
+        Some((host, _)) => Err(ValidationError::new("hosts")
+            .with_message(format!("{} needs at least one address", host).into())),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use validator::{ValidationErrors, ValidationErrorsKind};
+
+// A parse failure pinned to a location in the file and a path in the config tree
+#[derive(Debug)]
+pub struct ParseError {
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub path: String,
+    pub message: String,
+}
+
+impl ParseError {
+    pub fn new(path: String, message: String, location: Option<(usize, usize)>) -> Self {
+        let mut message = strip_location(&message);
+        // serde_yaml prefixes nested errors with their path, which we already report
+        if let Some((prefix, rest)) = message.split_once(": ") {
+            if path.starts_with(prefix) {
+                message = rest;
+            }
+        }
+
+        Self {
+            line: location.map(|(line, _)| line),
+            column: location.map(|(_, column)| column),
+            message: message.to_string(),
+            path,
+        }
+    }
+
+    // Suggest the closest expected field for errors like "unknown field `domians`, expected ..."
+    fn suggestion(&self) -> Option<&str> {
+        let unknown = self.message.strip_prefix("unknown field `")?;
+        let (unknown, expected) = unknown.split_once('`')?;
+
+        expected
+            .split('`')
+            .skip(1)
+            .step_by(2)
+            .map(|candidate| (candidate, strsim::levenshtein(unknown, candidate)))
+            .filter(|(candidate, distance)| *distance <= candidate.len().max(unknown.len()) / 3 + 1)
+            .min_by_key(|(_, distance)| *distance)
+            .map(|(candidate, _)| candidate)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "line {}, column {}: ", line, column)?;
+        }
+        write!(f, "{}", self.message)?;
+        // The root path is just "." which doesn't tell anyone anything
+        if self.path != "." {
+            write!(f, " (at `{}`)", self.path)?;
+        }
+        if let Some(suggestion) = self.suggestion() {
+            write!(f, "\n  hint: did you mean `{}`?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+// serde_json and serde_yaml append the location to their messages, which we already report
+fn strip_location(message: &str) -> &str {
+    match message.rfind(" at line ") {
+        Some(index) => &message[..index],
+        None => message,
+    }
+}
+
+// 1-based line and column of a byte offset
+pub(crate) fn line_column(contents: &str, offset: usize) -> (usize, usize) {
+    let before = &contents[..offset.min(contents.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|c| *c != '\n').count() + 1;
+    (line, column)
+}
+
+// Flatten nested validator errors into one "path: message" line per problem
+pub(crate) fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    let mut messages = Vec::new();
+    collect_messages(errors, "", &mut messages);
+    messages.sort();
+    messages
+}
+
+fn collect_messages(errors: &ValidationErrors, prefix: &str, messages: &mut Vec<String>) {
+    for (field, kind) in errors.errors() {
+        // Struct-level (schema) errors are reported against the struct itself
+        let path = if *field == "__all__" {
+            prefix.to_string()
+        } else if prefix.is_empty() {
+            field.to_string()
+        } else {
+            format!("{}.{}", prefix, field)
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => {
+                for error in errors {
+                    let message = error
+                        .message
+                        .as_ref()
+                        .map(|message| message.to_string())
+                        .unwrap_or_else(|| error.code.to_string());
+                    messages.push(format!("{}: {}", path, message));
+                }
+            }
+            ValidationErrorsKind::Struct(errors) => collect_messages(errors, &path, messages),
+            ValidationErrorsKind::List(items) => {
+                for (index, errors) in items {
+                    collect_messages(errors, &format!("{}[{}]", path, index), messages);
+                }
+            }
+        }
+    }
+}
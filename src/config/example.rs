@@ -0,0 +1,219 @@
+use super::schema::schema;
+use serde_json::{Map, Value};
+use std::fmt::Write;
+
+// A commented TOML configuration covering every option, rendered from the JSON Schema
+// so it follows the model. Required options are written out, with their example, so that
+// the result is a valid configuration. Everything else is commented, with its default, an
+// example or a placeholder value.
+pub fn example() -> String {
+    let schema = schema().to_value();
+    let definitions = schema.get("$defs").and_then(Value::as_object);
+
+    let mut writer = ExampleWriter {
+        definitions,
+        output: String::new(),
+    };
+    if let Some(description) = schema.get("description").and_then(Value::as_str) {
+        writer.comment(description);
+    }
+    writer.object(&schema, &[], false);
+    writer.output
+}
+
+struct ExampleWriter<'a> {
+    definitions: Option<&'a Map<String, Value>>,
+    output: String,
+}
+
+enum Kind<'a> {
+    Value,
+    Table(&'a Value),
+    ArrayOfTables(&'a Value),
+    Map(&'a Value),
+}
+
+impl<'a> ExampleWriter<'a> {
+    // Follow `$ref`s and unwrap `Option`s down to the schema of the value itself
+    fn resolve(&self, schema: &'a Value) -> &'a Value {
+        if let Some(name) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix("#/$defs/"))
+        {
+            if let Some(definition) = self.definitions.and_then(|defs| defs.get(name)) {
+                return self.resolve(definition);
+            }
+        }
+
+        if let Some(variants) = schema.get("anyOf").and_then(Value::as_array) {
+            let mut non_null = variants
+                .iter()
+                .filter(|variant| variant.get("type").and_then(Value::as_str) != Some("null"));
+            if let (Some(variant), None) = (non_null.next(), non_null.next()) {
+                return self.resolve(variant);
+            }
+        }
+        schema
+    }
+
+    fn kind(&self, schema: &'a Value) -> Kind<'a> {
+        let schema = self.resolve(schema);
+        if schema.get("properties").is_some() {
+            return Kind::Table(schema);
+        }
+        if let Some(items) = schema.get("items") {
+            let items = self.resolve(items);
+            if items.get("properties").is_some() {
+                return Kind::ArrayOfTables(items);
+            }
+        }
+        match schema.get("additionalProperties") {
+            Some(values) if values.is_object() => Kind::Map(self.resolve(values)),
+            _ => Kind::Value,
+        }
+    }
+
+    fn object(&mut self, schema: &'a Value, path: &[&str], commented: bool) {
+        let schema = self.resolve(schema);
+        let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        // Of options required in the alternative, those of the first one
+        let alternative = schema
+            .get("anyOf")
+            .and_then(|variants| variants.get(0))
+            .unwrap_or(&Value::Null);
+        let required: Vec<&str> = [schema, alternative]
+            .iter()
+            .filter_map(|schema| schema.get("required").and_then(Value::as_array))
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+
+        // TOML wants plain keys before any table of the same level
+        let (values, tables): (Vec<_>, Vec<_>) = properties
+            .iter()
+            .partition(|(_, property)| matches!(self.kind(property), Kind::Value));
+
+        for (name, property) in values.into_iter().chain(tables) {
+            let is_required = required.contains(&name.as_str())
+                || property.get("minItems").and_then(Value::as_u64) > Some(0);
+            let commented = commented || !is_required;
+            let mut key_path = path.to_vec();
+            key_path.push(name);
+
+            self.output.push('\n');
+            self.description(property);
+
+            match self.kind(property) {
+                Kind::Value => {
+                    let line = format!("{} = {}", name, self.value(property));
+                    self.line(&line, commented);
+                }
+                Kind::Table(table) => {
+                    self.line(&format!("[{}]", key_path.join(".")), commented);
+                    self.object(table, &key_path, commented);
+                }
+                Kind::ArrayOfTables(table) => {
+                    self.line(&format!("[[{}]]", key_path.join(".")), commented);
+                    self.object(table, &key_path, commented);
+                }
+                Kind::Map(values) => {
+                    key_path.push("name");
+                    match self.kind(values) {
+                        Kind::Table(table) => {
+                            self.line(&format!("[{}]", key_path.join(".")), true);
+                            self.object(table, &key_path, true);
+                        }
+                        _ => {
+                            self.line(&format!("[{}]", name), true);
+                            self.line(&format!("name = {}", self.value(values)), true);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // The property's own description, then the alternatives it accepts
+    fn description(&mut self, property: &Value) {
+        let resolved = self.resolve(property);
+        let description = property
+            .get("description")
+            .or_else(|| resolved.get("description"))
+            .and_then(Value::as_str);
+        if let Some(description) = description {
+            self.comment(description);
+        }
+
+        let variants = property
+            .get("$ref")
+            .map(|_| resolved)
+            .unwrap_or(property)
+            .get("anyOf")
+            .and_then(Value::as_array);
+        for variant in variants.into_iter().flatten() {
+            if let Some(description) = variant.get("description").and_then(Value::as_str) {
+                self.comment(&format!("  - {}", description));
+            }
+        }
+    }
+
+    fn value(&self, property: &Value) -> String {
+        let resolved = self.resolve(property);
+        let value = [property, resolved]
+            .iter()
+            .find_map(|schema| {
+                schema
+                    .get("examples")
+                    .and_then(|examples| examples.get(0))
+                    .or_else(|| schema.get("default"))
+            })
+            .cloned()
+            .or_else(|| placeholder(self.resolve(resolved)));
+
+        value
+            .and_then(|value| toml::Value::try_from(value).ok())
+            .map(|value| value.to_string())
+            .unwrap_or_else(|| "\"\"".to_string())
+    }
+
+    fn comment(&mut self, text: &str) {
+        for line in text.lines() {
+            let _ = writeln!(self.output, "# {}", line.trim_end());
+        }
+    }
+
+    fn line(&mut self, line: &str, commented: bool) {
+        let prefix = if commented { "# " } else { "" };
+        let _ = writeln!(self.output, "{}{}", prefix, line);
+    }
+}
+
+// A value of the right type for options without an example or a default
+fn placeholder(schema: &Value) -> Option<Value> {
+    if let Some(value) = schema
+        .get("enum")
+        .and_then(|values| values.get(0))
+        .or_else(|| schema.get("const"))
+    {
+        return Some(value.clone());
+    }
+    let variants = schema.get("anyOf").or_else(|| schema.get("oneOf"));
+    if let Some(variants) = variants.and_then(Value::as_array) {
+        return variants.iter().find_map(placeholder);
+    }
+
+    let types: Vec<&str> = match schema.get("type") {
+        Some(Value::String(name)) => vec![name],
+        Some(Value::Array(names)) => names.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    match types.iter().find(|name| **name != "null")? {
+        &"boolean" => Some(Value::Bool(false)),
+        &"integer" | &"number" => Some(schema.get("minimum").cloned().unwrap_or(Value::from(0))),
+        &"array" => Some(Value::Array(Vec::new())),
+        _ => Some(Value::String(String::new())),
+    }
+}
@@ -0,0 +1,36 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use validator::Validate;
+
+const DEFAULT_PERIOD: u64 = 60;
+
+/// When a record changes more often than an address normally does, which usually means a broken
+/// address source or two updaters fighting over the same records rather than the ISP
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Flapping {
+    /// Changes of one record within `period` that send a `flapping` notification
+    #[validate(range(min = 2, message = "Flapping changes must be at least 2"))]
+    pub changes: u32,
+
+    /// Minutes the changes are counted over
+    #[validate(range(min = 1, message = "The flapping period must be greater than 0"))]
+    #[serde(default = "default_period", skip_serializing_if = "is_default_period")]
+    #[schemars(!skip_serializing_if)]
+    pub period: u64,
+}
+
+fn default_period() -> u64 {
+    DEFAULT_PERIOD
+}
+
+fn is_default_period(period: &u64) -> bool {
+    *period == DEFAULT_PERIOD
+}
+
+impl Flapping {
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.period * 60)
+    }
+}
@@ -0,0 +1,24 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::net::SocketAddr;
+use validator::Validate;
+
+/// HTTP endpoints for liveness and readiness probes: `/healthz` answers while the process runs,
+/// `/readyz` while records keep being checked successfully. `/metrics` serves the metrics in the
+/// Prometheus format.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct HealthConfig {
+    /// Address and port to listen on, `0.0.0.0:8080` to accept probes from other hosts
+    #[schemars(example = &"127.0.0.1:8080")]
+    pub listen: SocketAddr,
+
+    /// `/readyz` fails once no check has succeeded for this many update intervals
+    #[validate(range(min = 1, message = "Ready intervals must be greater than 0"))]
+    #[serde(default = "default_ready_intervals")]
+    pub ready_intervals: u32,
+}
+
+fn default_ready_intervals() -> u32 {
+    3
+}
@@ -0,0 +1,58 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+const DEFAULT_TIMEOUT: u64 = 60;
+
+/// Commands run by the shell when something happens, with the details in environment variables:
+/// `EVENT`, `DOMAIN`, `RECORD_TYPE`, `OLD_IP`, `NEW_IP`, `ERROR` and `FAILURES`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Run after a record was updated to a new address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"systemctl restart wg-quick@wg0")]
+    pub on_change: Option<String>,
+
+    /// Run when a record could not be checked or updated, and when the daemon crashes
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_failure: Option<String>,
+
+    /// Run once when the daemon starts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on_startup: Option<String>,
+
+    /// Seconds a hook may run before it is killed
+    #[validate(range(min = 1, message = "Hook timeout must be greater than 0"))]
+    #[serde(
+        default = "default_timeout",
+        skip_serializing_if = "is_default_timeout"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub timeout: u64,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            on_change: None,
+            on_failure: None,
+            on_startup: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.on_change.is_none() && self.on_failure.is_none() && self.on_startup.is_none()
+    }
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+fn is_default_timeout(timeout: &u64) -> bool {
+    *timeout == DEFAULT_TIMEOUT
+}
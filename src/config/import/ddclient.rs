@@ -0,0 +1,274 @@
+//! ddclient's `ddclient.conf`: `key=value` settings and host names, separated by commas or
+//! whitespace. Settings on a line of their own apply to the hosts that follow, those on a line
+//! with hosts to these hosts only. Only the hosts using the `cloudflare` protocol are imported.
+
+use super::{Host, Imported};
+use crate::config::models::{IpSource, RecordTypes};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Settings that only matter to ddclient itself
+const IRRELEVANT: [&str; 19] = [
+    "cache",
+    "debug",
+    "foreground",
+    "mail",
+    "mail-failure",
+    "max-interval",
+    "min-error-interval",
+    "min-interval",
+    "pid",
+    "postscript",
+    "quiet",
+    "retry",
+    "server",
+    "ssl",
+    "ssl_ca_dir",
+    "ssl_ca_file",
+    "syslog",
+    "timeout",
+    "verbose",
+];
+
+// Settings read below or reported with the host they apply to, the others are listed as ignored
+const HANDLED: [&str; 21] = [
+    "cmd", "cmdv4", "cmdv6", "daemon", "if", "ifv4", "ifv6", "ip", "ipv4", "ipv6", "login",
+    "password", "protocol", "ttl", "use", "usev4", "usev6", "web", "webv4", "webv6", "zone",
+];
+
+/// Converts the Cloudflare hosts of a ddclient configuration
+pub fn parse(contents: &str) -> anyhow::Result<Imported> {
+    let mut warnings = Vec::new();
+    let mut globals = BTreeMap::new();
+    let mut ignored = BTreeSet::new();
+    let mut hosts = Vec::new();
+    let mut tokens = Vec::new();
+
+    for line in lines(contents) {
+        let mut settings = BTreeMap::new();
+        let mut names = Vec::new();
+        for word in words(&line) {
+            match word.split_once('=') {
+                Some((key, value)) => {
+                    settings.insert(key.trim().to_ascii_lowercase(), value.to_string());
+                }
+                None => names.push(word),
+            }
+        }
+        for key in settings.keys() {
+            if !HANDLED.contains(&key.as_str()) && !IRRELEVANT.contains(&key.as_str()) {
+                ignored.insert(key.clone());
+            }
+        }
+
+        if names.is_empty() {
+            globals.extend(settings);
+            continue;
+        }
+        let settings: BTreeMap<_, _> = globals.clone().into_iter().chain(settings).collect();
+
+        for name in names {
+            let protocol = settings.get("protocol").map(String::as_str);
+            if protocol != Some("cloudflare") {
+                warnings.push(format!(
+                    "{} uses the {} protocol, only cloudflare hosts are imported",
+                    name,
+                    protocol.unwrap_or("default")
+                ));
+                continue;
+            }
+            let Some(zone) = settings.get("zone") else {
+                warnings.push(format!("{} has no zone, it was left out", name));
+                continue;
+            };
+            match settings.get("login").map(String::as_str) {
+                None | Some("token") => match settings.get("password") {
+                    Some(token) if !tokens.contains(token) => tokens.push(token.clone()),
+                    Some(_) => {}
+                    None => warnings.push(format!("{} has no API token", name)),
+                },
+                Some(login) => warnings.push(format!(
+                    "{} signs in as {} with the global API key, which clouddns doesn't support: \
+                     create an API token with the Zone Read and DNS Edit permissions instead",
+                    name, login
+                )),
+            }
+
+            let ttl = settings.get("ttl").and_then(|ttl| match ttl.parse() {
+                Ok(ttl) => Some(ttl),
+                Err(_) => {
+                    warnings.push(format!("{} has an invalid ttl {:?}", name, ttl));
+                    None
+                }
+            });
+            let (record_type, ip_source) = addresses(&name, &settings, &mut warnings);
+            hosts.push(Host {
+                name,
+                zone: zone.clone(),
+                ttl,
+                proxied: None,
+                record_type,
+                ip_source,
+            });
+        }
+    }
+
+    if hosts.is_empty() {
+        anyhow::bail!("No host uses the cloudflare protocol, there is nothing to import");
+    }
+    super::ignored("ddclient", ignored, &mut warnings);
+
+    let update_interval = globals
+        .get("daemon")
+        .and_then(|daemon| match minutes(daemon) {
+            Some(minutes) => Some(minutes),
+            None => {
+                warnings.push(format!("Invalid daemon interval {:?}", daemon));
+                None
+            }
+        });
+
+    let config = super::config(
+        super::api_token(tokens, &mut warnings),
+        hosts,
+        update_interval,
+        &mut warnings,
+    );
+    Ok(Imported { config, warnings })
+}
+
+// The record types a host has addresses for, and where they come from. ddclient can also read them
+// from an interface, a command or a router, clouddns only looks them up on the web.
+fn addresses(
+    name: &str,
+    settings: &BTreeMap<String, String>,
+    warnings: &mut Vec<String>,
+) -> (Option<RecordTypes>, Option<IpSource>) {
+    let v4 = (settings.get("usev4").or(settings.get("use"))).filter(|use_| *use_ != "disabled");
+    let v6 = settings.get("usev6").filter(|use_| *use_ != "disabled");
+
+    let mut source = |method: Option<&String>, web: [&str; 2]| {
+        let method = method?;
+        if !method.starts_with("web") {
+            warnings.push(format!(
+                "{} gets its address with use={}, clouddns looks it up with a web service instead",
+                name, method
+            ));
+            return None;
+        }
+        let web = web.iter().find_map(|key| settings.get(*key))?;
+        web_source(name, web, warnings)
+    };
+    let v4_source = source(v4, ["webv4", "web"]);
+    let v6_source = source(v6, ["webv6", "web"]);
+
+    let record_type = match (v4.is_some(), v6.is_some()) {
+        (true, true) => Some(RecordTypes::Both),
+        (false, true) => Some(RecordTypes::Aaaa),
+        _ => None,
+    };
+    let ip_source = match (v4_source, v6_source) {
+        (Some(v4), Some(v6)) if v4 != v6 => {
+            warnings.push(format!(
+                "{} uses {} for IPv4 and {} for IPv6, clouddns uses the first for both",
+                name, v4, v6
+            ));
+            Some(v4)
+        }
+        (v4, v6) => v4.or(v6),
+    };
+    (record_type, ip_source)
+}
+
+// ipify is also clouddns' default, ddclient's other named services have no equivalent
+fn web_source(name: &str, web: &str, warnings: &mut Vec<String>) -> Option<IpSource> {
+    if web.starts_with("ipify") {
+        return None;
+    }
+    if web.contains('.') || web.contains('/') {
+        let url = match web.contains("://") {
+            true => web.to_string(),
+            false => format!("https://{}", web),
+        };
+        return Some(IpSource::Url(url));
+    }
+    warnings.push(format!(
+        "{} gets its address from ddclient's {} service, clouddns uses ipify instead",
+        name, web
+    ));
+    None
+}
+
+// ddclient's intervals are in seconds unless suffixed, clouddns' in whole minutes
+fn minutes(interval: &str) -> Option<u64> {
+    let (number, unit) = match interval.find(|c: char| !c.is_ascii_digit()) {
+        Some(index) => interval.split_at(index),
+        None => (interval, "s"),
+    };
+    let seconds = number.parse::<u64>().ok()?
+        * match unit {
+            "s" => 1,
+            "m" => 60,
+            "h" => 3600,
+            "d" => 86400,
+            _ => return None,
+        };
+    Some(super::minutes(seconds))
+}
+
+// The logical lines, without comments and with the continued ones joined
+fn lines(contents: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in contents.lines() {
+        let line = without_comment(line).trim_end();
+        match line.strip_suffix('\\') {
+            Some(continued) => {
+                current.push_str(continued);
+                current.push(' ');
+            }
+            None => {
+                current.push_str(line);
+                lines.push(std::mem::take(&mut current));
+            }
+        }
+    }
+    lines.push(current);
+    lines.retain(|line| !line.trim().is_empty());
+    lines
+}
+
+fn without_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (index, c) in line.char_indices() {
+        match (c, quote) {
+            ('#', None) => return &line[..index],
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+// Settings and host names, with the quotes around values removed
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote = None;
+    for c in line.chars() {
+        match (c, quote) {
+            ('\'' | '"', None) => quote = Some(c),
+            (c, Some(open)) if c == open => quote = None,
+            (c, None) if c == ',' || c.is_whitespace() => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            (c, _) => word.push(c),
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
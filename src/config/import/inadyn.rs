@@ -0,0 +1,350 @@
+//! inadyn's `inadyn.conf` (version 2 format): global `key = value` settings and one
+//! `provider <name> { … }` block per account. Only the `cloudflare.com` providers are imported,
+//! their `username` being the zone and `password` the API token.
+
+use super::{Host, Imported};
+use crate::config::models::{IpSource, RecordTypes};
+use anyhow::{bail, Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+
+// Settings that only matter to inadyn itself
+const IRRELEVANT: [&str; 8] = [
+    "allow-ipv6",
+    "broken-rtc",
+    "ca-trust-file",
+    "cache-dir",
+    "forced-update",
+    "secure-ssl",
+    "user-agent",
+    "verify-address",
+];
+
+// Provider settings read below
+const HANDLED: [&str; 9] = [
+    "checkip-command",
+    "checkip-path",
+    "checkip-server",
+    "checkip-ssl",
+    "hostname",
+    "password",
+    "proxied",
+    "ttl",
+    "username",
+];
+
+/// Converts the Cloudflare providers of an inadyn configuration
+pub fn parse(contents: &str) -> Result<Imported> {
+    let mut warnings = Vec::new();
+    let mut ignored = BTreeSet::new();
+    let mut hosts: Vec<Host> = Vec::new();
+    let mut tokens = Vec::new();
+    let mut update_interval = None;
+
+    for item in items(&tokens_of(contents)?)? {
+        let (kind, name, settings) = match item {
+            Item::Setting(key, value) => {
+                match key.as_str() {
+                    "period" => match value.single().and_then(|period| period.parse().ok()) {
+                        Some(seconds) => update_interval = Some(super::minutes(seconds)),
+                        None => warnings.push(format!("Invalid period {}", value)),
+                    },
+                    key if IRRELEVANT.contains(&key) => {}
+                    _ => {
+                        ignored.insert(key);
+                    }
+                }
+                continue;
+            }
+            Item::Block(kind, name, settings) => (kind, name, settings),
+        };
+
+        // Several accounts of a provider are told apart by a `:<n>` suffix
+        let provider = name.split(':').next().unwrap_or_default();
+        let record_type = match provider {
+            "cloudflare.com" | "default@cloudflare.com" => RecordTypes::A,
+            "ipv6@cloudflare.com" => RecordTypes::Aaaa,
+            _ => {
+                warnings.push(format!(
+                    "The {} {} is skipped, only cloudflare.com providers are imported",
+                    kind, name
+                ));
+                continue;
+            }
+        };
+        for key in settings.keys() {
+            if !HANDLED.contains(&key.as_str()) {
+                ignored.insert(key.clone());
+            }
+        }
+
+        let Some(zone) = settings.get("username").and_then(Value::single) else {
+            warnings.push(format!(
+                "The provider {} has no username, the zone name, it was left out",
+                name
+            ));
+            continue;
+        };
+        match settings.get("password").and_then(Value::single) {
+            Some(token) if !tokens.iter().any(|other| other == token) => {
+                tokens.push(token.to_string())
+            }
+            Some(_) => {}
+            None => warnings.push(format!("The provider {} has no API token", name)),
+        }
+        let ttl = settings.get("ttl").and_then(|ttl| {
+            let parsed = ttl.single().and_then(|ttl| ttl.parse().ok());
+            if parsed.is_none() {
+                warnings.push(format!("The provider {} has an invalid ttl {}", name, ttl));
+            }
+            parsed
+        });
+        let proxied = settings
+            .get("proxied")
+            .and_then(Value::single)
+            .map(|proxied| matches!(proxied, "true" | "yes" | "on"));
+        let ip_source = ip_source(&name, &settings, &mut warnings);
+
+        let names = settings
+            .get("hostname")
+            .map(Value::list)
+            .unwrap_or_default();
+        if names.is_empty() {
+            warnings.push(format!("The provider {} has no hostname", name));
+        }
+        for hostname in names {
+            let host = Host {
+                name: hostname.to_string(),
+                zone: zone.to_string(),
+                ttl,
+                proxied,
+                record_type: Some(record_type),
+                ip_source: ip_source.clone(),
+            };
+            // The same name in an IPv4 and an IPv6 provider is one dual-stack entry
+            match hosts.iter_mut().find(|other| {
+                other.name == host.name
+                    && other.zone == host.zone
+                    && other.record_type != host.record_type
+            }) {
+                Some(other) => {
+                    other.record_type = Some(RecordTypes::Both);
+                    match (&other.ip_source, host.ip_source) {
+                        (Some(first), Some(second)) if *first != second => warnings.push(format!(
+                            "{} uses {} for one address and {} for the other, clouddns uses \
+                                 the first for both",
+                            host.name, first, second
+                        )),
+                        (None, second) => other.ip_source = second,
+                        _ => {}
+                    }
+                }
+                None => hosts.push(host),
+            }
+        }
+    }
+
+    if hosts.is_empty() {
+        bail!("No cloudflare.com provider has a hostname, there is nothing to import");
+    }
+    // A is the default, left out of the converted file
+    for host in &mut hosts {
+        host.record_type = host.record_type.filter(|&types| types != RecordTypes::A);
+    }
+    super::ignored("inadyn", ignored, &mut warnings);
+
+    let config = super::config(
+        super::api_token(tokens, &mut warnings),
+        hosts,
+        update_interval,
+        &mut warnings,
+    );
+    Ok(Imported { config, warnings })
+}
+
+// Where the provider's address is looked up. ipify being clouddns' default, it's left unset for it
+// and for inadyn's own default.
+fn ip_source(
+    name: &str,
+    settings: &BTreeMap<String, Value>,
+    warnings: &mut Vec<String>,
+) -> Option<IpSource> {
+    if settings.contains_key("checkip-command") {
+        warnings.push(format!(
+            "The provider {} gets its address with checkip-command, clouddns looks it up with a \
+             web service instead",
+            name
+        ));
+        return None;
+    }
+    let server = settings.get("checkip-server").and_then(Value::single)?;
+    if server.contains("ipify") {
+        return None;
+    }
+    let path = settings
+        .get("checkip-path")
+        .and_then(Value::single)
+        .unwrap_or("/");
+    let scheme = match settings.get("checkip-ssl").and_then(Value::single) {
+        Some("false" | "no" | "off") => "http",
+        _ => "https",
+    };
+    Some(IpSource::Url(format!(
+        "{}://{}/{}",
+        scheme,
+        server,
+        path.trim_start_matches('/')
+    )))
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl Value {
+    fn single(&self) -> Option<&str> {
+        match self {
+            Value::Single(value) => Some(value),
+            Value::List(_) => None,
+        }
+    }
+
+    fn list(&self) -> Vec<&str> {
+        match self {
+            Value::Single(value) => vec![value.as_str()],
+            Value::List(values) => values.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Single(value) => write!(f, "{:?}", value),
+            Value::List(values) => write!(f, "{:?}", values),
+        }
+    }
+}
+
+enum Item {
+    Setting(String, Value),
+    // `provider` or `custom`, its name and settings
+    Block(String, String, BTreeMap<String, Value>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    // Quoted, so never a keyword
+    Text(String),
+    Symbol(char),
+}
+
+// The top-level settings and blocks
+fn items(tokens: &[Token]) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+    let mut tokens = tokens.iter().peekable();
+    while let Some(token) = tokens.next() {
+        let Token::Word(key) = token else {
+            bail!("Unexpected {:?}", token);
+        };
+        match tokens.next() {
+            Some(Token::Symbol('=')) => {
+                items.push(Item::Setting(key.to_ascii_lowercase(), value(&mut tokens)?))
+            }
+            Some(Token::Word(name) | Token::Text(name)) => {
+                if tokens.next() != Some(&Token::Symbol('{')) {
+                    bail!("Expected {{ after {} {}", key, name);
+                }
+                let mut settings = BTreeMap::new();
+                loop {
+                    match tokens.next() {
+                        Some(Token::Symbol('}')) => break,
+                        Some(Token::Word(setting)) => {
+                            if tokens.next() != Some(&Token::Symbol('=')) {
+                                bail!("Expected = after {} in {} {}", setting, key, name);
+                            }
+                            settings.insert(setting.to_ascii_lowercase(), value(&mut tokens)?);
+                        }
+                        other => bail!("Unexpected {:?} in {} {}", other, key, name),
+                    }
+                }
+                items.push(Item::Block(
+                    key.to_ascii_lowercase(),
+                    name.clone(),
+                    settings,
+                ));
+            }
+            other => bail!("Unexpected {:?} after {}", other, key),
+        }
+    }
+    Ok(items)
+}
+
+fn value<'a>(tokens: &mut impl Iterator<Item = &'a Token>) -> Result<Value> {
+    match tokens.next() {
+        Some(Token::Word(value) | Token::Text(value)) => Ok(Value::Single(value.clone())),
+        Some(Token::Symbol('{')) => {
+            let mut values = Vec::new();
+            loop {
+                match tokens.next() {
+                    Some(Token::Symbol('}')) => return Ok(Value::List(values)),
+                    Some(Token::Symbol(',')) => {}
+                    Some(Token::Word(value) | Token::Text(value)) => values.push(value.clone()),
+                    other => bail!("Unexpected {:?} in a list", other),
+                }
+            }
+        }
+        other => bail!("Expected a value, found {:?}", other),
+    }
+}
+
+// Split the file the way libConfuse does, skipping `#`, `//` and `/* */` comments
+fn tokens_of(contents: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '#' => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().find(|&c| c == '\n');
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                chars
+                    .by_ref()
+                    .find(|&c| std::mem::replace(&mut previous, c) == '*' && c == '/')
+                    .context("Unterminated comment")?;
+            }
+            '{' | '}' | '=' | ',' => tokens.push(Token::Symbol(c)),
+            '"' | '\'' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next().context("Unterminated string")? {
+                        '\\' if c == '"' => text.push(chars.next().context("Unterminated string")?),
+                        end if end == c => break,
+                        other => text.push(other),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "{}=,\"'#".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
@@ -0,0 +1,185 @@
+//! Conversion of other dynamic DNS clients' configuration files. The result holds everything
+//! clouddns can take over, with warnings for what it couldn't.
+
+pub mod ddclient;
+pub mod inadyn;
+
+use super::models::{Config, Defaults, Domain, IpSource, RecordTypes, Zone};
+use super::names;
+use super::secret::SecretSource;
+use std::{borrow::Cow, collections::BTreeSet};
+
+/// A configuration converted from another client
+#[derive(Debug)]
+pub struct Imported {
+    /// Zone IDs are [placeholders](placeholder_id), the other clients only knowing zone names
+    pub config: Config,
+    /// Settings that were left out or need checking, one sentence each
+    pub warnings: Vec<String>,
+}
+
+// Written when the file has no usable token, for the user to replace
+const TOKEN_PLACEHOLDER: &str = "<your API token>";
+
+/// The zone ID written for a zone until the real one is filled in
+pub fn placeholder_id(zone: &str) -> String {
+    format!("<ID of {}>", zone)
+}
+
+// A name to keep up to date, with the settings the other client had for it
+#[derive(Debug, Clone)]
+pub(crate) struct Host {
+    pub name: String,
+    pub zone: String,
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub record_type: Option<RecordTypes>,
+    pub ip_source: Option<IpSource>,
+}
+
+// The settings a domain entry is made of
+type Settings = (
+    Option<u32>,
+    Option<bool>,
+    Option<RecordTypes>,
+    Option<IpSource>,
+);
+
+// The domain entries of a zone, one per combination of settings
+type Entries = Vec<(Settings, Vec<Cow<'static, str>>)>;
+
+// One zone per zone name in the order they were first seen. Settings every host agrees on become
+// the defaults, the hosts of a zone sharing the rest are listed in the same domain entry.
+pub(crate) fn config(
+    api_token: SecretSource,
+    hosts: Vec<Host>,
+    update_interval: Option<u64>,
+    warnings: &mut Vec<String>,
+) -> Config {
+    let hosts: Vec<Host> = hosts
+        .into_iter()
+        .filter(|host| {
+            let inside = names::relative(&host.name, &host.zone).is_some();
+            if !inside {
+                warnings.push(format!(
+                    "{} is not in zone {}, it was left out",
+                    host.name, host.zone
+                ));
+            }
+            inside
+        })
+        .collect();
+
+    let ttl = shared(&hosts, |host| &host.ttl);
+    let proxied = shared(&hosts, |host| &host.proxied);
+    let record_type = shared(&hosts, |host| &host.record_type);
+    let ip_source = shared(&hosts, |host| &host.ip_source);
+
+    let mut zones: Vec<(String, Entries)> = Vec::new();
+    for host in hosts {
+        let settings = (
+            host.ttl.filter(|_| ttl.is_none()),
+            host.proxied.filter(|_| proxied.is_none()),
+            host.record_type.filter(|_| record_type.is_none()),
+            host.ip_source.filter(|_| ip_source.is_none()),
+        );
+        let name = names::relative(&host.name, &host.zone).unwrap_or_default();
+        let index = match zones.iter().position(|(zone, _)| *zone == host.zone) {
+            Some(index) => index,
+            None => {
+                zones.push((host.zone, Vec::new()));
+                zones.len() - 1
+            }
+        };
+        let entries = &mut zones[index].1;
+        match entries.iter_mut().find(|(other, _)| *other == settings) {
+            Some((_, records)) if !records.contains(&Cow::Owned(name.clone())) => {
+                records.push(Cow::Owned(name))
+            }
+            Some(_) => {}
+            None => entries.push((settings, vec![Cow::Owned(name)])),
+        }
+    }
+
+    let zones = zones
+        .into_iter()
+        .map(|(zone, entries)| Zone {
+            id: Cow::Owned(placeholder_id(&zone)),
+            name: Some(Cow::Owned(zone)),
+            domains: entries
+                .into_iter()
+                .map(|((ttl, proxied, record_type, ip_source), records)| Domain {
+                    name: Cow::Borrowed("@"),
+                    records,
+                    ttl,
+                    proxied,
+                    record_type,
+                    ip_source,
+                    ..Domain::default()
+                })
+                .collect(),
+            ..Zone::default()
+        })
+        .collect();
+
+    Config {
+        defaults: Defaults {
+            ttl: ttl.flatten(),
+            proxied: proxied.flatten(),
+            record_type: record_type.flatten(),
+            ip_source: ip_source.flatten(),
+            update_interval,
+        },
+        ..Config::new(api_token, zones)
+    }
+}
+
+// The value every host has for a setting, if they all agree
+fn shared<T: Clone + PartialEq>(
+    hosts: &[Host],
+    setting: fn(&Host) -> &Option<T>,
+) -> Option<Option<T>> {
+    let first = setting(hosts.first()?);
+    hosts
+        .iter()
+        .all(|host| setting(host) == first)
+        .then(|| first.clone())
+}
+
+// The token of the hosts, the first one when they use several
+pub(crate) fn api_token(mut tokens: Vec<String>, warnings: &mut Vec<String>) -> SecretSource {
+    if tokens.len() > 1 {
+        warnings.push(
+            "The hosts use different API tokens, only the first one was kept and it needs access \
+             to every zone"
+                .to_string(),
+        );
+    }
+    let token = match tokens.is_empty() {
+        true => {
+            warnings.push(format!(
+                "No API token found, replace {} with yours",
+                TOKEN_PLACEHOLDER
+            ));
+            TOKEN_PLACEHOLDER.to_string()
+        }
+        false => tokens.swap_remove(0),
+    };
+    SecretSource::Plain(token.into())
+}
+
+// One warning for every setting that was left out
+pub(crate) fn ignored(client: &str, settings: BTreeSet<String>, warnings: &mut Vec<String>) {
+    if !settings.is_empty() {
+        warnings.push(format!(
+            "These {} settings have no equivalent and were ignored: {}",
+            client,
+            settings.into_iter().collect::<Vec<_>>().join(", ")
+        ));
+    }
+}
+
+// clouddns checks every whole minute
+pub(crate) fn minutes(seconds: u64) -> u64 {
+    seconds.div_ceil(60).max(1)
+}
@@ -0,0 +1,52 @@
+use super::names;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use validator::{Validate, ValidationError};
+
+const DEFAULT_LEASE: u64 = 60;
+
+/// Several instances kept running for redundancy, of which only the one holding a lease writes
+/// records. The lease is a TXT record the leader renews; a standby takes it over once it runs out.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct LeaderElection {
+    /// Name of the TXT record holding the lease, inside one of the named zones
+    #[validate(custom(function = "validate_record"))]
+    #[schemars(example = &"_clouddns-leader.example.com")]
+    pub record: String,
+
+    /// Seconds the lease lasts unless renewed. It is renewed, and standbys look at it, every third
+    /// of that.
+    #[validate(range(min = 15, message = "The lease must last at least 15 seconds"))]
+    #[serde(default = "default_lease", skip_serializing_if = "is_default_lease")]
+    #[schemars(!skip_serializing_if)]
+    pub lease: u64,
+
+    /// Name this instance holds the lease under, the host name by default
+    #[validate(custom(function = "validate_identity"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identity: Option<String>,
+}
+
+fn default_lease() -> u64 {
+    DEFAULT_LEASE
+}
+
+fn is_default_lease(lease: &u64) -> bool {
+    *lease == DEFAULT_LEASE
+}
+
+fn validate_record(record: &str) -> Result<(), ValidationError> {
+    names::validate_fqdn(&names::normalize(record))
+        .map_err(|e| ValidationError::new("leader_record").with_message(Cow::Owned(e)))
+}
+
+// Written in the record's text, separated from the rest by spaces
+fn validate_identity(identity: &str) -> Result<(), ValidationError> {
+    match !identity.is_empty() && identity.chars().all(|c| c.is_ascii_graphic() && c != '"') {
+        true => Ok(()),
+        false => Err(ValidationError::new("leader_identity")
+            .with_message("The identity must be printable ASCII without spaces or quotes".into())),
+    }
+}
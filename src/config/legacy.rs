@@ -0,0 +1,45 @@
+use super::models::{Config, Defaults, Domain, Zone};
+use super::secret::{InlineSecret, SecretSource};
+use serde::Deserialize;
+use std::borrow::Cow;
+
+// The original flat format: one zone and a list of fully qualified names
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct LegacyConfig {
+    pub api_token: InlineSecret,
+    pub zone_id: String,
+    pub domain_list: Vec<String>,
+    #[serde(default)]
+    pub update_interval: Option<u64>,
+    #[serde(default)]
+    pub record_ttl: Option<u32>,
+}
+
+impl From<LegacyConfig> for Config {
+    fn from(legacy: LegacyConfig) -> Self {
+        let domains = legacy
+            .domain_list
+            .into_iter()
+            .map(|name| Domain {
+                name: Cow::Owned(name),
+                records: vec![Cow::Borrowed("@")],
+                ..Domain::default()
+            })
+            .collect();
+
+        let zones = vec![Zone {
+            id: Cow::Owned(legacy.zone_id),
+            domains,
+            ..Zone::default()
+        }];
+        Config {
+            defaults: Defaults {
+                ttl: legacy.record_ttl,
+                update_interval: legacy.update_interval,
+                ..Defaults::default()
+            },
+            ..Config::new(SecretSource::Plain(legacy.api_token), zones)
+        }
+    }
+}
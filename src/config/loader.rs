@@ -0,0 +1,225 @@
+use super::error::{line_column, validation_messages, ParseError};
+use super::legacy::LegacyConfig;
+use super::models::Config;
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use std::{fs, path::Path, str::FromStr};
+use validator::Validate;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl ConfigFormat {
+    // Parsers tried in order when the extension doesn't tell us anything
+    const ALL: [ConfigFormat; 3] = [ConfigFormat::Toml, ConfigFormat::Yaml, ConfigFormat::Json];
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Json => "JSON",
+        }
+    }
+
+    fn parse(&self, contents: &str) -> Result<Document, ParseError> {
+        let error = match self.parse_current(contents) {
+            Ok(config) => {
+                return Ok(Document {
+                    config,
+                    format: *self,
+                    legacy: false,
+                })
+            }
+            Err(e) => e,
+        };
+
+        // Only fall back to the legacy layout when the file really looks like one,
+        // otherwise the original error is the useful one
+        match self.parse_legacy(contents) {
+            Some(legacy) => Ok(Document {
+                config: legacy.into(),
+                format: *self,
+                legacy: true,
+            }),
+            None => Err(error),
+        }
+    }
+
+    fn parse_legacy(&self, contents: &str) -> Option<LegacyConfig> {
+        match self {
+            ConfigFormat::Toml => toml::from_str(contents).ok(),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).ok(),
+            ConfigFormat::Json => serde_json::from_str(contents).ok(),
+        }
+    }
+
+    pub fn serialize(&self, config: &Config) -> Result<String> {
+        let contents = match self {
+            ConfigFormat::Toml => toml::to_string_pretty(config)?,
+            ConfigFormat::Yaml => serde_yaml::to_string(config)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(config)? + "\n",
+        };
+        Ok(contents)
+    }
+
+    // Deserialize through serde_path_to_error so failures can name the offending field
+    fn parse_current(&self, contents: &str) -> Result<Config, ParseError> {
+        match self {
+            ConfigFormat::Toml => {
+                let deserializer = toml::Deserializer::new(contents);
+                serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                    let path = e.path().to_string();
+                    let inner = e.into_inner();
+                    let location = inner.span().map(|span| line_column(contents, span.start));
+                    ParseError::new(path, inner.message().to_string(), location)
+                })
+            }
+            ConfigFormat::Yaml => {
+                let deserializer = serde_yaml::Deserializer::from_str(contents);
+                serde_path_to_error::deserialize(deserializer).map_err(|e| {
+                    let path = e.path().to_string();
+                    let inner = e.into_inner();
+                    let location = inner
+                        .location()
+                        .map(|location| (location.line(), location.column()));
+                    ParseError::new(path, inner.to_string(), location)
+                })
+            }
+            ConfigFormat::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(contents);
+                serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+                    let path = e.path().to_string();
+                    let inner = e.into_inner();
+                    let location = (inner.line() > 0).then(|| (inner.line(), inner.column()));
+                    ParseError::new(path, inner.to_string(), location)
+                })
+            }
+        }
+    }
+}
+
+// A parsed config file along with how it was written
+pub struct Document {
+    pub config: Config,
+    pub format: ConfigFormat,
+    // Converted from the old zone_id/domain_list layout
+    pub legacy: bool,
+}
+
+impl FromStr for ConfigFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "toml" => Ok(ConfigFormat::Toml),
+            "yaml" | "yml" => Ok(ConfigFormat::Yaml),
+            "json" => Ok(ConfigFormat::Json),
+            other => Err(format!(
+                "Unknown config format `{}`, expected toml, yaml or json",
+                other
+            )),
+        }
+    }
+}
+
+pub fn load(config_file: &str, profile: Option<&str>) -> Result<Config> {
+    let mut document = read(config_file)?;
+
+    if document.legacy {
+        warn!(
+            "{} uses the legacy zone_id/domain_list format, run `clouddns migrate-config` to convert it",
+            config_file
+        );
+    }
+
+    if let Some(profile) = profile {
+        info!("Using profile: {}", profile);
+        document.config.apply_profile(profile)?;
+    }
+    for warning in document.config.ttl_warnings() {
+        warn!("{}", warning);
+    }
+    Ok(document.config)
+}
+
+pub fn read(config_file: &str) -> Result<Document> {
+    let mut document = read_raw(config_file)?;
+    resolve(&mut document.config)
+        .with_context(|| format!("Failed to expand placeholders in: {}", config_file))?;
+    Ok(document)
+}
+
+// The file as written, with placeholders and Unicode names left alone
+pub fn read_raw(config_file: &str) -> Result<Document> {
+    info!("Loading config from: {}", config_file);
+    let contents = fs::read_to_string(config_file)
+        .with_context(|| format!("Failed to read config file: {}", config_file))?;
+
+    parse(&contents, ConfigFormat::from_path(Path::new(config_file)))
+        .with_context(|| format!("Failed to parse config file: {}", config_file))
+}
+
+// Expand placeholders and bring names to the form sent to the API
+pub fn resolve(config: &mut Config) -> Result<()> {
+    config.expand_variables()?;
+    config.normalize_names();
+    Ok(())
+}
+
+pub fn parse(contents: &str, format: Option<ConfigFormat>) -> Result<Document> {
+    if let Some(format) = format {
+        return format
+            .parse(contents)
+            .map_err(|e| anyhow::anyhow!("Invalid {} configuration: {}", format.name(), e));
+    }
+
+    // Unknown extension, try every supported format and report all failures
+    let mut errors = Vec::new();
+    for format in ConfigFormat::ALL {
+        match format.parse(contents) {
+            Ok(document) => {
+                debug!("Detected {} configuration", format.name());
+                return Ok(document);
+            }
+            Err(e) => errors.push(format!("{}: {}", format.name(), e)),
+        }
+    }
+
+    Err(anyhow::anyhow!(
+        "Could not parse configuration as any supported format:\n{}",
+        errors.join("\n")
+    ))
+}
+
+// Run the validator rules, reporting every problem rather than just the first one
+pub fn validate(config: &Config) -> Result<()> {
+    let messages = problems(config);
+
+    if !messages.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Invalid configuration:\n  {}",
+            messages.join("\n  ")
+        ));
+    }
+    Ok(())
+}
+
+// Every validation problem as a "path: message" line
+pub fn problems(config: &Config) -> Vec<String> {
+    let mut messages = match config.validate() {
+        Ok(()) => Vec::new(),
+        Err(errors) => validation_messages(&errors),
+    };
+    messages.extend(config.name_errors());
+    messages.extend(config.tenant_errors());
+    messages.extend(config.ttl_errors());
+    messages
+}
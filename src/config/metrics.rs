@@ -0,0 +1,142 @@
+use super::secret::{validate_secret, SecretSource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use validator::{Validate, ValidationError};
+
+const DEFAULT_PUSHGATEWAY_JOB: &str = "clouddns";
+
+/// Where the metrics go besides `/metrics` on the health server
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// Send every counter, gauge and histogram observation to a StatsD or DogStatsD agent
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub statsd: Option<StatsdConfig>,
+
+    /// Push the metrics of `once` and `force-update` runs to a Prometheus Pushgateway, there being
+    /// nothing to scrape once they exit
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pushgateway: Option<PushgatewayConfig>,
+}
+
+impl MetricsConfig {
+    pub fn is_empty(&self) -> bool {
+        *self == MetricsConfig::default()
+    }
+}
+
+/// A StatsD agent, such as the Datadog agent or Telegraf's `statsd` input, sent one UDP datagram
+/// per metric as it's recorded
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct StatsdConfig {
+    /// Host and port of the agent
+    #[validate(custom(function = "validate_address"))]
+    #[schemars(example = &"127.0.0.1:8125")]
+    pub address: String,
+
+    /// Put before every metric name, e.g. `homelab.`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+
+    /// `dogstatsd` sends the labels as tags, `statsd` appends their values to the metric name
+    #[serde(default, skip_serializing_if = "StatsdFormat::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub format: StatsdFormat,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsdFormat {
+    /// `clouddns_records_updated_total:1|c|#record:home.example.com,type:A`, understood by the
+    /// Datadog agent and by Telegraf with `datadog_extensions`
+    #[default]
+    Dogstatsd,
+    /// `clouddns_records_updated_total.home_example_com.A:1|c`, durations as timings in
+    /// milliseconds
+    Statsd,
+}
+
+impl StatsdFormat {
+    fn is_default(&self) -> bool {
+        *self == StatsdFormat::default()
+    }
+}
+
+/// A Prometheus Pushgateway, the metrics of each run replacing those of the same name in its group
+/// (the job and the grouping labels)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PushgatewayConfig {
+    /// The Pushgateway's address
+    #[validate(url(message = "Pushgateway URL must be a valid URL"))]
+    #[schemars(example = &"http://pushgateway:9091")]
+    pub url: String,
+
+    /// `job` label of the pushed metrics
+    #[validate(custom(function = "validate_label_value"))]
+    #[serde(
+        default = "default_pushgateway_job",
+        skip_serializing_if = "is_default_pushgateway_job"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub job: String,
+
+    /// More labels grouping the metrics. `instance` is the host name unless given.
+    #[validate(custom(function = "validate_grouping"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub grouping: BTreeMap<String, String>,
+
+    /// User name for basic authentication
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Password for basic authentication
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretSource>,
+}
+
+fn default_pushgateway_job() -> String {
+    DEFAULT_PUSHGATEWAY_JOB.to_string()
+}
+
+fn is_default_pushgateway_job(job: &str) -> bool {
+    job == DEFAULT_PUSHGATEWAY_JOB
+}
+
+// Label values go in the URL path, where a slash would split them
+fn validate_label_value(value: &str) -> Result<(), ValidationError> {
+    match !value.is_empty() && !value.contains('/') {
+        true => Ok(()),
+        false => Err(ValidationError::new("label_value")
+            .with_message("Pushgateway label values must be non-empty and without `/`".into())),
+    }
+}
+
+fn validate_grouping(grouping: &BTreeMap<String, String>) -> Result<(), ValidationError> {
+    for (name, value) in grouping {
+        let valid_name = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name || name == "job" {
+            return Err(ValidationError::new("label_name")
+                .with_message(format!("{:?} can't be a Pushgateway grouping label", name).into()));
+        }
+        validate_label_value(value)?;
+    }
+    Ok(())
+}
+
+fn validate_address(address: &str) -> Result<(), ValidationError> {
+    let valid = address
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok_and(|p| p > 0));
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("statsd_address")
+            .with_message("StatsD address must look like host:8125".into())),
+    }
+}
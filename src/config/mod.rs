@@ -1,2 +1,59 @@
+pub mod acme_dns;
+pub mod admin;
+pub mod discovery;
+pub mod dns;
+pub mod error;
+pub mod example;
+pub mod flapping;
+pub mod health;
+pub mod hooks;
+pub mod import;
+pub mod leader;
+pub mod legacy;
+pub mod loader;
+pub mod metrics;
 pub mod models;
+pub mod mqtt;
+pub mod names;
+pub mod notify;
+pub mod order;
+pub mod overview;
+pub mod profile;
+pub mod provider;
+pub mod proxy;
+pub mod rate_limit;
+pub mod retry;
+pub mod schema;
+pub mod secret;
+pub mod sentry;
+pub mod startup;
+pub mod state;
+pub mod summary;
+pub mod tenant;
+pub mod ttl;
+pub mod variables;
+pub mod watchdog;
+pub mod window;
+pub use acme_dns::AcmeDnsConfig;
+pub use discovery::{DiscoveryConfig, DockerDiscovery, KubernetesDiscovery, ProxyDiscovery};
+pub use dns::DnsConfig;
+pub use example::example;
+pub use flapping::Flapping;
+pub use hooks::Hooks;
+pub use leader::LeaderElection;
+pub use loader::{load, problems, read, read_raw, resolve, validate, ConfigFormat, Document};
+pub use metrics::{MetricsConfig, PushgatewayConfig, StatsdConfig, StatsdFormat};
 pub use models::*;
+pub use notify::{EventKind, Notifications};
+pub use order::RecordOrder;
+pub use provider::{ProviderConfig, ProviderKind};
+pub use proxy::ProxyConfig;
+pub use rate_limit::RateLimit;
+pub use retry::RetryPolicy;
+pub use schema::schema;
+pub use secret::{SecretSource, VaultSecret, DEFAULT_KEYRING_ACCOUNT};
+pub use sentry::SentryConfig;
+pub use startup::Startup;
+pub use state::{StateConfig, StateStoreKind};
+pub use summary::{Summary, SummaryPeriod};
+pub use tenant::Tenant;
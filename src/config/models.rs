@@ -1,36 +1,809 @@
+use super::acme_dns::AcmeDnsConfig;
+use super::admin::AdminConfig;
+use super::discovery::DiscoveryConfig;
+use super::dns::DnsConfig;
+use super::flapping::Flapping;
+use super::health::HealthConfig;
+use super::hooks::Hooks;
+use super::leader::LeaderElection;
+use super::metrics::MetricsConfig;
+use super::mqtt::MqttConfig;
+use super::names;
+use super::notify::Notifications;
+use super::order::RecordOrder;
+use super::provider::ProviderConfig;
+use super::proxy::ProxyConfig;
+use super::rate_limit::RateLimit;
+use super::retry::RetryPolicy;
+use super::secret::{validate_secret, SecretSource};
+use super::sentry::SentryConfig;
+use super::startup::Startup;
+use super::state::StateConfig;
+use super::tenant::Tenant;
+use super::watchdog::Watchdog;
+use super::window::MaintenanceWindow;
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
 use serde::{Deserialize, Serialize};
-use std::borrow::Cow;
-use validator::Validate;
+use std::{borrow::Cow, collections::BTreeMap, fmt, path::PathBuf, str::FromStr};
+use validator::{Validate, ValidationError};
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+pub const DEFAULT_UPDATE_INTERVAL: u64 = 5;
+pub const DEFAULT_CONCURRENCY: usize = 4;
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 20;
+pub const DEFAULT_ZONE_CACHE_TTL: u64 = 60;
+pub const DEFAULT_DEBOUNCE_MS: u64 = 500;
+
+/// clouddns configuration
+#[derive(Debug, Clone, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
-    #[validate(length(min = 1, message = "API token cannot be empty"))]
-    pub api_token: Cow<'static, str>,
+    /// Cloudflare API token with DNS edit permission on the configured zones, or the credential
+    /// handed to an `exec` provider
+    #[validate(custom(function = "validate_secret"))]
+    #[schemars(example = &"your-api-token")]
+    pub api_token: SecretSource,
+
+    /// Minutes between re-reads of an API token kept in a file or a secret backend, so rotations
+    /// are picked up without a restart. A Vault secret's own `refresh_interval` takes precedence
+    #[validate(range(min = 1, message = "Token refresh interval must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_refresh_interval: Option<u64>,
+
+    /// The DNS provider, Cloudflare unless set otherwise
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "ProviderConfig::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub provider: ProviderConfig,
 
+    /// Minutes between checks. Kept for older configs, `defaults.update_interval` takes precedence
     #[validate(range(min = 1, message = "Update interval must be greater than 0"))]
-    pub update_interval: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_interval: Option<u64>,
 
+    /// Record TTL in seconds. Kept for older configs, `defaults.ttl` takes precedence
     #[validate(range(min = 1, message = "TTL must be greater than 0"))]
-    pub record_ttl: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_ttl: Option<u32>,
+
+    /// Settings inherited by every zone and domain
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+
+    /// Cloudflare zones and the domains managed in each of them
+    #[validate(length(min = 1, message = "At least one zone is required"), nested)]
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+
+    /// Values for `{name}` placeholders in zone, domain and record names, on top of the built-in
+    /// `{hostname}` and `{short_hostname}`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, String>,
+
+    /// Named alternatives to `defaults` and `zones`, selected with `--profile` or `CLOUDDNS_PROFILE`
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Customers managed from this one configuration, each with its own API token and channels.
+    /// Zones join one with `tenant`.
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub tenants: BTreeMap<String, Tenant>,
+
+    /// Reload the configuration automatically when this file changes
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub watch_config: bool,
+
+    /// Daily periods during which records are left alone, e.g. when an upstream renumbering
+    /// shouldn't page anyone. Changes detected meanwhile are applied when the window ends.
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub maintenance_windows: Vec<MaintenanceWindow>,
+
+    /// Retries of individual HTTP requests, so a dropped packet doesn't fail the whole check
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "RetryPolicy::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub retry: RetryPolicy,
+
+    /// Most requests sent to the provider's API, Cloudflare's documented limit by default
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate_limit: Option<RateLimit>,
+
+    /// Minutes the zones listed to look up those given by name are reused before being listed
+    /// again
+    #[validate(range(min = 1, message = "Zone cache TTL must be greater than 0"))]
+    #[serde(
+        default = "default_zone_cache_ttl",
+        skip_serializing_if = "is_default_zone_cache_ttl"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub zone_cache_ttl: u64,
+
+    /// Send HTTP requests through a proxy, including SOCKS5
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<ProxyConfig>,
+
+    /// PEM files of certificate authorities to trust on top of the system ones, such as the one
+    /// of a TLS-inspecting corporate proxy
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(example = &["/etc/ssl/corporate-ca.pem"])]
+    pub ca_certificates: Vec<PathBuf>,
+
+    /// Look host names up with fixed addresses or other resolvers than the system's
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "DnsConfig::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub dns: DnsConfig,
+
+    /// Records checked and updated at the same time
+    #[validate(range(min = 1, max = 64, message = "Concurrency must be between 1 and 64"))]
+    #[serde(
+        default = "default_concurrency",
+        skip_serializing_if = "is_default_concurrency"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub concurrency: usize,
+
+    /// Up to this many seconds, picked at random, are added to each interval so that many
+    /// instances started together drift apart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub interval_jitter: Option<u64>,
+
+    /// Wait a random number of seconds, up to this one, before the first check after starting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub startup_delay: Option<u64>,
+
+    /// Delay the first check, or hold it until the network is up
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Startup::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub startup: Startup,
+
+    /// What to do about checks whose time passed while a slow cycle ran or the machine slept:
+    /// `skip` to the next one on schedule, `burst` to catch up on each, or `delay` to start the
+    /// schedule over from the late check
+    #[serde(default, skip_serializing_if = "MissedChecks::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub missed_checks: MissedChecks,
+
+    /// Seconds an update under way may take to finish once the daemon is asked to stop
+    #[serde(
+        default = "default_shutdown_timeout",
+        skip_serializing_if = "is_default_shutdown_timeout"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub shutdown_timeout: u64,
+
+    /// Milliseconds a request to check every record, by signal or through the admin API or the
+    /// control socket, waits for others to run as one cycle with them
+    #[serde(
+        default = "default_debounce_ms",
+        skip_serializing_if = "is_default_debounce_ms"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub debounce_ms: u64,
+
+    /// Serve health probes over HTTP while the daemon runs
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub health: Option<HealthConfig>,
+
+    /// Serve an authenticated HTTP API to check on and control the daemon
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub admin: Option<AdminConfig>,
+
+    /// Serve an acme-dns compatible API for ACME clients to answer DNS-01 challenges through
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub acme_dns: Option<AcmeDnsConfig>,
+
+    /// Unix socket `clouddns ctl` talks to the daemon through, only accessible to its owner
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"/run/clouddns/control.sock")]
+    pub control_socket: Option<PathBuf>,
+
+    /// JSON file rewritten after every cycle with the addresses, the state of each record and when
+    /// the last cycle succeeded and failed, for monitors that read files rather than endpoints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"/var/lib/clouddns/status.json")]
+    pub status_file: Option<PathBuf>,
+
+    /// Manage records for hostnames other software asks for, e.g. labelled Docker containers
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "DiscoveryConfig::is_empty")]
+    pub discovery: DiscoveryConfig,
+
+    /// Publish the addresses and record states to an MQTT broker, e.g. for Home Assistant
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mqtt: Option<MqttConfig>,
+
+    /// Send the metrics to other systems too, e.g. a StatsD agent
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "MetricsConfig::is_empty")]
+    pub metrics: MetricsConfig,
+
+    /// Webhooks and other channels told about address changes and failures
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Notifications::is_empty")]
+    pub notifications: Notifications,
+
+    /// Commands to run when a record changes or fails, or when the daemon starts
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+
+    /// Escalate after too many failed cycles in a row
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub watchdog: Option<Watchdog>,
+
+    /// Notify about records changing too often
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flapping: Option<Flapping>,
+
+    /// Run several instances for redundancy, only one of them writing records at a time
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub leader_election: Option<LeaderElection>,
+
+    /// Report panics and cycles that keep failing to Sentry
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sentry: Option<SentryConfig>,
+
+    /// Where the daemon logs: `stderr`, or natively to `journald` or the local `syslog`
+    #[serde(default, skip_serializing_if = "LogTarget::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub log_target: LogTarget,
+
+    /// How the state kept across restarts is stored
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "StateConfig::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub state: StateConfig,
+}
+
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+fn is_default_concurrency(concurrency: &usize) -> bool {
+    *concurrency == DEFAULT_CONCURRENCY
+}
+
+fn default_shutdown_timeout() -> u64 {
+    DEFAULT_SHUTDOWN_TIMEOUT
+}
+
+fn is_default_shutdown_timeout(timeout: &u64) -> bool {
+    *timeout == DEFAULT_SHUTDOWN_TIMEOUT
+}
+
+fn default_debounce_ms() -> u64 {
+    DEFAULT_DEBOUNCE_MS
+}
+
+fn is_default_debounce_ms(debounce: &u64) -> bool {
+    *debounce == DEFAULT_DEBOUNCE_MS
+}
+
+fn default_zone_cache_ttl() -> u64 {
+    DEFAULT_ZONE_CACHE_TTL
+}
+
+fn is_default_zone_cache_ttl(ttl: &u64) -> bool {
+    *ttl == DEFAULT_ZONE_CACHE_TTL
+}
+
+/// Settings inherited by every zone and domain unless they override them
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    /// Record TTL in seconds, keeps the current TTL when unset
+    #[validate(range(min = 1, message = "TTL must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+
+    /// Route traffic through Cloudflare's proxy, keeps the current setting when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+
+    /// Type of record to manage: `A`, `AAAA` or `both`
+    #[serde(default, alias = "type", skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<RecordTypes>,
 
-    #[validate(length(min = 1, message = "At least one zone is required"))]
+    /// Service used to look up the public IP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_source: Option<IpSource>,
+
+    /// Minutes between checks
+    #[validate(range(min = 1, message = "Update interval must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_interval: Option<u64>,
+}
+
+/// A named set of zones and defaults, applied on top of the top-level ones when selected
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Settings overriding the top-level `defaults` while this profile is active
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Defaults::is_empty")]
+    pub defaults: Defaults,
+
+    /// Zones replacing the top-level `zones` while this profile is active, kept when empty
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub zones: Vec<Zone>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+/// A Cloudflare zone, its settings override `defaults`
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "validate_zone"))]
+#[schemars(extend("anyOf" = [{ "required": ["id"] }, { "required": ["name"] }]))]
 pub struct Zone {
-    #[validate(length(min = 1, message = "Zone ID cannot be empty"))]
+    /// Zone ID, shown on the zone's overview page in the Cloudflare dashboard. Without it, the
+    /// zone is looked up by `name`
+    #[serde(default, skip_serializing_if = "str::is_empty")]
+    #[schemars(example = &"your-zone-id")]
     pub id: Cow<'static, str>,
 
+    /// Set to false to stop updating every domain of this zone without removing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Tenant the zone belongs to, one of `tenants`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"acme")]
+    pub tenant: Option<String>,
+
+    /// Zone name (e.g. `example.com`), lets domain names be written relative to it
+    #[validate(length(min = 1, message = "Zone name cannot be empty"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"example.com")]
+    pub name: Option<Cow<'static, str>>,
+
+    /// Record TTL in seconds, keeps the current TTL when unset
+    #[validate(range(min = 1, message = "TTL must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+
+    /// Route traffic through Cloudflare's proxy, keeps the current setting when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+
+    /// Type of record to manage: `A`, `AAAA` or `both`
+    #[serde(default, alias = "type", skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<RecordTypes>,
+
+    /// Service used to look up the public IP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_source: Option<IpSource>,
+
+    /// Minutes between checks
+    #[validate(range(min = 1, message = "Update interval must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_interval: Option<u64>,
+
+    /// How the records of this zone are updated: `parallel`, `grouped` by name or `sequential`,
+    /// the provider's `order` when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<RecordOrder>,
+
+    /// Same as `order = "sequential"`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sequential: bool,
+
+    /// Domains managed in this zone
     #[validate(nested)]
+    #[serde(default)]
     pub domains: Vec<Domain>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Validate)]
+/// A domain and its records, its settings override the zone's
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
 pub struct Domain {
+    /// Domain name the records belong to, `@` or a relative name when the zone has a name
     #[validate(length(min = 1, message = "Domain name cannot be empty"))]
+    #[serde(default = "apex")]
     pub name: Cow<'static, str>,
 
+    /// Set to false to stop updating this domain without removing it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Records to keep up to date: `@` for the domain itself, relative names like `www`,
+    /// or fully qualified names
     #[validate(length(min = 1, message = "At least one record is required"))]
+    #[schemars(example = example_records())]
     pub records: Vec<Cow<'static, str>>,
+
+    /// Record TTL in seconds, keeps the current TTL when unset
+    #[validate(range(min = 1, message = "TTL must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<u32>,
+
+    /// Route traffic through Cloudflare's proxy, keeps the current setting when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proxied: Option<bool>,
+
+    /// Type of record to manage: `A`, `AAAA` or `both`
+    #[serde(default, alias = "type", skip_serializing_if = "Option::is_none")]
+    pub record_type: Option<RecordTypes>,
+
+    /// Service used to look up the public IP address
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_source: Option<IpSource>,
+
+    /// Minutes between checks
+    #[validate(range(min = 1, message = "Update interval must be greater than 0"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_interval: Option<u64>,
+}
+
+/// Destination of log messages
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogTarget {
+    /// Standard error, for terminals and supervisors that capture it
+    #[default]
+    Stderr,
+    /// The systemd journal, with the record and address as fields of their own
+    Journald,
+    /// The local syslog daemon, under the `daemon` facility
+    Syslog,
+    /// The Windows Event Log, as the `clouddns` source of the Application log
+    Eventlog,
+}
+
+impl LogTarget {
+    fn is_default(&self) -> bool {
+        *self == LogTarget::default()
+    }
+}
+
+/// Catching up on checks that couldn't run on time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MissedChecks {
+    /// Run once and carry on with the next check on schedule
+    #[default]
+    Skip,
+    /// Run every missed check, one right after the other
+    Burst,
+    /// Run once and count the interval from then on
+    Delay,
+}
+
+impl MissedChecks {
+    fn is_default(&self) -> bool {
+        *self == MissedChecks::default()
+    }
+}
+
+// DNS record type
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum RecordType {
+    A,
+    Aaaa,
+}
+
+impl RecordType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Aaaa => "AAAA",
+        }
+    }
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Record types to publish, `both` keeps the A and AAAA records of dual-stack hosts up to date
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub enum RecordTypes {
+    #[default]
+    A,
+    #[serde(rename = "AAAA")]
+    Aaaa,
+    #[serde(rename = "both")]
+    Both,
+}
+
+impl FromStr for RecordTypes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "a" => Ok(RecordTypes::A),
+            "aaaa" => Ok(RecordTypes::Aaaa),
+            "both" => Ok(RecordTypes::Both),
+            _ => Err(format!(
+                "Unknown record type `{}`, expected A, AAAA or both",
+                s
+            )),
+        }
+    }
+}
+
+impl RecordTypes {
+    pub fn types(&self) -> &'static [RecordType] {
+        match self {
+            RecordTypes::A => &[RecordType::A],
+            RecordTypes::Aaaa => &[RecordType::Aaaa],
+            RecordTypes::Both => &[RecordType::A, RecordType::Aaaa],
+        }
+    }
+}
+
+// Where the public IP address is looked up, either a known service or any URL
+// answering with the address as plain text or as `{"ip": "..."}`
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum IpSource {
+    #[default]
+    Ipify,
+    Icanhazip,
+    Cloudflare,
+    Url(String),
+}
+
+impl FromStr for IpSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ipify" => Ok(IpSource::Ipify),
+            "icanhazip" => Ok(IpSource::Icanhazip),
+            "cloudflare" => Ok(IpSource::Cloudflare),
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                Ok(IpSource::Url(url.to_string()))
+            }
+            other => Err(format!(
+                "Unknown IP source `{}`, expected ipify, icanhazip, cloudflare or an http(s) URL",
+                other
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for IpSource {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IpSource> for String {
+    fn from(source: IpSource) -> Self {
+        source.to_string()
+    }
+}
+
+impl JsonSchema for IpSource {
+    fn schema_name() -> Cow<'static, str> {
+        "IpSource".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "description": "Known IP lookup service, or any URL answering with the address as plain text or as {\"ip\": \"...\"}",
+            "anyOf": [
+                { "enum": ["ipify", "icanhazip", "cloudflare"] },
+                { "type": "string", "pattern": "^https?://" }
+            ]
+        })
+    }
+}
+
+impl fmt::Display for IpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpSource::Ipify => f.write_str("ipify"),
+            IpSource::Icanhazip => f.write_str("icanhazip"),
+            IpSource::Cloudflare => f.write_str("cloudflare"),
+            IpSource::Url(url) => f.write_str(url),
+        }
+    }
+}
+
+// Settings for one domain after walking domain -> zone -> defaults
+#[derive(Debug, Clone)]
+pub struct RecordSettings {
+    // None keeps whatever the provider currently has
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub record_types: RecordTypes,
+    pub ip_source: IpSource,
+    // Minutes
+    pub update_interval: u64,
+}
+
+fn apex() -> Cow<'static, str> {
+    Cow::Borrowed("@")
+}
+
+fn example_records() -> [&'static str; 2] {
+    ["@", "www"]
+}
+
+// A zone is found by its ID or its name, and relative domain names need a zone name to be
+// expanded against
+fn validate_zone(zone: &Zone) -> Result<(), ValidationError> {
+    if zone.id.is_empty() && zone.name.is_none() {
+        return Err(ValidationError::new("zone_id").with_message(Cow::Borrowed(
+            "Zone ID cannot be empty, unless the zone `name` is set",
+        )));
+    }
+    if zone.sequential
+        && zone
+            .order
+            .is_some_and(|order| order != RecordOrder::Sequential)
+    {
+        return Err(ValidationError::new("order").with_message(Cow::Borrowed(
+            "A sequential zone can't have another `order`",
+        )));
+    }
+    if zone.name.is_none() && zone.domains.iter().any(|domain| domain.name == "@") {
+        return Err(
+            ValidationError::new("relative_name").with_message(Cow::Borrowed(
+                "Domains without a name (or named `@`) require the zone `name` to be set",
+            )),
+        );
+    }
+    Ok(())
+}
+
+impl Defaults {
+    pub fn is_empty(&self) -> bool {
+        *self == Defaults::default()
+    }
+}
+
+impl Zone {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+}
+
+impl Domain {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled != Some(false)
+    }
+}
+
+impl Config {
+    /// A configuration for these zones, everything else at its default
+    pub fn new(api_token: SecretSource, zones: Vec<Zone>) -> Self {
+        Config {
+            api_token,
+            token_refresh_interval: None,
+            provider: ProviderConfig::default(),
+            update_interval: None,
+            record_ttl: None,
+            defaults: Defaults::default(),
+            zones,
+            variables: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+            tenants: BTreeMap::new(),
+            watch_config: false,
+            maintenance_windows: Vec::new(),
+            retry: RetryPolicy::default(),
+            rate_limit: None,
+            zone_cache_ttl: DEFAULT_ZONE_CACHE_TTL,
+            proxy: None,
+            ca_certificates: Vec::new(),
+            dns: DnsConfig::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            interval_jitter: None,
+            startup_delay: None,
+            startup: Startup::default(),
+            missed_checks: MissedChecks::default(),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            debounce_ms: DEFAULT_DEBOUNCE_MS,
+            health: None,
+            admin: None,
+            acme_dns: None,
+            control_socket: None,
+            status_file: None,
+            discovery: DiscoveryConfig::default(),
+            mqtt: None,
+            metrics: MetricsConfig::default(),
+            notifications: Notifications::default(),
+            hooks: Hooks::default(),
+            watchdog: None,
+            flapping: None,
+            leader_election: None,
+            sentry: None,
+            log_target: LogTarget::Stderr,
+            state: StateConfig::default(),
+        }
+    }
+
+    // The configured limit, or the one the provider documents
+    pub fn api_rate_limit(&self) -> RateLimit {
+        self.rate_limit.unwrap_or(RateLimit::CLOUDFLARE)
+    }
+
+    // The enabled named zone a fully qualified name falls in, the most specific one
+    pub fn zone_containing(&self, name: &str) -> Option<&Zone> {
+        let name = names::normalize(name);
+        self.zones
+            .iter()
+            .filter(|zone| zone.is_enabled())
+            .filter(|zone| {
+                zone.name
+                    .as_deref()
+                    .is_some_and(|zone_name| names::relative(&name, zone_name).is_some())
+            })
+            .max_by_key(|zone| zone.name.as_deref().map(str::len))
+    }
+
+    // Zones and domains parked with `enabled = false`, for reporting
+    pub fn disabled_entries(&self) -> Vec<String> {
+        let mut entries = Vec::new();
+        for zone in &self.zones {
+            if !zone.is_enabled() {
+                entries.push(format!("zone {}", zone.name.as_deref().unwrap_or(&zone.id)));
+                continue;
+            }
+            entries.extend(
+                zone.domains
+                    .iter()
+                    .filter(|domain| !domain.is_enabled())
+                    .map(|domain| format!("domain {}", domain.fqdn(zone))),
+            );
+        }
+        entries
+    }
+
+    // Effective settings for a domain, the most specific setting wins
+    pub fn settings(&self, zone: &Zone, domain: &Domain) -> RecordSettings {
+        let defaults = &self.defaults;
+
+        let proxied = domain.proxied.or(zone.proxied).or(defaults.proxied);
+        RecordSettings {
+            // Cloudflare gives proxied records an automatic TTL whatever is asked for
+            ttl: domain
+                .ttl
+                .or(zone.ttl)
+                .or(defaults.ttl)
+                .or(self.record_ttl)
+                .filter(|_| proxied != Some(true)),
+            proxied,
+            record_types: domain
+                .record_type
+                .or(zone.record_type)
+                .or(defaults.record_type)
+                .unwrap_or_default(),
+            ip_source: domain
+                .ip_source
+                .as_ref()
+                .or(zone.ip_source.as_ref())
+                .or(defaults.ip_source.as_ref())
+                .cloned()
+                .unwrap_or_default(),
+            update_interval: domain
+                .update_interval
+                .or(zone.update_interval)
+                .or(defaults.update_interval)
+                .or(self.update_interval)
+                .unwrap_or(DEFAULT_UPDATE_INTERVAL),
+        }
+    }
 }
@@ -0,0 +1,71 @@
+use super::secret::{validate_secret, SecretSource};
+use crate::redact;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::{Validate, ValidationError};
+
+/// An MQTT broker the current addresses and record states are published to
+#[derive(Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    /// Broker address, `mqtt://host:1883`, or `mqtts://host:8883` for TLS
+    #[validate(custom(function = "validate_broker"))]
+    #[schemars(example = &"mqtt://homeassistant.local:1883")]
+    pub url: String,
+
+    /// User name to log in with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Password to log in with
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretSource>,
+
+    /// Topic everything is published under, `clouddns/<hostname>` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+
+    /// Announce the sensors to Home Assistant through MQTT discovery
+    #[serde(default = "default_discovery")]
+    pub discovery: bool,
+
+    /// Topic prefix Home Assistant watches for discovery
+    #[serde(default = "default_discovery_prefix")]
+    pub discovery_prefix: String,
+}
+
+// The URL may carry the credentials too
+impl fmt::Debug for MqttConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MqttConfig")
+            .field("url", &redact::url(&self.url))
+            .field("username", &self.username)
+            .field("password", &self.password)
+            .field("topic", &self.topic)
+            .field("discovery", &self.discovery)
+            .field("discovery_prefix", &self.discovery_prefix)
+            .finish()
+    }
+}
+
+fn default_discovery() -> bool {
+    true
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+fn validate_broker(url: &str) -> Result<(), ValidationError> {
+    let valid = reqwest::Url::parse(url).is_ok_and(|url| {
+        matches!(url.scheme(), "mqtt" | "mqtts")
+            && url.host_str().is_some_and(|host| !host.is_empty())
+    });
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("broker_url")
+            .with_message("MQTT URL must look like mqtt://host:1883 or mqtts://host:8883".into())),
+    }
+}
@@ -0,0 +1,191 @@
+use super::models::{Config, Domain, Zone};
+use std::borrow::Cow;
+
+// Expand a possibly relative name against its origin, the way zone files do:
+// `@` is the origin itself, a trailing dot marks an absolute name and anything
+// already ending in the origin is left alone instead of being duplicated
+pub fn expand(name: &str, origin: &str) -> String {
+    let origin = origin.trim_end_matches('.');
+
+    if name == "@" {
+        return origin.to_string();
+    }
+    if let Some(absolute) = name.strip_suffix('.') {
+        return absolute.to_string();
+    }
+    if origin.is_empty() || name == origin || name.ends_with(&format!(".{}", origin)) {
+        return name.to_string();
+    }
+
+    format!("{}.{}", name, origin)
+}
+
+// The reverse of `expand`: `@` for the origin itself, the name without the origin for those
+// below it, and None for names outside of it
+pub fn relative(name: &str, origin: &str) -> Option<String> {
+    let name = name.trim_end_matches('.');
+    let origin = origin.trim_end_matches('.');
+
+    if name.eq_ignore_ascii_case(origin) {
+        return Some("@".to_string());
+    }
+    let (head, tail) = name.split_at(name.len().checked_sub(origin.len() + 1)?);
+    tail.strip_prefix('.')
+        .filter(|tail| tail.eq_ignore_ascii_case(origin))
+        .map(|_| head.to_string())
+}
+
+impl Domain {
+    // Fully qualified name of the domain, relative names are expanded against the zone name
+    pub fn fqdn(&self, zone: &Zone) -> String {
+        match &zone.name {
+            Some(zone_name) => expand(&self.name, zone_name),
+            None => expand(&self.name, ""),
+        }
+    }
+
+    // Fully qualified names of every record of the domain
+    pub fn record_names(&self, zone: &Zone) -> Vec<String> {
+        let origin = self.fqdn(zone);
+        self.records
+            .iter()
+            .map(|record| expand(record, &origin))
+            .collect()
+    }
+}
+
+// Punycode-encode and lowercase a configured name, keeping `@` and the trailing dot
+// that marks absolute names. Names that can't be encoded are left untouched for
+// `validate_fqdn` to report.
+pub fn normalize(name: &str) -> String {
+    if name == "@" {
+        return name.to_string();
+    }
+
+    let (name, absolute) = match name.strip_suffix('.') {
+        Some(name) => (name, true),
+        None => (name, false),
+    };
+
+    match idna::domain_to_ascii(name) {
+        Ok(ascii) if absolute => format!("{}.", ascii),
+        Ok(ascii) => ascii,
+        Err(_) => format!("{}{}", name, if absolute { "." } else { "" }),
+    }
+}
+
+// Check an expanded name against the DNS limits the API would otherwise reject at runtime
+pub(crate) fn validate_fqdn(fqdn: &str) -> Result<(), String> {
+    if fqdn.is_empty() {
+        return Err("name is empty".to_string());
+    }
+    if fqdn.len() > 253 {
+        return Err(format!("`{}` is longer than 253 characters", fqdn));
+    }
+
+    for (index, label) in fqdn.split('.').enumerate() {
+        if label.is_empty() {
+            return Err(format!("`{}` contains an empty label", fqdn));
+        }
+        if label.len() > 63 {
+            return Err(format!(
+                "label `{}` of `{}` is longer than 63 characters",
+                label, fqdn
+            ));
+        }
+        // A wildcard is only valid as the leftmost label
+        if label == "*" && index == 0 {
+            continue;
+        }
+        if !label
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(format!(
+                "label `{}` of `{}` contains invalid characters",
+                label, fqdn
+            ));
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err(format!(
+                "label `{}` of `{}` starts or ends with a hyphen",
+                label, fqdn
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn normalize_cow(name: &mut Cow<'static, str>) {
+    let normalized = normalize(name);
+    if normalized != *name {
+        *name = Cow::Owned(normalized);
+    }
+}
+
+impl Config {
+    // Bring every name to its ASCII form so comparisons with the API are exact
+    pub(crate) fn normalize_names(&mut self) {
+        normalize_zones(&mut self.zones);
+        for profile in self.profiles.values_mut() {
+            normalize_zones(&mut profile.zones);
+        }
+    }
+
+    // "path: problem" for every name that doesn't expand to a valid FQDN
+    pub(crate) fn name_errors(&self) -> Vec<String> {
+        let mut errors = zone_name_errors(&self.zones, "zones");
+        for (name, profile) in &self.profiles {
+            errors.extend(zone_name_errors(
+                &profile.zones,
+                &format!("profiles.{}.zones", name),
+            ));
+        }
+        errors
+    }
+}
+
+fn normalize_zones(zones: &mut [Zone]) {
+    for zone in zones {
+        if let Some(name) = &mut zone.name {
+            normalize_cow(name);
+            // A zone without an ID is looked up by name, see api::ZoneCache
+            if zone.id.is_empty() {
+                zone.id = name.clone();
+            }
+        }
+        for domain in &mut zone.domains {
+            normalize_cow(&mut domain.name);
+            domain.records.iter_mut().for_each(normalize_cow);
+        }
+    }
+}
+
+fn zone_name_errors(zones: &[Zone], prefix: &str) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    for (zone_index, zone) in zones.iter().enumerate() {
+        if let Some(name) = &zone.name {
+            if let Err(e) = validate_fqdn(name.trim_end_matches('.')) {
+                errors.push(format!("{}[{}].name: {}", prefix, zone_index, e));
+            }
+        }
+
+        for (domain_index, domain) in zone.domains.iter().enumerate() {
+            let path = format!("{}[{}].domains[{}]", prefix, zone_index, domain_index);
+            if let Err(e) = validate_fqdn(&domain.fqdn(zone)) {
+                errors.push(format!("{}.name: {}", path, e));
+                continue;
+            }
+
+            for (record_index, fqdn) in domain.record_names(zone).iter().enumerate() {
+                if let Err(e) = validate_fqdn(fqdn) {
+                    errors.push(format!("{}.records[{}]: {}", path, record_index, e));
+                }
+            }
+        }
+    }
+
+    errors
+}
@@ -0,0 +1,483 @@
+use super::secret::{validate_secret, SecretSource};
+use super::summary::Summary;
+use crate::redact;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, collections::BTreeMap, fmt};
+use validator::{Validate, ValidationError};
+
+// The `{name}` placeholders of messages, titles and payloads, with the event field each stands for
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("event", "event"),
+    ("record", "record"),
+    ("domain", "record"),
+    ("type", "type"),
+    ("address", "address"),
+    ("new_ip", "address"),
+    ("previous", "previous"),
+    ("old_ip", "previous"),
+    ("error", "error"),
+    ("failures", "failures"),
+    ("changes", "changes"),
+    ("minutes", "minutes"),
+    ("checks", "checks"),
+    ("since", "since"),
+    ("report", "report"),
+    ("tenant", "tenant"),
+    ("provider", "provider"),
+    ("version", "version"),
+    ("host", "host"),
+    ("cycle", "cycle"),
+    ("timestamp", "timestamp"),
+];
+
+/// Where to announce what happens to the records
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "validate_routes"))]
+pub struct Notifications {
+    /// URLs receiving a JSON document for each event
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<Webhook>,
+
+    /// Slack incoming webhooks
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub slack: Vec<ChatWebhook>,
+
+    /// Discord channel webhooks
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub discord: Vec<ChatWebhook>,
+
+    /// ntfy topics, on ntfy.sh or a self-hosted server
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ntfy: Vec<Ntfy>,
+
+    /// Pushover users or groups
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pushover: Vec<Pushover>,
+
+    /// Gotify applications
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub gotify: Vec<Gotify>,
+
+    /// Dead man's switch pinged after every cycle
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub healthchecks: Option<Healthchecks>,
+
+    /// A daily or weekly summary sent to the channels wanting `summary` events
+    #[validate(nested)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<Summary>,
+
+    /// The channels each event goes to, by their `name`. Events without a route go to every
+    /// channel wanting them.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(example = example_routes())]
+    pub routes: BTreeMap<EventKind, Vec<String>>,
+}
+
+fn example_routes() -> BTreeMap<EventKind, Vec<String>> {
+    BTreeMap::from([
+        (
+            EventKind::UpdateFailed,
+            vec!["pager".to_string(), "team".to_string()],
+        ),
+        (EventKind::IpChanged, vec!["team".to_string()]),
+    ])
+}
+
+impl Notifications {
+    pub fn is_empty(&self) -> bool {
+        self.webhooks.is_empty()
+            && self.slack.is_empty()
+            && self.discord.is_empty()
+            && self.ntfy.is_empty()
+            && self.pushover.is_empty()
+            && self.gotify.is_empty()
+            && self.healthchecks.is_none()
+            && self.summary.is_none()
+            && self.routes.is_empty()
+    }
+
+    // The names given to channels, in the order of the configuration
+    fn channel_names(&self) -> Vec<&str> {
+        let names = self.webhooks.iter().map(|webhook| &webhook.name);
+        let names = names
+            .chain(self.slack.iter().map(|chat| &chat.name))
+            .chain(self.discord.iter().map(|chat| &chat.name))
+            .chain(self.ntfy.iter().map(|ntfy| &ntfy.name))
+            .chain(self.pushover.iter().map(|pushover| &pushover.name))
+            .chain(self.gotify.iter().map(|gotify| &gotify.name));
+        names.filter_map(|name| name.as_deref()).collect()
+    }
+}
+
+fn validate_routes(notifications: &Notifications) -> Result<(), ValidationError> {
+    let names = notifications.channel_names();
+    for (index, name) in names.iter().enumerate() {
+        if names[..index].contains(name) {
+            return Err(
+                ValidationError::new("notification_routes").with_message(Cow::Owned(format!(
+                    "Several notification channels are named {}",
+                    name
+                ))),
+            );
+        }
+    }
+    for (event, routed) in &notifications.routes {
+        if let Some(name) = routed.iter().find(|name| !names.contains(&name.as_str())) {
+            return Err(
+                ValidationError::new("notification_routes").with_message(Cow::Owned(format!(
+                    "The {} route names {}, which isn't the name of a channel",
+                    event.as_str(),
+                    name
+                ))),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Something worth telling the outside world about
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    /// The daemon started
+    Startup,
+    /// A record was updated to a new address
+    IpChanged,
+    /// A record could not be checked or updated
+    UpdateFailed,
+    /// A record was changed behind clouddns' back and has been put back
+    DriftDetected,
+    /// Cycles failed as many times in a row as the watchdog allows
+    WatchdogTripped,
+    /// The daemon panicked, or stopped on an error
+    Crashed,
+    /// A record changed too often, see `flapping`
+    Flapping,
+    /// The daily or weekly summary
+    Summary,
+}
+
+impl EventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EventKind::Startup => "startup",
+            EventKind::IpChanged => "ip_changed",
+            EventKind::UpdateFailed => "update_failed",
+            EventKind::DriftDetected => "drift_detected",
+            EventKind::WatchdogTripped => "watchdog_tripped",
+            EventKind::Crashed => "crashed",
+            EventKind::Flapping => "flapping",
+            EventKind::Summary => "summary",
+        }
+    }
+}
+
+/// An HTTP endpoint the events are POSTed to
+#[derive(Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// Name the channel goes by in `routes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// URL receiving the events
+    #[validate(url(message = "Webhook URL must be a valid URL"))]
+    #[schemars(example = &"https://example.com/hooks/clouddns")]
+    pub url: String,
+
+    /// Events to send, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+
+    /// JSON document to send instead of the event itself. `{event}`, `{record}` (or `{domain}`),
+    /// `{type}`, `{address}` (or `{new_ip}`), `{previous}` (or `{old_ip}`), `{error}`,
+    /// `{failures}`, `{tenant}`, `{provider}`, `{version}`, `{host}`, `{cycle}` and `{timestamp}`
+    /// in its strings are replaced, by nothing when the event has no such field
+    #[validate(custom(function = "validate_payload"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payload: Option<serde_json::Value>,
+
+    /// Documents replacing `payload` for some events
+    #[validate(custom(function = "validate_payloads"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub payloads: BTreeMap<EventKind, serde_json::Value>,
+
+    /// Extra request headers, e.g. for authentication
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+
+    /// Key signing each body with HMAC-SHA256, sent as `X-Clouddns-Signature: sha256=<hex>`
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<SecretSource>,
+}
+
+// Headers are there for authentication, their values are left out
+impl fmt::Debug for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: BTreeMap<&str, &str> = self
+            .headers
+            .keys()
+            .map(|name| (name.as_str(), "[REDACTED]"))
+            .collect();
+        f.debug_struct("Webhook")
+            .field("name", &self.name)
+            .field("url", &redact::url(&self.url))
+            .field("events", &self.events)
+            .field("payload", &self.payload)
+            .field("payloads", &self.payloads)
+            .field("headers", &headers)
+            .field("secret", &self.secret)
+            .finish()
+    }
+}
+
+impl Webhook {
+    pub fn wants(&self, event: EventKind) -> bool {
+        wants(&self.events, event)
+    }
+
+    // The custom document for an event, if any
+    pub fn payload(&self, event: EventKind) -> Option<&serde_json::Value> {
+        self.payloads.get(&event).or(self.payload.as_ref())
+    }
+}
+
+// An empty list of events means all of them
+pub fn wants(events: &[EventKind], event: EventKind) -> bool {
+    events.is_empty() || events.contains(&event)
+}
+
+// Whether the channel of that name, if it has one, gets the event as far as routes go
+pub fn routes_to(
+    routes: &BTreeMap<EventKind, Vec<String>>,
+    name: Option<&str>,
+    event: EventKind,
+) -> bool {
+    match routes.get(&event) {
+        Some(names) => name.is_some_and(|name| names.iter().any(|routed| routed == name)),
+        None => true,
+    }
+}
+
+/// A chat webhook posting a short message for each event
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ChatWebhook {
+    /// Name the channel goes by in `routes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Webhook URL. It lets anyone post to the channel, so any secret source can hold it
+    #[validate(custom(function = "validate_secret"))]
+    #[schemars(example = &"https://hooks.slack.com/services/T000/B000/XXXX")]
+    pub url: SecretSource,
+
+    /// Events to post, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+
+    /// Messages replacing the default ones, by event, with the same `{field}` placeholders as
+    /// webhook payloads
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[schemars(example = example_messages())]
+    pub messages: BTreeMap<EventKind, String>,
+}
+
+fn example_messages() -> BTreeMap<EventKind, String> {
+    BTreeMap::from([(
+        EventKind::IpChanged,
+        ":house: {record} moved to {address}".to_string(),
+    )])
+}
+
+impl ChatWebhook {
+    pub fn wants(&self, event: EventKind) -> bool {
+        wants(&self.events, event)
+    }
+}
+
+/// An ntfy topic. Failures are sent with a high priority
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Ntfy {
+    /// Name the channel goes by in `routes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Server URL
+    #[validate(url(message = "ntfy server must be a valid URL"))]
+    #[serde(default = "default_ntfy_server")]
+    pub server: String,
+
+    /// Topic to publish to
+    #[validate(length(min = 1, message = "ntfy topic cannot be empty"))]
+    #[schemars(example = &"clouddns-home")]
+    pub topic: String,
+
+    /// Access token for a protected topic
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<SecretSource>,
+
+    /// Events to send, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+
+    /// Messages replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub messages: BTreeMap<EventKind, String>,
+
+    /// Titles replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub titles: BTreeMap<EventKind, String>,
+}
+
+fn default_ntfy_server() -> String {
+    "https://ntfy.sh".to_string()
+}
+
+/// A Pushover user or group. Failures are sent with a high priority
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Pushover {
+    /// Name the channel goes by in `routes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// API token of the Pushover application
+    #[validate(custom(function = "validate_secret"))]
+    pub token: SecretSource,
+
+    /// User or group key
+    #[validate(custom(function = "validate_secret"))]
+    pub user: SecretSource,
+
+    /// Device to notify, all of the user's devices when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+
+    /// Events to send, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+
+    /// Messages replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub messages: BTreeMap<EventKind, String>,
+
+    /// Titles replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub titles: BTreeMap<EventKind, String>,
+}
+
+/// A Gotify application. Failures are sent with a high priority
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Gotify {
+    /// Name the channel goes by in `routes`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+
+    /// Server URL
+    #[validate(url(message = "Gotify URL must be a valid URL"))]
+    #[schemars(example = &"https://gotify.example.com")]
+    pub url: String,
+
+    /// Token of the Gotify application
+    #[validate(custom(function = "validate_secret"))]
+    pub token: SecretSource,
+
+    /// Events to send, all of them when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<EventKind>,
+
+    /// Messages replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub messages: BTreeMap<EventKind, String>,
+
+    /// Titles replacing the default ones, by event
+    #[validate(custom(function = "validate_messages"))]
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub titles: BTreeMap<EventKind, String>,
+}
+
+fn validate_payload(payload: &serde_json::Value) -> Result<(), ValidationError> {
+    match payload {
+        serde_json::Value::String(text) => validate_placeholders(text),
+        serde_json::Value::Array(items) => items.iter().try_for_each(validate_payload),
+        serde_json::Value::Object(fields) => fields.values().try_for_each(validate_payload),
+        _ => Ok(()),
+    }
+}
+
+fn validate_payloads(
+    payloads: &BTreeMap<EventKind, serde_json::Value>,
+) -> Result<(), ValidationError> {
+    payloads.values().try_for_each(validate_payload)
+}
+
+fn validate_messages(messages: &BTreeMap<EventKind, String>) -> Result<(), ValidationError> {
+    messages
+        .values()
+        .try_for_each(|message| validate_placeholders(message))
+}
+
+// A misspelt placeholder would be sent as it is, it's caught when loading instead. Braces around
+// anything else than a name, e.g. JSON in a message, are left alone.
+fn validate_placeholders(text: &str) -> Result<(), ValidationError> {
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        rest = &rest[start + 1..];
+        let Some(end) = rest.find('}') else {
+            break;
+        };
+        let name = &rest[..end];
+        let is_name = !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+        if is_name
+            && !PLACEHOLDERS
+                .iter()
+                .any(|(placeholder, _)| *placeholder == name)
+        {
+            let known: Vec<String> = PLACEHOLDERS
+                .iter()
+                .map(|(placeholder, _)| format!("{{{}}}", placeholder))
+                .collect();
+            return Err(
+                ValidationError::new("placeholder").with_message(Cow::Owned(format!(
+                    "Unknown placeholder {{{}}}, known ones are {}",
+                    name,
+                    known.join(", ")
+                ))),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// A healthchecks.io check, or one on any server taking the same pings
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Healthchecks {
+    /// Ping URL of the check, with `/fail` appended after a failed cycle
+    #[validate(custom(function = "validate_secret"))]
+    #[schemars(example = &"https://hc-ping.com/your-check-uuid")]
+    pub url: SecretSource,
+}
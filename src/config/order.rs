@@ -0,0 +1,50 @@
+use super::models::{Config, Zone};
+use super::provider::ProviderKind;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How the records of a zone are checked and updated. Zones always run alongside each other, and
+/// no more than `concurrency` records at a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordOrder {
+    /// All at the same time
+    Parallel,
+    /// Records sharing a name, such as the A and AAAA records of a host, one after the other
+    Grouped,
+    /// One at a time, in the order they are listed
+    Sequential,
+}
+
+impl RecordOrder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordOrder::Parallel => "parallel",
+            RecordOrder::Grouped => "grouped",
+            RecordOrder::Sequential => "sequential",
+        }
+    }
+}
+
+impl ProviderKind {
+    // Cloudflare takes concurrent writes to a zone, where a program may rewrite a whole zone file
+    // on each call
+    pub fn default_order(&self) -> RecordOrder {
+        match self {
+            ProviderKind::Cloudflare => RecordOrder::Parallel,
+            ProviderKind::Exec => RecordOrder::Sequential,
+        }
+    }
+}
+
+impl Config {
+    // The zone's own order, else the provider's, else the one that suits the provider
+    pub fn record_order(&self, zone: &Zone) -> RecordOrder {
+        if zone.sequential {
+            return RecordOrder::Sequential;
+        }
+        zone.order
+            .or(self.provider.order)
+            .unwrap_or_else(|| self.provider.r#type.default_order())
+    }
+}
@@ -0,0 +1,176 @@
+use super::models::{Config, RecordType};
+use super::order::RecordOrder;
+use super::provider::ProviderKind;
+use super::secret::SecretSource;
+use super::state::StateStoreKind;
+use crate::redact;
+use std::collections::BTreeSet;
+
+impl Config {
+    // The configuration as it will run, a line per topic, for the log at startup. Secrets are
+    // told by where they come from, never by their value, and URLs are masked like in requests.
+    pub fn overview(&self) -> Vec<String> {
+        let mut lines = vec![match self.provider.r#type {
+            ProviderKind::Cloudflare => {
+                format!(
+                    "Provider: cloudflare, API token {}",
+                    secret(&self.api_token)
+                )
+            }
+            ProviderKind::Exec => format!(
+                "Provider: exec {}, credential {}",
+                self.provider
+                    .command
+                    .as_ref()
+                    .map(|command| command.display().to_string())
+                    .unwrap_or_default(),
+                secret(&self.api_token)
+            ),
+        }];
+        for (name, tenant) in &self.tenants {
+            let zones = self
+                .zones
+                .iter()
+                .filter(|zone| zone.tenant.as_deref() == Some(name.as_str()))
+                .count();
+            lines.push(format!(
+                "Tenant {}: API token {}, {} zone(s)",
+                name,
+                secret(&tenant.api_token),
+                zones
+            ));
+        }
+
+        for zone in self.zones.iter().filter(|zone| zone.is_enabled()) {
+            let label = match &zone.name {
+                Some(name) if *name == zone.id => format!("{} (looked up by name)", name),
+                Some(name) => format!("{} ({})", name, zone.id),
+                None => zone.id.to_string(),
+            };
+            let (mut records, mut types, mut intervals, mut sources) =
+                (0, BTreeSet::new(), BTreeSet::new(), BTreeSet::new());
+            for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+                let settings = self.settings(zone, domain);
+                let record_types = settings.record_types.types();
+                records += domain.record_names(zone).len() * record_types.len();
+                types.extend(record_types.iter().map(RecordType::as_str));
+                intervals.insert(settings.update_interval);
+                sources.insert(redact::url(&settings.ip_source.to_string()));
+            }
+            let mut line = format!("Zone {}: {} record(s)", label, records);
+            if records > 0 {
+                line.push_str(&format!(
+                    " ({}), every {} minute(s), addresses from {}",
+                    join(types),
+                    join(intervals),
+                    join(sources)
+                ));
+            }
+            let order = self.record_order(zone);
+            if order != RecordOrder::Parallel {
+                line.push_str(&format!(", {} updates", order.as_str()));
+            }
+            if let Some(tenant) = &zone.tenant {
+                line.push_str(&format!(", tenant {}", tenant));
+            }
+            lines.push(line);
+        }
+
+        lines.push(format!("Notifications: {}", self.channels()));
+        lines.push(match (&self.state.store, &self.state.path) {
+            (StateStoreKind::Kv, _) => format!(
+                "State: kv namespace {}",
+                self.state.namespace_id.as_deref().unwrap_or_default()
+            ),
+            (store, Some(path)) => format!("State: {} {}", store.as_str(), path.display()),
+            (store, None) => format!("State: {}", store.as_str()),
+        });
+        let mut servers = Vec::new();
+        if let Some(health) = &self.health {
+            servers.push(format!("health checks on {}", health.listen));
+        }
+        if let Some(admin) = &self.admin {
+            servers.push(format!(
+                "admin API on {}, token {}",
+                admin.listen,
+                secret(&admin.token)
+            ));
+        }
+        if let Some(acme_dns) = &self.acme_dns {
+            servers.push(format!("acme-dns on {}", acme_dns.listen));
+        }
+        if let Some(socket) = &self.control_socket {
+            servers.push(format!("control socket {}", socket.display()));
+        }
+        if !servers.is_empty() {
+            lines.push(format!("Serving: {}", servers.join(", ")));
+        }
+        lines
+    }
+
+    fn channels(&self) -> String {
+        let notifications = &self.notifications;
+        let named = |kind: &str, name: &Option<String>, target: String| match name {
+            Some(name) => format!("{} {} ({})", kind, name, target),
+            None => format!("{} ({})", kind, target),
+        };
+        let mut channels = Vec::new();
+        for webhook in &notifications.webhooks {
+            channels.push(named("webhook", &webhook.name, redact::url(&webhook.url)));
+        }
+        for (kind, chats) in [
+            ("slack", &notifications.slack),
+            ("discord", &notifications.discord),
+        ] {
+            for chat in chats {
+                channels.push(named(
+                    kind,
+                    &chat.name,
+                    format!("URL {}", secret(&chat.url)),
+                ));
+            }
+        }
+        // Anyone knowing a topic can read it, it's left out
+        for ntfy in &notifications.ntfy {
+            channels.push(named("ntfy", &ntfy.name, redact::url(&ntfy.server)));
+        }
+        for pushover in &notifications.pushover {
+            let target = format!("token {}", secret(&pushover.token));
+            channels.push(named("pushover", &pushover.name, target));
+        }
+        for gotify in &notifications.gotify {
+            channels.push(named("gotify", &gotify.name, redact::url(&gotify.url)));
+        }
+        if let Some(healthchecks) = &notifications.healthchecks {
+            channels.push(format!("healthchecks (URL {})", secret(&healthchecks.url)));
+        }
+        if channels.is_empty() {
+            return "none".to_string();
+        }
+        channels.join(", ")
+    }
+}
+
+// Where a secret is read from
+fn secret(source: &SecretSource) -> String {
+    match source {
+        SecretSource::Plain(_) => match source.docker_secret() {
+            Some(name) => format!("from Docker secret {}", name),
+            None => "inline [REDACTED]".to_string(),
+        },
+        SecretSource::Vault { vault } => format!("from Vault {}/{}", vault.mount, vault.path),
+        SecretSource::AwsSecret { aws_secret, .. } => {
+            format!("from AWS Secrets Manager {}", aws_secret)
+        }
+        SecretSource::AwsParameter { aws_ssm } => format!("from SSM parameter {}", aws_ssm),
+        SecretSource::Keyring { keyring } => format!("from keyring entry {}", keyring.account()),
+    }
+}
+
+fn join<T: ToString>(items: BTreeSet<T>) -> String {
+    items
+        .iter()
+        .map(T::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,36 @@
+use super::models::{Config, Defaults};
+use anyhow::Result;
+
+impl Config {
+    // Make the named profile the active configuration: its defaults win over the
+    // top-level ones and its zones, when it has any, replace the top-level zones
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self.profiles.remove(name).ok_or_else(|| {
+            let available: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+            match available.is_empty() {
+                true => anyhow::anyhow!("Unknown profile `{}`, the config defines none", name),
+                false => anyhow::anyhow!(
+                    "Unknown profile `{}`, expected one of: {}",
+                    name,
+                    available.join(", ")
+                ),
+            }
+        })?;
+
+        let defaults = std::mem::take(&mut self.defaults);
+        self.defaults = Defaults {
+            ttl: profile.defaults.ttl.or(defaults.ttl),
+            proxied: profile.defaults.proxied.or(defaults.proxied),
+            record_type: profile.defaults.record_type.or(defaults.record_type),
+            ip_source: profile.defaults.ip_source.or(defaults.ip_source),
+            update_interval: profile
+                .defaults
+                .update_interval
+                .or(defaults.update_interval),
+        };
+        if !profile.zones.is_empty() {
+            self.zones = profile.zones;
+        }
+        Ok(())
+    }
+}
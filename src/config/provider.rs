@@ -0,0 +1,102 @@
+use super::order::RecordOrder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use validator::{Validate, ValidationError};
+
+const DEFAULT_TIMEOUT: u64 = 30;
+
+/// The DNS provider the records are read from and written to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "validate_provider"))]
+pub struct ProviderConfig {
+    /// `cloudflare`, or `exec` for a program speaking the plugin protocol, for DNS hosts clouddns
+    /// doesn't support itself
+    #[serde(default, skip_serializing_if = "ProviderKind::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub r#type: ProviderKind,
+
+    /// The program run for every call to an `exec` provider, with a JSON request on its standard
+    /// input and the API token in `CLOUDDNS_API_TOKEN`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"/usr/local/lib/clouddns/my-dns-host")]
+    pub command: Option<PathBuf>,
+
+    /// Arguments given to the program
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub args: Vec<String>,
+
+    /// Seconds a call may take before the program is killed
+    #[validate(range(min = 1, message = "Provider timeout must be greater than 0"))]
+    #[serde(
+        default = "default_timeout",
+        skip_serializing_if = "is_default_timeout"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub timeout: u64,
+
+    /// How the records of each zone are updated unless the zone says otherwise: `parallel`,
+    /// `grouped` by name or `sequential`. `parallel` for Cloudflare, `sequential` for `exec`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<RecordOrder>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            r#type: ProviderKind::default(),
+            command: None,
+            args: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+            order: None,
+        }
+    }
+}
+
+impl ProviderConfig {
+    pub fn is_default(&self) -> bool {
+        *self == ProviderConfig::default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ProviderKind {
+    /// Cloudflare's API
+    #[default]
+    Cloudflare,
+    /// An external program, see `command`
+    Exec,
+}
+
+impl ProviderKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProviderKind::Cloudflare => "cloudflare",
+            ProviderKind::Exec => "exec",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == ProviderKind::default()
+    }
+}
+
+fn validate_provider(provider: &ProviderConfig) -> Result<(), ValidationError> {
+    match (provider.r#type, &provider.command) {
+        (ProviderKind::Exec, None) => Err(ValidationError::new("provider_command")
+            .with_message("An exec provider needs a command".into())),
+        (ProviderKind::Cloudflare, Some(_)) => Err(ValidationError::new("provider_command")
+            .with_message("A command is only used by an exec provider".into())),
+        _ => Ok(()),
+    }
+}
+
+fn default_timeout() -> u64 {
+    DEFAULT_TIMEOUT
+}
+
+fn is_default_timeout(timeout: &u64) -> bool {
+    *timeout == DEFAULT_TIMEOUT
+}
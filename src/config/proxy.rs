@@ -0,0 +1,55 @@
+use super::secret::{validate_secret, SecretSource};
+use crate::redact;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use validator::{Validate, ValidationError};
+
+/// Proxy every HTTP request goes through, to the API as well as the IP sources and notification
+/// services. Replaces the `HTTP_PROXY` and `NO_PROXY` variables
+#[derive(Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://`, or `socks5h://` to also resolve names through the proxy
+    #[validate(custom(function = "validate_proxy"))]
+    #[schemars(example = &"socks5h://proxy.lan:1080")]
+    pub url: String,
+
+    /// User name to log in to the proxy with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+
+    /// Password to log in to the proxy with
+    #[validate(custom(function = "validate_secret"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<SecretSource>,
+
+    /// Hosts reached directly, written like in `NO_PROXY`: `localhost`, `.lan` or `10.0.0.0/8`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+}
+
+// The URL may carry the credentials too
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &redact::url(&self.url))
+            .field("username", &self.username)
+            .field("password", &self.password)
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+fn validate_proxy(url: &str) -> Result<(), ValidationError> {
+    let valid = reqwest::Url::parse(url).is_ok_and(|url| {
+        matches!(url.scheme(), "http" | "https" | "socks5" | "socks5h")
+            && url.host_str().is_some_and(|host| !host.is_empty())
+    });
+    match valid {
+        true => Ok(()),
+        false => Err(ValidationError::new("proxy_url").with_message(
+            "Proxy URL must look like http://host:3128 or socks5://host:1080".into(),
+        )),
+    }
+}
@@ -0,0 +1,30 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use validator::Validate;
+
+/// Requests sent to the DNS provider's API over a period, so large configurations can't get the
+/// account blocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    /// Requests allowed per period
+    #[validate(range(min = 1, message = "Rate limit requests must be greater than 0"))]
+    pub requests: u32,
+
+    /// Length of the period in seconds
+    #[validate(range(min = 1, message = "Rate limit period must be greater than 0"))]
+    pub period: u64,
+}
+
+impl RateLimit {
+    // Cloudflare's documented limit, 1200 requests per five minutes for a user
+    pub const CLOUDFLARE: RateLimit = RateLimit {
+        requests: 1200,
+        period: 300,
+    };
+
+    pub fn period(&self) -> Duration {
+        Duration::from_secs(self.period)
+    }
+}
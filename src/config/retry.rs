@@ -0,0 +1,88 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use validator::{Validate, ValidationError};
+
+/// How HTTP requests to the API and to the IP sources are retried before a cycle is
+/// considered failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Attempts per request, the first one included. 1 disables retries
+    #[validate(range(min = 1, max = 20, message = "Max attempts must be between 1 and 20"))]
+    #[serde(default = "default_max_attempts")]
+    pub max_attempts: u32,
+
+    /// Milliseconds to wait before the first retry, doubled after each one
+    #[serde(default = "default_backoff_ms")]
+    pub backoff_ms: u64,
+
+    /// Longest wait between two attempts in milliseconds, also caps `Retry-After`
+    #[serde(default = "default_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+
+    /// HTTP status codes worth retrying, other errors fail right away
+    #[validate(custom(function = "validate_status_codes"))]
+    #[serde(default = "default_retry_status")]
+    pub retry_status: Vec<u16>,
+
+    /// Retry requests that couldn't connect, timed out or lost their connection
+    #[serde(default = "default_retry_network_errors")]
+    pub retry_network_errors: bool,
+}
+
+fn default_max_attempts() -> u32 {
+    3
+}
+
+fn default_backoff_ms() -> u64 {
+    500
+}
+
+fn default_max_backoff_ms() -> u64 {
+    10_000
+}
+
+fn default_retry_status() -> Vec<u16> {
+    vec![408, 429, 500, 502, 503, 504]
+}
+
+fn default_retry_network_errors() -> bool {
+    true
+}
+
+fn validate_status_codes(codes: &[u16]) -> Result<(), ValidationError> {
+    match codes.iter().find(|code| !(100..=599).contains(*code)) {
+        Some(code) => Err(ValidationError::new("status")
+            .with_message(format!("{} is not an HTTP status code", code).into())),
+        None => Ok(()),
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_max_attempts(),
+            backoff_ms: default_backoff_ms(),
+            max_backoff_ms: default_max_backoff_ms(),
+            retry_status: default_retry_status(),
+            retry_network_errors: default_retry_network_errors(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Wait before the given retry (1 for the first one), without jitter
+    pub(crate) fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u64.saturating_pow(retry.saturating_sub(1));
+        Duration::from_millis(
+            self.backoff_ms
+                .saturating_mul(factor)
+                .min(self.max_backoff_ms),
+        )
+    }
+}
@@ -0,0 +1,7 @@
+use super::models::Config;
+use schemars::{schema_for, Schema};
+
+// JSON Schema describing the config file, derived from the models so it can't drift
+pub fn schema() -> Schema {
+    schema_for!(Config)
+}
@@ -0,0 +1,185 @@
+use schemars::JsonSchema;
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize, Serializer};
+use std::{borrow::Cow, fmt};
+use validator::ValidationError;
+
+/// A credential, either written inline or fetched from a secret backend
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum SecretSource {
+    /// The secret itself, or `secret://<name>` to read the Docker secret `/run/secrets/<name>`
+    /// (the systemd credential `<name>` when running as a unit)
+    Plain(InlineSecret),
+    /// Read from a HashiCorp Vault KV v2 secret
+    Vault { vault: VaultSecret },
+    /// Read from AWS Secrets Manager, by name or ARN
+    AwsSecret {
+        aws_secret: String,
+        /// Key to extract when the secret value is a JSON object
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aws_secret_key: Option<String>,
+    },
+    /// Read from an AWS Systems Manager Parameter Store parameter, decrypting SecureStrings
+    AwsParameter { aws_ssm: String },
+    /// Read from the platform keyring, stored there with `clouddns token set`
+    Keyring { keyring: KeyringEntry },
+}
+
+/// A secret written in the configuration, wiped from memory when dropped and never printed
+#[derive(Clone, Deserialize, JsonSchema)]
+#[serde(transparent)]
+#[schemars(transparent)]
+pub struct InlineSecret(#[schemars(with = "String")] SecretString);
+
+impl InlineSecret {
+    pub(crate) fn expose(&self) -> &str {
+        self.0.expose_secret()
+    }
+
+    pub fn secret(&self) -> SecretString {
+        self.0.clone()
+    }
+}
+
+impl From<String> for InlineSecret {
+    fn from(value: String) -> Self {
+        Self(SecretString::from(value))
+    }
+}
+
+impl From<SecretString> for InlineSecret {
+    fn from(value: SecretString) -> Self {
+        Self(value)
+    }
+}
+
+impl PartialEq for InlineSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.expose() == other.expose()
+    }
+}
+
+// A reference to a Docker secret gives nothing away
+impl fmt::Debug for InlineSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.expose().starts_with("secret://") {
+            true => write!(f, "{:?}", self.expose()),
+            false => f.write_str("[REDACTED]"),
+        }
+    }
+}
+
+// Writing a configuration back, e.g. after `init` or `migrate`, needs the value itself
+impl Serialize for InlineSecret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.expose())
+    }
+}
+
+/// `true` for the default keyring entry, or the name of the entry to use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+pub enum KeyringEntry {
+    Default(bool),
+    Account(String),
+}
+
+pub const DEFAULT_KEYRING_ACCOUNT: &str = "api_token";
+
+impl KeyringEntry {
+    pub fn account(&self) -> &str {
+        match self {
+            KeyringEntry::Default(_) => DEFAULT_KEYRING_ACCOUNT,
+            KeyringEntry::Account(account) => account,
+        }
+    }
+}
+
+/// Location of a secret in Vault's KV v2 engine. The server address comes from `VAULT_ADDR`
+/// and authentication from `VAULT_TOKEN` or `VAULT_ROLE_ID`/`VAULT_SECRET_ID` (AppRole).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct VaultSecret {
+    /// Path of the secret inside the mount, e.g. `clouddns/cloudflare`
+    pub path: String,
+
+    /// KV v2 mount point
+    #[serde(default = "default_mount")]
+    pub mount: String,
+
+    /// Key holding the value inside the secret
+    #[serde(default = "default_key")]
+    pub key: String,
+
+    /// Minutes between re-fetches, so rotated secrets are picked up without a restart
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_interval: Option<u64>,
+}
+
+fn default_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_key() -> String {
+    "api_token".to_string()
+}
+
+impl SecretSource {
+    // Name of the Docker secret referenced as `secret://<name>`
+    pub(crate) fn docker_secret(&self) -> Option<&str> {
+        match self {
+            SecretSource::Plain(value) => value.expose().strip_prefix("secret://"),
+            _ => None,
+        }
+    }
+
+    // Written in the configuration itself, so there is nothing to re-read
+    pub(crate) fn is_inline(&self) -> bool {
+        matches!(self, SecretSource::Plain(_)) && self.docker_secret().is_none()
+    }
+
+    // Minutes between re-fetches for backends that support rotation
+    pub fn refresh_interval(&self) -> Option<u64> {
+        match self {
+            SecretSource::Vault { vault } => vault.refresh_interval,
+            _ => None,
+        }
+    }
+}
+
+// A file right in the secrets directory, not a path leading out of it
+pub(crate) fn is_docker_secret_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+pub(crate) fn validate_secret(secret: &SecretSource) -> Result<(), ValidationError> {
+    let message = match secret {
+        SecretSource::Plain(value) if value.expose().is_empty() => "Secret cannot be empty",
+        _ if secret
+            .docker_secret()
+            .is_some_and(|name| !is_docker_secret_name(name)) =>
+        {
+            "Docker secret name must be a plain file name, as in `secret://api_token`"
+        }
+        SecretSource::Vault { vault } if vault.path.is_empty() => "Vault path cannot be empty",
+        SecretSource::Vault { vault } if vault.refresh_interval == Some(0) => {
+            "Refresh interval must be greater than 0"
+        }
+        SecretSource::AwsSecret { aws_secret, .. } if aws_secret.is_empty() => {
+            "AWS secret name cannot be empty"
+        }
+        SecretSource::AwsParameter { aws_ssm } if aws_ssm.is_empty() => {
+            "AWS parameter name cannot be empty"
+        }
+        SecretSource::Keyring {
+            keyring: KeyringEntry::Default(false),
+        } => "Set keyring to true or to the name of a keyring entry",
+        SecretSource::Keyring {
+            keyring: KeyringEntry::Account(account),
+        } if account.is_empty() => "Keyring entry name cannot be empty",
+        _ => return Ok(()),
+    };
+
+    Err(ValidationError::new("secret").with_message(Cow::Borrowed(message)))
+}
@@ -0,0 +1,39 @@
+use super::secret::{validate_secret, SecretSource};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+const DEFAULT_FAILURES: u32 = 3;
+
+/// Report panics and cycles that keep failing to Sentry, or to a service speaking its protocol
+/// such as GlitchTip, with the provider, the domains, the chain of errors and the version
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct SentryConfig {
+    /// The project's DSN. It lets anyone send events to the project, so any secret source can
+    /// hold it
+    #[validate(custom(function = "validate_secret"))]
+    #[schemars(example = &"https://0123456789abcdef@o0.ingest.sentry.io/42")]
+    pub dsn: SecretSource,
+
+    /// Environment the events are filed under, e.g. `production`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+
+    /// Failed cycles in a row that are reported, once each time the daemon starts failing
+    #[validate(range(min = 1, message = "Sentry failures must be greater than 0"))]
+    #[serde(
+        default = "default_failures",
+        skip_serializing_if = "is_default_failures"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub failures: u32,
+}
+
+fn default_failures() -> u32 {
+    DEFAULT_FAILURES
+}
+
+fn is_default_failures(failures: &u32) -> bool {
+    *failures == DEFAULT_FAILURES
+}
@@ -0,0 +1,51 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+const DEFAULT_NETWORK_TIMEOUT: u64 = 300;
+
+/// When the daemon runs its first check, right away unless told otherwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Startup {
+    /// Seconds to wait before the first check, e.g. for a PPPoE session to come up
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delay: Option<u64>,
+
+    /// Hold the first check until the Cloudflare API can be reached
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub wait_for_network: bool,
+
+    /// Seconds to wait for the network before checking anyway
+    #[validate(range(min = 1, message = "Network timeout must be greater than 0"))]
+    #[serde(
+        default = "default_network_timeout",
+        skip_serializing_if = "is_default_network_timeout"
+    )]
+    #[schemars(!skip_serializing_if)]
+    pub network_timeout: u64,
+}
+
+impl Default for Startup {
+    fn default() -> Self {
+        Self {
+            delay: None,
+            wait_for_network: false,
+            network_timeout: DEFAULT_NETWORK_TIMEOUT,
+        }
+    }
+}
+
+impl Startup {
+    pub fn is_default(&self) -> bool {
+        *self == Startup::default()
+    }
+}
+
+fn default_network_timeout() -> u64 {
+    DEFAULT_NETWORK_TIMEOUT
+}
+
+fn is_default_network_timeout(timeout: &u64) -> bool {
+    *timeout == DEFAULT_NETWORK_TIMEOUT
+}
@@ -0,0 +1,94 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use validator::{Validate, ValidationError};
+
+/// Where the addresses last detected and the history of each record are kept across restarts
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "validate_state"))]
+pub struct StateConfig {
+    /// `file` for a JSON file, `sqlite` for a database several instances can share, `kv` for a
+    /// Cloudflare Workers KV namespace, or `memory` to remember nothing across restarts
+    #[serde(default, skip_serializing_if = "StateStoreKind::is_default")]
+    #[schemars(!skip_serializing_if)]
+    pub store: StateStoreKind,
+
+    /// The file or database, named after the configuration file in the state directory by
+    /// default. `--state-file` overrides it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"/var/lib/clouddns/state.db")]
+    pub path: Option<PathBuf>,
+
+    /// Cloudflare account owning the KV namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"023e105f4ecef8ad9ca31a8372d0c353")]
+    pub account_id: Option<String>,
+
+    /// ID of the KV namespace the state is kept in. The API token needs the Workers KV Storage
+    /// Edit permission on the account.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"0f2ac74b498b48028cb68387c421e279")]
+    pub namespace_id: Option<String>,
+
+    /// Key of the state in the namespace, named after the configuration file by default. Instances
+    /// sharing a key share the state.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(example = &"home")]
+    pub key: Option<String>,
+}
+
+impl StateConfig {
+    pub fn is_default(&self) -> bool {
+        *self == StateConfig::default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StateStoreKind {
+    /// A JSON file, rewritten after every cycle
+    #[default]
+    File,
+    /// An SQLite database, with the rows of each configuration kept apart. Needs the `sqlite`
+    /// feature.
+    Sqlite,
+    /// A key of a Cloudflare Workers KV namespace, reached with the API token, for instances
+    /// without persistent storage of their own
+    Kv,
+    /// Nothing is saved
+    Memory,
+}
+
+impl StateStoreKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StateStoreKind::File => "file",
+            StateStoreKind::Sqlite => "sqlite",
+            StateStoreKind::Kv => "kv",
+            StateStoreKind::Memory => "memory",
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == StateStoreKind::default()
+    }
+}
+
+fn validate_state(state: &StateConfig) -> Result<(), ValidationError> {
+    let kv = state.account_id.is_some() || state.namespace_id.is_some() || state.key.is_some();
+    match state.store {
+        StateStoreKind::Kv if state.account_id.is_none() || state.namespace_id.is_none() => {
+            Err(ValidationError::new("state_kv")
+                .with_message("The kv state store needs an account_id and a namespace_id".into()))
+        }
+        StateStoreKind::Kv if state.path.is_some() => Err(ValidationError::new("state_kv")
+            .with_message("The kv state store has no path, its key names the state".into())),
+        StateStoreKind::File | StateStoreKind::Sqlite | StateStoreKind::Memory if kv => {
+            Err(ValidationError::new("state_kv").with_message(
+                "account_id, namespace_id and key are only used by the kv state store".into(),
+            ))
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,69 @@
+use super::window::{TimeOfDay, Weekday};
+use chrono::{DateTime, Datelike, Duration, Local, TimeZone};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// A report of the last day or week sent to the notification channels, even when nothing changed,
+/// as a sign the updater is still running
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Summary {
+    /// `daily` or `weekly`
+    pub every: SummaryPeriod,
+
+    /// Local time it's sent at, `HH:MM`
+    #[serde(default = "default_at")]
+    #[schemars(example = &"08:00")]
+    pub at: TimeOfDay,
+
+    /// Day a weekly summary is sent on
+    #[serde(default = "default_day")]
+    pub day: Weekday,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum SummaryPeriod {
+    Daily,
+    Weekly,
+}
+
+fn default_at() -> TimeOfDay {
+    TimeOfDay::try_from("08:00".to_string()).expect("valid time")
+}
+
+fn default_day() -> Weekday {
+    Weekday::Mon
+}
+
+impl Summary {
+    // What each summary covers
+    pub fn period(&self) -> Duration {
+        match self.every {
+            SummaryPeriod::Daily => Duration::days(1),
+            SummaryPeriod::Weekly => Duration::weeks(1),
+        }
+    }
+
+    // When the next summary is due, strictly after `now`
+    pub fn next_after(&self, now: DateTime<Local>) -> DateTime<Local> {
+        let today = now.date_naive();
+        (0..=8)
+            .map(|days| today + Duration::days(days))
+            .filter(|date| {
+                self.every == SummaryPeriod::Daily || Weekday::from(date.weekday()) == self.day
+            })
+            .filter_map(|date| {
+                let at = date.and_time(self.at.0);
+                // A DST gap swallowing the time just moves it an hour later
+                Local.from_local_datetime(&at).earliest().or_else(|| {
+                    Local
+                        .from_local_datetime(&(at + Duration::hours(1)))
+                        .earliest()
+                })
+            })
+            .find(|at| *at > now)
+            .unwrap_or_else(|| now + self.period())
+    }
+}
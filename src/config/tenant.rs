@@ -0,0 +1,93 @@
+use super::notify::Notifications;
+use super::secret::{validate_secret, SecretSource};
+use super::{Config, Zone};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use validator::{Validate, ValidationError};
+
+/// A customer whose zones are kept apart from the others': their records are only ever touched
+/// with the tenant's own API token, and its channels only hear about them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Tenant {
+    /// API token for the tenant's zones, used instead of `api_token`
+    #[validate(custom(function = "validate_secret"))]
+    #[schemars(example = &"secret://acme_token")]
+    pub api_token: SecretSource,
+
+    /// Channels told about the tenant's records, on top of the top-level ones
+    #[validate(nested, custom(function = "validate_notifications"))]
+    #[serde(default, skip_serializing_if = "Notifications::is_empty")]
+    pub notifications: Notifications,
+}
+
+// Pinged once per cycle, and summed up, for every tenant at once
+fn validate_notifications(notifications: &Notifications) -> Result<(), ValidationError> {
+    if notifications.healthchecks.is_some() {
+        return Err(ValidationError::new("tenant_healthchecks")
+            .with_message("healthchecks can only be set in the top-level notifications".into()));
+    }
+    if notifications.summary.is_some() {
+        return Err(ValidationError::new("tenant_summary")
+            .with_message("summary can only be set in the top-level notifications".into()));
+    }
+    Ok(())
+}
+
+impl Config {
+    /// The tenant a zone belongs to, by zone ID
+    pub fn tenant_of(&self, zone_id: &str) -> Option<&str> {
+        self.zones
+            .iter()
+            .find(|zone| zone.id == zone_id)
+            .and_then(|zone| zone.tenant.as_deref())
+    }
+
+    // The tenant of every zone that has one, for the API clients
+    pub(crate) fn tenant_zones(&self) -> BTreeMap<String, String> {
+        self.zones
+            .iter()
+            .filter_map(|zone| Some((zone.id.to_string(), zone.tenant.clone()?)))
+            .collect()
+    }
+
+    pub(crate) fn tenant_errors(&self) -> Vec<String> {
+        let mut errors = zone_tenant_errors(&self.zones, "zones", &self.tenants);
+        for (name, profile) in &self.profiles {
+            errors.extend(zone_tenant_errors(
+                &profile.zones,
+                &format!("profiles.{}.zones", name),
+                &self.tenants,
+            ));
+        }
+        errors
+    }
+}
+
+fn zone_tenant_errors(
+    zones: &[Zone],
+    prefix: &str,
+    tenants: &BTreeMap<String, Tenant>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut zone_tenants: BTreeMap<&str, Option<&str>> = BTreeMap::new();
+    for (index, zone) in zones.iter().enumerate() {
+        let tenant = zone.tenant.as_deref();
+        if let Some(tenant) = tenant.filter(|tenant| !tenants.contains_key(*tenant)) {
+            errors.push(format!(
+                "{}[{}].tenant: no tenant named {:?} in tenants",
+                prefix, index, tenant
+            ));
+        }
+        // The zone's records would otherwise be written with two different tokens
+        match zone_tenants.insert(&zone.id, tenant) {
+            Some(other) if other != tenant => errors.push(format!(
+                "{}[{}].tenant: zone {} is listed again with another tenant",
+                prefix, index, zone.id
+            )),
+            _ => {}
+        }
+    }
+    errors
+}
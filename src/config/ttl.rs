@@ -0,0 +1,134 @@
+use super::models::{Config, Zone};
+use super::provider::ProviderKind;
+
+// What Cloudflare takes: 1 for automatic, otherwise a minute to a day
+pub const AUTOMATIC: u32 = 1;
+pub const MIN: u32 = 60;
+pub const MAX: u32 = 86400;
+
+pub fn is_valid(ttl: u32) -> bool {
+    ttl == AUTOMATIC || (MIN..=MAX).contains(&ttl)
+}
+
+impl Config {
+    // "path: problem" for every TTL Cloudflare would reject, other providers having rules of their
+    // own. One that only proxied records take is ignored instead, see `ttl_warnings`.
+    pub(crate) fn ttl_errors(&self) -> Vec<String> {
+        if self.provider.r#type != ProviderKind::Cloudflare {
+            return Vec::new();
+        }
+        let mut ttls = TtlSettings::default();
+        let inherited = vec![
+            ttls.add("record_ttl".to_string(), self.record_ttl),
+            ttls.add("defaults.ttl".to_string(), self.defaults.ttl),
+        ];
+        ttls.zones(&self.zones, "zones", &inherited, self.defaults.proxied);
+        for (name, profile) in &self.profiles {
+            let mut inherited = inherited.clone();
+            inherited.push(ttls.add(
+                format!("profiles.{}.defaults.ttl", name),
+                profile.defaults.ttl,
+            ));
+            let proxied = profile.defaults.proxied.or(self.defaults.proxied);
+            // Without zones of its own, the profile applies to the others
+            let (zones, prefix) = match profile.zones.is_empty() {
+                true => (&self.zones, "zones".to_string()),
+                false => (&profile.zones, format!("profiles.{}.zones", name)),
+            };
+            ttls.zones(zones, &prefix, &inherited, proxied);
+        }
+        ttls.0
+            .into_iter()
+            .filter(|setting| !is_valid(setting.ttl) && (setting.unproxied || !setting.used))
+            .map(|setting| {
+                format!(
+                    "{}: Cloudflare doesn't take a TTL of {}, use 1 (automatic) or {} to {} seconds",
+                    setting.path, setting.ttl, MIN, MAX
+                )
+            })
+            .collect()
+    }
+
+    // Settings ignored rather than wrong: Cloudflare gives proxied records an automatic TTL
+    // whatever is asked for
+    pub fn ttl_warnings(&self) -> Vec<String> {
+        if self.provider.r#type != ProviderKind::Cloudflare {
+            return Vec::new();
+        }
+        let mut warnings = Vec::new();
+        for zone in self.zones.iter().filter(|zone| zone.is_enabled()) {
+            for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+                let ttl = domain
+                    .ttl
+                    .or(zone.ttl)
+                    .or(self.defaults.ttl)
+                    .or(self.record_ttl);
+                let proxied = domain.proxied.or(zone.proxied).or(self.defaults.proxied);
+                if let (Some(ttl), Some(true)) = (ttl, proxied) {
+                    if ttl != AUTOMATIC {
+                        warnings.push(format!(
+                            "{} is proxied, its TTL of {} seconds is ignored",
+                            domain.fqdn(zone),
+                            ttl
+                        ));
+                    }
+                }
+            }
+        }
+        warnings
+    }
+}
+
+// The TTLs set, and which records take them
+#[derive(Default)]
+struct TtlSettings(Vec<TtlSetting>);
+
+struct TtlSetting {
+    path: String,
+    ttl: u32,
+    used: bool,
+    unproxied: bool,
+}
+
+impl TtlSettings {
+    // Its index, none when it isn't set. A path seen before is the same setting.
+    fn add(&mut self, path: String, ttl: Option<u32>) -> Option<usize> {
+        let ttl = ttl?;
+        if let Some(index) = self.0.iter().position(|setting| setting.path == path) {
+            return Some(index);
+        }
+        self.0.push(TtlSetting {
+            path,
+            ttl,
+            used: false,
+            unproxied: false,
+        });
+        Some(self.0.len() - 1)
+    }
+
+    // `inherited` from the least to the most specific
+    fn zones(
+        &mut self,
+        zones: &[Zone],
+        prefix: &str,
+        inherited: &[Option<usize>],
+        proxied: Option<bool>,
+    ) {
+        for (zone_index, zone) in zones.iter().enumerate() {
+            let zone_ttl = self.add(format!("{}[{}].ttl", prefix, zone_index), zone.ttl);
+            for (domain_index, domain) in zone.domains.iter().enumerate() {
+                let path = format!("{}[{}].domains[{}].ttl", prefix, zone_index, domain_index);
+                let domain_ttl = self.add(path, domain.ttl);
+                let taken = [domain_ttl, zone_ttl]
+                    .into_iter()
+                    .chain(inherited.iter().rev().copied())
+                    .flatten()
+                    .next();
+                if let Some(setting) = taken.map(|index| &mut self.0[index]) {
+                    setting.used = true;
+                    setting.unproxied |= domain.proxied.or(zone.proxied).or(proxied) != Some(true);
+                }
+            }
+        }
+    }
+}
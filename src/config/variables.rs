@@ -0,0 +1,128 @@
+use super::models::{Config, Zone};
+use anyhow::{Context, Result};
+use std::{borrow::Cow, collections::BTreeMap};
+
+impl Config {
+    // Replace `{hostname}`, `{short_hostname}` and the custom `variables` in zone,
+    // domain and record names, so one file can be shared by a whole fleet
+    pub(crate) fn expand_variables(&mut self) -> Result<()> {
+        let uses_placeholders = self
+            .all_zones()
+            .flat_map(zone_names)
+            .any(|name| name.contains('{') || name.contains('}'));
+        if !uses_placeholders {
+            return Ok(());
+        }
+
+        let variables = self.placeholder_values()?;
+        let mut errors = expand_zones(&mut self.zones, "zones", &variables);
+        for (name, profile) in &mut self.profiles {
+            errors.extend(expand_zones(
+                &mut profile.zones,
+                &format!("profiles.{}.zones", name),
+                &variables,
+            ));
+        }
+
+        if !errors.is_empty() {
+            return Err(anyhow::anyhow!(
+                "Invalid placeholders:\n  {}",
+                errors.join("\n  ")
+            ));
+        }
+        Ok(())
+    }
+
+    fn all_zones(&self) -> impl Iterator<Item = &Zone> {
+        self.zones
+            .iter()
+            .chain(self.profiles.values().flat_map(|profile| &profile.zones))
+    }
+
+    fn placeholder_values(&self) -> Result<BTreeMap<String, String>> {
+        let hostname = hostname::get()
+            .context("Failed to read the hostname")?
+            .into_string()
+            .map_err(|_| anyhow::anyhow!("The hostname is not valid UTF-8"))?
+            .to_lowercase();
+        let short_hostname = hostname.split('.').next().unwrap_or_default().to_string();
+
+        let mut values = BTreeMap::from([
+            ("hostname".to_string(), hostname),
+            ("short_hostname".to_string(), short_hostname),
+        ]);
+        // Custom variables win, which also allows pinning the hostname
+        values.extend(self.variables.clone());
+        Ok(values)
+    }
+}
+
+fn zone_names(zone: &Zone) -> impl Iterator<Item = &str> {
+    zone.name
+        .as_deref()
+        .into_iter()
+        .chain(zone.domains.iter().flat_map(|domain| {
+            std::iter::once(domain.name.as_ref()).chain(domain.records.iter().map(AsRef::as_ref))
+        }))
+}
+
+fn expand_zones(
+    zones: &mut [Zone],
+    prefix: &str,
+    variables: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut errors = Vec::new();
+    let mut expand_at = |name: &mut Cow<'static, str>, path: String| match expand(name, variables) {
+        Ok(Some(expanded)) => *name = Cow::Owned(expanded),
+        Ok(None) => {}
+        Err(e) => errors.push(format!("{}: {}", path, e)),
+    };
+
+    for (zone_index, zone) in zones.iter_mut().enumerate() {
+        if let Some(name) = &mut zone.name {
+            expand_at(name, format!("{}[{}].name", prefix, zone_index));
+        }
+        for (domain_index, domain) in zone.domains.iter_mut().enumerate() {
+            let path = format!("{}[{}].domains[{}]", prefix, zone_index, domain_index);
+            expand_at(&mut domain.name, format!("{}.name", path));
+            for (record_index, record) in domain.records.iter_mut().enumerate() {
+                expand_at(record, format!("{}.records[{}]", path, record_index));
+            }
+        }
+    }
+
+    errors
+}
+
+// The expanded name, or None when it has no placeholder
+fn expand(name: &str, variables: &BTreeMap<String, String>) -> Result<Option<String>, String> {
+    if !name.contains('{') && !name.contains('}') {
+        return Ok(None);
+    }
+
+    let mut expanded = String::new();
+    let mut rest = name;
+    while let Some(start) = rest.find(['{', '}']) {
+        let (before, after) = rest.split_at(start);
+        expanded.push_str(before);
+
+        let (placeholder, remaining) = after[1..]
+            .split_once('}')
+            .filter(|_| after.starts_with('{'))
+            .ok_or_else(|| format!("unbalanced braces in `{}`", name))?;
+        let value = variables.get(placeholder).ok_or_else(|| {
+            let known: Vec<&str> = variables.keys().map(String::as_str).collect();
+            format!(
+                "unknown placeholder `{{{}}}`, expected one of: {}",
+                placeholder,
+                known.join(", ")
+            )
+        })?;
+
+        expanded.push_str(value);
+        rest = remaining;
+    }
+    expanded.push_str(rest);
+
+    Ok(Some(expanded))
+}
@@ -0,0 +1,16 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// What to do once cycles keep failing, rather than logging the same error forever
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Watchdog {
+    /// Failed cycles in a row that trip the watchdog and send a `watchdog_tripped` notification
+    #[validate(range(min = 1, message = "Watchdog failures must be greater than 0"))]
+    pub failures: u32,
+
+    /// Exit with code 9 once tripped, so a service manager restarts the daemon
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exit: bool,
+}
@@ -0,0 +1,155 @@
+use super::models::Config;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveTime, TimeZone};
+use schemars::{json_schema, JsonSchema, Schema, SchemaGenerator};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, fmt};
+use validator::{Validate, ValidationError};
+
+/// A daily period, in local time, during which records are not updated and nothing is notified.
+/// Changes detected meanwhile are applied as soon as it ends.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Validate, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[validate(schema(function = "validate_window"))]
+pub struct MaintenanceWindow {
+    /// Start of the window, `HH:MM`
+    #[schemars(example = &"03:00")]
+    pub start: TimeOfDay,
+
+    /// End of the window, `HH:MM`. Earlier than `start` for a window spanning midnight
+    #[schemars(example = &"05:00")]
+    pub end: TimeOfDay,
+
+    /// Days the window starts on, every day when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[schemars(example = example_days())]
+    pub days: Vec<Weekday>,
+
+    /// Keep updating records and only hold back notifications
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub notifications_only: bool,
+}
+
+fn example_days() -> [Weekday; 2] {
+    [Weekday::Sat, Weekday::Sun]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct TimeOfDay(pub(super) NaiveTime);
+
+impl TryFrom<String> for TimeOfDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        NaiveTime::parse_from_str(&value, "%H:%M")
+            .map(TimeOfDay)
+            .map_err(|_| format!("Invalid time `{}`, expected HH:MM", value))
+    }
+}
+
+impl From<TimeOfDay> for String {
+    fn from(time: TimeOfDay) -> Self {
+        time.to_string()
+    }
+}
+
+impl fmt::Display for TimeOfDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.format("%H:%M"))
+    }
+}
+
+impl JsonSchema for TimeOfDay {
+    fn schema_name() -> Cow<'static, str> {
+        "TimeOfDay".into()
+    }
+
+    fn json_schema(_generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "type": "string",
+            "pattern": "^([01][0-9]|2[0-3]):[0-5][0-9]$"
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl From<chrono::Weekday> for Weekday {
+    fn from(day: chrono::Weekday) -> Self {
+        match day {
+            chrono::Weekday::Mon => Weekday::Mon,
+            chrono::Weekday::Tue => Weekday::Tue,
+            chrono::Weekday::Wed => Weekday::Wed,
+            chrono::Weekday::Thu => Weekday::Thu,
+            chrono::Weekday::Fri => Weekday::Fri,
+            chrono::Weekday::Sat => Weekday::Sat,
+            chrono::Weekday::Sun => Weekday::Sun,
+        }
+    }
+}
+
+fn validate_window(window: &MaintenanceWindow) -> Result<(), ValidationError> {
+    if window.start == window.end {
+        return Err(ValidationError::new("empty_window")
+            .with_message("start and end of a maintenance window must differ".into()));
+    }
+    Ok(())
+}
+
+impl MaintenanceWindow {
+    fn starts_on(&self, date: DateTime<Local>) -> bool {
+        self.days.is_empty() || self.days.contains(&date.weekday().into())
+    }
+
+    // When the window ends, if `now` falls inside it
+    fn end_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let time = now.time();
+        let (start, end) = (self.start.0, self.end.0);
+
+        let end_day = if start < end {
+            (self.starts_on(now) && start <= time && time < end).then_some(now)
+        } else if time >= start {
+            self.starts_on(now).then(|| now + Duration::days(1))
+        } else {
+            let yesterday = now - Duration::days(1);
+            (time < end && self.starts_on(yesterday)).then_some(now)
+        }?;
+
+        let end = end_day.date_naive().and_time(end);
+        // A DST gap swallowing the end time just moves it an hour later
+        Local.from_local_datetime(&end).earliest().or_else(|| {
+            Local
+                .from_local_datetime(&(end + Duration::hours(1)))
+                .earliest()
+        })
+    }
+}
+
+impl Config {
+    // End of the maintenance window holding back updates `now` falls in, the latest one if
+    // several overlap
+    pub(crate) fn maintenance_end(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        self.maintenance_windows
+            .iter()
+            .filter(|window| !window.notifications_only)
+            .filter_map(|window| window.end_after(now))
+            .max()
+    }
+
+    // Every window silences notifications, updates applied when it ends are notified then
+    pub(crate) fn notifications_muted(&self, now: DateTime<Local>) -> bool {
+        self.maintenance_windows
+            .iter()
+            .any(|window| window.end_after(now).is_some())
+    }
+}
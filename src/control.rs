@@ -0,0 +1,131 @@
+use crate::ddns::Control;
+use crate::snapshot::Snapshot;
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tokio::{
+    sync::{mpsc::UnboundedSender, watch},
+    task::JoinHandle,
+};
+
+// Longest request line accepted, every command is a single short word
+#[cfg(unix)]
+const MAX_REQUEST: u64 = 64;
+
+// Lets local tools steer the daemon through `clouddns ctl`, removed when dropped
+pub struct ControlSocket {
+    path: PathBuf,
+    task: JoinHandle<()>,
+}
+
+impl ControlSocket {
+    #[cfg(unix)]
+    pub async fn start(
+        path: &Path,
+        control: UnboundedSender<Control>,
+        snapshot: watch::Receiver<Snapshot>,
+    ) -> Result<Self> {
+        use anyhow::{bail, Context};
+        use log::{debug, info};
+        use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+        use tokio::net::UnixListener;
+
+        // A socket left behind by a crash is replaced, one still answering belongs to another
+        // instance
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                bail!("{} exists and is not a socket", path.display());
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                bail!("{} is in use by another instance", path.display());
+            }
+            std::fs::remove_file(path)
+                .with_context(|| format!("Failed to remove the stale socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Failed to create the control socket {}", path.display()))?;
+        // Anyone able to connect can trigger updates
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict access to {}", path.display()))?;
+        info!("Listening for control requests on {}", path.display());
+
+        let task = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let control = control.clone();
+                        let snapshot = snapshot.clone();
+                        tokio::spawn(async move {
+                            if let Err(e) = serve(stream, control, snapshot).await {
+                                debug!("Control connection failed: {}", e);
+                            }
+                        });
+                    }
+                    Err(e) => debug!("Failed to accept a control connection: {}", e),
+                }
+            }
+        });
+        Ok(Self {
+            path: path.to_path_buf(),
+            task,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub async fn start(
+        _path: &Path,
+        _control: UnboundedSender<Control>,
+        _snapshot: watch::Receiver<Snapshot>,
+    ) -> Result<Self> {
+        anyhow::bail!("Control sockets are not supported on this platform, use the admin API")
+    }
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        self.task.abort();
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// One request per connection: a command on a line, answered with `accepted`, the status as JSON,
+// or `error: ` and the reason
+#[cfg(unix)]
+async fn serve(
+    stream: tokio::net::UnixStream,
+    control: UnboundedSender<Control>,
+    snapshot: watch::Receiver<Snapshot>,
+) -> std::io::Result<()> {
+    use crate::snapshot::Report;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+
+    let (reader, mut writer) = stream.into_split();
+    let mut line = String::new();
+    BufReader::new(reader.take(MAX_REQUEST))
+        .read_line(&mut line)
+        .await?;
+
+    let request = match line.trim() {
+        "status" => {
+            let report = Report::from(snapshot.borrow().clone());
+            let mut response = serde_json::to_string(&report)?;
+            response.push('\n');
+            writer.write_all(response.as_bytes()).await?;
+            return writer.shutdown().await;
+        }
+        "update" => Some(Control::UpdateNow),
+        "force-update" => Some(Control::ForceUpdate),
+        "pause" => Some(Control::Pause),
+        "resume" => Some(Control::Resume),
+        "reload" => Some(Control::Reload),
+        _ => None,
+    };
+    let response = match request {
+        Some(request) => match control.send(request) {
+            Ok(()) => "accepted\n".to_string(),
+            Err(_) => "error: shutting down\n".to_string(),
+        },
+        None => format!("error: unknown command `{}`\n", line.trim()),
+    };
+    writer.write_all(response.as_bytes()).await?;
+    writer.shutdown().await
+}
@@ -0,0 +1,23 @@
+// IDs tying together what one cycle logs, traces and notifies, and each API request with its
+// answer, e.g. to find the lines of a failed update or quote a request to Cloudflare's support
+
+use std::future::Future;
+
+tokio::task_local! {
+    static CYCLE: String;
+}
+
+// 16 hex digits, enough to tell cycles and requests apart in a few days of logs
+pub fn new_id() -> String {
+    format!("{:016x}", fastrand::u64(..))
+}
+
+// Runs a cycle under an ID of its own
+pub async fn cycle<F: Future>(future: F) -> F::Output {
+    CYCLE.scope(new_id(), future).await
+}
+
+// The ID of the cycle under way in this task, if any
+pub fn current() -> Option<String> {
+    CYCLE.try_with(String::clone).ok()
+}
@@ -0,0 +1,84 @@
+// Tells the notification channels and the on_failure hook that the daemon is going down, so that a
+// crash doesn't go unnoticed until someone finds the records stale: the daemon panicking, and the
+// error `run` returns with. Tasks it spawns may panic without stopping it, those aren't reported.
+
+use crate::config::{Config, Hooks};
+use crate::notify::{Event, Notifier};
+use crate::{hooks, http};
+use anyhow::{Context, Result};
+use log::warn;
+use std::{
+    cell::RefCell,
+    panic,
+    sync::{Arc, Once},
+    time::Duration,
+};
+
+// The longest the channels may take, the hook having its own timeout
+const CRASH_TIMEOUT: Duration = Duration::from_secs(10);
+
+static PANIC_HOOK: Once = Once::new();
+
+thread_local! {
+    // "panicked at src/ddns.rs:12:5: message" for the last panic on this thread, which the payload
+    // caught doesn't tell where it happened
+    static LAST_PANIC: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub(crate) struct Reporter {
+    notifier: Notifier,
+    hooks: Hooks,
+}
+
+impl Reporter {
+    pub async fn new(config: &Config) -> Result<Arc<Self>> {
+        let client = http::builder(config)
+            .await?
+            .build()
+            .context("Failed to set up the HTTP client")?;
+        let notifier = Notifier::new(
+            &config.notifications,
+            &config.retry,
+            config.provider.r#type,
+            client,
+        )
+        .await?;
+        Ok(Arc::new(Self {
+            notifier,
+            hooks: config.hooks.clone(),
+        }))
+    }
+
+    // Maintenance windows don't hold a crash back, nothing would tell of it once they're over
+    pub async fn report(&self, error: &str) {
+        let events = [Event::Crashed {
+            error: error.to_string(),
+        }];
+        let notified = tokio::time::timeout(CRASH_TIMEOUT, self.notifier.send(&events));
+        let (notified, ()) = tokio::join!(notified, hooks::run(&self.hooks, &events));
+        if notified.is_err() {
+            warn!("Timed out notifying of the crash");
+        }
+    }
+}
+
+// Keeps where panics happen from now on, for `last_panic`. The hook is set once, on top of the
+// ones already there.
+pub(crate) fn record_panics() {
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            // On one line
+            let error = info.to_string().replace('\n', " ");
+            LAST_PANIC.with(|last| *last.borrow_mut() = Some(error));
+        }));
+    });
+}
+
+// The panic just caught on this thread
+pub(crate) fn last_panic() -> String {
+    LAST_PANIC
+        .with(|last| last.borrow_mut().take())
+        .unwrap_or_else(|| "panicked".to_string())
+}
@@ -1,112 +1,2054 @@
-use crate::api::{CloudflareClient, DnsApiClient};
-use crate::config::Config;
-use anyhow::{Context, Result};
-use log::{error, info};
-use serde::Deserialize;
-use std::{fs::File, future::Future, io::Read, net::Ipv4Addr, str::FromStr};
+use crate::acme_dns::AcmeDnsServer;
+use crate::admin::{AdminServer, Tokens};
+use crate::api::{self, DnsApiClient, Limiter, RecordContent};
+use crate::breaker::Breakers;
+use crate::clock::{Clock, SystemClock};
+use crate::config::{self, Config, IpSource, RecordOrder, RecordType};
+use crate::control::ControlSocket;
+use crate::correlation;
+use crate::crash;
+use crate::discovery::{self, DiscoveredHost, Discovery};
+use crate::dns::Precheck;
+use crate::error::{DdnsError, ErrorKind, NotFound, ResultExt, Unreachable};
+use crate::events::{self, DdnsEvent};
+use crate::flapping::Flaps;
+use crate::health::HealthServer;
+use crate::hooks;
+use crate::http;
+use crate::ip::{self, Detector, HttpDetector};
+use crate::leader::Leader;
+use crate::lifecycle::{DdnsHooks, RecordChange};
+use crate::lock::InstanceLock;
+use crate::logging;
+use crate::metrics::{self, Metrics, PrometheusMetrics, StatsdMetrics, Tee};
+use crate::mqtt::MqttPublisher;
+use crate::network;
+use crate::notify::{Event, Notifier};
+use crate::plan::{Action, DesiredRecord, UpdatePlanner};
+use crate::scheduler::{DomainKey, Requests, Scheduler};
+use crate::secrets;
+use crate::sentry::{self, Sentry};
+use crate::signals::SignalListener;
+use crate::snapshot::{RecordState, RecordStatus, Snapshot};
+use crate::state::{self, ChangeReason, HistoryEntry, HistoryQuery, State, StateStore, Trigger};
+use crate::status_file::StatusFile;
+use crate::watcher::{self, ConfigWatcher};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, Utc};
+use futures::stream::{FuturesUnordered, StreamExt};
+use futures::FutureExt;
+use log::{debug, error, info, warn};
+use secrecy::{ExposeSecret, SecretString};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    future::Future,
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    path::PathBuf,
+    sync::Arc,
+};
 use tokio::signal;
-use tokio::time::{sleep, Duration};
-use validator::Validate;
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, UnboundedReceiver},
+    watch, Semaphore,
+};
+use tokio::time::{sleep_until, Duration, Instant};
+use tokio_util::sync::CancellationToken;
+use tracing::Instrument;
 
-const IP_CHECK_URL: &str = "https://api64.ipify.org?format=json";
+// Circuit breaker name of the DNS provider, IP sources go by their URL
+const PROVIDER: &str = "The Cloudflare API";
 
-// Establish the structure of the Domain as it pertains to the Cloudflare API
+// Gauges of each record, dropped with it
+const RECORD_METRICS: [&str; 3] = [
+    "clouddns_record_last_success_timestamp_seconds",
+    "clouddns_record_last_change_timestamp_seconds",
+    "clouddns_record_status",
+];
 
-#[derive(Debug, Deserialize)]
-struct TraceResponse {
-    ip: String,
+/// Requests from an interactive front end, the admin API or the control socket, handled between
+/// checks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    UpdateNow,
+    ForceUpdate,
+    Pause,
+    Resume,
+    Reload,
+    /// Use this address for its family in the next check rather than detecting it, e.g. one a
+    /// router pushed. Doesn't start a check by itself.
+    PushAddress(IpAddr),
 }
 
+/// The updater: detects the addresses and keeps the configured records pointed at them, once or
+/// on a schedule
 pub struct CloudflareDdns {
+    // None when built from a configuration in memory, which can't be reloaded
+    config_file: Option<String>,
+    profile: Option<String>,
     config: Config,
+    // Resolved from config.api_token, which may point at a secret backend
+    api_token: SecretString,
+    // Those of the tenants, for their zones
+    tenant_tokens: BTreeMap<String, SecretString>,
     api_client: Box<dyn DnsApiClient>,
-    current_ip: Option<Ipv4Addr>,
+    // Given to the builder, kept as is when the token or the settings change
+    provided_api_client: bool,
+    detector: Box<dyn Detector>,
+    clock: Box<dyn Clock>,
+    // Outlives the clients, a new token doesn't reset the allowance
+    limiter: Limiter,
+    // Last address seen from each source and the history of each record, kept across reloads
+    // and, with a state store, across restarts
+    state: State,
+    state_store: Option<Box<dyn StateStore>>,
+    // Set until the first cycle after loading a state file
+    resumed: bool,
+    // Print what would change instead of writing it
+    dry_run: bool,
+    // Addresses given on the command line, used instead of detection for their family
+    ip_override: HashMap<RecordType, IpAddr>,
+    // Addresses pushed through the admin API, used by the next check instead of detecting them
+    pushed: HashMap<RecordType, IpAddr>,
+    // What started the cycle under way, kept with the changes it makes
+    trigger: Trigger,
+    // Domains with changes held back by a maintenance window
+    deferred: HashSet<DomainKey>,
+    // Domains with a record that failed in the last cycle, retried on their own
+    failed: HashSet<DomainKey>,
+    // Endpoints that keep failing, skipped for a while
+    breakers: Breakers,
+    // Cycles that failed since the last one that didn't, for the watchdog
+    failed_cycles: u32,
+    // Recent changes of each record, for flapping
+    flaps: Flaps,
+    // Cycles run since the last summary, and when that was sent
+    checks: u32,
+    last_summary: DateTime<Local>,
+    // Probes in a row that found no network, cycles are skipped meanwhile
+    offline: u32,
+    notifier: Notifier,
+    // Told about panics and cycles that keep failing
+    sentry: Option<Arc<Sentry>>,
+    // Notifies of a crash of the daemon, while it runs
+    crashes: Option<Arc<crash::Reporter>>,
+    // The lease of the instance allowed to write, when several run for redundancy
+    leader: Option<Leader>,
+    // Looks records up in DNS before asking the API, when configured
+    precheck: Option<Precheck>,
+    // Told about the records of their tenant on top of the notifier, for the tenants with channels
+    tenant_notifiers: BTreeMap<String, Notifier>,
+    // Sources of hostnames to manage besides the configured ones, while running
+    discovery: Option<Discovery>,
+    // Every hostname they last asked for
+    discovered_hosts: Vec<DiscoveredHost>,
+    // Those added to the configured zones as domains of their own, whose records are created
+    // when missing
+    discovered: HashSet<String>,
+    _lock: Option<InstanceLock>,
+    control: Option<UnboundedReceiver<Control>>,
+    snapshot: Option<watch::Sender<Snapshot>>,
+    // Rewritten from the snapshot after every cycle
+    status_file: Option<StatusFile>,
+    events: broadcast::Sender<DdnsEvent>,
+    hooks: Vec<Box<dyn DdnsHooks>>,
+    metrics: Arc<dyn Metrics>,
+    // The built-in registry served on `/metrics`, unless metrics go to the embedder's own
+    prometheus: Option<PrometheusMetrics>,
+    // Sent everything on top of the registry or the embedder's metrics
+    statsd: Option<Arc<StatsdMetrics>>,
+    // Cancelled once the updater is asked to stop, by the shutdown future given to `run` or by
+    // the host application
+    cancel: CancellationToken,
 }
 
-impl CloudflareDdns {
-    pub async fn new(config_file: &str) -> Result<Self> {
-        let config = Self::load_config(config_file)?;
+/// Puts an updater together from a configuration in memory, for embedders and tests bringing their
+/// own DNS provider, address source or clock. Nothing is read from disk but what the configuration
+/// points at, e.g. the API token or CA certificates.
+pub struct CloudflareDdnsBuilder {
+    config: Config,
+    api_client: Option<Box<dyn DnsApiClient>>,
+    detector: Box<dyn Detector>,
+    clock: Box<dyn Clock>,
+}
 
-        if let Err(e) = config.validate() {
-            return Err(anyhow::anyhow!("Invalid configuration: {}", &e));
-        }
+impl CloudflareDdnsBuilder {
+    /// Used instead of Cloudflare's API, the API token is then never read
+    pub fn api_client(mut self, api_client: Box<dyn DnsApiClient>) -> Self {
+        self.api_client = Some(api_client);
+        self
+    }
+
+    /// Used instead of the configured address sources
+    pub fn detector(mut self, detector: Box<dyn Detector>) -> Self {
+        self.detector = detector;
+        self
+    }
+
+    /// Used instead of the system's clock
+    pub fn clock(mut self, clock: Box<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Checks the configuration, then sets up the API and notification clients
+    pub async fn build(self) -> Result<CloudflareDdns, DdnsError> {
+        let mut config = self.config;
+        // As loading a file would, zones given by name then have it for an ID
+        config.normalize_names();
+        config::validate(&config).kind(ErrorKind::Config)?;
+        set_log_target(config.log_target);
+        let api_token = match self.api_client {
+            Some(_) => SecretString::default(),
+            None => secrets::resolve(&config.api_token)
+                .await
+                .kind(ErrorKind::Auth)?,
+        };
+        let tenant_tokens = match self.api_client {
+            Some(_) => BTreeMap::new(),
+            None => api::tenant_tokens(&config).await.kind(ErrorKind::Auth)?,
+        };
+        // Before the API client, which takes the shared HTTP client as it is
+        http::configure(&config).await?;
+        let limiter = Limiter::new(config.api_rate_limit());
+        let provided_api_client = self.api_client.is_some();
+        let api_client = match self.api_client {
+            Some(api_client) => api_client,
+            None => api::with_tenants(&config, &api_token, &tenant_tokens, limiter.clone())?,
+        };
+        let notifier = Notifier::new(
+            &config.notifications,
+            &config.retry,
+            config.provider.r#type,
+            http::client(),
+        )
+        .await?;
+        let tenant_notifiers = tenant_notifiers(&config, http::client()).await?;
+        let sentry = Sentry::new(&config).await.kind(ErrorKind::Config)?;
+        sentry::report_panics(sentry.clone());
+        let precheck = Precheck::new(&config.dns);
+        let last_summary = self.clock.now();
+        let prometheus = PrometheusMetrics::new();
+        let statsd = match &config.metrics.statsd {
+            Some(statsd) => Some(Arc::new(
+                StatsdMetrics::connect(statsd)
+                    .await
+                    .kind(ErrorKind::Config)?,
+            )),
+            None => None,
+        };
 
-        let api_client = Box::new(CloudflareClient::new(&config.api_token));
+        let metrics = with_statsd(Arc::new(prometheus.clone()), &statsd);
+        metrics::build_info(&*metrics);
 
-        Ok(Self {
+        Ok(CloudflareDdns {
+            config_file: None,
+            profile: None,
             config,
+            api_token,
+            tenant_tokens,
             api_client,
-            current_ip: None,
+            provided_api_client,
+            detector: self.detector,
+            clock: self.clock,
+            limiter,
+            state: State::default(),
+            state_store: None,
+            resumed: false,
+            dry_run: false,
+            ip_override: HashMap::new(),
+            pushed: HashMap::new(),
+            trigger: Trigger::default(),
+            deferred: HashSet::new(),
+            failed: HashSet::new(),
+            breakers: Breakers::default(),
+            failed_cycles: 0,
+            flaps: Flaps::default(),
+            checks: 0,
+            last_summary,
+            offline: 0,
+            notifier,
+            sentry,
+            crashes: None,
+            leader: None,
+            precheck,
+            tenant_notifiers,
+            discovery: None,
+            discovered_hosts: Vec::new(),
+            discovered: HashSet::new(),
+            _lock: None,
+            control: None,
+            snapshot: None,
+            status_file: None,
+            events: broadcast::channel(events::CAPACITY).0,
+            hooks: Vec::new(),
+            metrics,
+            prometheus: Some(prometheus),
+            statsd,
+            cancel: CancellationToken::new(),
         })
     }
+}
+
+impl CloudflareDdns {
+    /// Loads the configuration, with the profile if given, and reads the API token
+    pub async fn new(config_file: &str, profile: Option<&str>) -> Result<Self, DdnsError> {
+        let config = Self::load_config(config_file, profile)?;
+        let mut ddns = Self::builder(config).build().await?;
+        ddns.config_file = Some(config_file.to_string());
+        ddns.profile = profile.map(str::to_string);
+        Ok(ddns)
+    }
+
+    /// Starts from a configuration already in memory, the parts the updater talks to can then be
+    /// replaced
+    pub fn builder(config: Config) -> CloudflareDdnsBuilder {
+        CloudflareDdnsBuilder {
+            config,
+            api_client: None,
+            detector: Box::new(HttpDetector),
+            clock: Box::new(SystemClock),
+        }
+    }
+
+    /// Reads the records but never updates them
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        if dry_run {
+            warn!("Dry run, records are read but never updated");
+        }
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Uses these addresses instead of detecting them, one per family at most
+    pub fn ip_override(mut self, addresses: &[IpAddr]) -> Result<Self, DdnsError> {
+        for &ip in addresses {
+            let record_type = match ip {
+                IpAddr::V4(_) => RecordType::A,
+                IpAddr::V6(_) => RecordType::Aaaa,
+            };
+            if let Some(previous) = self.ip_override.insert(record_type, ip) {
+                return Err(DdnsError::Other(anyhow::anyhow!(
+                    "Only one {} address can be given, got {} and {}",
+                    record_type,
+                    previous,
+                    ip
+                )));
+            }
+            warn!(
+                "Using {} for {} records instead of detecting it",
+                ip, record_type
+            );
+        }
+        Ok(self)
+    }
+
+    /// Fails if another instance manages the same configuration. Dry runs write nothing, so they
+    /// may run alongside the daemon
+    pub fn lock(mut self, lock_file: Option<&str>) -> Result<Self, DdnsError> {
+        if !self.dry_run {
+            let config_file = match (&self.config_file, lock_file) {
+                (None, None) => {
+                    return Err(DdnsError::Other(anyhow::anyhow!(
+                        "Without a configuration file, the lock file must be given"
+                    )))
+                }
+                (config_file, _) => config_file.as_deref().unwrap_or_default(),
+            };
+            self._lock = Some(InstanceLock::acquire(config_file, lock_file)?);
+        }
+        Ok(self)
+    }
 
-    fn load_config(config_file: &str) -> Result<Config> {
-        info!("Loading config from: {}", config_file);
-        let mut file = File::open(config_file)
-            .with_context(|| format!("Failed to open config file: {}", config_file))?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)
-            .with_context(|| format!("Failed to read config file: {}", config_file))?;
-        toml::from_str(&contents)
-            .with_context(|| format!("Failed to parse config file: {}", config_file))
+    /// Picks up the addresses and record history saved by a previous run, and saves them there,
+    /// in the store the configuration selects. `state_file` overrides the configured path, the
+    /// default one is named after the configuration file. Only kept in memory without either.
+    pub fn state(self, state_file: Option<&str>) -> Result<Self, DdnsError> {
+        let path = state_file
+            .map(PathBuf::from)
+            .or_else(|| self.config.state.path.clone());
+        match state::open(&self.config, path, self.config_file.as_deref())
+            .kind(ErrorKind::Config)?
+        {
+            Some(store) => Ok(self.state_store(store)),
+            None => Ok(self),
+        }
     }
 
-    // Using ipify to get the current IP address, seems to be the one with the least restrictions
-    async fn get_current_ip(&self) -> Result<Ipv4Addr, anyhow::Error> {
-        let response = reqwest::get(IP_CHECK_URL)
-            .await?
-            .json::<TraceResponse>()
-            .await?;
+    /// Keeps the state in a store of the host application's, loading what it holds
+    pub fn state_store(mut self, store: Box<dyn StateStore>) -> Self {
+        self.state = store.load().unwrap_or_else(|e| {
+            warn!("Ignoring the saved state: {:#}", e);
+            State::default()
+        });
+        self.resumed = !self.state.records.is_empty();
+        self.state_store = Some(store);
+        self
+    }
 
-        let ipv4_response = Ipv4Addr::from_str(&response.ip);
+    /// Takes requests from the host application
+    pub fn control(mut self, control: UnboundedReceiver<Control>) -> Self {
+        self.control = Some(control);
+        self
+    }
 
-        // Handle error if the response is empty
-        match ipv4_response {
-            Ok(ip) => Ok(ip),
-            Err(e) => Err(anyhow::anyhow!("Failed to parse IP address: {}", &e)),
+    /// Hooks into the run loop, see [`DdnsHooks`]. Hooks registered more than once all run, in
+    /// the order they were registered; any of them can veto.
+    pub fn hooks(mut self, hooks: Box<dyn DdnsHooks>) -> Self {
+        self.hooks.push(hooks);
+        self
+    }
+
+    /// Reports to the embedder's metrics system instead of the built-in registry, which the health
+    /// server then no longer serves on `/metrics`. A configured StatsD agent still gets them. See
+    /// [`Metrics`] for what is reported.
+    pub fn metrics(mut self, metrics: Arc<dyn Metrics>) -> Self {
+        self.metrics = with_statsd(metrics, &self.statsd);
+        metrics::build_info(&*self.metrics);
+        self.prometheus = None;
+        self
+    }
+
+    /// A stream of what the updater does from now on, see [`DdnsEvent`]. A receiver that falls
+    /// behind by more than 64 events misses the oldest, and is told how many with
+    /// `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<DdnsEvent> {
+        self.events.subscribe()
+    }
+
+    /// Stops the updater when cancelled, as the shutdown future given to [`CloudflareDdns::run`]
+    /// would: an update under way gets `shutdown_timeout` seconds to finish, anything else
+    /// stops right away. Once cancelled, the updater stays stopped.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancel.clone()
+    }
+
+    /// Publishes the detected addresses and record states after every change
+    pub fn snapshots(mut self, snapshot: watch::Sender<Snapshot>) -> Self {
+        self.snapshot = Some(snapshot);
+        self
+    }
+
+    fn publish(&self, update: impl FnOnce(&mut Snapshot)) {
+        if let Some(snapshot) = &self.snapshot {
+            snapshot.send_modify(update);
+        }
+    }
+
+    fn publish_record(
+        &self,
+        record: &str,
+        record_type: RecordType,
+        tenant: Option<&str>,
+        content: Option<&str>,
+        state: RecordState,
+    ) {
+        let mut labels = vec![("record", record), ("type", record_type.as_str())];
+        if let Some(tenant) = tenant {
+            labels.push(("tenant", tenant));
+        }
+        let now = self.clock.now().timestamp() as f64;
+        if matches!(state, RecordState::InSync | RecordState::Updated) {
+            self.metrics.gauge(
+                "clouddns_record_last_success_timestamp_seconds",
+                &labels,
+                now,
+            );
         }
+        if matches!(state, RecordState::Updated) {
+            self.metrics.gauge(
+                "clouddns_record_last_change_timestamp_seconds",
+                &labels,
+                now,
+            );
+        }
+        for name in RecordState::NAMES {
+            let mut labels = labels.clone();
+            labels.push(("status", name));
+            let value = if name == state.as_str() { 1.0 } else { 0.0 };
+            self.metrics.gauge("clouddns_record_status", &labels, value);
+        }
+
+        self.publish(|snapshot| {
+            snapshot.records.insert(
+                (record.to_string(), record_type.as_str()),
+                RecordStatus {
+                    tenant: tenant.map(str::to_string),
+                    content: content.map(str::to_string),
+                    state,
+                    checked: self.clock.now(),
+                },
+            );
+        });
     }
 
-    async fn update_all_records(&mut self) -> Result<(), anyhow::Error> {
-        let current_ip = self.get_current_ip().await?;
-        self.current_ip = Some(current_ip);
-        info!("Current IP: {}", &current_ip);
+    fn load_config(config_file: &str, profile: Option<&str>) -> Result<Config> {
+        let config = config::load(config_file, profile)
+            .and_then(|config| config::validate(&config).map(|()| config))
+            .kind(ErrorKind::Config)?;
+        for entry in config.disabled_entries() {
+            info!("Skipping disabled {}", entry);
+        }
+        Ok(config)
+    }
 
+    // Swap in a freshly loaded config, keeping the current one if the new file is invalid.
+    // Runtime state such as the last known IP is left untouched.
+    async fn reload_config(&mut self) -> Result<()> {
+        let Some(config_file) = self.config_file.clone() else {
+            bail!("The configuration wasn't loaded from a file, there is nothing to reload");
+        };
+        let config = Self::load_config(&config_file, self.profile.as_deref())?;
+        let (api_token, tenant_tokens) = match self.provided_api_client {
+            true => (SecretString::default(), BTreeMap::new()),
+            false => (
+                secrets::resolve(&config.api_token).await?,
+                api::tenant_tokens(&config).await?,
+            ),
+        };
+        let client = match http::changed(&self.config, &config) {
+            true => Some(http::build(&config).await?),
+            false => None,
+        };
+        let notifier = match config.notifications != self.config.notifications
+            || config.retry != self.config.retry
+            || config.provider.r#type != self.config.provider.r#type
+            || client.is_some()
+        {
+            true => {
+                let client = client.clone().unwrap_or_else(http::client);
+                Some(
+                    Notifier::new(
+                        &config.notifications,
+                        &config.retry,
+                        config.provider.r#type,
+                        client,
+                    )
+                    .await?,
+                )
+            }
+            false => None,
+        };
+        let tenant_notifiers = match config.tenants != self.config.tenants
+            || config.retry != self.config.retry
+            || config.provider.r#type != self.config.provider.r#type
+            || client.is_some()
+        {
+            true => {
+                let client = client.clone().unwrap_or_else(http::client);
+                Some(tenant_notifiers(&config, client).await?)
+            }
+            false => None,
+        };
+        let sentry = match config.sentry != self.config.sentry
+            || config.provider != self.config.provider
+            || client.is_some()
+        {
+            true => Some(Sentry::new(&config).await?),
+            false => None,
+        };
+        let crashes = match self.crashes.is_some()
+            && (config.notifications != self.config.notifications
+                || config.hooks != self.config.hooks
+                || config.retry != self.config.retry
+                || config.provider.r#type != self.config.provider.r#type
+                || client.is_some())
+        {
+            true => Some(crash::Reporter::new(&config).await?),
+            false => None,
+        };
+
+        set_log_target(config.log_target);
+        let retry_changed = config.retry != self.config.retry;
+        let rate_limit_changed = config.api_rate_limit() != self.config.api_rate_limit();
+        let zone_cache_changed = config.zone_cache_ttl != self.config.zone_cache_ttl;
+        let discovery_changed = config.discovery != self.config.discovery;
+        let tenants_changed = config.tenant_zones() != self.config.tenant_zones()
+            || !same_tokens(&tenant_tokens, &self.tenant_tokens);
+        self.config = config;
+        self.tenant_tokens = tenant_tokens;
+        if discovery_changed {
+            // The sources send what they find again, records of hosts gone meanwhile are deleted
+            // then. Without any source left, the records stay as they are.
+            self.discovery = Discovery::start(&self.config.discovery);
+            if self.discovery.is_none() {
+                self.discovered_hosts.clear();
+            }
+        }
+        self.discovered.clear();
+        self.merge_discovered();
+        self.forget_unmanaged();
+        let proxy_changed = client.is_some();
+        if let Some(client) = client {
+            info!("Proxy, CA certificates or DNS settings changed");
+            http::set_client(client);
+        }
+        if let Some(notifier) = notifier {
+            self.notifier = notifier;
+        }
+        if let Some(tenant_notifiers) = tenant_notifiers {
+            self.tenant_notifiers = tenant_notifiers;
+        }
+        if let Some(sentry) = sentry {
+            sentry::report_panics(sentry.clone());
+            self.sentry = sentry;
+        }
+        if let Some(crashes) = crashes {
+            self.crashes = Some(crashes);
+        }
+        self.precheck = Precheck::new(&self.config.dns);
+        if rate_limit_changed {
+            self.limiter = Limiter::new(self.config.api_rate_limit());
+        }
+        if retry_changed
+            || rate_limit_changed
+            || zone_cache_changed
+            || proxy_changed
+            || tenants_changed
+        {
+            self.rebuild_api_client();
+        }
+        self.set_api_token(api_token);
+        info!("Configuration reloaded from: {}", config_file);
+        Ok(())
+    }
+
+    // Forget records that are no longer managed, the others keep their last state
+    fn forget_unmanaged(&mut self) {
+        let managed = self.managed(|_| true);
+        let managed: HashSet<_> = managed
+            .into_iter()
+            .map(|(_, record, record_type)| (record, record_type.as_str()))
+            .collect();
+        self.publish(|snapshot| snapshot.records.retain(|key, _| managed.contains(key)));
+        let metrics = &self.metrics;
+        self.state.records.retain(|(record, record_type), _| {
+            let keep = managed.contains(&(record.clone(), record_type.as_str()));
+            if !keep {
+                let labels = [("record", record.as_str()), ("type", record_type.as_str())];
+                for name in RECORD_METRICS {
+                    metrics.remove(name, &labels);
+                }
+            }
+            keep
+        });
+    }
+
+    // The zone ID, name and type of every record of the enabled domains picked
+    fn managed(&self, pick: impl Fn(&str) -> bool) -> HashSet<(String, String, RecordType)> {
+        let mut managed = HashSet::new();
+        let config = &self.config;
+        for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+            let domains = zone.domains.iter().filter(|domain| domain.is_enabled());
+            for domain in domains.filter(|domain| pick(&domain.name)) {
+                for &record_type in config.settings(zone, domain).record_types.types() {
+                    for record in domain.record_names(zone) {
+                        managed.insert((zone.id.to_string(), record, record_type));
+                    }
+                }
+            }
+        }
+        managed
+    }
+
+    // Adds the discovered hostnames to the configured zones, in place of those added before
+    fn merge_discovered(&mut self) {
+        for zone in &mut self.config.zones {
+            zone.domains
+                .retain(|domain| !self.discovered.contains(domain.name.as_ref()));
+        }
+        let (added, outside) = discovery::merge(&mut self.config.zones, &self.discovered_hosts);
+        for hostname in outside {
+            let source = self
+                .discovered_hosts
+                .iter()
+                .find(|host| host.hostname == hostname)
+                .map(|host| host.source.as_str())
+                .unwrap_or_default();
+            warn!(
+                "{} of {} is in none of the named zones, its records aren't managed",
+                hostname, source
+            );
+        }
+        self.discovered = added.into_iter().collect();
+    }
+
+    // The hosts asked for changed: their domains are swapped in, and the records of those no
+    // longer asked for deleted unless they are kept
+    async fn discovered(&mut self, hosts: Vec<DiscoveredHost>, scheduler: &mut Scheduler) {
+        let before = self.managed(|domain| self.discovered.contains(domain));
+        let previous = self.discovered.clone();
+        self.discovered_hosts = hosts;
+        self.merge_discovered();
+        let after = self.managed(|domain| self.discovered.contains(domain));
+        for host in &self.discovered_hosts {
+            if self.discovered.contains(&host.hostname) && !previous.contains(&host.hostname) {
+                info!(
+                    "Managing the records of {} for {}",
+                    host.hostname, host.source
+                );
+            }
+        }
+        scheduler.sync(&self.config);
+
+        let mut removed: Vec<_> = before.difference(&after).cloned().collect();
+        removed.sort_by(|a, b| (&a.1, a.2.as_str()).cmp(&(&b.1, b.2.as_str())));
+        for (zone_id, record, record_type) in removed {
+            match self.config.discovery.keep_records {
+                true => info!(
+                    "Nothing asks for {} {} anymore, keeping it",
+                    record, record_type
+                ),
+                false => self.delete_discovered(&zone_id, &record, record_type).await,
+            }
+        }
+        self.forget_unmanaged();
+        self.save_state();
+    }
+
+    // Only a record clouddns created and still holding what it was last set to is deleted, one
+    // that was already there or was changed by someone else since is left alone
+    async fn delete_discovered(&mut self, zone_id: &str, record: &str, record_type: RecordType) {
+        let history = self.state.records.get(&(record.to_string(), record_type));
+        let Some(confirmed) = history.and_then(|history| history.content.clone()) else {
+            debug!("{} {} was never set, not deleting it", record, record_type);
+            return;
+        };
+        if !history.is_some_and(|history| history.created) {
+            info!(
+                "{} {} was there before clouddns managed it, not deleting it",
+                record, record_type
+            );
+            return;
+        }
+        if self.dry_run {
+            match self.snapshot.is_some() {
+                true => info!("Dry run, would delete {} {}", record, record_type),
+                false => println!("{} {} deleted", record, record_type),
+            }
+            return;
+        }
+        if !self.leads() {
+            debug!(
+                "Standing by, {} {} is left to the leader",
+                record, record_type
+            );
+            return;
+        }
+        if self.state.paused {
+            info!(
+                "Updates are paused, nothing asks for {} {} anymore but it is kept",
+                record, record_type
+            );
+            return;
+        }
+
+        let current = match self
+            .api_client
+            .get_record(zone_id, record, record_type.as_str())
+            .await
+        {
+            Ok(current) => current,
+            Err(e) if e.is::<NotFound>() => return,
+            Err(e) => {
+                warn!("Failed to delete {} {}: {:#}", record, record_type, e);
+                return;
+            }
+        };
+        if current.content != confirmed {
+            info!(
+                "{} {} was changed to {} since, not deleting it",
+                record, record_type, current.content
+            );
+            return;
+        }
+        let content = current.content.clone();
+        let action = Action::Delete { record: current };
+        match action.apply(&*self.api_client, zone_id).await {
+            Ok(_) => {
+                info!(
+                    record = record, record_type = record_type.as_str();
+                    "{} {} deleted, nothing asks for it anymore", record, record_type
+                );
+                self.save_history(&[HistoryEntry {
+                    time: self.clock.now().to_utc(),
+                    record: record.to_string(),
+                    r#type: record_type,
+                    previous: Some(content.clone()),
+                    content: None,
+                    reason: ChangeReason::Released,
+                    trigger: Trigger::Discovery,
+                    error: None,
+                }]);
+                self.emit(DdnsEvent::RecordDeleted {
+                    record: record.to_string(),
+                    record_type,
+                    content,
+                });
+            }
+            Err(e) => warn!("Failed to delete {} {}: {:#}", record, record_type, e),
+        }
+    }
+
+    // Whether the token changed
+    fn set_api_token(&mut self, api_token: SecretString) -> bool {
+        if api_token.expose_secret() == self.api_token.expose_secret() {
+            return false;
+        }
+        info!("API token changed, recreating API client");
+        self.api_token = api_token;
+        self.rebuild_api_client();
+        true
+    }
+
+    // The builder's client is left alone, it has its own settings
+    fn rebuild_api_client(&mut self) {
+        if self.provided_api_client {
+            return;
+        }
+        let limiter = self.limiter.clone();
+        match api::with_tenants(&self.config, &self.api_token, &self.tenant_tokens, limiter) {
+            Ok(api_client) => self.api_client = api_client,
+            Err(e) => warn!("{:#}, keeping the current API client", e),
+        }
+    }
+
+    // Re-fetch the token from its file or backend so rotated secrets are picked up. Whether the
+    // token changed.
+    async fn refresh_api_token(&mut self) -> bool {
+        match secrets::resolve(&self.config.api_token).await {
+            Ok(api_token) => self.set_api_token(api_token),
+            Err(e) => {
+                error!(
+                    "Failed to refresh API token, keeping the current one: {:#}",
+                    &e
+                );
+                false
+            }
+        }
+    }
+
+    // Minutes between scheduled re-reads of the token, there is nothing to re-read when it is
+    // written in the configuration or not used at all
+    fn token_refresh_interval(&self) -> Option<u64> {
+        let token = &self.config.api_token;
+        match token.is_inline() || self.provided_api_client {
+            true => None,
+            false => token
+                .refresh_interval()
+                .or(self.config.token_refresh_interval),
+        }
+    }
+
+    async fn reload(&mut self, watcher: &mut Option<ConfigWatcher>, scheduler: &mut Scheduler) {
+        let election = self.config.leader_election.clone();
+        if let Err(e) = self.reload_config().await {
+            error!(
+                "Failed to reload configuration, keeping the current one: {:#}",
+                &e
+            );
+            return;
+        }
+        scheduler.sync(&self.config);
+        if self.config.leader_election != election {
+            match self.start_leader().await {
+                Ok(()) => {
+                    self.check_lease().await;
+                }
+                Err(e) => error!("{:#}", e),
+            }
+        }
+
+        // watch_config itself may have been toggled by the reload
+        if self.config.watch_config != watcher.is_some() {
+            *watcher = self.start_watcher();
+        }
+    }
+
+    // Bound once at startup, a reload doesn't move the listener
+    async fn start_health(&mut self, scheduler: &Scheduler) -> Result<Option<HealthServer>> {
+        let Some(health) = self.config.health.clone() else {
+            return Ok(None);
+        };
+        let max_age = scheduler
+            .shortest_interval()
+            .unwrap_or(Duration::from_secs(config::DEFAULT_UPDATE_INTERVAL * 60))
+            * health.ready_intervals;
+        let snapshot = self
+            .snapshot
+            .get_or_insert_with(|| watch::channel(Snapshot::default()).0)
+            .subscribe();
+        HealthServer::start(health.listen, snapshot, max_age, self.prometheus.clone())
+            .await
+            .map(Some)
+    }
+
+    // Bound once at startup, with its own channel next to the one of `watch`
+    async fn start_admin(&mut self) -> Result<Option<(AdminServer, UnboundedReceiver<Control>)>> {
+        let Some(admin) = self.config.admin.clone() else {
+            return Ok(None);
+        };
+        let token = secrets::resolve(&admin.token)
+            .await
+            .context("Failed to read the admin API token")
+            .kind(ErrorKind::Auth)?;
+        let trigger_token = match &admin.trigger_token {
+            Some(trigger_token) => Some(
+                secrets::resolve(trigger_token)
+                    .await
+                    .context("Failed to read the trigger token")
+                    .kind(ErrorKind::Auth)?,
+            ),
+            None => None,
+        };
+        let (control, requests) = mpsc::unbounded_channel();
+        let snapshot = self
+            .snapshot
+            .get_or_insert_with(|| watch::channel(Snapshot::default()).0)
+            .subscribe();
+        let tokens = Tokens {
+            admin: token,
+            trigger: trigger_token,
+        };
+        let server = AdminServer::start(admin.listen, tokens, control, snapshot).await?;
+        Ok(Some((server, requests)))
+    }
+
+    // Started once like the admin API, with a provider client of its own answering from its task.
+    // The records go in the named zone the domain belongs to.
+    async fn start_acme_dns(&self) -> Result<Option<AcmeDnsServer>> {
+        let Some(acme_dns) = &self.config.acme_dns else {
+            return Ok(None);
+        };
+        if self.dry_run {
+            warn!("Dry run, the acme-dns API isn't served");
+            return Ok(None);
+        }
+        if self.provided_api_client {
+            warn!("The acme-dns API needs the configured provider, it isn't served");
+            return Ok(None);
+        }
+        let zone = self
+            .config
+            .zone_containing(&acme_dns.domain)
+            .with_context(|| {
+                format!(
+                    "acme-dns domain {} is in none of the named zones",
+                    acme_dns.domain
+                )
+            })
+            .kind(ErrorKind::Config)?;
+        let path = match (&acme_dns.accounts, &self.config_file) {
+            (Some(path), _) => path.clone(),
+            (None, Some(config_file)) => state::default_path(config_file, "acme-dns.json"),
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "Without a configuration file, the acme-dns accounts file must be given"
+                ))
+                .kind(ErrorKind::Config)
+            }
+        };
+        // The tenant's token when the zone is a tenant's
+        let api_token = zone
+            .tenant
+            .as_ref()
+            .and_then(|tenant| self.tenant_tokens.get(tenant))
+            .unwrap_or(&self.api_token);
+        let client = api::shared_from_config(&self.config, api_token, self.limiter.clone())?;
+        AcmeDnsServer::start(acme_dns, zone.id.to_string(), path, client)
+            .await
+            .map(Some)
+    }
+
+    // Created once at startup like the admin API, a reload doesn't move the socket
+    async fn start_control(
+        &mut self,
+    ) -> Result<Option<(ControlSocket, UnboundedReceiver<Control>)>> {
+        let Some(path) = self.config.control_socket.clone() else {
+            return Ok(None);
+        };
+        let (control, requests) = mpsc::unbounded_channel();
+        let snapshot = self
+            .snapshot
+            .get_or_insert_with(|| watch::channel(Snapshot::default()).0)
+            .subscribe();
+        let socket = ControlSocket::start(&path, control, snapshot).await?;
+        Ok(Some((socket, requests)))
+    }
+
+    // Connected once at startup like the health endpoint, reloads keep the same broker
+    async fn start_mqtt(&mut self) -> Result<Option<MqttPublisher>> {
+        let Some(mqtt) = self.config.mqtt.clone() else {
+            return Ok(None);
+        };
+        let snapshot = self
+            .snapshot
+            .get_or_insert_with(|| watch::channel(Snapshot::default()).0)
+            .subscribe();
+        MqttPublisher::start(&mqtt, snapshot).await.map(Some)
+    }
+
+    fn start_watcher(&self) -> Option<ConfigWatcher> {
+        if !self.config.watch_config {
+            return None;
+        }
+
+        let config_file = self.config_file.as_deref()?;
+        match ConfigWatcher::new(config_file) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                error!(
+                    "Failed to watch config file, automatic reload disabled: {:#}",
+                    &e
+                );
+                None
+            }
+        }
+    }
+
+    // The address of every source and family the due domains use, each asked once and all at once,
+    // so the A and AAAA records of a dual-stack name are updated in the same pass. A family whose
+    // address can't be detected is left out of the addresses, its error returned.
+    async fn detect_addresses(
+        &mut self,
+        due: &HashSet<DomainKey>,
+    ) -> (HashMap<(IpSource, RecordType), IpAddr>, Vec<anyhow::Error>) {
+        let mut sources = Vec::new();
         for zone in &self.config.zones {
             for domain in &zone.domains {
-                for record in &domain.records {
-                    let full_record = if record == "@" {
-                        domain.name.to_string()
-                    } else {
-                        format!("{}.{}", record, domain.name)
-                    };
+                let key = DomainKey {
+                    zone_id: zone.id.to_string(),
+                    domain: domain.name.to_string(),
+                };
+                if !due.contains(&key) {
+                    continue;
+                }
+                let settings = self.config.settings(zone, domain);
+                for &record_type in settings.record_types.types() {
+                    let source = (settings.ip_source.clone(), record_type);
+                    if !sources.contains(&source) {
+                        sources.push(source);
+                    }
+                }
+            }
+        }
+
+        let mut found = Vec::new();
+        let mut asked = Vec::new();
+        for source in sources {
+            let (ip_source, record_type) = (&source.0, source.1);
+            let endpoint = ip::source_url(ip_source, record_type);
+            let given = match self.ip_override.get(&record_type) {
+                Some(ip) => Some(("--ip", *ip)),
+                None => self.pushed.get(&record_type).map(|ip| ("push", *ip)),
+            };
+            match given {
+                Some((from, ip)) => found.push((source, from.to_string(), Ok(ip))),
+                None if !self.breakers.allows(endpoint, Instant::now()) => {
+                    let last_known = self.state.addresses.get(&source).copied();
+                    debug!("Skipping {}, using the last known address", endpoint);
+                    let last_known = last_known
+                        .ok_or_else(|| anyhow::anyhow!("{} is failing, skipped for now", endpoint));
+                    let from = ip_source.to_string();
+                    found.push((source, from, last_known));
+                }
+                None => asked.push(source),
+            }
+        }
+        let detections = asked.iter().map(|(ip_source, record_type)| {
+            self.detector
+                .detect(ip_source, *record_type, &self.config.retry)
+        });
+        let detected = futures::future::join_all(detections).await;
+        for (source, detected_ip) in asked.into_iter().zip(detected) {
+            let endpoint = ip::source_url(&source.0, source.1);
+            match detected_ip {
+                Ok(_) => self.breakers.success(endpoint),
+                Err(_) => self.breakers.failure(endpoint, Instant::now()),
+            }
+            let from = source.0.to_string();
+            found.push((source, from, detected_ip));
+        }
 
-                    info!("Updating record: {}", &full_record);
+        let mut addresses = HashMap::new();
+        let mut errors = Vec::new();
+        for (source, from, detected_ip) in found {
+            let record_type = source.1;
+            let address = (from.clone(), record_type.as_str());
+            let ip = match detected_ip {
+                Ok(ip) => ip,
+                Err(e) => {
+                    let message = format!("{:#}", e);
+                    self.publish(|snapshot| {
+                        snapshot.addresses.insert(address, Err(message));
+                    });
+                    errors.push(e);
+                    continue;
+                }
+            };
+            self.publish(|snapshot| {
+                snapshot.addresses.insert(address, Ok(ip));
+            });
+            info!("Current {} address from {}: {}", record_type, &from, &ip);
+            self.emit(DdnsEvent::IpDetected {
+                source: from,
+                record_type,
+                address: ip,
+            });
+            if !self.ip_override.contains_key(&record_type) {
+                self.state.addresses.insert(source.clone(), ip);
+            }
+            addresses.insert(source, ip);
+        }
+        (addresses, errors)
+    }
 
-                    let record = self.api_client.get_record(&zone.id, &full_record).await?;
+    // Without `write`, changes are only logged and the domains remembered for later
+    #[tracing::instrument(
+        name = "cycle",
+        skip_all,
+        fields(cycle.id = correlation::current(), domains = due.len(), force, write)
+    )]
+    async fn update_records(
+        &mut self,
+        due: &HashSet<DomainKey>,
+        force: bool,
+        write: bool,
+    ) -> Result<usize> {
+        self.failed.clear();
+        for hooks in &self.hooks {
+            if !hooks.before_cycle().await {
+                info!("Skipping this check, a hook vetoed it");
+                return Ok(0);
+            }
+        }
+        let (detected, detection_errors) = self.detect_addresses(due).await;
+        let mut jobs = Vec::new();
 
-                    if &record.content == &current_ip.to_string() {
-                        info!("Record already up to date");
+        for zone in &self.config.zones {
+            for domain in &zone.domains {
+                let key = DomainKey {
+                    zone_id: zone.id.to_string(),
+                    domain: domain.name.to_string(),
+                };
+                if !due.contains(&key) {
+                    continue;
+                }
+
+                let settings = self.config.settings(zone, domain);
+                for &record_type in settings.record_types.types() {
+                    let source = (settings.ip_source.clone(), record_type);
+                    // The other family of a dual-stack name still goes ahead, this one is retried
+                    let Some(&current_ip) = detected.get(&source) else {
+                        self.failed.insert(key.clone());
                         continue;
+                    };
+
+                    // Right after a restart, records confirmed within their interval are
+                    // trusted rather than fetched again
+                    let confirmed_since = self.clock.now().with_timezone(&Utc)
+                        - chrono::Duration::minutes(settings.update_interval as i64);
+                    for record in domain.record_names(zone) {
+                        let content = current_ip.to_string();
+                        if self.resumed
+                            && !force
+                            && self.state.confirmed_since(
+                                &record,
+                                record_type,
+                                &content,
+                                confirmed_since,
+                            )
+                        {
+                            info!(
+                                "{} {} confirmed recently, not checking it again yet",
+                                &record, record_type
+                            );
+                            self.publish_record(
+                                &record,
+                                record_type,
+                                zone.tenant.as_deref(),
+                                Some(&content),
+                                RecordState::InSync,
+                            );
+                            continue;
+                        }
+                        jobs.push(RecordJob {
+                            key: key.clone(),
+                            order: self.config.record_order(zone),
+                            record,
+                            record_type,
+                            ip: current_ip,
+                            ttl: settings.ttl,
+                            proxied: settings.proxied,
+                            create: self.discovered.contains(domain.name.as_ref()),
+                            tenant: zone.tenant.clone(),
+                        });
                     }
+                }
+            }
+        }
+
+        self.pushed.clear();
+        let jobs = self.precheck(jobs, force).await;
 
-                    match self
-                        .api_client
-                        .update_record(&zone.id, &record, &current_ip, self.config.record_ttl)
-                        .await
-                    {
-                        Ok(_) => info!("Record updated successfully"),
-                        Err(e) => {
-                            error!("Failed to update record: {}", &e);
-                            return Err(e);
+        // A cycle that wrote nothing because of an outage failed, however quiet it was
+        if !jobs.is_empty() && !self.breakers.allows(PROVIDER, Instant::now()) {
+            debug!("Skipping {} record(s), the API is failing", jobs.len());
+            return match detection_errors.into_iter().next() {
+                Some(e) => Err(e).kind(ErrorKind::Detection),
+                None => Err(anyhow::anyhow!(
+                    "API circuit open, skipped {} record(s)",
+                    jobs.len()
+                ))
+                .kind(ErrorKind::Update),
+            };
+        }
+        let outcomes = self
+            .check_records(&jobs, force, write && !self.dry_run)
+            .await;
+
+        // Records that were (or in a dry run would have been) updated
+        let mut changed = 0;
+        let mut failures = Vec::new();
+        let mut events = Vec::new();
+        let mut history = Vec::new();
+        for (job, outcome) in jobs.iter().zip(outcomes) {
+            let (record, record_type) = (&job.record, job.record_type);
+            let (tenant, shown) = (job.tenant.as_deref(), job.shown());
+            match outcome {
+                Outcome::InSync(content) => {
+                    info!(
+                        record = record.as_str(), record_type = record_type.as_str(), tenant;
+                        "{} {} already up to date", shown, record_type
+                    );
+                    self.publish_record(
+                        record,
+                        record_type,
+                        tenant,
+                        Some(&content),
+                        RecordState::InSync,
+                    );
+                    self.record_succeeded(record, record_type, content);
+                }
+                Outcome::Updated(previous) => {
+                    changed += 1;
+                    let labels = job.labels();
+                    self.metrics
+                        .counter("clouddns_records_updated_total", &labels, 1);
+                    self.emit(DdnsEvent::RecordUpdated {
+                        record: record.clone(),
+                        record_type,
+                        address: job.ip,
+                        previous: previous.clone(),
+                    });
+                    let event = self.change_event(job, previous.clone());
+                    let reason = change_reason(event.as_ref());
+                    history.push(self.history_entry(job, Some(previous), reason));
+                    if let Some(event) = event {
+                        if let Event::DriftDetected { previous, .. } = &event {
+                            self.emit(DdnsEvent::DriftDetected {
+                                record: record.clone(),
+                                record_type,
+                                address: job.ip,
+                                previous: previous.clone(),
+                            });
                         }
+                        events.push(event);
                     }
+                    info!(
+                        record = record.as_str(), record_type = record_type.as_str(), tenant, address:% = job.ip;
+                        "{} {} updated to {}", shown, record_type, job.ip
+                    );
+                    events.extend(self.flapping(job));
+                    let content = job.ip.to_string();
+                    self.publish_record(
+                        record,
+                        record_type,
+                        tenant,
+                        Some(&content),
+                        RecordState::Updated,
+                    );
+                    self.record_succeeded(record, record_type, content);
+                }
+                Outcome::Created => {
+                    changed += 1;
+                    let labels = job.labels();
+                    self.metrics
+                        .counter("clouddns_records_updated_total", &labels, 1);
+                    self.emit(DdnsEvent::RecordCreated {
+                        record: record.clone(),
+                        record_type,
+                        address: job.ip,
+                    });
+                    history.push(self.history_entry(job, None, ChangeReason::Discovered));
+                    info!(
+                        record = record.as_str(), record_type = record_type.as_str(), tenant, address:% = job.ip;
+                        "{} {} created with {}", shown, record_type, job.ip
+                    );
+                    let content = job.ip.to_string();
+                    self.publish_record(
+                        record,
+                        record_type,
+                        tenant,
+                        Some(&content),
+                        RecordState::Updated,
+                    );
+                    self.record_succeeded(record, record_type, content);
+                    self.state.created(record, record_type);
+                }
+                Outcome::Held { content, changes } if self.dry_run => {
+                    changed += 1;
+                    match self.snapshot.is_some() {
+                        true => {
+                            info!("Dry run, would update {} {}", shown, record_type);
+                            let state = RecordState::Pending;
+                            self.publish_record(
+                                record,
+                                record_type,
+                                tenant,
+                                content.as_deref(),
+                                state,
+                            );
+                        }
+                        false if changes.is_empty() => {
+                            println!("{} {} forced update", record, record_type)
+                        }
+                        false => println!("{} {} {}", record, record_type, changes.join(", ")),
+                    }
+                }
+                Outcome::Held { content, changes } => {
+                    changed += 1;
+                    let until = match self.state.paused {
+                        true => "updates are resumed",
+                        false => "the maintenance window ends",
+                    };
+                    info!(
+                        "{} {} {}, deferred until {}",
+                        shown,
+                        record_type,
+                        changes.join(", "),
+                        until
+                    );
+                    self.deferred.insert(job.key.clone());
+                    let state = RecordState::Deferred;
+                    self.publish_record(record, record_type, tenant, content.as_deref(), state);
+                }
+                Outcome::Vetoed(content) => {
+                    info!("{} {} not updated, a hook vetoed it", shown, record_type);
+                    let state = RecordState::Deferred;
+                    self.publish_record(record, record_type, tenant, content.as_deref(), state);
+                }
+                Outcome::Failed { content, error } => {
+                    let failures_in_row = self.state.failed(record, record_type);
+                    let labels = job.labels();
+                    self.metrics
+                        .counter("clouddns_record_update_failures_total", &labels, 1);
+                    self.emit(DdnsEvent::UpdateFailed {
+                        record: record.clone(),
+                        record_type,
+                        error: format!("{:#}", error),
+                        failures: failures_in_row,
+                    });
+                    events.push(Event::UpdateFailed {
+                        record: record.clone(),
+                        record_type,
+                        error: format!("{:#}", error),
+                        failures: failures_in_row,
+                        tenant: job.tenant.clone(),
+                    });
+                    match failures_in_row {
+                        1 => error!(
+                            record = record.as_str(), record_type = record_type.as_str(), tenant, failures = 1;
+                            "Failed to update {} {}: {:#}", shown, record_type, &error
+                        ),
+                        failures => error!(
+                            record = record.as_str(), record_type = record_type.as_str(), tenant, failures;
+                            "Failed to update {} {} ({} times in a row): {:#}",
+                            shown, record_type, failures, &error
+                        ),
+                    }
+                    // What the update was for, when the record could be read
+                    let reason = match &content {
+                        Some(content) => {
+                            change_reason(self.change_event(job, content.clone()).as_ref())
+                        }
+                        None if job.create => ChangeReason::Discovered,
+                        None => ChangeReason::AddressChanged,
+                    };
+                    history.push(HistoryEntry {
+                        error: Some(format!("{:#}", error)),
+                        ..self.history_entry(job, content.clone(), reason)
+                    });
+                    let state = RecordState::Failed(format!("{:#}", error));
+                    self.publish_record(record, record_type, tenant, content.as_deref(), state);
+                    self.failed.insert(job.key.clone());
+                    failures.push(error);
+                }
+            }
+        }
+
+        self.resumed = false;
+        self.save_history(&history);
+        self.save_state();
+        self.notify(&events).await;
+
+        // The API only counts as down when nothing got through
+        let unreachable = failures.iter().filter(|e| is_network_error(e)).count();
+        match unreachable == jobs.len() && !jobs.is_empty() {
+            true => self.breakers.failure(PROVIDER, Instant::now()),
+            false => self.breakers.success(PROVIDER),
+        }
+
+        let failed = failures.len();
+        match failures.into_iter().next() {
+            None => match detection_errors.into_iter().next() {
+                // The records of the family that was detected are up to date, the cycle still
+                // failed
+                Some(e) => Err(e).kind(ErrorKind::Detection),
+                None => {
+                    self.publish(|snapshot| snapshot.last_success = Some(Instant::now()));
+                    Ok(changed)
+                }
+            },
+            // The first failure is kept as the cause, with its classification, e.g. a rejected
+            // token
+            Some(first) => Err(first.context(format!(
+                "{} of {} record(s) could not be updated",
+                failed,
+                jobs.len()
+            )))
+            .kind(ErrorKind::Update),
+        }
+    }
+
+    fn record_succeeded(&mut self, record: &str, record_type: RecordType, content: String) {
+        let failures = self.state.succeeded(record, record_type, content);
+        if failures > 0 {
+            info!(
+                "{} {} recovered after {} failed attempt(s)",
+                record, record_type, failures
+            );
+        }
+    }
+
+    // A record set back to the address it was last confirmed with was changed by someone else
+    fn change_event(&self, job: &RecordJob, previous: String) -> Option<Event> {
+        let content = job.ip.to_string();
+        if previous == content {
+            return None;
+        }
+        let confirmed = self
+            .state
+            .records
+            .get(&(job.record.clone(), job.record_type))
+            .and_then(|history| history.content.as_deref());
+        let (record, record_type, address) = (job.record.clone(), job.record_type, job.ip);
+        let tenant = job.tenant.clone();
+        Some(match confirmed == Some(content.as_str()) {
+            true => Event::DriftDetected {
+                record,
+                record_type,
+                address,
+                previous,
+                tenant,
+            },
+            false => Event::IpChanged {
+                record,
+                record_type,
+                address,
+                previous,
+                tenant,
+            },
+        })
+    }
+
+    // Nobody listening isn't an error
+    fn emit(&self, event: DdnsEvent) {
+        let _ = self.events.send(event);
+    }
+
+    async fn notify(&self, events: &[Event]) {
+        if self.dry_run || events.is_empty() {
+            return;
+        }
+        // Hooks do work rather than tell anyone, maintenance windows don't hold them back
+        hooks::run(&self.config.hooks, events).await;
+        if self.config.notifications_muted(self.clock.now()) {
+            debug!(
+                "Not notifying {} event(s) during a maintenance window",
+                events.len()
+            );
+            return;
+        }
+        self.notifier.send(events).await;
+        for (tenant, notifier) in &self.tenant_notifiers {
+            let theirs: Vec<Event> = events
+                .iter()
+                .filter(|event| event.tenant() == Some(tenant.as_str()))
+                .cloned()
+                .collect();
+            notifier.send(&theirs).await;
+        }
+    }
+
+    async fn on_error(&self, error: &DdnsError) {
+        for hooks in &self.hooks {
+            hooks.on_error(error).await;
+        }
+    }
+
+    // Dry runs change nothing worth vouching for
+    async fn cycle_finished(&mut self, result: &Result<usize>) {
+        if self.dry_run {
+            return;
+        }
+        self.checks += 1;
+        self.notifier.cycle_finished(result.as_ref().err()).await;
+        let now = self.clock.now().to_utc();
+        if let (Some(status_file), Some(snapshot)) = (&mut self.status_file, &self.snapshot) {
+            let snapshot = snapshot.borrow().clone();
+            if let Err(e) = status_file.write(now, result.as_ref().err(), snapshot) {
+                warn!("{:#}", e);
+            }
+        }
+    }
+
+    // Follows the configuration, the records are then tracked in the snapshot like for the
+    // admin API
+    fn open_status_file(&mut self) {
+        let path = self.config.status_file.as_ref().filter(|_| !self.dry_run);
+        if self.status_file.as_ref().map(StatusFile::path) == path.map(PathBuf::as_path) {
+            return;
+        }
+        self.status_file = path.map(|path| StatusFile::new(path.clone()));
+        if self.status_file.is_some() {
+            self.snapshot
+                .get_or_insert_with(|| watch::channel(Snapshot::default()).0);
+        }
+    }
+
+    // The changes and failures of the last day or week as the history has them, with the records
+    // as they are now. Only the leader speaks for the records.
+    async fn send_summary(&mut self) {
+        let Some(summary) = &self.config.notifications.summary else {
+            return;
+        };
+        let now = self.clock.now();
+        // Monotonic and wall clocks may disagree slightly, the wall clock decides
+        if summary.next_after(self.last_summary) > now {
+            return;
+        }
+        let since = (now - summary.period()).to_utc();
+        self.last_summary = now;
+        if !self.leads() {
+            debug!(
+                "Not sending the summary, {} holds the lease",
+                self.lease_holder()
+            );
+            return;
+        }
+        let query = HistoryQuery {
+            since: Some(since),
+            ..HistoryQuery::default()
+        };
+        let history = match &self.state_store {
+            Some(store) => store.history(&query).unwrap_or_else(|e| {
+                warn!("Failed to read the history for the summary: {:#}", e);
+                Vec::new()
+            }),
+            None => Vec::new(),
+        };
+        info!("Sending the summary of {} cycle(s)", self.checks);
+        let event = Event::summary(since, self.checks, &history, &self.state.records);
+        self.checks = 0;
+        self.notify(&[event]).await;
+    }
+
+    fn history_entry(
+        &self,
+        job: &RecordJob,
+        previous: Option<String>,
+        reason: ChangeReason,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            time: self.clock.now().to_utc(),
+            record: job.record.clone(),
+            r#type: job.record_type,
+            previous,
+            content: Some(job.ip.to_string()),
+            reason,
+            trigger: self.trigger,
+            error: None,
+        }
+    }
+
+    fn save_history(&self, entries: &[HistoryEntry]) {
+        if entries.is_empty() {
+            return;
+        }
+        if let (Some(store), false) = (&self.state_store, self.dry_run) {
+            if let Err(e) = store.append_history(entries) {
+                warn!(
+                    "Failed to save {} history entry(ies): {:#}",
+                    entries.len(),
+                    e
+                );
+            }
+        }
+    }
+
+    // A single run leaves nothing to scrape, its metrics are handed over instead
+    async fn push_metrics(&self) {
+        let Some(pushgateway) = &self.config.metrics.pushgateway else {
+            return;
+        };
+        let Some(prometheus) = self.prometheus.as_ref().filter(|_| !self.dry_run) else {
+            return;
+        };
+        match metrics::push(pushgateway, &self.config.retry, prometheus).await {
+            Ok(()) => debug!("Pushed the metrics to {}", pushgateway.url),
+            Err(e) => warn!("{:#}", e),
+        }
+    }
+
+    fn save_state(&self) {
+        if let (Some(store), false) = (&self.state_store, self.dry_run) {
+            if let Err(e) = store.save(&self.state) {
+                warn!("Failed to save state: {:#}", e);
+            }
+        }
+    }
+
+    // Check and update the records, `concurrency` at a time. Records of a sequential zone go
+    // one after the other, in the order of the configuration, as do those of one name in a
+    // grouped zone. Outcomes come back in job order.
+    // Leaves out the records already resolving to their address, looked up together rather than one
+    // after the other. A proxied record answers with Cloudflare's edge addresses, only the API
+    // knows its content.
+    async fn precheck(&self, jobs: Vec<RecordJob>, force: bool) -> Vec<RecordJob> {
+        let Some(precheck) = self.precheck.as_ref().filter(|_| !force) else {
+            return jobs;
+        };
+        let permits = &Semaphore::new(self.config.concurrency);
+        let lookups = jobs.iter().map(|job| async move {
+            if job.proxied == Some(true) {
+                return false;
+            }
+            let _permit = permits.acquire().await;
+            precheck.confirms(&job.record, job.ip).await
+        });
+        let resolved = futures::future::join_all(lookups).await;
+
+        let mut unresolved = Vec::new();
+        for (job, resolves) in jobs.into_iter().zip(resolved) {
+            if !resolves {
+                unresolved.push(job);
+                continue;
+            }
+            let content = job.ip.to_string();
+            info!(
+                "{} {} already resolves to {}, not checking it with the API",
+                &job.record, job.record_type, &content
+            );
+            self.publish_record(
+                &job.record,
+                job.record_type,
+                job.tenant.as_deref(),
+                Some(&content),
+                RecordState::InSync,
+            );
+        }
+        unresolved
+    }
+
+    async fn check_records(&self, jobs: &[RecordJob], force: bool, apply: bool) -> Vec<Outcome> {
+        let mut lanes: Vec<Vec<usize>> = Vec::new();
+        let mut shared_lanes: HashMap<(&str, Option<&str>), usize> = HashMap::new();
+        let mut zone_spans: HashMap<&str, tracing::Span> = HashMap::new();
+        for (index, job) in jobs.iter().enumerate() {
+            zone_spans.entry(&job.key.zone_id).or_insert_with(|| {
+                let tenant = job.tenant.as_deref();
+                tracing::info_span!("zone", zone_id = %job.key.zone_id, tenant)
+            });
+            let shared = match job.order {
+                RecordOrder::Parallel => {
+                    lanes.push(vec![index]);
+                    continue;
+                }
+                RecordOrder::Grouped => (job.key.zone_id.as_str(), Some(job.record.as_str())),
+                RecordOrder::Sequential => (job.key.zone_id.as_str(), None),
+            };
+            let lane = *shared_lanes.entry(shared).or_insert_with(|| {
+                lanes.push(Vec::new());
+                lanes.len() - 1
+            });
+            lanes[lane].push(index);
+        }
+
+        let permits = &Semaphore::new(self.config.concurrency);
+        let client = &*self.api_client;
+        let hooks = &self.hooks[..];
+        let zone_spans = &zone_spans;
+        let mut running: FuturesUnordered<_> = lanes
+            .into_iter()
+            .map(|lane| async move {
+                let mut outcomes = Vec::new();
+                for index in lane {
+                    let job = &jobs[index];
+                    let span = tracing::info_span!(
+                        parent: &zone_spans[job.key.zone_id.as_str()],
+                        "record",
+                        record = %job.record,
+                        r#type = %job.record_type,
+                    );
+                    let _permit = permits.acquire().await;
+                    let outcome = check_record(client, hooks, job, force, apply)
+                        .instrument(span)
+                        .await;
+                    outcomes.push((index, outcome));
                 }
+                outcomes
+            })
+            .collect();
+
+        let mut outcomes: Vec<Option<Outcome>> = jobs.iter().map(|_| None).collect();
+        while let Some(lane) = running.next().await {
+            for (index, outcome) in lane {
+                outcomes[index] = Some(outcome);
+            }
+        }
+        outcomes.into_iter().flatten().collect()
+    }
+
+    // Check every domain now rather than when due. Forcing pushes the records even when they
+    // match, and even inside a maintenance window.
+    async fn update_all(&mut self, scheduler: &mut Scheduler, force: bool, trigger: Trigger) {
+        let all = Scheduler::new(&self.config).due(Instant::now());
+        let write = self.writes(force);
+        self.run_cycle(scheduler, &all, force, write, trigger).await;
+    }
+
+    // Everything a cycle logs, traces and notifies carries its ID
+    async fn run_cycle(
+        &mut self,
+        scheduler: &mut Scheduler,
+        due: &HashSet<DomainKey>,
+        force: bool,
+        write: bool,
+        trigger: Trigger,
+    ) {
+        correlation::cycle(self.cycle(scheduler, due, force, write, trigger)).await
+    }
+
+    // A failed cycle is retried with a growing delay rather than after the full interval, one
+    // skipped for a lack of network sooner
+    async fn cycle(
+        &mut self,
+        scheduler: &mut Scheduler,
+        due: &HashSet<DomainKey>,
+        force: bool,
+        write: bool,
+        trigger: Trigger,
+    ) {
+        self.trigger = trigger;
+        self.open_status_file();
+        if !self.leads() {
+            debug!("Standing by, {} holds the lease", self.lease_holder());
+            let now = Instant::now();
+            scheduler.complete(due, now, now);
+            return;
+        }
+        // The probe is of Cloudflare's API, which a provider of the host application's may not use
+        if !self.provided_api_client && !network::online().await {
+            self.offline += 1;
+            if self.offline == 1 {
+                info!("The network is down, skipping checks until it is back");
             }
+            let delay = network::offline_retry_delay(self.offline);
+            debug!("Checking the network again in {}s", delay.as_secs());
+            scheduler.postpone(due, Instant::now() + delay);
+            return;
         }
+        if self.offline > 0 {
+            info!("The network is back");
+            self.offline = 0;
+        }
+
+        let started = Instant::now();
+        let Some(mut result) = self.update_until_stopped(due, force, write).await else {
+            return;
+        };
+        // The token may have been rotated since it was read, the new one gets a try right away
+        if rejected_token(&result)
+            && !self.config.api_token.is_inline()
+            && !self.provided_api_client
+            && self.refresh_api_token().await
+        {
+            info!("Retrying with the new API token");
+            result = match self.update_until_stopped(due, force, write).await {
+                Some(result) => result,
+                None => return,
+            };
+        }
+        self.cycle_finished(&result).await;
+        match result {
+            Ok(_) => {
+                scheduler.complete(due, started, Instant::now());
+                if self.watchdog_tripped() {
+                    info!("Recovered after {} failed cycles", self.failed_cycles);
+                }
+                self.failed_cycles = 0;
+            }
+            Err(e) => {
+                let e = DdnsError::from(e);
+                self.on_error(&e).await;
+                let e = e.inner();
+                error!("Error updating records: {:#}", e);
+                // Domains whose records all went through keep their interval. Without failed
+                // records, e.g. when detection failed, the whole cycle is retried.
+                let now = Instant::now();
+                let failed = match self.failed.is_empty() {
+                    true => due.clone(),
+                    false => std::mem::take(&mut self.failed),
+                };
+                let succeeded = due.difference(&failed).cloned().collect();
+                scheduler.complete(&succeeded, started, now);
+                let retry = scheduler.failed(&failed, now);
+                self.failed_cycles += 1;
+                self.check_watchdog(e).await;
+                self.report_failures(e, &failed).await;
+                if self.watchdog_exits() {
+                    return;
+                }
+                match succeeded.is_empty() {
+                    true => warn!("Retrying in {}s", retry.as_secs()),
+                    false => warn!(
+                        "Retrying {} of {} domain(s) in {}s",
+                        failed.len(),
+                        due.len(),
+                        retry.as_secs()
+                    ),
+                }
+            }
+        }
+    }
+
+    // None when the daemon stopped before the update could finish
+    async fn update_until_stopped(
+        &mut self,
+        due: &HashSet<DomainKey>,
+        force: bool,
+        write: bool,
+    ) -> Option<Result<usize>> {
+        let cancel = self.cancel.clone();
+        let grace = Duration::from_secs(self.config.shutdown_timeout);
+        let started = Instant::now();
+        let update = self.update_records(due, force, write);
+        let result = unless_stopped(update, cancel, grace).await;
+        match &result {
+            Some(result) => self.cycle_metrics(result, started.elapsed()),
+            None => warn!(
+                "The update under way didn't finish within {}s, stopping anyway",
+                grace.as_secs()
+            ),
+        }
+        result
+    }
+
+    fn cycle_metrics(&self, result: &Result<usize>, duration: Duration) {
+        let outcome = match result {
+            Ok(_) => "success",
+            Err(_) => "failure",
+        };
+        let metrics = &self.metrics;
+        metrics.counter("clouddns_cycles_total", &[("result", outcome)], 1);
+        metrics.histogram(
+            "clouddns_cycle_duration_seconds",
+            &[],
+            duration.as_secs_f64(),
+        );
+        if result.is_ok() {
+            let now = self.clock.now().timestamp() as f64;
+            metrics.gauge("clouddns_last_success_timestamp_seconds", &[], now);
+        }
+    }
+
+    // Escalates once, when the count of failed cycles reaches the threshold
+    async fn check_watchdog(&self, e: &anyhow::Error) {
+        let Some(watchdog) = self.config.watchdog else {
+            return;
+        };
+        if self.failed_cycles != watchdog.failures {
+            return;
+        }
+        error!(
+            "{} cycles failed in a row, the watchdog tripped",
+            self.failed_cycles
+        );
+        self.notify(&[Event::WatchdogTripped {
+            error: format!("{:#}", e),
+            failures: self.failed_cycles,
+        }])
+        .await;
+    }
+
+    // Once per streak, when a record reaches the changes the configuration allows in its period
+    fn flapping(&mut self, job: &RecordJob) -> Option<Event> {
+        let flapping = self.config.flapping?;
+        let changes =
+            self.flaps
+                .changed(&job.record, job.record_type, &flapping, Instant::now())?;
+        warn!(
+            "{} {} changed {} times in {} minutes, an address source may be broken or another \
+             updater may be changing it",
+            job.shown(),
+            job.record_type,
+            changes,
+            flapping.period
+        );
+        Some(Event::Flapping {
+            record: job.record.clone(),
+            record_type: job.record_type,
+            changes,
+            minutes: flapping.period,
+            tenant: job.tenant.clone(),
+        })
+    }
+
+    // Joins the election the configuration asks for, giving up a lease held under other settings
+    async fn start_leader(&mut self) -> Result<()> {
+        self.release_lease().await;
+        self.leader = None;
+        let Some(election) = &self.config.leader_election else {
+            return Ok(());
+        };
+        if self.dry_run {
+            debug!("Dry run, leaving the lease alone");
+            return Ok(());
+        }
+        let zone = self
+            .config
+            .zone_containing(&election.record)
+            .with_context(|| {
+                format!(
+                    "Leader election record {} is in none of the named zones",
+                    election.record
+                )
+            })
+            .kind(ErrorKind::Config)?;
+        let identity = match &election.identity {
+            Some(identity) => identity.clone(),
+            None => hostname::get()
+                .ok()
+                .and_then(|name| name.into_string().ok())
+                .context("Failed to read the hostname, set leader_election.identity")
+                .kind(ErrorKind::Config)?,
+        };
+        self.leader = Some(Leader::new(election, zone.id.to_string(), identity));
         Ok(())
     }
 
+    // Returns whether this instance just took the lease over
+    async fn check_lease(&mut self) -> bool {
+        let now = self.clock.now().to_utc();
+        let Some(leader) = &mut self.leader else {
+            return false;
+        };
+        let led = leader.leads(now);
+        let holder = leader.holder().map(str::to_string);
+        let result = leader.check(&*self.api_client, now).await;
+        let leads = leader.leads(now);
+        match result {
+            Ok(true) if !led => info!("Took the lease as {}", leader.identity()),
+            Ok(true) => debug!("Renewed the lease"),
+            Ok(false) if led || leader.holder() != holder.as_deref() => info!(
+                "Standing by, {} holds the lease",
+                leader.holder().unwrap_or("another instance")
+            ),
+            Ok(false) => debug!("Still standing by"),
+            Err(e) if leads => warn!(
+                "Failed to renew the lease, leading until it runs out: {:#}",
+                e
+            ),
+            Err(e) => warn!("Failed to check the lease: {:#}", e),
+        }
+        self.metrics
+            .gauge("clouddns_leader", &[], if leads { 1.0 } else { 0.0 });
+        leads && !led
+    }
+
+    async fn release_lease(&mut self) {
+        let Some(leader) = &mut self.leader else {
+            return;
+        };
+        match leader.release(&*self.api_client).await {
+            Ok(()) => self.metrics.gauge("clouddns_leader", &[], 0.0),
+            Err(e) => warn!("Failed to release the lease: {:#}", e),
+        }
+    }
+
+    // Without an election, every instance writes
+    fn leads(&self) -> bool {
+        let now = self.clock.now().to_utc();
+        self.leader.as_ref().is_none_or(|leader| leader.leads(now))
+    }
+
+    fn lease_holder(&self) -> &str {
+        self.leader
+            .as_ref()
+            .and_then(Leader::holder)
+            .unwrap_or("another instance")
+    }
+
+    // Once as well, when the count reaches Sentry's threshold
+    async fn report_failures(&self, e: &anyhow::Error, failed: &HashSet<DomainKey>) {
+        let Some(sentry) = self.sentry.as_ref().filter(|_| !self.dry_run) else {
+            return;
+        };
+        if self.failed_cycles != sentry.failures() {
+            return;
+        }
+        let mut domains = BTreeSet::new();
+        for zone in &self.config.zones {
+            for domain in &zone.domains {
+                let key = DomainKey {
+                    zone_id: zone.id.to_string(),
+                    domain: domain.name.to_string(),
+                };
+                if failed.contains(&key) {
+                    domains.insert(domain.fqdn(zone));
+                }
+            }
+        }
+        let domains: Vec<String> = domains.into_iter().collect();
+        sentry
+            .capture_failures(e, self.failed_cycles, &domains)
+            .await;
+    }
+
+    fn watchdog_tripped(&self) -> bool {
+        self.config
+            .watchdog
+            .is_some_and(|watchdog| self.failed_cycles >= watchdog.failures)
+    }
+
+    // Once tripped, the daemon stops for its service manager to restart it
+    fn watchdog_exits(&self) -> bool {
+        self.watchdog_tripped() && self.config.watchdog.is_some_and(|watchdog| watchdog.exit)
+    }
+
+    fn in_maintenance(&self) -> bool {
+        self.config.maintenance_end(self.clock.now()).is_some()
+    }
+
+    // Nothing is written while paused, not even when forced
+    fn writes(&self, force: bool) -> bool {
+        !self.state.paused && (force || !self.in_maintenance())
+    }
+
+    /// Resolves on Ctrl+C, or SIGTERM on Unix, for [`CloudflareDdns::run`]
     pub async fn shutdown_signal() {
         let ctrl_c = async {
             signal::ctrl_c()
@@ -131,28 +2073,577 @@ impl CloudflareDdns {
         }
     }
 
-    pub async fn run(&mut self, shutdown: impl Future<Output = ()>) -> Result<()> {
-        let interval = Duration::from_secs(self.config.update_interval * 60);
+    /// A single detection and update pass over every enabled domain, for cron and timers.
+    /// Forcing pushes every record even when it already matches, maintenance window or not.
+    /// Cancelling the [`cancellation_token`](Self::cancellation_token) stops it like `run`.
+    pub async fn run_once(&mut self, force: bool) -> Result<(), DdnsError> {
+        correlation::cycle(self.once(force)).await
+    }
 
-        if let Err(e) = self.update_all_records().await {
-            error!("Error during initial update: {}", &e);
+    async fn once(&mut self, force: bool) -> Result<(), DdnsError> {
+        let due = Scheduler::new(&self.config).due(Instant::now());
+        if self.state.paused {
+            warn!("Updates are paused, checking the records without writing them");
         }
+        let write = self.writes(force);
+        self.trigger = Trigger::Once;
+        self.open_status_file();
+        self.start_leader().await?;
+        self.check_lease().await;
+        if !self.leads() {
+            info!(
+                "Not holding the lease, leaving the records to {}",
+                self.lease_holder()
+            );
+            return Ok(());
+        }
+        let result = self.update_until_stopped(&due, force, write).await;
+        self.release_lease().await;
+        let Some(result) = result else {
+            return Err(anyhow::anyhow!("Stopped before the records were updated"))
+                .kind(ErrorKind::Update)
+                .map_err(DdnsError::from);
+        };
+        self.cycle_finished(&result).await;
+        self.push_metrics().await;
+        let changed = match result {
+            Ok(changed) => changed,
+            Err(e) => {
+                let e = DdnsError::from(e);
+                self.on_error(&e).await;
+                return Err(e);
+            }
+        };
 
-        tokio::pin!(shutdown);
+        if self.dry_run && changed > 0 {
+            return Err(anyhow::anyhow!("{} record(s) would be updated", changed))
+                .kind(ErrorKind::PendingChanges)
+                .map_err(DdnsError::from);
+        }
+        Ok(())
+    }
+
+    /// Runs until `shutdown` resolves. An update under way then gets `shutdown_timeout` seconds to
+    /// finish. A panic ending it, or an error other than a tripped watchdog, is sent as a `crashed`
+    /// notification and runs the `on_failure` hook.
+    pub async fn run(
+        &mut self,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<(), DdnsError> {
+        if !self.dry_run {
+            self.crashes = Some(
+                crash::Reporter::new(&self.config)
+                    .await
+                    .kind(ErrorKind::Config)?,
+            );
+        }
+        crash::record_panics();
+        let result = AssertUnwindSafe(self.serve(shutdown)).catch_unwind().await;
+        // The watchdog sent its own notification
+        let crash = match &result {
+            Ok(Err(e)) if e.inner().downcast_ref::<ErrorKind>() != Some(&ErrorKind::Watchdog) => {
+                Some(format!("{:#}", e.inner()))
+            }
+            Ok(_) => None,
+            Err(_) => Some(crash::last_panic()),
+        };
+        if let (Some(crash), Some(crashes)) = (crash, &self.crashes) {
+            crashes.report(&crash).await;
+        }
+        self.crashes = None;
+        result.unwrap_or_else(|panic| panic::resume_unwind(panic))
+    }
+
+    async fn serve(
+        &mut self,
+        shutdown: impl Future<Output = ()> + Send + 'static,
+    ) -> Result<(), DdnsError> {
+        let mut reload = SignalListener::reload()?;
+        let mut update_now = SignalListener::update_now()?;
+        let mut force_update = SignalListener::force_update()?;
+        for line in self.config.overview() {
+            info!("{}", line);
+        }
+        let mut watcher = self.start_watcher();
+        let mut scheduler = Scheduler::new(&self.config);
+        // A fixed delay, plus a random one spreading the first checks of a fleet started at the
+        // same moment
+        let fixed = Duration::from_secs(self.config.startup.delay.unwrap_or(0));
+        let spread = Duration::from_secs(self.config.startup_delay.unwrap_or(0));
+        let delay = fixed + spread.mul_f64(fastrand::f64());
+        if !delay.is_zero() {
+            info!("Waiting {}s before the first check", delay.as_secs());
+            scheduler.delay_start(Instant::now() + delay);
+        }
+        // Checks are held back until the network is up, or the wait is over
+        let limit = Duration::from_secs(self.config.startup.network_timeout);
+        let mut network = self
+            .config
+            .startup
+            .wait_for_network
+            .then(|| Box::pin(network::wait_online(limit)));
+        let _health = self.start_health(&scheduler).await?;
+        let _mqtt = self.start_mqtt().await?;
+        self.discovery = Discovery::start(&self.config.discovery);
+        let (_admin, mut admin_control) = match self.start_admin().await? {
+            Some((server, control)) => (Some(server), Some(control)),
+            None => (None, None),
+        };
+        let _acme_dns = self.start_acme_dns().await?;
+        let (_socket, mut socket_control) = match self.start_control().await? {
+            Some((socket, control)) => (Some(socket), Some(control)),
+            None => (None, None),
+        };
+        self.start_leader().await?;
+        let cancel = self.cancel.clone();
+        cancel.run_until_cancelled(self.check_lease()).await;
+        cancel
+            .run_until_cancelled(self.notify(&[Event::startup()]))
+            .await;
+        let mut last_token_refresh = Instant::now();
+        let mut control = self.control.take();
+        if self.state.paused {
+            warn!("Updates are paused, records are checked but not written until resumed");
+        }
+        let mut requests = Requests::default();
+
+        // Watched from its own task, so that a cycle under way sees it too
+        let stop = cancel.clone();
+        let _stop = AbortOnDrop(tokio::spawn(async move {
+            shutdown.await;
+            stop.cancel();
+        }));
 
         loop {
+            if self.watchdog_exits() {
+                return Err(anyhow::anyhow!(
+                    "{} cycles failed in a row",
+                    self.failed_cycles
+                ))
+                .kind(ErrorKind::Watchdog)
+                .map_err(DdnsError::from);
+            }
+            // Recomputed every iteration so reloaded intervals take effect right away
+            let next_run = scheduler.next_deadline();
+            let lease_check = self.leader.as_ref().map(Leader::next_check);
+            let token_refresh = self
+                .token_refresh_interval()
+                .map(|minutes| last_token_refresh + Duration::from_secs(minutes * 60));
+            let now = self.clock.now();
+            let maintenance_end = self
+                .config
+                .maintenance_end(now)
+                .map(|end| Instant::now() + (end - now).to_std().unwrap_or_default());
+            let summary = self.config.notifications.summary.as_ref().map(|summary| {
+                let next = summary.next_after(self.last_summary);
+                Instant::now() + (next - now).to_std().unwrap_or_default()
+            });
+            let requested = requests.due();
+            let debounce = Duration::from_millis(self.config.debounce_ms);
+            self.publish(|snapshot| {
+                snapshot.next_run = next_run;
+                snapshot.paused = self.state.paused;
+                snapshot.in_maintenance = maintenance_end.is_some();
+            });
+
+            // Also set when a cycle was cut short by the signal
+            if cancel.is_cancelled() {
+                break;
+            }
+            // In order, so that requests queued up during a cycle are in before it stops covering
+            // them
             tokio::select! {
-                _ = &mut shutdown => {
+                biased;
+                _ = cancel.cancelled() => {
                     info!("Shutdown signal received");
                     break;
                 }
-                _ = sleep(interval) => {
-                    if let Err(e) = self.update_all_records().await {
-                        error!("Error updating records: {}", &e);
+                _ = reload.recv() => {
+                    info!("Received SIGHUP, reloading configuration");
+                    cancel.run_until_cancelled(self.reload(&mut watcher, &mut scheduler)).await;
+                }
+                _ = update_now.recv() => {
+                    info!("Received SIGUSR1, checking every record");
+                    requests.add(false, Trigger::Signal, debounce);
+                }
+                _ = force_update.recv() => {
+                    info!("Received SIGUSR2, forcing an update of every record");
+                    requests.add(true, Trigger::Signal, debounce);
+                }
+                request = next_control(&mut control) => {
+                    self.handle(request, &mut scheduler, &mut watcher, &mut requests).await;
+                }
+                request = next_control(&mut admin_control) => {
+                    self.handle(request, &mut scheduler, &mut watcher, &mut requests).await;
+                }
+                request = next_control(&mut socket_control) => {
+                    self.handle(request, &mut scheduler, &mut watcher, &mut requests).await;
+                }
+                _ = std::future::ready(()), if requests.covering() => requests.settle(),
+                _ = sleep_until(requested.unwrap_or_else(Instant::now)), if requested.is_some() => {
+                    let (force, trigger) = requests.take();
+                    self.update_all(&mut scheduler, force, trigger).await;
+                    requests.covered_by(force);
+                }
+                hosts = discovery::changed(&mut self.discovery) => {
+                    cancel.run_until_cancelled(self.discovered(hosts, &mut scheduler)).await;
+                }
+                _ = watcher::changed(&mut watcher) => {
+                    info!("Config file changed, reloading configuration");
+                    cancel.run_until_cancelled(self.reload(&mut watcher, &mut scheduler)).await;
+                }
+                _ = sleep_until(lease_check.unwrap_or_else(Instant::now)), if lease_check.is_some() => {
+                    if cancel.run_until_cancelled(self.check_lease()).await == Some(true) {
+                        requests.add(false, Trigger::Takeover, debounce);
+                    }
+                }
+                _ = sleep_until(token_refresh.unwrap_or_else(Instant::now)), if token_refresh.is_some() => {
+                    debug!("Reading the API token again");
+                    cancel.run_until_cancelled(self.refresh_api_token()).await;
+                    last_token_refresh = Instant::now();
+                }
+                _ = sleep_until(maintenance_end.unwrap_or_else(Instant::now)), if maintenance_end.is_some() => {
+                    // Monotonic and wall clocks may disagree slightly, the window decides
+                    if self.in_maintenance() || self.state.paused || self.deferred.is_empty() {
+                        continue;
+                    }
+                    info!("Maintenance window over, applying deferred changes");
+                    let deferred = std::mem::take(&mut self.deferred);
+                    let trigger = Trigger::Maintenance;
+                    self.run_cycle(&mut scheduler, &deferred, false, true, trigger).await;
+                }
+                _ = sleep_until(summary.unwrap_or_else(Instant::now)), if summary.is_some() => {
+                    cancel.run_until_cancelled(self.send_summary()).await;
+                }
+                online = until_online(&mut network) => {
+                    match online {
+                        true => info!("Network is up"),
+                        false => warn!(
+                            "No network after {}s, checking anyway",
+                            self.config.startup.network_timeout
+                        ),
+                    }
+                }
+                // A check requested meanwhile covers the records due
+                _ = sleep_until(next_run.unwrap_or_else(Instant::now)), if next_run.is_some() && network.is_none() && requested.is_none() => {
+                    let due = scheduler.due(Instant::now());
+                    let write = self.writes(false);
+                    self.run_cycle(&mut scheduler, &due, false, write, Trigger::Schedule).await;
+                    if write && scheduler.covers_all(&due) {
+                        requests.covered_by(false);
                     }
                 }
             }
         }
+
+        self.discovery = None;
+        self.save_state();
+        self.release_lease().await;
+        let records = self.state.records.values();
+        let failing = records
+            .clone()
+            .filter(|history| history.failures > 0)
+            .count();
+        let in_sync = records
+            .filter(|history| history.failures == 0 && history.content.is_some())
+            .count();
+        info!(
+            "Stopped with {} record(s) in sync and {} failing",
+            in_sync, failing
+        );
         Ok(())
     }
+
+    // Requests are handled between cycles, in the order they come
+    async fn handle(
+        &mut self,
+        request: Control,
+        scheduler: &mut Scheduler,
+        watcher: &mut Option<ConfigWatcher>,
+        requests: &mut Requests,
+    ) {
+        let debounce = Duration::from_millis(self.config.debounce_ms);
+        match request {
+            Control::UpdateNow => {
+                info!("Checking every record");
+                let trigger = match self.pushed.is_empty() {
+                    true => Trigger::Request,
+                    false => Trigger::Push,
+                };
+                requests.add(false, trigger, debounce);
+            }
+            Control::ForceUpdate => {
+                info!("Forcing an update of every record");
+                requests.add(true, Trigger::Request, debounce);
+            }
+            Control::Pause => {
+                if !self.state.paused {
+                    info!("Updates paused, records are checked but not written until resumed");
+                    self.state.paused = true;
+                    self.save_state();
+                }
+            }
+            Control::Resume => {
+                if self.state.paused {
+                    info!("Updates resumed");
+                    self.state.paused = false;
+                    self.save_state();
+                    if !self.in_maintenance() && !self.deferred.is_empty() {
+                        info!("Applying the changes held while paused");
+                        let deferred = std::mem::take(&mut self.deferred);
+                        let trigger = Trigger::Request;
+                        self.run_cycle(scheduler, &deferred, false, true, trigger)
+                            .await;
+                    }
+                }
+            }
+            Control::Reload => {
+                info!("Reloading configuration");
+                self.reload(watcher, scheduler).await;
+            }
+            Control::PushAddress(ip) => {
+                info!("Address {} pushed, using it for the next check", ip);
+                let record_type = match ip {
+                    IpAddr::V4(_) => RecordType::A,
+                    IpAddr::V6(_) => RecordType::Aaaa,
+                };
+                self.pushed.insert(record_type, ip);
+            }
+        }
+    }
+}
+
+// The channels of each tenant that has some, for the events of its zones
+async fn tenant_notifiers(
+    config: &Config,
+    client: reqwest::Client,
+) -> Result<BTreeMap<String, Notifier>> {
+    let mut notifiers = BTreeMap::new();
+    for (name, tenant) in &config.tenants {
+        if !tenant.notifications.is_empty() {
+            let notifier = Notifier::new(
+                &tenant.notifications,
+                &config.retry,
+                config.provider.r#type,
+                client.clone(),
+            );
+            notifiers.insert(name.clone(), notifier.await?);
+        }
+    }
+    Ok(notifiers)
+}
+
+fn same_tokens(a: &BTreeMap<String, SecretString>, b: &BTreeMap<String, SecretString>) -> bool {
+    a.len() == b.len()
+        && a.iter().zip(b).all(|((a_name, a), (b_name, b))| {
+            a_name == b_name && a.expose_secret() == b.expose_secret()
+        })
+}
+
+// The configured StatsD agent gets everything too
+fn with_statsd(metrics: Arc<dyn Metrics>, statsd: &Option<Arc<StatsdMetrics>>) -> Arc<dyn Metrics> {
+    match statsd {
+        Some(statsd) => Arc::new(Tee(vec![metrics, statsd.clone()])),
+        None => metrics,
+    }
+}
+
+// Never resolves without a control channel, or once every sender is gone
+async fn next_control(control: &mut Option<UnboundedReceiver<Control>>) -> Control {
+    if let Some(receiver) = control {
+        if let Some(request) = receiver.recv().await {
+            return request;
+        }
+        *control = None;
+    }
+    std::future::pending().await
+}
+
+// Cloudflare turned the token down, see `check_status`
+fn rejected_token(result: &Result<usize>) -> bool {
+    matches!(result, Err(e) if e.downcast_ref::<ErrorKind>() == Some(&ErrorKind::Auth))
+}
+
+// Lets work run to the end, or for the grace period once shutdown is requested. None when it was
+// cut short.
+async fn unless_stopped<T>(
+    work: impl Future<Output = T>,
+    cancel: CancellationToken,
+    grace: Duration,
+) -> Option<T> {
+    tokio::pin!(work);
+    tokio::select! {
+        output = &mut work => return Some(output),
+        _ = cancel.cancelled() => {}
+    }
+    info!(
+        "Shutdown signal received, letting the update under way finish for up to {}s",
+        grace.as_secs()
+    );
+    tokio::time::timeout(grace, work).await.ok()
+}
+
+// Stops a background task along with its owner
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+// Resolves once with the outcome of the wait for the network, then never again
+async fn until_online(wait: &mut Option<impl Future<Output = bool> + Unpin>) -> bool {
+    let Some(future) = wait else {
+        return std::future::pending().await;
+    };
+    let online = future.await;
+    *wait = None;
+    online
+}
+
+// A record to check, with the address and settings it should have
+struct RecordJob {
+    key: DomainKey,
+    order: RecordOrder,
+    record: String,
+    record_type: RecordType,
+    ip: IpAddr,
+    ttl: Option<u32>,
+    proxied: Option<bool>,
+    // Created when missing, for discovered hostnames
+    create: bool,
+    tenant: Option<String>,
+}
+
+impl RecordJob {
+    // The record as logged, after its tenant
+    fn shown(&self) -> Cow<'_, str> {
+        match &self.tenant {
+            Some(tenant) => Cow::Owned(format!("[{}] {}", tenant, self.record)),
+            None => Cow::Borrowed(&self.record),
+        }
+    }
+
+    fn labels(&self) -> Vec<(&str, &str)> {
+        let mut labels = vec![
+            ("record", self.record.as_str()),
+            ("type", self.record_type.as_str()),
+        ];
+        if let Some(tenant) = &self.tenant {
+            labels.push(("tenant", tenant));
+        }
+        labels
+    }
+}
+
+fn change_reason(event: Option<&Event>) -> ChangeReason {
+    match event {
+        Some(Event::DriftDetected { .. }) => ChangeReason::Drift,
+        Some(_) => ChangeReason::AddressChanged,
+        None => ChangeReason::Forced,
+    }
+}
+
+enum Outcome {
+    InSync(String),
+    // With the content the record had before
+    Updated(String),
+    Created,
+    // Needs changes that weren't applied, in a dry run or a maintenance window. The content is
+    // None for a record yet to be created.
+    Held {
+        content: Option<String>,
+        changes: Vec<String>,
+    },
+    // Left as is by a hook, with its content
+    Vetoed(Option<String>),
+    Failed {
+        content: Option<String>,
+        error: anyhow::Error,
+    },
+}
+
+// Without `apply`, records needing changes are only reported
+async fn check_record(
+    client: &dyn DnsApiClient,
+    hooks: &[Box<dyn DdnsHooks>],
+    job: &RecordJob,
+    force: bool,
+    apply: bool,
+) -> Outcome {
+    let zone_id = &job.key.zone_id;
+    let record = match client
+        .get_record(zone_id, &job.record, job.record_type.as_str())
+        .await
+    {
+        Ok(record) => Some(record),
+        Err(error) if job.create && error.is::<NotFound>() => None,
+        Err(error) => {
+            return Outcome::Failed {
+                content: None,
+                error,
+            }
+        }
+    };
+    let content = record.as_ref().map(|record| record.content.clone());
+
+    let desired = DesiredRecord::new(&job.record, RecordContent::from(job.ip))
+        .ttl(job.ttl)
+        .proxied(job.proxied);
+    let plan = UpdatePlanner::new()
+        .force(force)
+        .plan(&[desired], record.as_slice());
+    let (action, changes) = match plan.actions.first() {
+        Some(action @ Action::Update { changes, .. }) => (action, changes.clone()),
+        Some(action @ Action::Create { .. }) => (action, vec![format!("none → {}", job.ip)]),
+        _ => return Outcome::InSync(content.unwrap_or_default()),
+    };
+    if !apply {
+        return Outcome::Held { content, changes };
+    }
+
+    let change = RecordChange {
+        record: job.record.clone(),
+        record_type: job.record_type,
+        address: job.ip,
+        previous: content.clone().unwrap_or_default(),
+        changes,
+    };
+    for hooks in hooks {
+        if !hooks.before_update(&change).await {
+            return Outcome::Vetoed(content);
+        }
+    }
+
+    match action.apply(client, zone_id).await {
+        Ok(_) => {
+            for hooks in hooks {
+                hooks.after_update(&change).await;
+            }
+            match content {
+                Some(previous) => Outcome::Updated(previous),
+                None => Outcome::Created,
+            }
+        }
+        Err(error) => Outcome::Failed { content, error },
+    }
+}
+
+// The previous target keeps logging when the configured one can't be reached
+fn set_log_target(target: config::LogTarget) {
+    if let Err(e) = logging::set_target(target) {
+        warn!("{:#}, keeping the current log target", e);
+    }
+}
+
+// Only failures to reach the API count, not a rejected token, a missing record or an error about
+// a single zone, which retrying that zone alone would otherwise turn into an outage for all
+fn is_network_error(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| {
+        cause.is::<Unreachable>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect() || e.is_timeout() || e.is_request())
+    })
 }
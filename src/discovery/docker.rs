@@ -0,0 +1,149 @@
+// The running containers with a `clouddns.hostname` label, listed again whenever one starts or
+// stops. Docker's API is plain HTTP over its Unix socket.
+
+use super::{add, from_labels, DiscoveredHost};
+use crate::config::DockerDiscovery;
+use anyhow::{bail, Context, Result};
+use log::{debug, info, warn};
+use reqwest::Url;
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::Path};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration};
+
+const LABEL_PREFIX: &str = "clouddns.";
+
+// Compose starts and stops containers in quick succession, they are listed once it settled
+const SETTLE: Duration = Duration::from_secs(1);
+
+// Between attempts to reach Docker
+const RETRY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Container {
+    #[serde(default)]
+    names: Vec<String>,
+    #[serde(default)]
+    labels: BTreeMap<String, String>,
+}
+
+pub(super) fn spawn(
+    config: DockerDiscovery,
+    sender: watch::Sender<Vec<DiscoveredHost>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        info!("Watching the containers of {}", config.socket.display());
+        let mut failing = false;
+        loop {
+            // Listening before listing, so no container starting in between goes unnoticed
+            let events = events(&config.socket).await;
+            match containers(&config.socket).await {
+                Ok(hosts) => {
+                    if failing {
+                        info!("Docker is reachable again");
+                        failing = false;
+                    }
+                    sender.send_if_modified(|current| {
+                        let changed = *current != hosts;
+                        *current = hosts;
+                        changed
+                    });
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!("Failed to list the Docker containers: {:#}", e);
+                        failing = true;
+                    }
+                }
+            }
+
+            match events {
+                Ok(mut events) if !failing => {
+                    let mut line = String::new();
+                    // Ends with the connection, e.g. when Docker restarts
+                    if matches!(events.read_line(&mut line).await, Ok(0) | Err(_)) {
+                        sleep(RETRY).await;
+                        continue;
+                    }
+                    debug!("Docker event: {}", line.trim());
+                    while let Ok(Ok(read)) = timeout(SETTLE, events.read_line(&mut line)).await {
+                        if read == 0 {
+                            break;
+                        }
+                    }
+                }
+                _ => sleep(RETRY).await,
+            }
+        }
+    })
+}
+
+// The hostnames of the running containers
+async fn containers(socket: &Path) -> Result<Vec<DiscoveredHost>> {
+    let filters = serde_json::json!({ "label": ["clouddns.hostname"] });
+    let mut response = get(socket, "/containers/json", &filters).await?;
+    let mut body = Vec::new();
+    response.read_to_end(&mut body).await?;
+    let containers: Vec<Container> =
+        serde_json::from_slice(&body).context("Invalid container list from Docker")?;
+
+    let mut hosts: Vec<DiscoveredHost> = Vec::new();
+    for container in containers {
+        let name = container
+            .names
+            .first()
+            .map(|name| name.trim_start_matches('/'))
+            .unwrap_or("unnamed");
+        let source = format!("container {}", name);
+        add(
+            &mut hosts,
+            from_labels(&container.labels, LABEL_PREFIX, &source),
+        );
+    }
+    Ok(hosts)
+}
+
+// A line of JSON for every container that starts or stops, for as long as the connection lasts
+async fn events(socket: &Path) -> Result<BufReader<UnixStream>> {
+    let filters = serde_json::json!({ "type": ["container"], "event": ["start", "die"] });
+    get(socket, "/events", &filters).await
+}
+
+// The body of the answer to a GET request. HTTP/1.0 keeps Docker from chunking it.
+async fn get(
+    socket: &Path,
+    path: &str,
+    filters: &serde_json::Value,
+) -> Result<BufReader<UnixStream>> {
+    let mut url = Url::parse("http://docker")?.join(path)?;
+    url.query_pairs_mut()
+        .append_pair("filters", &filters.to_string());
+    let target = format!("{}?{}", url.path(), url.query().unwrap_or_default());
+
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("Failed to connect to {}", socket.display()))?;
+    stream
+        .write_all(format!("GET {} HTTP/1.0\r\nHost: docker\r\n\r\n", target).as_bytes())
+        .await?;
+
+    let mut reader = BufReader::new(stream);
+    let mut status = String::new();
+    reader.read_line(&mut status).await?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    if status.split_whitespace().nth(1) != Some("200") {
+        let mut body = String::new();
+        reader.read_to_string(&mut body).await?;
+        bail!("Docker answered {}: {}", status.trim(), body.trim());
+    }
+    Ok(reader)
+}
@@ -0,0 +1,252 @@
+// Ingresses and Services with a `clouddns.io/hostname` annotation, listed again whenever one of
+// them changes. Inside a pod the API server is reached with the pod's service account, which
+// needs to list and watch both.
+
+use super::{add, from_labels, DiscoveredHost};
+use crate::config::KubernetesDiscovery;
+use anyhow::{Context, Result};
+use futures::future::select_all;
+use log::{debug, info, warn};
+use reqwest::{Certificate, Client, Response, Url};
+use serde::Deserialize;
+use std::{collections::BTreeMap, path::PathBuf};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, Duration};
+
+const ANNOTATION_PREFIX: &str = "clouddns.io/";
+
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+// Rolling out a deployment touches its objects several times, they are listed once it settled
+const SETTLE: Duration = Duration::from_secs(1);
+
+// Between attempts to reach the API server
+const RETRY: Duration = Duration::from_secs(30);
+
+// The API server ends watches after this many seconds, they are opened again after listing
+const WATCH_TIMEOUT: u32 = 300;
+
+struct Resource {
+    kind: &'static str,
+    // Of the API group
+    path: &'static str,
+    plural: &'static str,
+}
+
+const RESOURCES: [Resource; 2] = [
+    Resource {
+        kind: "ingress",
+        path: "/apis/networking.k8s.io/v1",
+        plural: "ingresses",
+    },
+    Resource {
+        kind: "service",
+        path: "/api/v1",
+        plural: "services",
+    },
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct List {
+    metadata: ListMetadata,
+    #[serde(default)]
+    items: Vec<Object>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListMetadata {
+    #[serde(default)]
+    resource_version: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Object {
+    metadata: ObjectMetadata,
+}
+
+#[derive(Debug, Deserialize)]
+struct ObjectMetadata {
+    name: String,
+    #[serde(default)]
+    namespace: String,
+    #[serde(default)]
+    annotations: BTreeMap<String, String>,
+}
+
+pub(super) fn spawn(
+    config: KubernetesDiscovery,
+    sender: watch::Sender<Vec<DiscoveredHost>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut failing = false;
+        let api = loop {
+            match Api::new(&config) {
+                Ok(api) => break api,
+                Err(e) => {
+                    if !failing {
+                        warn!("Can't reach the Kubernetes API: {:#}", e);
+                        failing = true;
+                    }
+                    sleep(RETRY).await;
+                }
+            }
+        };
+        info!("Watching the Kubernetes objects of {}", api.server);
+        loop {
+            let versions = match api.hosts().await {
+                Ok((hosts, versions)) => {
+                    if failing {
+                        info!("The Kubernetes API is reachable again");
+                        failing = false;
+                    }
+                    sender.send_if_modified(|current| {
+                        let changed = *current != hosts;
+                        *current = hosts;
+                        changed
+                    });
+                    versions
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!("Failed to list the Kubernetes objects: {:#}", e);
+                        failing = true;
+                    }
+                    sleep(RETRY).await;
+                    continue;
+                }
+            };
+            // A watch ending without a change, once its timeout is over, just lists them again
+            match api.changed(&versions).await {
+                Ok(()) => sleep(SETTLE).await,
+                Err(e) => {
+                    debug!("Kubernetes watch failed: {:#}", e);
+                    sleep(RETRY).await;
+                }
+            }
+        }
+    })
+}
+
+struct Api {
+    client: Client,
+    server: Url,
+    namespace: Option<String>,
+    // Read again for every request, the service account's token being rotated
+    token_file: Option<PathBuf>,
+}
+
+impl Api {
+    fn new(config: &KubernetesDiscovery) -> Result<Self> {
+        let mut builder = Client::builder();
+        #[cfg(feature = "rustls")]
+        {
+            builder = builder.use_rustls_tls();
+        }
+        let (server, token_file) = match &config.api_server {
+            Some(server) => (server.clone(), None),
+            None => {
+                let host = std::env::var("KUBERNETES_SERVICE_HOST")
+                    .context("Not running in a pod, set the API server")?;
+                let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or("443".to_string());
+                let path = format!("{}/ca.crt", SERVICE_ACCOUNT);
+                let pem = std::fs::read(&path)
+                    .with_context(|| format!("Failed to read the cluster's CA from {}", path))?;
+                let certificate = Certificate::from_pem(&pem)
+                    .with_context(|| format!("Invalid CA certificate in {}", path))?;
+                builder = builder.add_root_certificate(certificate);
+                // An IPv6 address needs brackets in a URL
+                let host = match host.contains(':') {
+                    true => format!("[{}]", host),
+                    false => host,
+                };
+                let token_file = PathBuf::from(SERVICE_ACCOUNT).join("token");
+                (format!("https://{}:{}", host, port), Some(token_file))
+            }
+        };
+        Ok(Self {
+            client: builder
+                .build()
+                .context("Failed to set up the Kubernetes client")?,
+            server: Url::parse(&server).context("Invalid Kubernetes API server")?,
+            namespace: config.namespace.clone(),
+            token_file,
+        })
+    }
+
+    fn url(&self, resource: &Resource) -> Result<Url> {
+        let path = match &self.namespace {
+            Some(namespace) => format!(
+                "{}/namespaces/{}/{}",
+                resource.path, namespace, resource.plural
+            ),
+            None => format!("{}/{}", resource.path, resource.plural),
+        };
+        Ok(self.server.join(&path)?)
+    }
+
+    async fn get(&self, url: Url) -> Result<Response> {
+        let mut request = self.client.get(url.clone());
+        if let Some(token_file) = &self.token_file {
+            let token = tokio::fs::read_to_string(token_file)
+                .await
+                .with_context(|| format!("Failed to read {}", token_file.display()))?;
+            request = request.bearer_auth(token.trim());
+        }
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("Failed to reach {}", url))?;
+        Ok(response.error_for_status()?)
+    }
+
+    // The hostnames asked for, and the version of each resource's list to watch from
+    async fn hosts(&self) -> Result<(Vec<DiscoveredHost>, Vec<String>)> {
+        let mut hosts = Vec::new();
+        let mut versions = Vec::new();
+        for resource in &RESOURCES {
+            let list: List = self
+                .get(self.url(resource)?)
+                .await?
+                .json()
+                .await
+                .with_context(|| format!("Invalid list of {}", resource.plural))?;
+            for object in list.items {
+                let source = format!(
+                    "{} {}/{}",
+                    resource.kind, object.metadata.namespace, object.metadata.name
+                );
+                add(
+                    &mut hosts,
+                    from_labels(&object.metadata.annotations, ANNOTATION_PREFIX, &source),
+                );
+            }
+            versions.push(list.metadata.resource_version);
+        }
+        Ok((hosts, versions))
+    }
+
+    // Until an object of any of the resources is added, changed or deleted
+    async fn changed(&self, versions: &[String]) -> Result<()> {
+        let mut watches = Vec::new();
+        for (resource, version) in RESOURCES.iter().zip(versions) {
+            let mut url = self.url(resource)?;
+            url.query_pairs_mut()
+                .append_pair("watch", "true")
+                .append_pair("resourceVersion", version)
+                .append_pair("timeoutSeconds", &WATCH_TIMEOUT.to_string());
+            watches.push(self.get(url).await?);
+        }
+        let events = watches.iter_mut().map(|watch| Box::pin(watch.chunk()));
+        let (event, _, _) = select_all(events).await;
+        if let Some(event) = event? {
+            debug!(
+                "Kubernetes event: {}",
+                String::from_utf8_lossy(&event).trim()
+            );
+        }
+        Ok(())
+    }
+}
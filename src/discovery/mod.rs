@@ -0,0 +1,234 @@
+//! Hostnames other software asks records for, such as labelled Docker containers or the routes of
+//! a reverse proxy. Each source runs in a task of its own and hands over its full set of hostnames
+//! whenever it changes.
+
+#[cfg(unix)]
+mod docker;
+#[cfg(feature = "k8s")]
+mod kubernetes;
+mod proxy;
+
+use crate::config::{names, DiscoveryConfig, Domain, RecordTypes, Zone};
+use futures::future::select_all;
+use log::warn;
+use std::{borrow::Cow, collections::BTreeMap};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+/// A hostname asked for by a container or another source, with the settings it asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct DiscoveredHost {
+    pub hostname: String,
+    pub ttl: Option<u32>,
+    pub proxied: Option<bool>,
+    pub record_type: Option<RecordTypes>,
+    /// Who asked, e.g. `container web-1`
+    pub source: String,
+}
+
+pub(crate) struct Discovery {
+    sources: Vec<watch::Receiver<Vec<DiscoveredHost>>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Discovery {
+    // None when no source is configured
+    pub fn start(config: &DiscoveryConfig) -> Option<Self> {
+        let mut discovery = Self {
+            sources: Vec::new(),
+            tasks: Vec::new(),
+        };
+        if let Some(docker) = &config.docker {
+            #[cfg(unix)]
+            {
+                let (sender, receiver) = watch::channel(Vec::new());
+                discovery.tasks.push(docker::spawn(docker.clone(), sender));
+                discovery.sources.push(receiver);
+            }
+            #[cfg(not(unix))]
+            {
+                let _ = docker;
+                warn!("Docker discovery needs the Docker socket of a Unix host, it is off");
+            }
+        }
+        if let Some(kubernetes) = &config.kubernetes {
+            #[cfg(feature = "k8s")]
+            {
+                let (sender, receiver) = watch::channel(Vec::new());
+                discovery
+                    .tasks
+                    .push(kubernetes::spawn(kubernetes.clone(), sender));
+                discovery.sources.push(receiver);
+            }
+            #[cfg(not(feature = "k8s"))]
+            {
+                let _ = kubernetes;
+                warn!(
+                    "Kubernetes discovery is set, but clouddns was built without the `k8s` feature"
+                );
+            }
+        }
+        let proxies = [
+            (proxy::Proxy::Traefik, &config.traefik),
+            (proxy::Proxy::Caddy, &config.caddy),
+        ];
+        for (proxy, config) in proxies {
+            if let Some(config) = config {
+                let (sender, receiver) = watch::channel(Vec::new());
+                discovery
+                    .tasks
+                    .push(proxy::spawn(proxy, config.clone(), sender));
+                discovery.sources.push(receiver);
+            }
+        }
+        (!discovery.sources.is_empty()).then_some(discovery)
+    }
+
+    // Every hostname asked for, each once, the first source asking for it giving its settings
+    fn hosts(&self) -> Vec<DiscoveredHost> {
+        let mut hosts: Vec<DiscoveredHost> = Vec::new();
+        for source in &self.sources {
+            for host in source.borrow().iter() {
+                if !hosts.iter().any(|other| other.hostname == host.hostname) {
+                    hosts.push(host.clone());
+                }
+            }
+        }
+        hosts
+    }
+}
+
+impl Drop for Discovery {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+// Resolves with every hostname asked for once a source's changed, never without a source
+pub(crate) async fn changed(discovery: &mut Option<Discovery>) -> Vec<DiscoveredHost> {
+    let Some(current) = discovery else {
+        return std::future::pending().await;
+    };
+    let changes = current
+        .sources
+        .iter_mut()
+        .map(|source| Box::pin(source.changed()));
+    let (result, _, _) = select_all(changes).await;
+    if result.is_err() {
+        // A source task ended, which only happens when it's aborted
+        *discovery = None;
+        return std::future::pending().await;
+    }
+    current.hosts()
+}
+
+// Hostnames and their settings from labels such as `clouddns.hostname`, invalid names and settings
+// being left out with a warning
+pub(crate) fn from_labels(
+    labels: &BTreeMap<String, String>,
+    prefix: &str,
+    source: &str,
+) -> Vec<DiscoveredHost> {
+    let Some(hostnames) = labels.get(&format!("{}hostname", prefix)) else {
+        return Vec::new();
+    };
+    let ttl = setting(labels, prefix, "ttl", source, |ttl| {
+        ttl.parse::<u32>().ok().filter(|&ttl| ttl > 0)
+    });
+    let proxied = setting(labels, prefix, "proxied", source, |proxied| {
+        proxied.parse::<bool>().ok()
+    });
+    let record_type = setting(labels, prefix, "type", source, |record_type| {
+        record_type.parse::<RecordTypes>().ok()
+    });
+
+    hostnames
+        .split(',')
+        .map(|hostname| names::normalize(hostname.trim().trim_end_matches('.')))
+        .filter(|hostname| match names::validate_fqdn(hostname) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("Ignoring hostname {:?} of {}: {}", hostname, source, e);
+                false
+            }
+        })
+        .map(|hostname| DiscoveredHost {
+            hostname,
+            ttl,
+            proxied,
+            record_type,
+            source: source.to_string(),
+        })
+        .collect()
+}
+
+// Adds what one container or object asks for, hostnames already taken by another being left out
+// with a warning
+fn add(hosts: &mut Vec<DiscoveredHost>, found: Vec<DiscoveredHost>) {
+    for host in found {
+        match hosts.iter().find(|other| other.hostname == host.hostname) {
+            Some(other) => warn!(
+                "{} asks for {} too, {} already has it",
+                host.source, host.hostname, other.source
+            ),
+            None => hosts.push(host),
+        }
+    }
+}
+
+fn setting<T>(
+    labels: &BTreeMap<String, String>,
+    prefix: &str,
+    name: &str,
+    source: &str,
+    parse: impl Fn(&str) -> Option<T>,
+) -> Option<T> {
+    let value = labels.get(&format!("{}{}", prefix, name))?;
+    let parsed = parse(value.trim());
+    if parsed.is_none() {
+        warn!("Ignoring {}{}={:?} of {}", prefix, name, value, source);
+    }
+    parsed
+}
+
+// The discovered hostnames as domains of the named zone they belong to, the one with the longest
+// name when zones are nested. Hostnames already configured keep their configuration. Returns the
+// hostnames that were added, and those that belong to no zone.
+pub(crate) fn merge(zones: &mut [Zone], hosts: &[DiscoveredHost]) -> (Vec<String>, Vec<String>) {
+    let mut added = Vec::new();
+    let mut outside = Vec::new();
+    for host in hosts {
+        let zone = zones
+            .iter_mut()
+            .filter(|zone| zone.is_enabled())
+            .filter(|zone| {
+                zone.name
+                    .as_deref()
+                    .is_some_and(|name| names::relative(&host.hostname, name).is_some())
+            })
+            .max_by_key(|zone| zone.name.as_deref().map(str::len));
+        let Some(zone) = zone else {
+            outside.push(host.hostname.clone());
+            continue;
+        };
+        let configured = zone
+            .domains
+            .iter()
+            .any(|domain| domain.record_names(zone).contains(&host.hostname));
+        if configured {
+            continue;
+        }
+        zone.domains.push(Domain {
+            name: Cow::Owned(host.hostname.clone()),
+            records: vec![Cow::Borrowed("@")],
+            ttl: host.ttl,
+            proxied: host.proxied,
+            record_type: host.record_type,
+            ..Domain::default()
+        });
+        added.push(host.hostname.clone());
+    }
+    (added, outside)
+}
@@ -0,0 +1,203 @@
+// Hostnames routed by a reverse proxy, read from its API every so often: the `Host` rules of
+// Traefik's HTTP routers, or the `host` matchers of Caddy's routes. Addresses and other names a
+// record can't have are left out.
+
+use super::DiscoveredHost;
+use crate::config::{names, ProxyDiscovery};
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use reqwest::{Client, Url};
+use serde::{de::DeserializeOwned, Deserialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, Duration, MissedTickBehavior};
+
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Proxy {
+    Traefik,
+    Caddy,
+}
+
+impl std::fmt::Display for Proxy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Proxy::Traefik => write!(f, "Traefik"),
+            Proxy::Caddy => write!(f, "Caddy"),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Router {
+    name: String,
+    #[serde(default)]
+    rule: String,
+    #[serde(default)]
+    status: Option<String>,
+}
+
+pub(super) fn spawn(
+    proxy: Proxy,
+    config: ProxyDiscovery,
+    sender: watch::Sender<Vec<DiscoveredHost>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        // The proxy runs next to clouddns, an HTTP proxy in between would only get in the way
+        let client = match Client::builder().no_proxy().build() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Can't read the routes of {}: {:#}", proxy, e);
+                return;
+            }
+        };
+        info!("Reading the routes of {} from {}", proxy, config.url);
+        let mut failing = false;
+        let mut ticks = interval(Duration::from_secs(config.interval));
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            ticks.tick().await;
+            let hosts = match proxy {
+                Proxy::Traefik => traefik(&client, &config.url).await,
+                Proxy::Caddy => caddy(&client, &config.url).await,
+            };
+            match hosts {
+                Ok(hosts) => {
+                    if failing {
+                        info!("{} is reachable again", proxy);
+                        failing = false;
+                    }
+                    sender.send_if_modified(|current| {
+                        let changed = *current != hosts;
+                        *current = hosts;
+                        changed
+                    });
+                }
+                Err(e) => {
+                    if !failing {
+                        warn!("Failed to read the routes of {}: {:#}", proxy, e);
+                        failing = true;
+                    }
+                }
+            }
+        }
+    })
+}
+
+async fn traefik(client: &Client, url: &str) -> Result<Vec<DiscoveredHost>> {
+    // Traefik pages its lists, this one page holds them all
+    let routers: Vec<Router> = get(client, url, "/api/http/routers?per_page=10000").await?;
+    let mut hosts = Vec::new();
+    for router in routers {
+        if router
+            .status
+            .as_deref()
+            .is_some_and(|status| status != "enabled")
+        {
+            continue;
+        }
+        let source = format!("Traefik router {}", router.name);
+        for hostname in rule_hosts(&router.rule) {
+            add(&mut hosts, &hostname, &source);
+        }
+    }
+    Ok(hosts)
+}
+
+async fn caddy(client: &Client, url: &str) -> Result<Vec<DiscoveredHost>> {
+    // null without an HTTP app
+    let servers: Option<BTreeMap<String, Value>> =
+        get(client, url, "/config/apps/http/servers").await?;
+    let mut hosts = Vec::new();
+    for (name, server) in servers.unwrap_or_default() {
+        let source = format!("Caddy server {}", name);
+        let mut hostnames = Vec::new();
+        matched_hosts(&server, &mut hostnames);
+        for hostname in hostnames {
+            add(&mut hosts, &hostname, &source);
+        }
+    }
+    Ok(hosts)
+}
+
+async fn get<T: DeserializeOwned>(client: &Client, url: &str, path: &str) -> Result<T> {
+    let url = Url::parse(url)?.join(path)?;
+    client
+        .get(url.clone())
+        .send()
+        .await
+        .with_context(|| format!("Failed to reach {}", url))?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("Invalid answer from {}", url))
+}
+
+// The names of the `Host` matchers of a Traefik rule, e.g. ``Host(`a.example.com`) && PathPrefix(`/api`)``
+fn rule_hosts(rule: &str) -> Vec<String> {
+    let mut hosts = Vec::new();
+    let mut rest = rule;
+    while let Some(start) = rest.find("Host(") {
+        let (before, after) = (&rest[..start], &rest[start + "Host(".len()..]);
+        let Some(end) = after.find(')') else {
+            break;
+        };
+        // Not the end of another matcher's name
+        if !before.ends_with(|c: char| c.is_ascii_alphanumeric()) {
+            hosts.extend(after[..end].split(',').map(|name| {
+                name.trim()
+                    .trim_matches(|c| matches!(c, '`' | '"' | '\''))
+                    .to_string()
+            }));
+        }
+        rest = &after[end..];
+    }
+    hosts
+}
+
+// The `host` lists of the `match` blocks of Caddy routes, subroutes included
+fn matched_hosts(value: &Value, hosts: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                match (key.as_str(), value) {
+                    ("match", Value::Array(matchers)) => {
+                        let names = matchers
+                            .iter()
+                            .filter_map(|matcher| matcher.get("host")?.as_array())
+                            .flatten()
+                            .filter_map(Value::as_str);
+                        hosts.extend(names.map(str::to_string));
+                    }
+                    _ => matched_hosts(value, hosts),
+                }
+            }
+        }
+        Value::Array(values) => {
+            for value in values {
+                matched_hosts(value, hosts);
+            }
+        }
+        _ => {}
+    }
+}
+
+// The same name often has several routes, e.g. one per entrypoint, the first one counts
+fn add(hosts: &mut Vec<DiscoveredHost>, hostname: &str, source: &str) {
+    let hostname = names::normalize(hostname.trim_end_matches('.'));
+    if let Err(e) = names::validate_fqdn(&hostname) {
+        debug!("Skipping {} of {}: {}", hostname, source, e);
+        return;
+    }
+    if hosts.iter().any(|host| host.hostname == hostname) {
+        return;
+    }
+    hosts.push(DiscoveredHost {
+        hostname,
+        ttl: None,
+        proxied: None,
+        record_type: None,
+        source: source.to_string(),
+    });
+}
@@ -0,0 +1,77 @@
+use crate::config::DnsConfig;
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use hickory_resolver::TokioAsyncResolver;
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use std::net::{IpAddr, SocketAddr};
+
+// Looks names up with the configured resolvers rather than the system's
+pub struct Nameservers {
+    resolver: TokioAsyncResolver,
+}
+
+impl Nameservers {
+    pub fn new(nameservers: &[IpAddr]) -> Self {
+        Self {
+            resolver: resolver(nameservers),
+        }
+    }
+}
+
+// Looks records up before the API is asked about them, with the configured resolvers or the
+// system's
+pub struct Precheck {
+    resolver: Option<TokioAsyncResolver>,
+}
+
+impl Precheck {
+    pub fn new(config: &DnsConfig) -> Option<Self> {
+        config.precheck.then(|| Self {
+            resolver: (!config.nameservers.is_empty()).then(|| resolver(&config.nameservers)),
+        })
+    }
+
+    // Whether the record answers with that address and no other. Any failure means asking the API.
+    pub async fn confirms(&self, record: &str, address: IpAddr) -> bool {
+        // Fully qualified, so no search domain is tried
+        let name = format!("{}.", record.trim_end_matches('.'));
+        let answers: Vec<IpAddr> = match &self.resolver {
+            Some(resolver) => match resolver.lookup_ip(name.as_str()).await {
+                Ok(lookup) => lookup.iter().collect(),
+                Err(_) => return false,
+            },
+            None => match tokio::net::lookup_host((name.as_str(), 0)).await {
+                Ok(addresses) => addresses.map(|address| address.ip()).collect(),
+                Err(_) => return false,
+            },
+        };
+        let mut answers = answers
+            .into_iter()
+            .filter(|answer| answer.is_ipv4() == address.is_ipv4());
+        answers.next() == Some(address) && answers.next().is_none()
+    }
+}
+
+fn resolver(nameservers: &[IpAddr]) -> TokioAsyncResolver {
+    let group = NameServerConfigGroup::from_ips_clear(nameservers, 53, true);
+    let config = ResolverConfig::from_parts(None, Vec::new(), group);
+    TokioAsyncResolver::tokio(config, ResolverOpts::default())
+}
+
+impl Resolve for Nameservers {
+    fn resolve(&self, name: Name) -> Resolving {
+        let resolver = self.resolver.clone();
+        Box::pin(async move {
+            let lookup = resolver.lookup_ip(name.as_str()).await?;
+            // The port is taken from the URL
+            let addrs: Addrs = Box::new(
+                lookup
+                    .into_iter()
+                    .map(|ip| SocketAddr::new(ip, 0))
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            );
+            Ok(addrs)
+        })
+    }
+}
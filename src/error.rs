@@ -0,0 +1,162 @@
+use reqwest::StatusCode;
+use std::{fmt, net::AddrParseError};
+
+// Broad classes of failure, each mapped to its own process exit code so wrapper
+// scripts and service managers can tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    Config,
+    Auth,
+    Detection,
+    Update,
+    PendingChanges,
+    AlreadyRunning,
+    Watchdog,
+}
+
+impl ErrorKind {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            ErrorKind::Config => 3,
+            ErrorKind::Auth => 4,
+            ErrorKind::Detection => 5,
+            ErrorKind::Update => 6,
+            ErrorKind::PendingChanges => 7,
+            ErrorKind::AlreadyRunning => 8,
+            ErrorKind::Watchdog => 9,
+        }
+    }
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ErrorKind::Config => "Configuration error",
+            ErrorKind::Auth => "Authentication failed",
+            ErrorKind::Detection => "Address detection failed",
+            ErrorKind::Update => "Record update failed",
+            ErrorKind::PendingChanges => "Records are out of date",
+            ErrorKind::AlreadyRunning => "Another instance is running",
+            ErrorKind::Watchdog => "Giving up after too many failed cycles",
+        })
+    }
+}
+
+pub trait ResultExt<T> {
+    // Classify an error, unless something closer to its cause already did
+    fn kind(self, kind: ErrorKind) -> anyhow::Result<T>;
+}
+
+impl<T> ResultExt<T> for anyhow::Result<T> {
+    fn kind(self, kind: ErrorKind) -> anyhow::Result<T> {
+        self.map_err(|e| match e.downcast_ref::<ErrorKind>() {
+            Some(_) => e,
+            None => e.context(kind),
+        })
+    }
+}
+
+/// What went wrong, for callers of the library to tell a failure worth retrying from one that
+/// needs attention. The message and the chain of causes are those of the underlying error.
+#[derive(Debug, thiserror::Error)]
+pub enum DdnsError {
+    /// The configuration couldn't be read or isn't valid
+    #[error(transparent)]
+    Config(anyhow::Error),
+    /// The API token couldn't be read, or Cloudflare rejected it
+    #[error(transparent)]
+    Auth(anyhow::Error),
+    /// Cloudflare asked to slow down, beyond what the retries could wait out
+    #[error(transparent)]
+    RateLimited(anyhow::Error),
+    /// A zone or record doesn't exist on Cloudflare
+    #[error(transparent)]
+    NotFound(anyhow::Error),
+    /// Cloudflare or an address source couldn't be reached
+    #[error(transparent)]
+    Network(anyhow::Error),
+    /// An answer didn't make sense, e.g. an address source returned something else than an address
+    #[error(transparent)]
+    Parse(anyhow::Error),
+    /// Anything else
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl DdnsError {
+    /// The error as it was before being classified
+    pub fn inner(&self) -> &anyhow::Error {
+        match self {
+            DdnsError::Config(e)
+            | DdnsError::Auth(e)
+            | DdnsError::RateLimited(e)
+            | DdnsError::NotFound(e)
+            | DdnsError::Network(e)
+            | DdnsError::Parse(e)
+            | DdnsError::Other(e) => e,
+        }
+    }
+
+    /// Whether trying again later may work, without changing the configuration
+    pub fn is_transient(&self) -> bool {
+        matches!(self, DdnsError::RateLimited(_) | DdnsError::Network(_))
+    }
+}
+
+// The first cause that tells what kind of failure it was decides
+impl From<anyhow::Error> for DdnsError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast_ref::<ErrorKind>() {
+            Some(ErrorKind::Config) => return DdnsError::Config(error),
+            Some(ErrorKind::Auth) => return DdnsError::Auth(error),
+            _ => {}
+        }
+        for cause in error.chain() {
+            if let Some(e) = cause.downcast_ref::<reqwest::Error>() {
+                match e.status() {
+                    Some(StatusCode::TOO_MANY_REQUESTS) => return DdnsError::RateLimited(error),
+                    Some(StatusCode::NOT_FOUND) => return DdnsError::NotFound(error),
+                    _ if e.is_decode() => return DdnsError::Parse(error),
+                    _ if e.is_connect() || e.is_timeout() || e.is_request() => {
+                        return DdnsError::Network(error)
+                    }
+                    _ => {}
+                }
+            }
+            if cause.is::<NotFound>() {
+                return DdnsError::NotFound(error);
+            }
+            if cause.is::<Unreachable>() {
+                return DdnsError::Network(error);
+            }
+            if cause.is::<serde_json::Error>() || cause.is::<AddrParseError>() {
+                return DdnsError::Parse(error);
+            }
+        }
+        DdnsError::Other(error)
+    }
+}
+
+/// Cloudflare has no record of that name and type
+#[derive(Debug, thiserror::Error)]
+#[error("{record_type} record not found for domain: {domain}")]
+pub struct NotFound {
+    pub record_type: String,
+    pub domain: String,
+}
+
+/// The provider couldn't be reached, for providers that don't go through HTTP
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct Unreachable(pub String);
+
+// 1 for anything unclassified, clap already uses 2 for usage errors
+pub fn exit_code(error: &anyhow::Error) -> u8 {
+    let error = match error.downcast_ref::<DdnsError>() {
+        Some(e) => e.inner(),
+        None => error,
+    };
+    error
+        .downcast_ref::<ErrorKind>()
+        .map_or(1, ErrorKind::exit_code)
+}
@@ -0,0 +1,210 @@
+// The Windows Event Log, as the `clouddns` source of the Application log. `service install`
+// registers the source; messages then show in the Event Viewer as they were written. The message
+// file is the one .NET ships with every Windows since 8, whose every event ID reads `%1`.
+
+use anyhow::{bail, Result};
+use log::kv::{self, Key, Value, VisitSource};
+use log::{Level, Record};
+use std::ptr;
+use windows_sys::Win32::Foundation::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS, HANDLE};
+use windows_sys::Win32::System::EventLog::{
+    DeregisterEventSource, RegisterEventSourceW, ReportEventW, EVENTLOG_ERROR_TYPE,
+    EVENTLOG_INFORMATION_TYPE, EVENTLOG_WARNING_TYPE,
+};
+use windows_sys::Win32::System::Registry::{
+    RegCloseKey, RegCreateKeyExW, RegDeleteKeyW, RegOpenKeyExW, RegSetValueExW, HKEY,
+    HKEY_LOCAL_MACHINE, KEY_READ, KEY_WRITE, REG_DWORD, REG_EXPAND_SZ, REG_OPTION_NON_VOLATILE,
+};
+
+const SOURCE: &str = "clouddns";
+
+const KEY: &str = r"SYSTEM\CurrentControlSet\Services\EventLog\Application\clouddns";
+
+const MESSAGE_FILE: &str =
+    r"%SystemRoot%\Microsoft.NET\Framework64\v4.0.30319\EventLogMessages.dll";
+
+// Error, warning and information, the types the message file is registered for
+const TYPES_SUPPORTED: u32 = 0x7;
+
+pub struct EventLog(HANDLE);
+
+// The handle is only passed to ReportEventW, which may be called from any thread
+unsafe impl Send for EventLog {}
+unsafe impl Sync for EventLog {}
+
+impl EventLog {
+    pub fn open() -> Result<Self> {
+        if !registered() {
+            bail!(
+                "Cannot log to the Event Log, its {} source isn't registered, run `clouddns \
+                 service install` as an administrator",
+                SOURCE
+            );
+        }
+        let source = wide(SOURCE);
+        // SAFETY: the source name is a NUL-terminated UTF-16 string that outlives the call
+        let handle = unsafe { RegisterEventSourceW(ptr::null(), source.as_ptr()) };
+        if handle.is_null() {
+            bail!(
+                "Failed to open the Event Log: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(Self(handle))
+    }
+
+    // Debug and trace lines are information to the Event Log, which has no lower level
+    pub fn log(&self, record: &Record) {
+        let (r#type, id) = match record.level() {
+            Level::Error => (EVENTLOG_ERROR_TYPE, 1),
+            Level::Warn => (EVENTLOG_WARNING_TYPE, 2),
+            _ => (EVENTLOG_INFORMATION_TYPE, 3),
+        };
+        let mut message = record.args().to_string();
+        // The fields the journal would keep apart, one per line below the message
+        let mut fields = Fields(String::new());
+        if record.key_values().visit(&mut fields).is_ok() && !fields.0.is_empty() {
+            message.push_str("\r\n");
+            message.push_str(&fields.0);
+        }
+        let message = wide(&message);
+        let strings = [message.as_ptr()];
+        // SAFETY: the handle is open until drop, and the one string is NUL-terminated and
+        // outlives the call
+        unsafe {
+            ReportEventW(
+                self.0,
+                r#type,
+                0,
+                id,
+                ptr::null_mut(),
+                1,
+                0,
+                strings.as_ptr(),
+                ptr::null(),
+            );
+        }
+    }
+}
+
+impl Drop for EventLog {
+    fn drop(&mut self) {
+        // SAFETY: the handle came from RegisterEventSourceW and isn't used past this point
+        unsafe {
+            DeregisterEventSource(self.0);
+        }
+    }
+}
+
+struct Fields(String);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push_str(&format!("\r\n{}: {}", key, value));
+        Ok(())
+    }
+}
+
+/// Registers the `clouddns` source, so that the Event Viewer can show its messages. Needs
+/// administrator rights, as `service install` does anyway.
+pub fn register() -> Result<()> {
+    let key = wide(KEY);
+    let mut handle: HKEY = ptr::null_mut();
+    // SAFETY: the key name is NUL-terminated and outlives the call, the handle is written once
+    let status = unsafe {
+        RegCreateKeyExW(
+            HKEY_LOCAL_MACHINE,
+            key.as_ptr(),
+            0,
+            ptr::null(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            ptr::null(),
+            &mut handle,
+            ptr::null_mut(),
+        )
+    };
+    if status != ERROR_SUCCESS {
+        bail!(
+            "Failed to register the {} Event Log source: {}",
+            SOURCE,
+            std::io::Error::from_raw_os_error(status as i32)
+        );
+    }
+    let message_file = wide(MESSAGE_FILE);
+    let types = TYPES_SUPPORTED.to_le_bytes();
+    let values: [(&str, u32, &[u8]); 2] = [
+        ("EventMessageFile", REG_EXPAND_SZ, bytes(&message_file)),
+        ("TypesSupported", REG_DWORD, &types),
+    ];
+    let mut result = Ok(());
+    for (name, r#type, data) in values {
+        let name = wide(name);
+        // SAFETY: the key is open, the name is NUL-terminated and data holds `len` bytes
+        let status = unsafe {
+            RegSetValueExW(
+                handle,
+                name.as_ptr(),
+                0,
+                r#type,
+                data.as_ptr(),
+                data.len() as u32,
+            )
+        };
+        if status != ERROR_SUCCESS {
+            result = Err(anyhow::anyhow!(
+                "Failed to register the {} Event Log source: {}",
+                SOURCE,
+                std::io::Error::from_raw_os_error(status as i32)
+            ));
+            break;
+        }
+    }
+    // SAFETY: the key was opened above
+    unsafe {
+        RegCloseKey(handle);
+    }
+    result
+}
+
+/// Removes the source, the messages already logged are kept but lose their text in the viewer
+pub fn deregister() -> Result<()> {
+    let key = wide(KEY);
+    // SAFETY: the key name is NUL-terminated and outlives the call
+    let status = unsafe { RegDeleteKeyW(HKEY_LOCAL_MACHINE, key.as_ptr()) };
+    match status {
+        ERROR_SUCCESS | ERROR_FILE_NOT_FOUND => Ok(()),
+        _ => bail!(
+            "Failed to remove the {} Event Log source: {}",
+            SOURCE,
+            std::io::Error::from_raw_os_error(status as i32)
+        ),
+    }
+}
+
+// Without the source, the viewer shows that it can't find the description of the messages
+fn registered() -> bool {
+    let key = wide(KEY);
+    let mut handle: HKEY = ptr::null_mut();
+    // SAFETY: the key name is NUL-terminated and outlives the call, the handle is written once
+    let status =
+        unsafe { RegOpenKeyExW(HKEY_LOCAL_MACHINE, key.as_ptr(), 0, KEY_READ, &mut handle) };
+    if status != ERROR_SUCCESS {
+        return false;
+    }
+    // SAFETY: the key was opened above
+    unsafe {
+        RegCloseKey(handle);
+    }
+    true
+}
+
+// NUL-terminated UTF-16, as the wide Win32 functions take it
+fn wide(text: &str) -> Vec<u16> {
+    text.encode_utf16().chain([0]).collect()
+}
+
+fn bytes(text: &[u16]) -> &[u8] {
+    // SAFETY: any u16 slice is a valid u8 slice twice as long
+    unsafe { std::slice::from_raw_parts(text.as_ptr().cast(), text.len() * 2) }
+}
@@ -0,0 +1,56 @@
+use crate::config::RecordType;
+use std::net::IpAddr;
+
+// Events kept for subscribers that fall behind, older ones are dropped first
+pub(crate) const CAPACITY: usize = 64;
+
+/// What the updater did, streamed to embedders through [`CloudflareDdns::subscribe`] to build their
+/// own interface or notifications on. Dry runs report the addresses they detect, but no updates.
+///
+/// [`CloudflareDdns::subscribe`]: crate::CloudflareDdns::subscribe
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DdnsEvent {
+    /// An address was detected, or given instead of detecting it. `source` names where it came
+    /// from.
+    IpDetected {
+        source: String,
+        record_type: RecordType,
+        address: IpAddr,
+    },
+    /// A record was written, `previous` is what it held before
+    RecordUpdated {
+        record: String,
+        record_type: RecordType,
+        address: IpAddr,
+        previous: String,
+    },
+    /// A record of a discovered hostname was created, the provider having none
+    RecordCreated {
+        record: String,
+        record_type: RecordType,
+        address: IpAddr,
+    },
+    /// A record of a discovered hostname was deleted, nothing asking for it anymore. `content` is
+    /// what it held.
+    RecordDeleted {
+        record: String,
+        record_type: RecordType,
+        content: String,
+    },
+    /// A record couldn't be checked or written, `failures` counts the attempts in a row
+    UpdateFailed {
+        record: String,
+        record_type: RecordType,
+        error: String,
+        failures: u32,
+    },
+    /// A record was found changed by someone else since it was last confirmed, and set back.
+    /// Sent after its `RecordUpdated`.
+    DriftDetected {
+        record: String,
+        record_type: RecordType,
+        address: IpAddr,
+        previous: String,
+    },
+}
@@ -0,0 +1,118 @@
+//! A C interface to the updater, for firmwares and programs in other languages. The library is
+//! built with `cargo rustc --release --lib --features ffi --crate-type cdylib` and declared in
+//! `include/clouddns.h`.
+//!
+//! Calls block until done. A handle may be moved between threads but not used by two at once.
+
+use crate::blocking::Ddns;
+use crate::error;
+use std::{
+    cell::RefCell,
+    ffi::{c_char, c_int, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+// What went wrong in the last call that failed on this thread
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Loads the configuration file and reads the API token. `state_file` may be null to keep the
+/// state in memory only. Returns null on failure, see `clouddns_last_error`.
+///
+/// # Safety
+///
+/// Both strings must be null or valid NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn clouddns_new(
+    config_file: *const c_char,
+    state_file: *const c_char,
+) -> *mut Ddns {
+    // SAFETY: the caller passes valid strings, or null
+    let (config_file, state_file) = unsafe { (string(config_file), string(state_file)) };
+    let Some(Ok(config_file)) = config_file else {
+        set_last_error("The configuration file must be a UTF-8 path".to_string());
+        return ptr::null_mut();
+    };
+    let state_file = match state_file {
+        None => None,
+        Some(Ok(state_file)) => Some(state_file),
+        Some(Err(_)) => {
+            set_last_error("The state file must be a UTF-8 path".to_string());
+            return ptr::null_mut();
+        }
+    };
+
+    let created = panic::catch_unwind(|| {
+        Ddns::new(config_file, None).and_then(|ddns| ddns.state(state_file))
+    });
+    match created {
+        Ok(Ok(ddns)) => Box::into_raw(Box::new(ddns)),
+        Ok(Err(e)) => {
+            set_last_error(format!("{:#}", e.inner()));
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("The updater panicked".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// One detection and update pass over every enabled domain, as `clouddns once`. Returns 0 on
+/// success, otherwise the exit code `clouddns once` would have, e.g. 4 when the token was rejected.
+///
+/// # Safety
+///
+/// `ddns` must come from `clouddns_new` and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn clouddns_update_once(ddns: *mut Ddns) -> c_int {
+    // SAFETY: the caller passes a live handle, used by one thread at a time
+    let Some(ddns) = (unsafe { ddns.as_mut() }) else {
+        set_last_error("No updater given".to_string());
+        return 1;
+    };
+    match panic::catch_unwind(AssertUnwindSafe(|| ddns.run_once())) {
+        Ok(Ok(())) => 0,
+        Ok(Err(e)) => {
+            set_last_error(format!("{:#}", e.inner()));
+            c_int::from(error::exit_code(e.inner()))
+        }
+        Err(_) => {
+            set_last_error("The updater panicked".to_string());
+            1
+        }
+    }
+}
+
+/// Releases an updater, null is ignored
+///
+/// # Safety
+///
+/// `ddns` must be null or come from `clouddns_new`, and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn clouddns_free(ddns: *mut Ddns) {
+    if !ddns.is_null() {
+        // SAFETY: the handle was boxed by `clouddns_new` and is given back once
+        drop(unsafe { Box::from_raw(ddns) });
+    }
+}
+
+/// The message of the last call that failed on this thread, or null. It stays valid until the
+/// next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn clouddns_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+// None for null, an error for a string that isn't UTF-8
+unsafe fn string<'a>(value: *const c_char) -> Option<Result<&'a str, std::str::Utf8Error>> {
+    // SAFETY: non-null pointers are valid strings, as the callers require
+    (!value.is_null()).then(|| unsafe { CStr::from_ptr(value) }.to_str())
+}
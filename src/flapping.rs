@@ -0,0 +1,39 @@
+use crate::config::{Flapping, RecordType};
+use std::collections::{HashMap, HashSet, VecDeque};
+use tokio::time::Instant;
+
+// The recent changes of each record, to tell a record changing over and over from an address that
+// moved once
+#[derive(Debug, Default)]
+pub struct Flaps {
+    changes: HashMap<(String, RecordType), VecDeque<Instant>>,
+    // Reported as flapping, until a change finds the record below the limit again
+    reported: HashSet<(String, RecordType)>,
+}
+
+impl Flaps {
+    // Counts a change, and returns the changes within the period when they just reached the limit
+    pub fn changed(
+        &mut self,
+        record: &str,
+        record_type: RecordType,
+        limit: &Flapping,
+        now: Instant,
+    ) -> Option<u32> {
+        let key = (record.to_string(), record_type);
+        let changes = self.changes.entry(key.clone()).or_default();
+        changes.push_back(now);
+        while changes
+            .front()
+            .is_some_and(|&at| now.duration_since(at) > limit.period())
+        {
+            changes.pop_front();
+        }
+        let count = changes.len() as u32;
+        if count < limit.changes {
+            self.reported.remove(&key);
+            return None;
+        }
+        self.reported.insert(key).then_some(count)
+    }
+}
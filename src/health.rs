@@ -0,0 +1,95 @@
+use crate::metrics::PrometheusMetrics;
+use crate::snapshot::Snapshot;
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    routing::get,
+    Router,
+};
+use log::{error, info};
+use std::net::SocketAddr;
+use tokio::{
+    net::TcpListener,
+    sync::watch,
+    task::JoinHandle,
+    time::{Duration, Instant},
+};
+
+// Probes for Docker healthchecks and Kubernetes, and the metrics for Prometheus to scrape,
+// stopped when dropped
+pub struct HealthServer {
+    task: JoinHandle<()>,
+}
+
+#[derive(Clone)]
+struct Probe {
+    snapshot: watch::Receiver<Snapshot>,
+    // Oldest successful check that still counts as ready
+    max_age: Duration,
+    started: Instant,
+}
+
+impl HealthServer {
+    pub async fn start(
+        listen: SocketAddr,
+        snapshot: watch::Receiver<Snapshot>,
+        max_age: Duration,
+        metrics: Option<PrometheusMetrics>,
+    ) -> Result<Self> {
+        let listener = TcpListener::bind(listen)
+            .await
+            .with_context(|| format!("Failed to listen on {} for health probes", listen))?;
+        info!("Serving health probes on http://{}", listener.local_addr()?);
+
+        let probe = Probe {
+            snapshot,
+            max_age,
+            started: Instant::now(),
+        };
+        let mut app = Router::new()
+            .route("/healthz", get(|| async { "ok\n" }))
+            .route("/readyz", get(ready))
+            .with_state(probe);
+        if let Some(metrics) = metrics {
+            app = app.route("/metrics", get(move || async move { exposition(&metrics) }));
+        }
+        let task = tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                error!("Health endpoint stopped: {}", e);
+            }
+        });
+        Ok(Self { task })
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn ready(State(probe): State<Probe>) -> (StatusCode, String) {
+    let last_success = probe.snapshot.borrow().last_success;
+    match last_success {
+        Some(at) if at.elapsed() <= probe.max_age => (StatusCode::OK, "ok\n".to_string()),
+        Some(at) => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("last successful check {}s ago\n", at.elapsed().as_secs()),
+        ),
+        None => (
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!(
+                "no successful check since starting {}s ago\n",
+                probe.started.elapsed().as_secs()
+            ),
+        ),
+    }
+}
+
+fn exposition(metrics: &PrometheusMetrics) -> ([(header::HeaderName, &'static str); 1], String) {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(),
+    )
+}
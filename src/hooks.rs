@@ -0,0 +1,152 @@
+use crate::config::Hooks;
+use crate::notify::Event;
+use log::{debug, info, warn};
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+
+// Runs the hook of each event in turn, so a command restarting a tunnel finishes before the next
+// one starts. Failures are only logged.
+pub async fn run(hooks: &Hooks, events: &[Event]) {
+    for event in events {
+        let hook = match event {
+            Event::Startup { .. } => &hooks.on_startup,
+            Event::IpChanged { .. } => &hooks.on_change,
+            Event::UpdateFailed { .. } | Event::Crashed { .. } => &hooks.on_failure,
+            Event::DriftDetected { .. }
+            | Event::WatchdogTripped { .. }
+            | Event::Flapping { .. }
+            | Event::Summary { .. } => &None,
+        };
+        if let Some(command) = hook {
+            execute(command, event, Duration::from_secs(hooks.timeout)).await;
+        }
+    }
+}
+
+async fn execute(command: &str, event: &Event, limit: Duration) {
+    let name = event.kind().as_str();
+    let mut process = shell(command);
+    process
+        .envs(environment(event))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    // In a group of its own, so whatever the shell started can be killed with it
+    #[cfg(unix)]
+    process.process_group(0);
+
+    info!("Running the {} hook", name);
+    let child = match process.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("Failed to run the {} hook: {}", name, e);
+            return;
+        }
+    };
+    let pid = child.id();
+    match timeout(limit, child.wait_with_output()).await {
+        Err(_) => {
+            #[cfg(unix)]
+            if let Some(pid) = pid {
+                // SAFETY: only sends a signal, to the group created above
+                unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+            }
+            #[cfg(not(unix))]
+            let _ = pid;
+            warn!(
+                "The {} hook was killed after running for {}s",
+                name,
+                limit.as_secs()
+            );
+        }
+        Ok(Err(e)) => warn!("Failed to run the {} hook: {}", name, e),
+        Ok(Ok(output)) if output.status.success() => {
+            debug!(
+                "The {} hook finished: {}",
+                name,
+                String::from_utf8_lossy(&output.stdout).trim()
+            );
+        }
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            match stderr.trim() {
+                "" => warn!("The {} hook failed ({})", name, output.status),
+                stderr => warn!("The {} hook failed ({}): {}", name, output.status, stderr),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("/bin/sh");
+    process.arg("-c").arg(command);
+    process
+}
+
+#[cfg(windows)]
+fn shell(command: &str) -> Command {
+    let mut process = Command::new("cmd");
+    process.arg("/C").arg(command);
+    process
+}
+
+// Fields the event doesn't have are left unset
+fn environment(event: &Event) -> Vec<(&'static str, String)> {
+    let mut env = vec![("EVENT", event.kind().as_str().to_string())];
+    match event {
+        Event::Startup { version } => env.push(("VERSION", version.to_string())),
+        Event::IpChanged {
+            record,
+            record_type,
+            address,
+            previous,
+            ..
+        }
+        | Event::DriftDetected {
+            record,
+            record_type,
+            address,
+            previous,
+            ..
+        } => env.extend([
+            ("DOMAIN", record.clone()),
+            ("RECORD_TYPE", record_type.as_str().to_string()),
+            ("OLD_IP", previous.clone()),
+            ("NEW_IP", address.to_string()),
+        ]),
+        Event::UpdateFailed {
+            record,
+            record_type,
+            error,
+            failures,
+            ..
+        } => env.extend([
+            ("DOMAIN", record.clone()),
+            ("RECORD_TYPE", record_type.as_str().to_string()),
+            ("ERROR", error.clone()),
+            ("FAILURES", failures.to_string()),
+        ]),
+        Event::WatchdogTripped { error, failures } => {
+            env.extend([("ERROR", error.clone()), ("FAILURES", failures.to_string())])
+        }
+        Event::Crashed { error } => env.push(("ERROR", error.clone())),
+        Event::Flapping {
+            record,
+            record_type,
+            changes,
+            ..
+        } => env.extend([
+            ("DOMAIN", record.clone()),
+            ("RECORD_TYPE", record_type.as_str().to_string()),
+            ("CHANGES", changes.to_string()),
+        ]),
+        Event::Summary { .. } => {}
+    }
+    if let Some(tenant) = event.tenant() {
+        env.push(("TENANT", tenant.to_string()));
+    }
+    env
+}
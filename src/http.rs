@@ -0,0 +1,94 @@
+use crate::config::{Config, ProxyConfig};
+use crate::dns::Nameservers;
+use crate::secrets;
+use anyhow::{Context, Result};
+use reqwest::{Certificate, Client, ClientBuilder, NoProxy, Proxy};
+use secrecy::{ExposeSecret, SecretString};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+// Shared by every HTTP request so the proxy settings apply everywhere, replaced when they change
+static CLIENT: RwLock<Option<Client>> = RwLock::new(None);
+
+// Until a proxy is configured, the HTTP_PROXY, HTTPS_PROXY, ALL_PROXY and NO_PROXY variables apply.
+// Clones share the connection pool, so requests to the same host skip the TLS handshake.
+pub fn client() -> Client {
+    if let Some(client) = CLIENT.read().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        return client.clone();
+    }
+    CLIENT
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get_or_insert_with(Client::new)
+        .clone()
+}
+
+pub fn set_client(client: Client) {
+    *CLIENT.write().unwrap_or_else(|e| e.into_inner()) = Some(client);
+}
+
+// A client going through the proxy, trusting the extra certificate authorities and looking names
+// up as configured, or one honoring the environment without them
+pub async fn build(config: &Config) -> Result<Client> {
+    builder(config)
+        .await?
+        .build()
+        .context("Failed to set up the HTTP client")
+}
+
+// The settings of `build`, for clients that need a few of their own on top
+pub(crate) async fn builder(config: &Config) -> Result<ClientBuilder> {
+    let mut builder = Client::builder();
+    #[cfg(feature = "rustls")]
+    {
+        builder = builder.use_rustls_tls();
+    }
+    for path in &config.ca_certificates {
+        let pem = std::fs::read(path)
+            .with_context(|| format!("Failed to read CA certificates from {}", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid CA certificates in {}", path.display()))?;
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+    for (host, addresses) in &config.dns.hosts {
+        let addresses: Vec<SocketAddr> = addresses
+            .iter()
+            .map(|address| SocketAddr::new(*address, 0))
+            .collect();
+        builder = builder.resolve_to_addrs(host, &addresses);
+    }
+    if !config.dns.nameservers.is_empty() {
+        builder = builder.dns_resolver(Arc::new(Nameservers::new(&config.dns.nameservers)));
+    }
+    if let Some(proxy) = &config.proxy {
+        builder = builder.proxy(proxy_for(proxy).await?);
+    }
+    Ok(builder)
+}
+
+async fn proxy_for(config: &ProxyConfig) -> Result<Proxy> {
+    let mut proxy = Proxy::all(&config.url).context("Invalid proxy URL")?;
+    if let Some(username) = &config.username {
+        let password = match &config.password {
+            Some(password) => secrets::resolve(password)
+                .await
+                .context("Failed to read the proxy password")?,
+            None => SecretString::default(),
+        };
+        proxy = proxy.basic_auth(username, password.expose_secret());
+    }
+    Ok(proxy.no_proxy(NoProxy::from_string(&config.no_proxy.join(","))))
+}
+
+// Builds and shares the client for these settings
+pub async fn configure(config: &Config) -> Result<()> {
+    set_client(build(config).await?);
+    Ok(())
+}
+
+// Whether the settings behind the client differ
+pub(crate) fn changed(old: &Config, new: &Config) -> bool {
+    old.proxy != new.proxy || old.ca_certificates != new.ca_certificates || old.dns != new.dns
+}
@@ -0,0 +1,147 @@
+use crate::config::{IpSource, RecordType, RetryPolicy};
+use crate::{http, retry};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Deserialize)]
+struct JsonResponse {
+    ip: String,
+}
+
+/// Where the updater gets the public addresses from, which embedders and tests can replace with
+/// their own
+#[async_trait]
+pub trait Detector: Send + Sync {
+    async fn detect(
+        &self,
+        source: &IpSource,
+        record_type: RecordType,
+        retry: &RetryPolicy,
+    ) -> Result<IpAddr>;
+}
+
+/// Asks the configured sources over HTTP
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HttpDetector;
+
+#[async_trait]
+impl Detector for HttpDetector {
+    async fn detect(
+        &self,
+        source: &IpSource,
+        record_type: RecordType,
+        retry: &RetryPolicy,
+    ) -> Result<IpAddr> {
+        detect(source, record_type, retry).await
+    }
+}
+
+/// Always detects the same addresses, for testing an integration without network access. A family
+/// given no address can't be detected, as on a host without it:
+///
+/// ```
+/// use clouddns::ip::FixedDetector;
+///
+/// let detector = FixedDetector::new().ipv4([192, 0, 2, 7].into());
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FixedDetector {
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+}
+
+impl FixedDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The address of A records
+    pub fn ipv4(mut self, address: Ipv4Addr) -> Self {
+        self.ipv4 = Some(address);
+        self
+    }
+
+    /// The address of AAAA records
+    pub fn ipv6(mut self, address: Ipv6Addr) -> Self {
+        self.ipv6 = Some(address);
+        self
+    }
+}
+
+#[async_trait]
+impl Detector for FixedDetector {
+    async fn detect(
+        &self,
+        _source: &IpSource,
+        record_type: RecordType,
+        _retry: &RetryPolicy,
+    ) -> Result<IpAddr> {
+        let address = match record_type {
+            RecordType::A => self.ipv4.map(IpAddr::from),
+            RecordType::Aaaa => self.ipv6.map(IpAddr::from),
+        };
+        address.with_context(|| format!("No {} address to detect", record_type))
+    }
+}
+
+/// The endpoint queried for addresses of this family
+pub fn source_url(source: &IpSource, record_type: RecordType) -> &str {
+    match (source, record_type) {
+        // ipify seems to be the one with the least restrictions
+        (IpSource::Ipify, RecordType::A) => "https://api.ipify.org?format=json",
+        (IpSource::Ipify, RecordType::Aaaa) => "https://api6.ipify.org?format=json",
+        (IpSource::Icanhazip, RecordType::A) => "https://ipv4.icanhazip.com",
+        (IpSource::Icanhazip, RecordType::Aaaa) => "https://ipv6.icanhazip.com",
+        (IpSource::Cloudflare, RecordType::A) => "https://1.1.1.1/cdn-cgi/trace",
+        (IpSource::Cloudflare, RecordType::Aaaa) => "https://[2606:4700:4700::1111]/cdn-cgi/trace",
+        (IpSource::Url(url), _) => url,
+    }
+}
+
+// Accepts `{"ip": "..."}`, Cloudflare's `key=value` trace format or a bare address
+fn parse_response(body: &str) -> Result<IpAddr> {
+    let body = body.trim();
+
+    let ip = if body.starts_with('{') {
+        serde_json::from_str::<JsonResponse>(body)
+            .context("Unexpected JSON response")?
+            .ip
+    } else if let Some(line) = body.lines().find(|line| line.starts_with("ip=")) {
+        line["ip=".len()..].to_string()
+    } else {
+        body.to_string()
+    };
+
+    ip.trim()
+        .parse()
+        .map_err(|e| anyhow::anyhow!("Failed to parse IP address {:?}: {}", &ip, &e))
+}
+
+/// Asks the source for the public address of the family, retrying as the policy allows
+#[tracing::instrument(skip(retry), fields(source = %source, family = %record_type))]
+pub async fn detect(
+    source: &IpSource,
+    record_type: RecordType,
+    retry: &RetryPolicy,
+) -> Result<IpAddr> {
+    let url = source_url(source, record_type);
+    let body = retry::send(retry, http::client().get(url))
+        .await?
+        .error_for_status()?
+        .text()
+        .await?;
+
+    let ip = parse_response(&body).with_context(|| format!("Invalid response from {}", url))?;
+
+    match (record_type, ip) {
+        (RecordType::A, IpAddr::V4(_)) | (RecordType::Aaaa, IpAddr::V6(_)) => Ok(ip),
+        _ => Err(anyhow::anyhow!(
+            "{} returned {}, which can't be used for an {} record",
+            url,
+            ip,
+            record_type
+        )),
+    }
+}
@@ -0,0 +1,210 @@
+// Leader election between redundant instances through a TXT record at the provider, reading
+// e.g. `clouddns-lease holder=host-a expires=1717171717`. The holder renews it every third of the
+// lease; the others stand by and take it over once it has run out. The provider can't compare and
+// set, so an instance taking the lease reads it back a moment later: of two instances writing at
+// once, the last one keeps it, and of two records created at once, the holder sorting first.
+
+use crate::api::{models::DnsRecordUpdate, DnsApiClient, RecordContent};
+use crate::config::{names, LeaderElection};
+use anyhow::Result;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::time::Duration;
+use tokio::time::{sleep, Instant};
+
+const PREFIX: &str = "clouddns-lease";
+
+// The lowest Cloudflare takes
+const TTL: u32 = 60;
+
+// Between taking the lease and reading it back, for a concurrent write to land
+const SETTLE: Duration = Duration::from_secs(2);
+
+// Writes stop this long before the lease runs out, for clocks that disagree a little
+const CLOCK_SKEW: TimeDelta = TimeDelta::seconds(5);
+
+pub(crate) struct Leader {
+    zone_id: String,
+    record: String,
+    identity: String,
+    lease: Duration,
+    // When the lease this instance holds runs out, None while standing by
+    until: Option<DateTime<Utc>>,
+    // Who holds it otherwise, as last seen
+    holder: Option<String>,
+    next_check: Instant,
+}
+
+struct Lease {
+    record: DnsRecordUpdate,
+    holder: String,
+    expires: DateTime<Utc>,
+}
+
+impl Leader {
+    pub fn new(config: &LeaderElection, zone_id: String, identity: String) -> Self {
+        Self {
+            zone_id,
+            record: names::normalize(&config.record)
+                .trim_end_matches('.')
+                .to_string(),
+            identity,
+            lease: Duration::from_secs(config.lease),
+            until: None,
+            holder: None,
+            next_check: Instant::now(),
+        }
+    }
+
+    pub fn identity(&self) -> &str {
+        &self.identity
+    }
+
+    // Whether records may be written now
+    pub fn leads(&self, now: DateTime<Utc>) -> bool {
+        self.until.is_some_and(|until| now < until - CLOCK_SKEW)
+    }
+
+    pub fn holder(&self) -> Option<&str> {
+        self.holder.as_deref()
+    }
+
+    pub fn next_check(&self) -> Instant {
+        self.next_check
+    }
+
+    // Takes the lease or renews it if no other instance holds it, returns whether this one does.
+    // Until the lease runs out, an instance that can't reach the provider keeps leading.
+    pub async fn check(&mut self, client: &dyn DnsApiClient, now: DateTime<Utc>) -> Result<bool> {
+        self.next_check = Instant::now() + self.lease / 3;
+        let leases = self.leases(client).await?;
+        if let Some(holder) = self.other_holder(&leases, now) {
+            // Records of this instance left from a race it lost
+            for lease in leases.iter().filter(|lease| lease.holder == self.identity) {
+                client
+                    .delete_record(&self.zone_id, &lease.record.id)
+                    .await?;
+            }
+            self.standby(holder);
+            return Ok(false);
+        }
+
+        let expires = now + TimeDelta::from_std(self.lease)?;
+        let content = RecordContent::Txt(format!(
+            "{} holder={} expires={}",
+            PREFIX,
+            self.identity,
+            expires.timestamp()
+        ));
+        let (mine, others): (Vec<&Lease>, Vec<&Lease>) = leases
+            .iter()
+            .partition(|lease| lease.holder == self.identity);
+        // Its own record, or one another instance let run out
+        match mine.first().or(others.first()) {
+            Some(lease) => {
+                client
+                    .update_record(&self.zone_id, &lease.record, &content, TTL, false)
+                    .await?;
+            }
+            None => {
+                client
+                    .create_record(&self.zone_id, &self.record, &content, TTL, false)
+                    .await?;
+            }
+        }
+        for lease in mine.iter().skip(1) {
+            client
+                .delete_record(&self.zone_id, &lease.record.id)
+                .await?;
+        }
+
+        if self.until.is_none() {
+            sleep(SETTLE).await;
+            let leases = self.leases(client).await?;
+            if let Some(holder) = self.other_holder(&leases, now) {
+                self.standby(holder);
+                return Ok(false);
+            }
+            // Deleted meanwhile, it's taken again at the next check
+            if !leases.iter().any(|lease| lease.holder == self.identity) {
+                return Ok(false);
+            }
+        }
+        self.until = Some(expires);
+        self.holder = None;
+        Ok(true)
+    }
+
+    // Lets a standby take over at its next check rather than once the lease runs out
+    pub async fn release(&mut self, client: &dyn DnsApiClient) -> Result<()> {
+        if self.until.take().is_none() {
+            return Ok(());
+        }
+        for lease in self.leases(client).await? {
+            if lease.holder == self.identity {
+                client
+                    .delete_record(&self.zone_id, &lease.record.id)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn standby(&mut self, holder: String) {
+        self.until = None;
+        self.holder = Some(holder);
+    }
+
+    // The instance holding a lease that hasn't run out, unless this one holds one as well and
+    // sorts first
+    fn other_holder(&self, leases: &[Lease], now: DateTime<Utc>) -> Option<String> {
+        let live = |lease: &&Lease| lease.expires > now;
+        let mine = leases
+            .iter()
+            .filter(live)
+            .any(|lease| lease.holder == self.identity);
+        leases
+            .iter()
+            .filter(live)
+            .filter(|lease| lease.holder != self.identity)
+            .map(|lease| lease.holder.clone())
+            .filter(|holder| !mine || *holder < self.identity)
+            .min()
+    }
+
+    async fn leases(&self, client: &dyn DnsApiClient) -> Result<Vec<Lease>> {
+        let records = client.list_records(&self.zone_id).await?;
+        Ok(records
+            .into_iter()
+            .filter(|record| record.r#type == "TXT" && record.name == self.record)
+            .filter_map(|record| {
+                let (holder, expires) = parse(record.content.trim_matches('"'))?;
+                Some(Lease {
+                    record: record.into(),
+                    holder,
+                    expires,
+                })
+            })
+            .collect())
+    }
+}
+
+fn parse(content: &str) -> Option<(String, DateTime<Utc>)> {
+    let mut words = content.split_whitespace();
+    if words.next() != Some(PREFIX) {
+        return None;
+    }
+    let (mut holder, mut expires) = (None, None);
+    for word in words {
+        match word.split_once('=') {
+            Some(("holder", value)) => holder = Some(value.to_string()),
+            Some(("expires", value)) => {
+                expires = value
+                    .parse()
+                    .ok()
+                    .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+            }
+            _ => {}
+        }
+    }
+    Some((holder?, expires?))
+}
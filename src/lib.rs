@@ -0,0 +1,89 @@
+//! Keeps Cloudflare DNS records pointed at the public addresses of the machine it runs on.
+//!
+//! The `clouddns` command is a thin layer over this crate, other programs can embed the updater
+//! the same way, e.g. a router firmware or a home automation daemon:
+//!
+//! ```no_run
+//! use clouddns::CloudflareDdns;
+//!
+//! # async fn example() -> anyhow::Result<()> {
+//! let mut ddns = CloudflareDdns::new("/etc/clouddns/config.toml", None)
+//!     .await?
+//!     .state(Some("/var/lib/clouddns/state.json"))?;
+//! ddns.run(CloudflareDdns::shutdown_signal()).await?;
+//! Ok(())
+//! # }
+//! ```
+//!
+//! [`config`] holds the configuration and its loader, [`api`] the DNS provider clients, [`ip`] the
+//! address detection and [`plan`] what an update would change. Failures come as a [`DdnsError`],
+//! telling e.g. a network error worth retrying from a rejected token. [`prelude`] gathers the
+//! types meant for embedders, which only change with a major release.
+
+mod acme_dns;
+mod admin;
+pub mod api;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod breaker;
+pub mod clock;
+pub mod config;
+mod control;
+mod correlation;
+mod crash;
+pub mod ddns;
+mod discovery;
+mod dns;
+pub mod error;
+#[cfg(windows)]
+#[doc(hidden)]
+pub mod eventlog;
+pub mod events;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod flapping;
+mod health;
+mod hooks;
+pub mod http;
+pub mod ip;
+mod leader;
+pub mod lifecycle;
+mod lock;
+#[doc(hidden)]
+pub mod logging;
+pub mod metrics;
+mod mqtt;
+mod network;
+mod notify;
+pub mod plan;
+pub mod prelude;
+#[doc(hidden)]
+pub mod redact;
+mod retry;
+mod scheduler;
+pub mod secrets;
+mod sentry;
+mod signals;
+pub mod snapshot;
+pub mod state;
+mod status_file;
+#[doc(hidden)]
+pub mod telemetry;
+mod watcher;
+
+#[cfg(not(any(feature = "native-tls", feature = "rustls")))]
+compile_error!("Enable the `native-tls` or `rustls` feature, HTTPS requests need one of them");
+
+pub use api::{CloudflareClient, DnsApiClient, MemoryDnsProvider, RecordContent};
+pub use clock::{Clock, SystemClock};
+pub use config::Config;
+pub use ddns::{CloudflareDdns, CloudflareDdnsBuilder, Control};
+pub use error::{DdnsError, ErrorKind};
+pub use events::DdnsEvent;
+pub use ip::{Detector, FixedDetector, HttpDetector};
+pub use lifecycle::{DdnsHooks, RecordChange};
+pub use metrics::Metrics;
+pub use plan::{Plan, UpdatePlanner};
+pub use snapshot::Snapshot;
+pub use state::StateStore;
+pub use tokio_util::sync::CancellationToken;
@@ -0,0 +1,43 @@
+use crate::config::RecordType;
+use crate::error::DdnsError;
+use async_trait::async_trait;
+use std::net::IpAddr;
+
+/// A record about to be written, or just written
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RecordChange {
+    pub record: String,
+    pub record_type: RecordType,
+    pub address: IpAddr,
+    /// What the record held before
+    pub previous: String,
+    /// What the update changes, e.g. `1.2.3.4 → 5.6.7.8` or `ttl 300 → 60`. Empty when forced.
+    pub changes: Vec<String>,
+}
+
+/// Hooks into the run loop, registered with [`CloudflareDdns::hooks`], for embedders to veto
+/// updates or add side effects of their own. Every method does nothing by default.
+///
+/// Records are checked concurrently, `before_update` and `after_update` may be called for several
+/// records at once.
+///
+/// [`CloudflareDdns::hooks`]: crate::CloudflareDdns::hooks
+#[async_trait]
+pub trait DdnsHooks: Send + Sync {
+    /// Before each pass over the records that are due, `false` skips it
+    async fn before_cycle(&self) -> bool {
+        true
+    }
+
+    /// Before a record is written, `false` leaves it as it is until the next check
+    async fn before_update(&self, _change: &RecordChange) -> bool {
+        true
+    }
+
+    /// After a record was written
+    async fn after_update(&self, _change: &RecordChange) {}
+
+    /// When a pass fails, with the first failure as its cause
+    async fn on_error(&self, _error: &DdnsError) {}
+}
@@ -0,0 +1,85 @@
+use crate::error::{ErrorKind, ResultExt};
+use anyhow::{Context, Result};
+use log::debug;
+use std::{
+    env,
+    fs::{self, File, OpenOptions, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+
+// An exclusive lock held for as long as this value lives, so two daemons can't manage the same
+// configuration and race each other on the API. The operating system releases it when the process
+// exits, however that happens; the file itself is left behind on purpose, removing it would let a
+// third instance lock a new file while the second still holds the old one.
+#[derive(Debug)]
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    pub fn acquire(config_file: &str, lock_file: Option<&str>) -> Result<Self> {
+        let path = match lock_file {
+            Some(path) => PathBuf::from(path),
+            None => default_path(config_file),
+        };
+
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .with_context(|| format!("Failed to open lock file: {}", path.display()))?;
+
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = fs::read_to_string(&path).unwrap_or_default();
+                let holder = match pid.trim() {
+                    "" => "Another clouddns instance".to_string(),
+                    pid => format!("Another clouddns instance (pid {})", pid),
+                };
+                return Err(anyhow::anyhow!(
+                    "{} is already managing {}, lock held on {}",
+                    holder,
+                    config_file,
+                    path.display()
+                ))
+                .kind(ErrorKind::AlreadyRunning);
+            }
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+        }
+
+        file.set_len(0)?;
+        writeln!(file, "{}", process::id())?;
+        debug!("Holding lock on {}", path.display());
+        Ok(Self { _file: file })
+    }
+}
+
+// One lock per configuration file
+fn default_path(config_file: &str) -> PathBuf {
+    let dir = env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir);
+    dir.join(format!("clouddns-{}.lock", instance_name(config_file)))
+}
+
+// Files kept per configuration are named after its absolute path, so they stay readable
+pub fn instance_name(config_file: &str) -> String {
+    let config = Path::new(config_file);
+    let config = fs::canonicalize(config).unwrap_or_else(|_| config.to_path_buf());
+    config
+        .to_string_lossy()
+        .trim_start_matches(['/', '\\'])
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' => '-',
+            c => c,
+        })
+        .collect()
+}
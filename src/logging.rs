@@ -0,0 +1,247 @@
+use crate::config::LogTarget;
+use crate::{correlation, redact};
+use anyhow::Result;
+use chrono::Local;
+use env_logger::{Builder, Target, WriteStyle};
+use log::kv::{self, Key, Source, Value, VisitSource, VisitValue};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::{
+    env,
+    io::Write,
+    sync::{OnceLock, RwLock},
+};
+
+// Set by `init`, the target can then follow the configuration
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+// Filters with env_logger, then writes to stderr or hands over to the native target
+struct Logger {
+    stderr: env_logger::Logger,
+    output: RwLock<(LogTarget, Output)>,
+}
+
+enum Output {
+    Stderr,
+    #[cfg(unix)]
+    Journald(systemd_journal_logger::JournalLog),
+    #[cfg(unix)]
+    Syslog(syslog::BasicLogger),
+    #[cfg(windows)]
+    EventLog(crate::eventlog::EventLog),
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.stderr.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.stderr.matches(record) {
+            return;
+        }
+        // Whatever wrote the line, credentials don't leave the process
+        let message = redact::scrub(&record.args().to_string());
+        // The journal keeps the cycle as a field, plain text lines start with it
+        let cycle = correlation::current();
+        let message = match &cycle {
+            Some(cycle) if !self.to_journal() => format!("[{}] {}", cycle, message),
+            _ => message,
+        };
+        self.write(
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .key_values(&Present(record.key_values(), cycle.as_deref()))
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.stderr.flush();
+    }
+}
+
+// The fields of a record but those without a value, such as a tenant for records that have none,
+// and the cycle it was written in
+struct Present<'a>(&'a dyn Source, Option<&'a str>);
+
+impl Source for Present<'_> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn VisitSource<'kvs>) -> Result<(), kv::Error> {
+        self.0.visit(&mut SkipNull(visitor))?;
+        match self.1 {
+            Some(cycle) => visitor.visit_pair(Key::from("cycle_id"), Value::from(cycle)),
+            None => Ok(()),
+        }
+    }
+}
+
+struct SkipNull<'a, 'kvs>(&'a mut dyn VisitSource<'kvs>);
+
+impl<'kvs> VisitSource<'kvs> for SkipNull<'_, 'kvs> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        match is_null(&value) {
+            true => Ok(()),
+            false => self.0.visit_pair(key, value),
+        }
+    }
+}
+
+fn is_null(value: &Value) -> bool {
+    struct Null(bool);
+
+    impl VisitValue<'_> for Null {
+        fn visit_any(&mut self, _: Value) -> Result<(), kv::Error> {
+            Ok(())
+        }
+
+        fn visit_null(&mut self) -> Result<(), kv::Error> {
+            self.0 = true;
+            Ok(())
+        }
+    }
+
+    let mut null = Null(false);
+    let _ = value.visit(&mut null);
+    null.0
+}
+
+impl Logger {
+    fn write(&self, record: &Record) {
+        let output = self.output.read().unwrap_or_else(|e| e.into_inner());
+        match &output.1 {
+            Output::Stderr => self.stderr.log(record),
+            #[cfg(unix)]
+            Output::Journald(journal) => journal.log(record),
+            #[cfg(unix)]
+            Output::Syslog(syslog) => syslog.log(record),
+            #[cfg(windows)]
+            Output::EventLog(event_log) => event_log.log(record),
+        }
+    }
+
+    fn to_journal(&self) -> bool {
+        let output = self.output.read().unwrap_or_else(|e| e.into_inner());
+        output.0 == LogTarget::Journald
+    }
+}
+
+// Configure the logger from -q/-v and --log-level, falling back to RUST_LOG when
+// none of them is given. --log-level takes env_logger directives, so single
+// modules can be tuned: `--log-level clouddns::ddns=debug,reqwest=off`
+pub fn init(verbose: u8, quiet: u8, log_level: Option<&str>) {
+    let stderr = builder(verbose, quiet, log_level, LevelFilter::Warn).build();
+    let max_level = stderr.filter();
+    let logger = LOGGER.get_or_init(|| Logger {
+        stderr,
+        output: RwLock::new((LogTarget::Stderr, Output::Stderr)),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(max_level);
+    }
+}
+
+// Switch to the target from the configuration. Views logging into a pipe keep it.
+pub(crate) fn set_target(target: LogTarget) -> Result<()> {
+    let Some(logger) = LOGGER.get() else {
+        return Ok(());
+    };
+    if logger.output.read().unwrap_or_else(|e| e.into_inner()).0 == target {
+        return Ok(());
+    }
+    let output = open(target)?;
+    *logger.output.write().unwrap_or_else(|e| e.into_inner()) = (target, output);
+    Ok(())
+}
+
+#[cfg(unix)]
+fn open(target: LogTarget) -> Result<Output> {
+    use anyhow::{bail, Context};
+    use std::path::Path;
+
+    match target {
+        LogTarget::Stderr => Ok(Output::Stderr),
+        LogTarget::Journald => {
+            // Messages sent to a missing journal would be dropped silently
+            if !Path::new("/run/systemd/journal/socket").exists() {
+                bail!("Cannot log to journald, it isn't running");
+            }
+            let journal = systemd_journal_logger::JournalLog::new()
+                .context("Failed to connect to journald")?
+                .with_syslog_identifier("clouddns".to_string());
+            Ok(Output::Journald(journal))
+        }
+        LogTarget::Syslog => {
+            let formatter = syslog::Formatter3164 {
+                facility: syslog::Facility::LOG_DAEMON,
+                hostname: None,
+                process: "clouddns".to_string(),
+                pid: std::process::id(),
+            };
+            let logger = syslog::unix(formatter).context("Failed to connect to syslog")?;
+            Ok(Output::Syslog(syslog::BasicLogger::new(logger)))
+        }
+        LogTarget::Eventlog => bail!("Logging to the Event Log is only available on Windows"),
+    }
+}
+
+#[cfg(not(unix))]
+fn open(target: LogTarget) -> Result<Output> {
+    match target {
+        LogTarget::Stderr => Ok(Output::Stderr),
+        #[cfg(windows)]
+        LogTarget::Eventlog => Ok(Output::EventLog(crate::eventlog::EventLog::open()?)),
+        _ => anyhow::bail!(
+            "Logging to {} is not supported on this platform",
+            format!("{:?}", target).to_lowercase()
+        ),
+    }
+}
+
+// Same filters, but logging plain `HH:MM:SS LEVEL message` lines into `pipe`, from the info
+// level by default, for views that own the terminal
+pub fn init_pipe(verbose: u8, quiet: u8, log_level: Option<&str>, pipe: Box<dyn Write + Send>) {
+    builder(verbose, quiet, log_level, LevelFilter::Info)
+        .target(Target::Pipe(pipe))
+        .write_style(WriteStyle::Never)
+        .format(|buf, record| {
+            writeln!(
+                buf,
+                "{} {:<5} {}",
+                Local::now().format("%H:%M:%S"),
+                record.level(),
+                redact::scrub(&record.args().to_string())
+            )
+        })
+        .init();
+}
+
+fn builder(verbose: u8, quiet: u8, log_level: Option<&str>, default: LevelFilter) -> Builder {
+    let mut builder = Builder::new();
+
+    if verbose == 0 && quiet == 0 && log_level.is_none() {
+        match env::var("RUST_LOG") {
+            Ok(filters) => builder.parse_filters(&filters),
+            Err(_) => builder.filter_level(default),
+        };
+    } else {
+        builder.filter_level(level(verbose, quiet, default));
+        if let Some(filters) = log_level {
+            builder.parse_filters(filters);
+        }
+    }
+    builder
+}
+
+fn level(verbose: u8, quiet: u8, default: LevelFilter) -> LevelFilter {
+    match (verbose, quiet) {
+        (0, 0) => default,
+        (0, 1) => LevelFilter::Error,
+        (0, _) => LevelFilter::Off,
+        (1, _) => LevelFilter::Info,
+        (2, _) => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    }
+}
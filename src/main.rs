@@ -1,16 +1,215 @@
-mod api;
-mod config;
-mod ddns;
+mod cli;
+mod commands;
+#[cfg(windows)]
+mod service;
+
 use anyhow::Result;
-use ddns::CloudflareDdns;
-use tokio;
+use clap::Parser;
+#[cfg(windows)]
+use cli::ServiceCommand;
+use cli::{Cli, Command, ConfigCommand, HistoryCommand};
+use clouddns::{api, error, logging, redact, telemetry, CloudflareDdns};
+use std::process::ExitCode;
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    // Initialize logging
-    env_logger::init();
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    // The dashboard shows the log itself
+    if !matches!(cli.command, Some(Command::Watch)) {
+        logging::init(cli.verbose, cli.quiet, cli.log_level.as_deref());
+    }
+    // Flushes the remaining spans when main returns
+    let _telemetry = telemetry::init().unwrap_or_else(|e| {
+        log::warn!("Tracing export disabled: {:#}", e);
+        None
+    });
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", redact::scrub(&format!("{:?}", e)));
+            ExitCode::from(error::exit_code(&e))
+        }
+    }
+}
 
-    // Create and run the DDNS updater
-    let mut ddns = CloudflareDdns::new("config.toml").await?;
-    ddns.run(CloudflareDdns::shutdown_signal()).await
+async fn run(cli: Cli) -> Result<()> {
+    // Before the provider's client is created, so it goes through the cassette
+    if let Some(path) = &cli.record_api {
+        api::cassette::record(path);
+    }
+    if let Some(path) = &cli.replay_api {
+        api::cassette::replay(path)?;
+    }
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            // Create and run the DDNS updater
+            let mut ddns = CloudflareDdns::new(&cli.config, cli.profile.as_deref())
+                .await?
+                .dry_run(cli.dry_run)
+                .ip_override(&cli.ip_override)?
+                .lock(cli.lock_file.as_deref())?
+                .state(cli.state_file.as_deref())?;
+            Ok(ddns.run(CloudflareDdns::shutdown_signal()).await?)
+        }
+        Command::Once => {
+            let mut ddns = CloudflareDdns::new(&cli.config, cli.profile.as_deref())
+                .await?
+                .dry_run(cli.dry_run)
+                .ip_override(&cli.ip_override)?
+                .lock(cli.lock_file.as_deref())?
+                .state(cli.state_file.as_deref())?;
+            Ok(ddns.run_once(false).await?)
+        }
+        Command::ForceUpdate => {
+            let mut ddns = CloudflareDdns::new(&cli.config, cli.profile.as_deref())
+                .await?
+                .dry_run(cli.dry_run)
+                .ip_override(&cli.ip_override)?
+                .lock(cli.lock_file.as_deref())?
+                .state(cli.state_file.as_deref())?;
+            Ok(ddns.run_once(true).await?)
+        }
+        Command::Watch => {
+            commands::watch::watch(commands::watch::Options {
+                config_file: &cli.config,
+                profile: cli.profile.as_deref(),
+                dry_run: cli.dry_run,
+                lock_file: cli.lock_file.as_deref(),
+                state_file: cli.state_file.as_deref(),
+                ip_override: &cli.ip_override,
+                verbose: cli.verbose,
+                quiet: cli.quiet,
+                log_level: cli.log_level.as_deref(),
+            })
+            .await
+        }
+        #[cfg(unix)]
+        Command::Ctl { socket, action } => {
+            commands::ctl::ctl(
+                &cli.config,
+                cli.profile.as_deref(),
+                socket.as_deref(),
+                action,
+            )
+            .await
+        }
+        Command::Init => commands::init::init(&cli.config).await,
+        Command::List { output } => {
+            commands::list::list(&cli.config, cli.profile.as_deref(), output)
+        }
+        Command::Status { tenant, output } => {
+            let profile = cli.profile.as_deref();
+            commands::status::status(&cli.config, profile, tenant.as_deref(), output).await
+        }
+        Command::Diff { output } => {
+            commands::diff::diff(&cli.config, cli.profile.as_deref(), output).await
+        }
+        Command::Plan {
+            out,
+            output,
+            detailed_exitcode,
+        } => {
+            let options = commands::plan::PlanOptions {
+                ip_override: &cli.ip_override,
+                out: out.as_deref(),
+                output,
+                detailed_exitcode,
+            };
+            commands::plan::plan(&cli.config, cli.profile.as_deref(), options).await
+        }
+        Command::Apply { plan, auto_approve } => {
+            commands::plan::apply(
+                &cli.config,
+                cli.profile.as_deref(),
+                plan.as_deref(),
+                &cli.ip_override,
+                auto_approve,
+            )
+            .await
+        }
+        Command::History { action, query } => {
+            let query = match action {
+                Some(HistoryCommand::Query(query)) => query,
+                None => query,
+            };
+            commands::history::history(
+                &cli.config,
+                cli.profile.as_deref(),
+                cli.state_file.as_deref(),
+                &query,
+            )
+        }
+        Command::Ip {
+            source,
+            ipv4,
+            ipv6,
+            output,
+        } => commands::ip::ip(source, ipv4, ipv6, output).await,
+        Command::Add {
+            zone,
+            record,
+            zone_id,
+            record_type,
+            proxied,
+            ttl,
+            update,
+            force,
+        } => {
+            let new = commands::edit::NewRecord {
+                zone,
+                record,
+                zone_id,
+                record_type,
+                proxied,
+                ttl,
+            };
+            commands::edit::add(&cli.config, new, update, force).await
+        }
+        Command::Remove {
+            zone,
+            record,
+            force,
+        } => commands::edit::remove(&cli.config, &zone, &record, force),
+        Command::Validate { online, output } => {
+            commands::validate::validate(&cli.config, cli.profile.as_deref(), online, output).await
+        }
+        Command::Doctor => commands::doctor::doctor(&cli.config, cli.profile.as_deref()).await,
+        Command::Schema => commands::schema(),
+        Command::Config {
+            action: ConfigCommand::Example,
+        } => commands::example(),
+        Command::MigrateConfig {
+            input,
+            output,
+            format,
+        } => commands::migrate::migrate_config(
+            input.as_deref().unwrap_or(&cli.config),
+            output.as_deref(),
+            format,
+        ),
+        Command::ImportDdclient {
+            input,
+            output,
+            format,
+            online,
+        } => commands::import::import_ddclient(&input, output.as_deref(), format, online).await,
+        Command::ImportInadyn {
+            input,
+            output,
+            format,
+            online,
+        } => commands::import::import_inadyn(&input, output.as_deref(), format, online).await,
+        Command::Completions { shell } => commands::completions(shell),
+        #[cfg(windows)]
+        Command::Service { action } => match action {
+            ServiceCommand::Install => service::install(&cli.config, cli.profile.as_deref()),
+            ServiceCommand::Uninstall => service::uninstall(),
+            ServiceCommand::Run => service::run().await,
+        },
+        Command::SystemdUnit { token_file } => {
+            commands::systemd::unit(&cli.config, cli.profile.as_deref(), token_file.as_deref())
+        }
+        Command::Token { action } => commands::token::token(action).await,
+    }
 }
@@ -0,0 +1,403 @@
+use crate::config::{PushgatewayConfig, RetryPolicy, StatsdConfig, StatsdFormat};
+use crate::{http, retry, secrets};
+use anyhow::{Context, Result};
+use reqwest::{header, Url};
+use secrecy::ExposeSecret;
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket},
+    sync::{Arc, Mutex},
+};
+
+/// Where the updater reports what it does as numbers, for embedders to send into their own
+/// metrics system. Names are Prometheus style, e.g. `clouddns_records_updated_total`, and labels
+/// come as name/value pairs:
+///
+/// - `clouddns_cycles_total{result}`, counter of passes over the records due, `success` or `failure`
+/// - `clouddns_cycle_duration_seconds`, histogram of how long they took
+/// - `clouddns_last_success_timestamp_seconds`, gauge of when the last one succeeded
+/// - `clouddns_records_updated_total{record,type}`, counter of updates written
+/// - `clouddns_record_update_failures_total{record,type}`, counter of updates that failed
+/// - `clouddns_record_last_success_timestamp_seconds{record,type}`, gauge of when the record was
+///   last found in sync or updated
+/// - `clouddns_record_last_change_timestamp_seconds{record,type}`, gauge of when it was last changed
+/// - `clouddns_record_status{record,type,status}`, gauge of 1 for the status the record is in and
+///   0 for the others, `in_sync`, `updated`, `pending`, `deferred` or `failed`
+/// - `clouddns_build_info{version,git_sha}`, gauge of 1
+///
+/// The record metrics of a tenant's zones have a `tenant` label as well.
+///
+/// Methods are called from the update loop and must not block.
+pub trait Metrics: Send + Sync {
+    /// Adds `value` to a counter
+    fn counter(&self, name: &str, labels: &[(&str, &str)], value: u64);
+
+    /// Sets a gauge
+    fn gauge(&self, name: &str, labels: &[(&str, &str)], value: f64);
+
+    /// Records one observation, e.g. a duration in seconds
+    fn histogram(&self, name: &str, labels: &[(&str, &str)], value: f64);
+
+    /// Drops the series of a metric whose labels start with `labels`, e.g. those of a record no
+    /// longer managed. Sinks keeping nothing have nothing to drop.
+    fn remove(&self, _name: &str, _labels: &[(&str, &str)]) {}
+}
+
+/// Drops everything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopMetrics;
+
+impl Metrics for NoopMetrics {
+    fn counter(&self, _name: &str, _labels: &[(&str, &str)], _value: u64) {}
+    fn gauge(&self, _name: &str, _labels: &[(&str, &str)], _value: f64) {}
+    fn histogram(&self, _name: &str, _labels: &[(&str, &str)], _value: f64) {}
+}
+
+// Upper bounds of the histogram buckets, in seconds, from a quick API call to a slow cycle
+const BUCKETS: [f64; 11] = [0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// Keeps the metrics in memory and renders them in the Prometheus text format, as served on
+/// `/metrics` next to the health probes. Clones share the same values.
+#[derive(Debug, Clone, Default)]
+pub struct PrometheusMetrics {
+    families: Arc<Mutex<BTreeMap<String, Family>>>,
+}
+
+#[derive(Debug)]
+struct Family {
+    kind: &'static str,
+    // By their rendered labels, e.g. `record="home.example.com",type="A"`
+    series: BTreeMap<String, Value>,
+}
+
+#[derive(Debug)]
+enum Value {
+    Counter(u64),
+    Gauge(f64),
+    Histogram {
+        buckets: [u64; BUCKETS.len()],
+        count: u64,
+        sum: f64,
+    },
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every metric recorded so far, in the text exposition format
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let _ = writeln!(out, "# TYPE {} {}", name, family.kind);
+            for (labels, value) in &family.series {
+                match value {
+                    Value::Counter(value) => {
+                        let _ = writeln!(out, "{}{} {}", name, braced(labels), value);
+                    }
+                    Value::Gauge(value) => {
+                        let _ = writeln!(out, "{}{} {}", name, braced(labels), value);
+                    }
+                    Value::Histogram {
+                        buckets,
+                        count,
+                        sum,
+                    } => {
+                        for (bound, count) in BUCKETS.iter().zip(buckets) {
+                            let le = with_label(labels, &format!("le=\"{}\"", bound));
+                            let _ = writeln!(out, "{}_bucket{} {}", name, le, count);
+                        }
+                        let le = with_label(labels, "le=\"+Inf\"");
+                        let _ = writeln!(out, "{}_bucket{} {}", name, le, count);
+                        let _ = writeln!(out, "{}_sum{} {}", name, braced(labels), sum);
+                        let _ = writeln!(out, "{}_count{} {}", name, braced(labels), count);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // Creates the series at zero if needed, then changes it
+    fn update(
+        &self,
+        name: &str,
+        kind: &'static str,
+        labels: &[(&str, &str)],
+        change: impl FnOnce(&mut Value),
+    ) {
+        let mut families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        let family = families.entry(name.to_string()).or_insert_with(|| Family {
+            kind,
+            series: BTreeMap::new(),
+        });
+        // A name reused for another kind of metric starts over
+        if family.kind != kind {
+            family.kind = kind;
+            family.series.clear();
+        }
+        let labels = rendered(labels);
+        let value = family.series.entry(labels).or_insert_with(|| match kind {
+            "counter" => Value::Counter(0),
+            "gauge" => Value::Gauge(0.0),
+            _ => Value::Histogram {
+                buckets: [0; BUCKETS.len()],
+                count: 0,
+                sum: 0.0,
+            },
+        });
+        change(value);
+    }
+}
+
+impl Metrics for PrometheusMetrics {
+    fn counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.update(name, "counter", labels, |counter| {
+            if let Value::Counter(total) = counter {
+                *total += value;
+            }
+        });
+    }
+
+    fn gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, "gauge", labels, |gauge| *gauge = Value::Gauge(value));
+    }
+
+    fn remove(&self, name: &str, labels: &[(&str, &str)]) {
+        let mut families = self.families.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(family) = families.get_mut(name) else {
+            return;
+        };
+        let prefix = rendered(labels);
+        family.series.retain(|labels, _| {
+            labels
+                .strip_prefix(&prefix)
+                .is_none_or(|rest| !rest.is_empty() && !rest.starts_with(','))
+        });
+        if family.series.is_empty() {
+            families.remove(name);
+        }
+    }
+
+    fn histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, "histogram", labels, |histogram| {
+            if let Value::Histogram {
+                buckets,
+                count,
+                sum,
+            } = histogram
+            {
+                for (bound, bucket) in BUCKETS.iter().zip(buckets.iter_mut()) {
+                    if value <= *bound {
+                        *bucket += 1;
+                    }
+                }
+                *count += 1;
+                *sum += value;
+            }
+        });
+    }
+}
+
+/// Sends every metric to a StatsD agent as it's recorded, one UDP datagram each. Datagrams that
+/// can't be sent right away are dropped rather than waited for.
+#[derive(Debug)]
+pub struct StatsdMetrics {
+    socket: UdpSocket,
+    prefix: String,
+    format: StatsdFormat,
+}
+
+impl StatsdMetrics {
+    /// Resolves the agent's address once, a later change of address takes a restart
+    pub async fn connect(config: &StatsdConfig) -> Result<Self> {
+        let address = tokio::net::lookup_host(&config.address)
+            .await
+            .ok()
+            .and_then(|mut addresses| addresses.next())
+            .with_context(|| format!("Failed to resolve the StatsD agent {}", config.address))?;
+        let local = match address {
+            SocketAddr::V4(_) => SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            SocketAddr::V6(_) => SocketAddr::from((Ipv6Addr::UNSPECIFIED, 0)),
+        };
+        let socket = UdpSocket::bind(local)
+            .and_then(|socket| socket.connect(address).map(|()| socket))
+            .and_then(|socket| socket.set_nonblocking(true).map(|()| socket))
+            .with_context(|| format!("Failed to set up a socket to {}", address))?;
+        Ok(Self {
+            socket,
+            prefix: config.prefix.clone().unwrap_or_default(),
+            format: config.format,
+        })
+    }
+
+    fn send(&self, name: &str, labels: &[(&str, &str)], value: &str, kind: &str) {
+        let mut line = format!("{}{}", self.prefix, name);
+        match self.format {
+            StatsdFormat::Dogstatsd => {
+                let _ = write!(line, ":{}|{}", value, kind);
+                let tags: Vec<String> = labels
+                    .iter()
+                    .map(|(name, value)| format!("{}:{}", name, statsd_safe(value)))
+                    .collect();
+                if !tags.is_empty() {
+                    let _ = write!(line, "|#{}", tags.join(","));
+                }
+            }
+            StatsdFormat::Statsd => {
+                for (_, value) in labels {
+                    let _ = write!(line, ".{}", statsd_safe(value).replace('.', "_"));
+                }
+                let _ = write!(line, ":{}|{}", value, kind);
+            }
+        }
+        // Nothing to do about a full buffer or an agent that's away, the next value will do
+        let _ = self.socket.send(line.as_bytes());
+    }
+}
+
+impl Metrics for StatsdMetrics {
+    fn counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        self.send(name, labels, &value.to_string(), "c");
+    }
+
+    fn gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.send(name, labels, &value.to_string(), "g");
+    }
+
+    // Plain StatsD has no histograms, durations are sent as timings in milliseconds
+    fn histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        match (self.format, name.strip_suffix("_seconds")) {
+            (StatsdFormat::Statsd, Some(stem)) => {
+                let name = format!("{}_milliseconds", stem);
+                self.send(&name, labels, &(value * 1000.0).to_string(), "ms");
+            }
+            (StatsdFormat::Statsd, None) => self.send(name, labels, &value.to_string(), "ms"),
+            (StatsdFormat::Dogstatsd, _) => self.send(name, labels, &value.to_string(), "h"),
+        }
+    }
+}
+
+// Hands everything recorded to a Pushgateway. POST replaces the metrics of the group by name only,
+// so a failed run leaves the last success of an earlier one in place.
+pub(crate) async fn push(
+    config: &PushgatewayConfig,
+    retry: &RetryPolicy,
+    metrics: &PrometheusMetrics,
+) -> Result<()> {
+    let mut grouping = config.grouping.clone();
+    if !grouping.contains_key("instance") {
+        let host = hostname::get()
+            .ok()
+            .and_then(|host| host.into_string().ok())
+            .unwrap_or_else(|| "clouddns".to_string());
+        grouping.insert("instance".to_string(), host);
+    }
+    let mut url = Url::parse(&config.url)?;
+    url.path_segments_mut()
+        .map_err(|()| anyhow::anyhow!("{} can't have a path", config.url))?
+        .pop_if_empty()
+        .extend(["metrics", "job", &config.job])
+        .extend(grouping.iter().flat_map(|(name, value)| [name, value]));
+
+    let mut request = http::client()
+        .post(url)
+        .header(header::CONTENT_TYPE, "text/plain; version=0.0.4")
+        .body(metrics.render());
+    if let Some(username) = &config.username {
+        let password = match &config.password {
+            Some(password) => Some(
+                secrets::resolve(password)
+                    .await
+                    .context("Failed to read the Pushgateway password")?,
+            ),
+            None => None,
+        };
+        request = request.basic_auth(
+            username,
+            password.as_ref().map(|password| password.expose_secret()),
+        );
+    }
+    retry::send(retry, request)
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Failed to push the metrics to the Pushgateway")?;
+    Ok(())
+}
+
+// Hands every metric to each of several sinks
+pub(crate) struct Tee(pub Vec<Arc<dyn Metrics>>);
+
+impl Metrics for Tee {
+    fn counter(&self, name: &str, labels: &[(&str, &str)], value: u64) {
+        for metrics in &self.0 {
+            metrics.counter(name, labels, value);
+        }
+    }
+
+    fn gauge(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        for metrics in &self.0 {
+            metrics.gauge(name, labels, value);
+        }
+    }
+
+    fn histogram(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        for metrics in &self.0 {
+            metrics.histogram(name, labels, value);
+        }
+    }
+
+    fn remove(&self, name: &str, labels: &[(&str, &str)]) {
+        for metrics in &self.0 {
+            metrics.remove(name, labels);
+        }
+    }
+}
+
+// What is running, for dashboards to tell versions apart across a fleet
+pub(crate) fn build_info(metrics: &dyn Metrics) {
+    let labels = [
+        ("version", env!("CARGO_PKG_VERSION")),
+        ("git_sha", env!("CLOUDDNS_GIT_SHA")),
+    ];
+    metrics.gauge("clouddns_build_info", &labels, 1.0);
+}
+
+// The characters separating the parts of a StatsD line can't appear in a value
+fn statsd_safe(value: &str) -> String {
+    value.replace([':', '|', ',', '#', '@', '\n'], "_")
+}
+
+// As series are keyed, e.g. `record="home.example.com",type="A"`
+fn rendered(labels: &[(&str, &str)]) -> String {
+    labels
+        .iter()
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn braced(labels: &str) -> String {
+    match labels.is_empty() {
+        true => String::new(),
+        false => format!("{{{}}}", labels),
+    }
+}
+
+fn with_label(labels: &str, label: &str) -> String {
+    match labels.is_empty() {
+        true => format!("{{{}}}", label),
+        false => format!("{{{},{}}}", labels, label),
+    }
+}
+
+// Label values are quoted, with backslashes, quotes and newlines escaped
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,284 @@
+use crate::config::mqtt::MqttConfig;
+use crate::secrets;
+use crate::snapshot::Snapshot;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, SecondsFormat};
+use log::{debug, info, warn};
+use rumqttc::{
+    AsyncClient, Event, EventLoop, LastWill, MqttOptions, Outgoing, Packet, QoS, Transport,
+};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::json;
+use std::collections::BTreeMap;
+use tokio::{
+    sync::watch,
+    task::JoinHandle,
+    time::{sleep, Duration, Instant},
+};
+
+// Publishes the snapshot as retained messages, with Home Assistant discovery, until dropped
+pub struct MqttPublisher {
+    client: AsyncClient,
+    status_topic: String,
+    _task: JoinHandle<()>,
+}
+
+struct Publisher {
+    client: AsyncClient,
+    topic: String,
+    // Identifies this instance in Home Assistant
+    node: String,
+    discovery_prefix: Option<String>,
+    // What the broker holds, so only changes are sent
+    published: BTreeMap<String, String>,
+    last_success: Option<(Instant, DateTime<Local>)>,
+}
+
+impl MqttPublisher {
+    pub async fn start(config: &MqttConfig, snapshot: watch::Receiver<Snapshot>) -> Result<Self> {
+        let url = reqwest::Url::parse(&config.url).context("Invalid MQTT URL")?;
+        let tls = url.scheme() == "mqtts";
+        let host = url.host_str().unwrap_or_default().to_string();
+        let port = url.port().unwrap_or(if tls { 8883 } else { 1883 });
+
+        let node: String = hostname::get()
+            .ok()
+            .and_then(|host| host.into_string().ok())
+            .and_then(|host| host.split('.').next().map(str::to_string))
+            .unwrap_or_else(|| "clouddns".to_string())
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let topic = config
+            .topic
+            .clone()
+            .unwrap_or_else(|| format!("clouddns/{}", node));
+        let status_topic = format!("{}/status", topic);
+
+        let mut options = MqttOptions::new(format!("clouddns-{}", node), &host, port);
+        options.set_keep_alive(Duration::from_secs(30));
+        // The broker marks the sensors unavailable when the connection drops
+        options.set_last_will(LastWill::new(
+            &status_topic,
+            "offline",
+            QoS::AtLeastOnce,
+            true,
+        ));
+        if let Some(username) = &config.username {
+            let password = match &config.password {
+                Some(password) => secrets::resolve(password)
+                    .await
+                    .context("Failed to read the MQTT password")?,
+                None => SecretString::default(),
+            };
+            // The client keeps its own copy, for reconnecting
+            options.set_credentials(username, password.expose_secret());
+        }
+        if tls {
+            options.set_transport(Transport::tls_with_default_config());
+        }
+
+        let (client, eventloop) = AsyncClient::new(options, 256);
+        info!(
+            "Publishing to MQTT broker {}:{} under {}",
+            host, port, topic
+        );
+        let publisher = Publisher {
+            client: client.clone(),
+            topic,
+            discovery_prefix: config.discovery.then(|| config.discovery_prefix.clone()),
+            node,
+            published: BTreeMap::new(),
+            last_success: None,
+        };
+        let task = tokio::spawn(publisher.run(eventloop, snapshot));
+        Ok(Self {
+            client,
+            status_topic,
+            _task: task,
+        })
+    }
+}
+
+impl Drop for MqttPublisher {
+    // Best effort, the broker sends the last will anyway if the process exits first
+    fn drop(&mut self) {
+        let _ = self
+            .client
+            .try_publish(&self.status_topic, QoS::AtLeastOnce, true, "offline");
+        let _ = self.client.try_disconnect();
+    }
+}
+
+impl Publisher {
+    async fn run(mut self, mut eventloop: EventLoop, mut snapshot: watch::Receiver<Snapshot>) {
+        let mut connected = false;
+        loop {
+            tokio::select! {
+                event = eventloop.poll() => match event {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        info!("Connected to the MQTT broker");
+                        connected = true;
+                        // A new session may have lost anything not retained
+                        self.published.clear();
+                        let current = snapshot.borrow_and_update().clone();
+                        self.publish(&current);
+                    }
+                    Ok(Event::Outgoing(Outgoing::Disconnect)) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        match connected {
+                            true => warn!("Lost the connection to the MQTT broker: {}", e),
+                            false => debug!("Failed to connect to the MQTT broker: {}", e),
+                        }
+                        connected = false;
+                        sleep(Duration::from_secs(5)).await;
+                    }
+                },
+                changed = snapshot.changed() => {
+                    if changed.is_err() {
+                        break;
+                    }
+                    let current = snapshot.borrow_and_update().clone();
+                    if connected {
+                        self.publish(&current);
+                    }
+                }
+            }
+        }
+    }
+
+    // Sends what changed since the last call, and clears what is gone
+    fn publish(&mut self, snapshot: &Snapshot) {
+        let mut messages = BTreeMap::new();
+        messages.insert(format!("{}/status", self.topic), "online".to_string());
+
+        for ((_, record_type), address) in &snapshot.addresses {
+            let Ok(address) = address else { continue };
+            let object = format!("address_{}", record_type.to_ascii_lowercase());
+            let name = match *record_type {
+                "AAAA" => "Public IPv6 address",
+                _ => "Public IPv4 address",
+            };
+            let topic = format!(
+                "{}/address/{}",
+                self.topic,
+                record_type.to_ascii_lowercase()
+            );
+            self.discover(
+                &mut messages,
+                &object,
+                json!({ "name": name, "icon": "mdi:ip-network" }),
+                &topic,
+            );
+            messages.entry(topic).or_insert_with(|| address.to_string());
+        }
+
+        if let Some(at) = snapshot.last_success {
+            // Instants have no calendar time, convert each one once so retained values are stable
+            let time = match self.last_success {
+                Some((instant, time)) if instant == at => time,
+                _ => {
+                    let elapsed = chrono::Duration::from_std(at.elapsed()).unwrap_or_default();
+                    let time = Local::now() - elapsed;
+                    self.last_success = Some((at, time));
+                    time
+                }
+            };
+            let topic = format!("{}/last_update", self.topic);
+            let sensor = json!({ "name": "Last update", "device_class": "timestamp" });
+            self.discover(&mut messages, "last_update", sensor, &topic);
+            messages.insert(topic, time.to_rfc3339_opts(SecondsFormat::Secs, true));
+        }
+
+        for ((record, record_type), status) in &snapshot.records {
+            let topic = format!(
+                "{}/record/{}/{}",
+                self.topic,
+                record,
+                record_type.to_ascii_lowercase()
+            );
+            let object: String = format!("record_{}_{}", record, record_type)
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let sensor = json!({
+                "name": format!("{} {}", record, record_type),
+                "icon": "mdi:dns",
+                "value_template": "{{ value_json.state }}",
+                "json_attributes_topic": topic,
+            });
+            self.discover(&mut messages, &object, sensor, &topic);
+            let payload = json!({
+                "state": status.state.as_str(),
+                "content": status.content,
+                "checked": status.checked.to_rfc3339_opts(SecondsFormat::Secs, true),
+                "error": status.state.error(),
+            });
+            messages.insert(topic, payload.to_string());
+        }
+
+        // An empty retained message deletes the value, and the Home Assistant entity with it
+        let gone: Vec<String> = self
+            .published
+            .keys()
+            .filter(|topic| !messages.contains_key(*topic))
+            .cloned()
+            .collect();
+        for topic in gone {
+            if self.send(&topic, String::new()) {
+                self.published.remove(&topic);
+            }
+        }
+        for (topic, payload) in messages {
+            if self.published.get(&topic) != Some(&payload) && self.send(&topic, payload.clone()) {
+                self.published.insert(topic, payload);
+            }
+        }
+    }
+
+    // Adds the Home Assistant discovery message of a sensor
+    fn discover(
+        &self,
+        messages: &mut BTreeMap<String, String>,
+        object: &str,
+        mut sensor: serde_json::Value,
+        state_topic: &str,
+    ) {
+        let Some(prefix) = &self.discovery_prefix else {
+            return;
+        };
+        sensor["state_topic"] = json!(state_topic);
+        sensor["unique_id"] = json!(format!("clouddns_{}_{}", self.node, object));
+        sensor["availability_topic"] = json!(format!("{}/status", self.topic));
+        sensor["device"] = json!({
+            "identifiers": [format!("clouddns_{}", self.node)],
+            "name": format!("clouddns {}", self.node),
+            "manufacturer": "clouddns",
+            "sw_version": env!("CARGO_PKG_VERSION"),
+        });
+        let topic = format!("{}/sensor/clouddns_{}/{}/config", prefix, self.node, object);
+        messages.insert(topic, sensor.to_string());
+    }
+
+    // Never waits, the event loop runs on the same task. Whatever doesn't fit is sent again
+    // with the next change.
+    fn send(&self, topic: &str, payload: String) -> bool {
+        match self
+            .client
+            .try_publish(topic, QoS::AtLeastOnce, true, payload)
+        {
+            Ok(()) => true,
+            Err(e) => {
+                debug!("Failed to queue an MQTT message for {}: {}", topic, e);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use crate::api::CloudflareClient;
+use log::info;
+use tokio::time::{sleep_until, Duration, Instant};
+
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+// Checks skipped for a lack of network are tried again after this, doubling up to the cap
+const OFFLINE_RETRY_BASE: Duration = Duration::from_secs(5);
+const OFFLINE_RETRY_MAX: Duration = Duration::from_secs(60);
+
+// A cheap probe before each cycle, so an uplink that is down doesn't fail every request in turn
+pub async fn online() -> bool {
+    CloudflareClient::reachable().await
+}
+
+// After the given number of probes in a row found no network
+pub fn offline_retry_delay(probes: u32) -> Duration {
+    let exponent = probes.saturating_sub(1).min(8);
+    (OFFLINE_RETRY_BASE * 2u32.pow(exponent)).min(OFFLINE_RETRY_MAX)
+}
+
+// Polls the API until it answers or the time is up, returning whether it answered
+pub async fn wait_online(limit: Duration) -> bool {
+    let deadline = Instant::now() + limit;
+    let mut waiting = false;
+    loop {
+        if CloudflareClient::reachable().await {
+            return true;
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            return false;
+        }
+        if !waiting {
+            info!("Waiting for the network before the first check");
+            waiting = true;
+        }
+        sleep_until((now + POLL_INTERVAL).min(deadline)).await;
+    }
+}
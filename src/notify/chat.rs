@@ -0,0 +1,95 @@
+use super::{message, Channel};
+use crate::config::notify::ChatWebhook;
+use crate::config::{EventKind, RetryPolicy};
+use crate::{retry, secrets};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{json, Value};
+
+// Discord rejects longer messages
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Service {
+    Slack,
+    Discord,
+}
+
+impl Service {
+    fn name(&self) -> &'static str {
+        match self {
+            Service::Slack => "Slack",
+            Service::Discord => "Discord",
+        }
+    }
+}
+
+pub struct ChatChannel {
+    service: Service,
+    config: ChatWebhook,
+    // Resolved from config.url, which may point at a secret backend
+    url: SecretString,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl ChatChannel {
+    pub async fn new(
+        service: Service,
+        config: &ChatWebhook,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let url = secrets::resolve(&config.url)
+            .await
+            .with_context(|| format!("Failed to read the {} webhook URL", service.name()))?;
+        Ok(Self {
+            service,
+            config: config.clone(),
+            url,
+            client,
+            retry: retry.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl Channel for ChatChannel {
+    // The URL is a credential, it stays out of the logs
+    fn name(&self) -> &str {
+        self.service.name()
+    }
+
+    fn wants(&self, event: EventKind) -> bool {
+        self.config.wants(event)
+    }
+
+    async fn send(&self, kind: EventKind, event: &Value) -> Result<()> {
+        let message = message(kind, event, &self.config.messages);
+
+        let body = match self.service {
+            Service::Slack => json!({ "text": message }),
+            Service::Discord => json!({ "content": truncate(&message, DISCORD_MAX_LENGTH) }),
+        };
+        retry::send(
+            &self.retry,
+            self.client.post(self.url.expose_secret()).json(&body),
+        )
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.without_url())?;
+        Ok(())
+    }
+}
+
+fn truncate(message: &str, max_chars: usize) -> String {
+    if message.chars().count() <= max_chars {
+        return message.to_string();
+    }
+    let end = message
+        .char_indices()
+        .nth(max_chars - 1)
+        .map_or(message.len(), |(end, _)| end);
+    format!("{}…", &message[..end])
+}
@@ -0,0 +1,51 @@
+use crate::config::notify::Healthchecks;
+use crate::config::RetryPolicy;
+use crate::{retry, secrets};
+use anyhow::{Context, Result};
+use log::{debug, warn};
+use secrecy::{ExposeSecret, SecretString};
+
+// Pings a check after each cycle, so the check alerts when the pings stop
+pub struct Pinger {
+    // Resolved from the configured URL, which may point at a secret backend
+    url: SecretString,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl Pinger {
+    pub async fn new(
+        config: &Healthchecks,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let url = secrets::resolve(&config.url)
+            .await
+            .context("Failed to read the healthchecks ping URL")?;
+        Ok(Self {
+            url: url.expose_secret().trim_end_matches('/').into(),
+            client,
+            retry: retry.clone(),
+        })
+    }
+
+    // A failed cycle pings `/fail` with the error as the body, which shows up in the check's log
+    pub async fn ping(&self, error: Option<&anyhow::Error>) {
+        let request = match error {
+            None => self.client.post(self.url.expose_secret()),
+            Some(error) => self
+                .client
+                .post(format!("{}/fail", self.url.expose_secret()))
+                .body(format!("{:#}", error)),
+        };
+
+        let result = retry::send(&self.retry, request)
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => debug!("Pinged healthchecks"),
+            // The URL holds the check's key, only the cause is logged
+            Err(e) => warn!("Failed to ping healthchecks: {}", e.without_url()),
+        }
+    }
+}
@@ -0,0 +1,391 @@
+mod chat;
+mod healthchecks;
+mod push;
+mod webhook;
+
+use crate::config::notify::{self, PLACEHOLDERS};
+use crate::config::{EventKind, Notifications, ProviderKind, RecordType, RetryPolicy};
+use crate::correlation;
+use crate::state::{HistoryEntry, RecordHistory};
+use anyhow::Result;
+use async_trait::async_trait;
+use chat::{ChatChannel, Service};
+use chrono::{DateTime, Utc};
+use healthchecks::Pinger;
+use log::{debug, warn};
+use push::PushChannel;
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    net::IpAddr,
+};
+use webhook::WebhookChannel;
+
+// What happened, sent as `{"event": "ip_changed", "record": ..., "host": ..., "timestamp": ...}`
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Startup {
+        version: &'static str,
+    },
+    IpChanged {
+        record: String,
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        address: IpAddr,
+        previous: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+    },
+    UpdateFailed {
+        record: String,
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        error: String,
+        failures: u32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+    },
+    // `previous` is what the record was changed to, `address` what it was set back to
+    DriftDetected {
+        record: String,
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        address: IpAddr,
+        previous: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+    },
+    // `failures` counts cycles rather than attempts at a record
+    WatchdogTripped {
+        error: String,
+        failures: u32,
+    },
+    // The daemon is going down, from a panic or the error it stopped with
+    Crashed {
+        error: String,
+    },
+    // `changes` within the last `minutes`
+    Flapping {
+        record: String,
+        #[serde(rename = "type")]
+        record_type: RecordType,
+        changes: u32,
+        minutes: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+    },
+    // What happened since `since`, `report` being the same as text. `checks` counts cycles, since
+    // the daemon started if that was later.
+    Summary {
+        since: DateTime<Utc>,
+        checks: u32,
+        failures: usize,
+        changes: Vec<SummaryChange>,
+        records: Vec<SummaryRecord>,
+        report: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryChange {
+    pub time: DateTime<Utc>,
+    pub record: String,
+    #[serde(rename = "type")]
+    pub record_type: RecordType,
+    pub address: Option<String>,
+    pub previous: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SummaryRecord {
+    pub record: String,
+    #[serde(rename = "type")]
+    pub record_type: RecordType,
+    pub address: Option<String>,
+    // Updates failed in a row, none when the record is fine
+    pub failures: u32,
+}
+
+impl Event {
+    pub fn startup() -> Self {
+        Event::Startup {
+            version: env!("CARGO_PKG_VERSION"),
+        }
+    }
+
+    pub fn summary(
+        since: DateTime<Utc>,
+        checks: u32,
+        history: &[HistoryEntry],
+        records: &HashMap<(String, RecordType), RecordHistory>,
+    ) -> Self {
+        let failures = history.iter().filter(|entry| entry.error.is_some()).count();
+        let changes: Vec<SummaryChange> = history
+            .iter()
+            .filter(|entry| entry.error.is_none())
+            .map(|entry| SummaryChange {
+                time: entry.time,
+                record: entry.record.clone(),
+                record_type: entry.r#type,
+                address: entry.content.clone(),
+                previous: entry.previous.clone(),
+            })
+            .collect();
+        let mut records: Vec<SummaryRecord> = records
+            .iter()
+            .map(|((record, record_type), history)| SummaryRecord {
+                record: record.clone(),
+                record_type: *record_type,
+                address: history.content.clone(),
+                failures: history.failures,
+            })
+            .collect();
+        records.sort_by(|a, b| {
+            (&a.record, a.record_type.as_str()).cmp(&(&b.record, b.record_type.as_str()))
+        });
+
+        let mut report = format!(
+            "{} check(s), {} change(s) and {} failed update(s) since {}",
+            checks,
+            changes.len(),
+            failures,
+            since.format("%Y-%m-%d %H:%M UTC")
+        );
+        for change in &changes {
+            report += &format!(
+                "\n{} {} {}: {} -> {}",
+                change.time.format("%Y-%m-%d %H:%M"),
+                change.record,
+                change.record_type,
+                change.previous.as_deref().unwrap_or("(none)"),
+                change.address.as_deref().unwrap_or("(deleted)")
+            );
+        }
+        for record in &records {
+            let address = record.address.as_deref().unwrap_or("unknown");
+            report += &match record.failures {
+                0 => format!("\n{} {} is {}", record.record, record.record_type, address),
+                failures => format!(
+                    "\n{} {} is {}, {} update(s) failed in a row",
+                    record.record, record.record_type, address, failures
+                ),
+            };
+        }
+
+        Event::Summary {
+            since,
+            checks,
+            failures,
+            changes,
+            records,
+            report,
+        }
+    }
+
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::Startup { .. } => EventKind::Startup,
+            Event::IpChanged { .. } => EventKind::IpChanged,
+            Event::UpdateFailed { .. } => EventKind::UpdateFailed,
+            Event::DriftDetected { .. } => EventKind::DriftDetected,
+            Event::WatchdogTripped { .. } => EventKind::WatchdogTripped,
+            Event::Crashed { .. } => EventKind::Crashed,
+            Event::Flapping { .. } => EventKind::Flapping,
+            Event::Summary { .. } => EventKind::Summary,
+        }
+    }
+
+    // The tenant of the record the event is about
+    pub fn tenant(&self) -> Option<&str> {
+        match self {
+            Event::IpChanged { tenant, .. }
+            | Event::UpdateFailed { tenant, .. }
+            | Event::DriftDetected { tenant, .. }
+            | Event::Flapping { tenant, .. } => tenant.as_deref(),
+            Event::Startup { .. }
+            | Event::WatchdogTripped { .. }
+            | Event::Crashed { .. }
+            | Event::Summary { .. } => None,
+        }
+    }
+}
+
+// An event with where and when it happened
+#[derive(Serialize)]
+struct Envelope<'a> {
+    #[serde(flatten)]
+    event: &'a Event,
+    provider: &'a str,
+    host: &'a str,
+    // The cycle it happened in, to find its lines in the logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cycle: Option<&'a str>,
+    timestamp: DateTime<Utc>,
+}
+
+#[async_trait]
+trait Channel: Send + Sync {
+    // Named in logs
+    fn name(&self) -> &str;
+    fn wants(&self, event: EventKind) -> bool;
+    async fn send(&self, kind: EventKind, event: &serde_json::Value) -> Result<()>;
+}
+
+// Fans events out to the configured channels, as routed
+pub struct Notifier {
+    // With the name each one goes by in the routes
+    channels: Vec<(Option<String>, Box<dyn Channel>)>,
+    routes: BTreeMap<EventKind, Vec<String>>,
+    pinger: Option<Pinger>,
+    provider: ProviderKind,
+    host: String,
+}
+
+impl Notifier {
+    pub async fn new(
+        notifications: &Notifications,
+        retry: &RetryPolicy,
+        provider: ProviderKind,
+        client: reqwest::Client,
+    ) -> Result<Self> {
+        let mut channels: Vec<(Option<String>, Box<dyn Channel>)> = Vec::new();
+        for webhook in &notifications.webhooks {
+            let channel = WebhookChannel::new(webhook, client.clone(), retry).await?;
+            channels.push((webhook.name.clone(), Box::new(channel)));
+        }
+        let slack = notifications
+            .slack
+            .iter()
+            .map(|chat| (Service::Slack, chat));
+        let discord = notifications
+            .discord
+            .iter()
+            .map(|chat| (Service::Discord, chat));
+        for (service, chat) in slack.chain(discord) {
+            let channel = ChatChannel::new(service, chat, client.clone(), retry).await?;
+            channels.push((chat.name.clone(), Box::new(channel)));
+        }
+        for ntfy in &notifications.ntfy {
+            let channel = PushChannel::ntfy(ntfy, client.clone(), retry).await?;
+            channels.push((ntfy.name.clone(), Box::new(channel)));
+        }
+        for pushover in &notifications.pushover {
+            let channel = PushChannel::pushover(pushover, client.clone(), retry).await?;
+            channels.push((pushover.name.clone(), Box::new(channel)));
+        }
+        for gotify in &notifications.gotify {
+            let channel = PushChannel::gotify(gotify, client.clone(), retry).await?;
+            channels.push((gotify.name.clone(), Box::new(channel)));
+        }
+
+        let pinger = match &notifications.healthchecks {
+            Some(healthchecks) => Some(Pinger::new(healthchecks, client, retry).await?),
+            None => None,
+        };
+
+        let host = hostname::get()
+            .ok()
+            .and_then(|host| host.into_string().ok())
+            .unwrap_or_default();
+        Ok(Self {
+            channels,
+            routes: notifications.routes.clone(),
+            pinger,
+            provider,
+            host,
+        })
+    }
+
+    // Report how a cycle went to the dead man's switch
+    pub async fn cycle_finished(&self, error: Option<&anyhow::Error>) {
+        if let Some(pinger) = &self.pinger {
+            pinger.ping(error).await;
+        }
+    }
+
+    // Channels are sent to at the same time, each one gets the events in order. Failures are
+    // only logged, a notification never fails a cycle.
+    pub async fn send(&self, events: &[Event]) {
+        if events.is_empty() || self.channels.is_empty() {
+            return;
+        }
+        let timestamp = Utc::now();
+        let cycle = correlation::current();
+        let documents: Vec<(EventKind, serde_json::Value)> = events
+            .iter()
+            .filter_map(|event| {
+                let envelope = Envelope {
+                    event,
+                    provider: self.provider.as_str(),
+                    host: &self.host,
+                    cycle: cycle.as_deref(),
+                    timestamp,
+                };
+                serde_json::to_value(envelope)
+                    .ok()
+                    .map(|document| (event.kind(), document))
+            })
+            .collect();
+
+        let sends = self.channels.iter().map(|(name, channel)| async {
+            for (kind, document) in &documents {
+                if !channel.wants(*kind) || !notify::routes_to(&self.routes, name.as_deref(), *kind)
+                {
+                    continue;
+                }
+                match channel.send(*kind, document).await {
+                    Ok(()) => debug!("Notified {} of {}", channel.name(), kind.as_str()),
+                    Err(e) => warn!("Failed to notify {}: {:#}", channel.name(), e),
+                }
+            }
+        });
+        futures::future::join_all(sends).await;
+    }
+}
+
+// The text of an event for channels showing a message, the configured one or the default
+fn message(
+    kind: EventKind,
+    event: &serde_json::Value,
+    messages: &BTreeMap<EventKind, String>,
+) -> String {
+    let template = match messages.get(&kind) {
+        Some(template) => template.as_str(),
+        None => match kind {
+            EventKind::Startup => "clouddns {version} started on {host}",
+            EventKind::IpChanged => "{record} now points to {address} (was {previous})",
+            EventKind::UpdateFailed => "Failed to update {record} {type}: {error}",
+            EventKind::DriftDetected => {
+                "{record} was changed to {previous} outside clouddns, set back to {address}"
+            }
+            EventKind::WatchdogTripped => {
+                "clouddns on {host} failed {failures} cycles in a row: {error}"
+            }
+            EventKind::Crashed => "clouddns on {host} crashed: {error}",
+            EventKind::Flapping => {
+                "{record} {type} changed {changes} times in {minutes} minutes, an address source may be broken or another updater may be changing it"
+            }
+            EventKind::Summary => "clouddns on {host}: {report}",
+        },
+    };
+    fill(template, event)
+}
+
+// Replace the `{field}` placeholders in a message with the fields of the event, by nothing for
+// fields the event doesn't have
+fn fill(text: &str, event: &serde_json::Value) -> String {
+    let mut filled = text.to_string();
+    for (name, field) in PLACEHOLDERS {
+        let placeholder = format!("{{{}}}", name);
+        if filled.contains(&placeholder) {
+            let value = match event.get(field) {
+                Some(serde_json::Value::String(value)) => value.clone(),
+                Some(value) => value.to_string(),
+                None => String::new(),
+            };
+            filled = filled.replace(&placeholder, &value);
+        }
+    }
+    filled
+}
@@ -0,0 +1,237 @@
+use super::{fill, message, Channel};
+use crate::config::notify::{self, Gotify, Ntfy, Pushover};
+use crate::config::{EventKind, RetryPolicy, SecretSource};
+use crate::{redact, retry, secrets};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+const PUSHOVER_URL: &str = "https://api.pushover.net/1/messages.json";
+
+// Phone push services, with their secrets resolved
+enum Service {
+    Ntfy {
+        server: String,
+        topic: String,
+        token: Option<SecretString>,
+    },
+    Pushover {
+        token: SecretString,
+        user: SecretString,
+        device: Option<String>,
+    },
+    Gotify {
+        url: String,
+        token: SecretString,
+    },
+}
+
+enum Priority {
+    Normal,
+    High,
+    Urgent,
+}
+
+pub struct PushChannel {
+    name: String,
+    service: Service,
+    events: Vec<EventKind>,
+    messages: BTreeMap<EventKind, String>,
+    titles: BTreeMap<EventKind, String>,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+}
+
+impl PushChannel {
+    pub async fn ntfy(config: &Ntfy, client: reqwest::Client, retry: &RetryPolicy) -> Result<Self> {
+        let name = format!("ntfy topic {}", config.topic);
+        let token = match &config.token {
+            Some(token) => Some(resolve(token, &name).await?),
+            None => None,
+        };
+        let service = Service::Ntfy {
+            server: config.server.trim_end_matches('/').to_string(),
+            topic: config.topic.clone(),
+            token,
+        };
+        Ok(Self::new(
+            name,
+            service,
+            &config.events,
+            &config.messages,
+            &config.titles,
+            client,
+            retry,
+        ))
+    }
+
+    pub async fn pushover(
+        config: &Pushover,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let name = "Pushover".to_string();
+        let service = Service::Pushover {
+            token: resolve(&config.token, &name).await?,
+            user: resolve(&config.user, &name).await?,
+            device: config.device.clone(),
+        };
+        Ok(Self::new(
+            name,
+            service,
+            &config.events,
+            &config.messages,
+            &config.titles,
+            client,
+            retry,
+        ))
+    }
+
+    pub async fn gotify(
+        config: &Gotify,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let name = format!("Gotify at {}", redact::url(&config.url));
+        let service = Service::Gotify {
+            url: config.url.trim_end_matches('/').to_string(),
+            token: resolve(&config.token, &name).await?,
+        };
+        Ok(Self::new(
+            name,
+            service,
+            &config.events,
+            &config.messages,
+            &config.titles,
+            client,
+            retry,
+        ))
+    }
+
+    fn new(
+        name: String,
+        service: Service,
+        events: &[EventKind],
+        messages: &BTreeMap<EventKind, String>,
+        titles: &BTreeMap<EventKind, String>,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Self {
+        Self {
+            name,
+            service,
+            events: events.to_vec(),
+            messages: messages.clone(),
+            titles: titles.clone(),
+            client,
+            retry: retry.clone(),
+        }
+    }
+}
+
+async fn resolve(secret: &SecretSource, name: &str) -> Result<SecretString> {
+    secrets::resolve(secret)
+        .await
+        .with_context(|| format!("Failed to read the credentials of {}", name))
+}
+
+#[async_trait]
+impl Channel for PushChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn wants(&self, event: EventKind) -> bool {
+        notify::wants(&self.events, event)
+    }
+
+    async fn send(&self, kind: EventKind, event: &Value) -> Result<()> {
+        let message = message(kind, event, &self.messages);
+        let title = match self.titles.get(&kind) {
+            Some(template) => fill(template, event),
+            None => title(kind).to_string(),
+        };
+        // Failures are worth waking a phone up for, a crash or a tripped watchdog above all
+        let priority = match kind {
+            EventKind::WatchdogTripped | EventKind::Crashed => Priority::Urgent,
+            EventKind::UpdateFailed | EventKind::Flapping => Priority::High,
+            _ => Priority::Normal,
+        };
+
+        let request = match &self.service {
+            Service::Ntfy {
+                server,
+                topic,
+                token,
+            } => {
+                let request = self.client.post(server).json(&json!({
+                    "topic": topic,
+                    "title": title,
+                    "message": message,
+                    "priority": match priority {
+                        Priority::Normal => 3,
+                        Priority::High => 4,
+                        Priority::Urgent => 5,
+                    },
+                }));
+                match token {
+                    Some(token) => request.bearer_auth(token.expose_secret()),
+                    None => request,
+                }
+            }
+            Service::Pushover {
+                token,
+                user,
+                device,
+            } => {
+                let mut body = json!({
+                    "token": token.expose_secret(),
+                    "user": user.expose_secret(),
+                    "title": title,
+                    "message": message,
+                    "priority": match priority {
+                        Priority::Normal => 0,
+                        Priority::High | Priority::Urgent => 1,
+                    },
+                });
+                if let Some(device) = device {
+                    body["device"] = json!(device);
+                }
+                self.client.post(PUSHOVER_URL).json(&body)
+            }
+            Service::Gotify { url, token } => self
+                .client
+                .post(format!("{}/message", url))
+                .header("X-Gotify-Key", token.expose_secret())
+                .json(&json!({
+                    "title": title,
+                    "message": message,
+                    "priority": match priority {
+                        Priority::Normal => 5,
+                        Priority::High => 8,
+                        Priority::Urgent => 10,
+                    },
+                })),
+        };
+
+        retry::send(&self.retry, request)
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+fn title(kind: EventKind) -> &'static str {
+    match kind {
+        EventKind::Startup => "clouddns started",
+        EventKind::IpChanged => "DNS record updated",
+        EventKind::UpdateFailed => "DNS update failed",
+        EventKind::DriftDetected => "DNS record drift corrected",
+        EventKind::WatchdogTripped => "clouddns keeps failing",
+        EventKind::Crashed => "clouddns crashed",
+        EventKind::Flapping => "DNS record flapping",
+        EventKind::Summary => "clouddns summary",
+    }
+}
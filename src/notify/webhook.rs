@@ -0,0 +1,98 @@
+use super::{fill, Channel};
+use crate::config::notify::Webhook;
+use crate::config::{EventKind, RetryPolicy};
+use crate::{redact, retry, secrets};
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::Value;
+use sha2::Sha256;
+
+pub struct WebhookChannel {
+    config: Webhook,
+    // The URL, its credentials masked
+    name: String,
+    client: reqwest::Client,
+    retry: RetryPolicy,
+    // Resolved once, like the API token
+    secret: Option<SecretString>,
+}
+
+impl WebhookChannel {
+    pub async fn new(
+        config: &Webhook,
+        client: reqwest::Client,
+        retry: &RetryPolicy,
+    ) -> Result<Self> {
+        let secret = match &config.secret {
+            Some(secret) => Some(secrets::resolve(secret).await.with_context(|| {
+                format!("Failed to read the secret of {}", redact::url(&config.url))
+            })?),
+            None => None,
+        };
+        Ok(Self {
+            config: config.clone(),
+            name: redact::url(&config.url),
+            client,
+            retry: retry.clone(),
+            secret,
+        })
+    }
+}
+
+#[async_trait]
+impl Channel for WebhookChannel {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn wants(&self, event: EventKind) -> bool {
+        self.config.wants(event)
+    }
+
+    async fn send(&self, kind: EventKind, event: &Value) -> Result<()> {
+        let body = match self.config.payload(kind) {
+            Some(template) => serde_json::to_vec(&render(template, event))?,
+            None => serde_json::to_vec(event)?,
+        };
+
+        let mut request = self
+            .client
+            .post(&self.config.url)
+            .header("Content-Type", "application/json");
+        request = request.header("X-Clouddns-Event", kind.as_str());
+        for (name, value) in &self.config.headers {
+            request = request.header(name, value);
+        }
+        if let Some(secret) = &self.secret {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+                .context("Invalid webhook secret")?;
+            mac.update(&body);
+            let signature = hex::encode(mac.finalize().into_bytes());
+            request = request.header("X-Clouddns-Signature", format!("sha256={}", signature));
+        }
+
+        // The channel name already tells which webhook failed, without its credentials
+        retry::send(&self.retry, request.body(body))
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| e.without_url())?;
+        Ok(())
+    }
+}
+
+// Fill the `{field}` placeholders in the strings of a custom payload from the event
+fn render(template: &Value, event: &Value) -> Value {
+    match template {
+        Value::String(text) => Value::String(fill(text, event)),
+        Value::Array(items) => Value::Array(items.iter().map(|item| render(item, event)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(name, value)| (name.clone(), render(value, event)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
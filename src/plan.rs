@@ -0,0 +1,299 @@
+//! Working out what should change on the provider, apart from changing it.
+//!
+//! [`UpdatePlanner`] compares the records the configuration asks for with those the provider
+//! serves and returns a [`Plan`], without any I/O, so a dry run, a diff and a real update all start
+//! from the same answer. [`Plan::apply`] then carries it out through a [`DnsApiClient`]:
+//!
+//! ```
+//! use clouddns::api::models::DnsRecordUpdate;
+//! use clouddns::plan::{Action, DesiredRecord, UpdatePlanner};
+//! use clouddns::RecordContent;
+//!
+//! let current = DnsRecordUpdate {
+//!     id: "record-id".to_string(),
+//!     name: "home.example.com".to_string(),
+//!     content: "192.0.2.1".to_string(),
+//!     ttl: 300,
+//!     proxied: false,
+//!     r#type: "A".to_string(),
+//!     modified_on: None,
+//! };
+//! let desired = DesiredRecord::new("home.example.com", RecordContent::A([192, 0, 2, 7].into()));
+//! let plan = UpdatePlanner::new().plan(&[desired], &[current]);
+//! let Action::Update { changes, .. } = &plan.actions[0] else { unreachable!() };
+//! assert_eq!(changes, &["192.0.2.1 → 192.0.2.7"]);
+//! ```
+
+use crate::api::{models::DnsRecordUpdate, DnsApiClient, RecordContent};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A record as the configuration wants it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DesiredRecord {
+    pub name: String,
+    pub content: RecordContent,
+    /// Kept as the provider has it when unset, automatic for a new record
+    pub ttl: Option<u32>,
+    /// Kept as the provider has it when unset, off for a new record
+    pub proxied: Option<bool>,
+}
+
+impl DesiredRecord {
+    pub fn new(name: &str, content: RecordContent) -> Self {
+        Self {
+            name: name.to_string(),
+            content,
+            ttl: None,
+            proxied: None,
+        }
+    }
+
+    pub fn ttl(mut self, ttl: Option<u32>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn proxied(mut self, proxied: Option<bool>) -> Self {
+        self.proxied = proxied;
+        self
+    }
+
+    fn matches(&self, record: &DnsRecordUpdate) -> bool {
+        record.name == self.name && record.r#type == self.content.record_type()
+    }
+}
+
+/// One step of a [`Plan`]. Serialized with its kind in `action`, e.g. `"action": "create"`, so a
+/// plan can be saved and applied later.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    /// The provider has no such record
+    Create {
+        name: String,
+        content: RecordContent,
+        ttl: u32,
+        proxied: bool,
+    },
+    /// The record differs from the configuration, or the update is forced
+    Update {
+        record: DnsRecordUpdate,
+        content: RecordContent,
+        ttl: u32,
+        proxied: bool,
+        /// e.g. `1.2.3.4 → 5.6.7.8` or `ttl 300 → 60`, empty when forced
+        changes: Vec<String>,
+    },
+    /// A second record of a name and type the configuration wants once, which would leave
+    /// resolvers picking either address
+    Delete { record: DnsRecordUpdate },
+    /// The record already is as the configuration wants it
+    NoOp { record: DnsRecordUpdate },
+}
+
+impl Action {
+    /// Whether applying it would change anything on the provider
+    pub fn is_change(&self) -> bool {
+        !matches!(self, Action::NoOp { .. })
+    }
+
+    /// The record's name and type, e.g. `home.example.com A`
+    pub fn target(&self) -> (&str, &str) {
+        match self {
+            Action::Create { name, content, .. } => (name, content.record_type()),
+            Action::Update { record, .. } | Action::Delete { record } | Action::NoOp { record } => {
+                (&record.name, &record.r#type)
+            }
+        }
+    }
+
+    /// Carries out this step alone, returning the record as the provider now has it, `None` once
+    /// deleted
+    pub async fn apply(
+        &self,
+        client: &dyn DnsApiClient,
+        zone_id: &str,
+    ) -> Result<Option<DnsRecordUpdate>> {
+        match self {
+            Action::Create {
+                name,
+                content,
+                ttl,
+                proxied,
+            } => client
+                .create_record(zone_id, name, content, *ttl, *proxied)
+                .await
+                .map(|record| Some(record.into())),
+            Action::Update {
+                record,
+                content,
+                ttl,
+                proxied,
+                ..
+            } => client
+                .update_record(zone_id, record, content, *ttl, *proxied)
+                .await
+                .map(|record| Some(record.into())),
+            Action::Delete { record } => client
+                .delete_record(zone_id, &record.id)
+                .await
+                .map(|()| None),
+            Action::NoOp { record } => Ok(Some(record.clone())),
+        }
+    }
+}
+
+// `+ home.example.com A 192.0.2.7`, `~ home.example.com A 192.0.2.1 → 192.0.2.7`, ...
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, record_type) = self.target();
+        match self {
+            Action::Create { content, .. } => write!(f, "+ {} {} {}", name, record_type, content),
+            Action::Update { changes, .. } if changes.is_empty() => {
+                write!(f, "~ {} {} forced update", name, record_type)
+            }
+            Action::Update { changes, .. } => {
+                write!(f, "~ {} {} {}", name, record_type, changes.join(", "))
+            }
+            Action::Delete { record } => {
+                write!(f, "- {} {} {}", name, record_type, record.content)
+            }
+            Action::NoOp { .. } => write!(f, "  {} {}", name, record_type),
+        }
+    }
+}
+
+/// What it takes to bring the provider in line with the configuration, one [`Action`] per record
+/// in the order of the desired records, deletions after the record they duplicate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Plan {
+    pub actions: Vec<Action>,
+}
+
+impl Plan {
+    /// Whether applying it would change anything on the provider
+    pub fn has_changes(&self) -> bool {
+        self.actions.iter().any(Action::is_change)
+    }
+
+    /// The steps that change something, leaving out the records already in sync
+    pub fn changes(&self) -> impl Iterator<Item = &Action> {
+        self.actions.iter().filter(|action| action.is_change())
+    }
+
+    /// Carries out every step one after the other, going on after a failure. The results come
+    /// back in the order of the actions.
+    pub async fn apply(
+        &self,
+        client: &dyn DnsApiClient,
+        zone_id: &str,
+    ) -> Vec<Result<Option<DnsRecordUpdate>>> {
+        let mut results = Vec::with_capacity(self.actions.len());
+        for action in &self.actions {
+            results.push(action.apply(client, zone_id).await);
+        }
+        results
+    }
+}
+
+// One line per action, as a diff
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for action in &self.actions {
+            writeln!(f, "{}", action)?;
+        }
+        Ok(())
+    }
+}
+
+/// Works out a [`Plan`] from the desired records and those the provider has, without I/O
+#[derive(Debug, Clone, Default)]
+pub struct UpdatePlanner {
+    force: bool,
+}
+
+impl UpdatePlanner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Updates records even when they already match
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// `current` may hold more records than those desired, e.g. all of a zone, only those with a
+    /// desired name and type are planned for
+    pub fn plan(&self, desired: &[DesiredRecord], current: &[DnsRecordUpdate]) -> Plan {
+        let mut actions = Vec::new();
+        for wanted in desired {
+            let mut matching: Vec<&DnsRecordUpdate> = current
+                .iter()
+                .filter(|record| wanted.matches(record))
+                .collect();
+            // The record already pointing at the right content is the one to keep
+            if let Some(index) = matching
+                .iter()
+                .position(|record| wanted.content.matches(&record.content))
+            {
+                let kept = matching.remove(index);
+                matching.insert(0, kept);
+            }
+            let mut matching = matching.into_iter();
+            let Some(record) = matching.next() else {
+                actions.push(Action::Create {
+                    name: wanted.name.clone(),
+                    content: wanted.content.clone(),
+                    ttl: wanted.ttl.unwrap_or(1),
+                    proxied: wanted.proxied.unwrap_or(false),
+                });
+                continue;
+            };
+
+            let ttl = wanted.ttl.unwrap_or(record.ttl);
+            let proxied = wanted.proxied.unwrap_or(record.proxied);
+            let changes = describe_changes(record, &wanted.content, ttl, proxied);
+            actions.push(match changes.is_empty() && !self.force {
+                true => Action::NoOp {
+                    record: record.clone(),
+                },
+                false => Action::Update {
+                    record: record.clone(),
+                    content: wanted.content.clone(),
+                    ttl,
+                    proxied,
+                    changes,
+                },
+            });
+            actions.extend(matching.map(|record| Action::Delete {
+                record: record.clone(),
+            }));
+        }
+        Plan { actions }
+    }
+}
+
+// What an update would change on a record, e.g. `1.2.3.4 → 5.6.7.8` and `ttl 300 → 60`. Only what
+// the API reports counts: public DNS answers for a proxied record with Cloudflare's edge addresses,
+// and the API reports its TTL as automatic whatever was asked for, so that is left out.
+fn describe_changes(
+    record: &DnsRecordUpdate,
+    content: &RecordContent,
+    ttl: u32,
+    proxied: bool,
+) -> Vec<String> {
+    let mut changes = Vec::new();
+    if !content.matches(&record.content) {
+        changes.push(format!("{} → {}", &record.content, content));
+    }
+    if record.ttl != ttl && !(record.proxied && proxied) {
+        changes.push(format!("ttl {} → {}", record.ttl, ttl));
+    }
+    if record.proxied != proxied {
+        changes.push(format!("proxied {} → {}", record.proxied, proxied));
+    }
+    changes
+}
@@ -0,0 +1,22 @@
+//! The types most embedders need, in one import:
+//!
+//! ```
+//! use clouddns::prelude::*;
+//! ```
+//!
+//! Everything here is kept stable across minor releases. The modules also hold what the
+//! `clouddns` command itself uses, which may change more often.
+
+pub use crate::api::{DnsApiClient, MemoryDnsProvider, RecordContent};
+pub use crate::clock::{Clock, SystemClock};
+pub use crate::config::{Config, Domain, IpSource, RecordType, Zone};
+pub use crate::ddns::{CloudflareDdns, CloudflareDdnsBuilder, Control};
+pub use crate::error::{DdnsError, ErrorKind};
+pub use crate::events::DdnsEvent;
+pub use crate::ip::{Detector, HttpDetector};
+pub use crate::lifecycle::{DdnsHooks, RecordChange};
+pub use crate::metrics::Metrics;
+pub use crate::plan::{Plan, UpdatePlanner};
+pub use crate::snapshot::Snapshot;
+pub use crate::state::StateStore;
+pub use tokio_util::sync::CancellationToken;
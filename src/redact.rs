@@ -0,0 +1,86 @@
+use secrecy::{ExposeSecret, SecretString};
+use std::sync::RwLock;
+
+const REDACTED: &str = "[REDACTED]";
+
+// Shorter values would mask ordinary words
+const MIN_LENGTH: usize = 8;
+
+// Every secret resolved so far, masked wherever it shows up in a log line or an error
+static SECRETS: RwLock<Vec<SecretString>> = RwLock::new(Vec::new());
+
+pub fn register(secret: &SecretString) {
+    let value = secret.expose_secret();
+    if value.len() < MIN_LENGTH {
+        return;
+    }
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|known| known.expose_secret() == value) {
+        secrets.push(secret.clone());
+    }
+}
+
+// Masks the known secrets, and the credentials of any Authorization header or bearer token in
+// request or response text
+pub fn scrub(text: &str) -> String {
+    let mut text = text.to_string();
+    for secret in SECRETS.read().unwrap_or_else(|e| e.into_inner()).iter() {
+        if text.contains(secret.expose_secret()) {
+            text = text.replace(secret.expose_secret(), REDACTED);
+        }
+    }
+    let text = mask_after(&text, "authorization: ", |c| c == '\r' || c == '\n');
+    let text = mask_after(&text, "authorization\": \"", |c| c == '"');
+    mask_after(&text, "bearer ", |c| {
+        c.is_whitespace() || matches!(c, '"' | '\'' | ',' | ')' | '}')
+    })
+}
+
+// A URL fit for logs: the password and query string, which often carry credentials, are masked
+pub fn url(url: &str) -> String {
+    let Ok(parsed) = reqwest::Url::parse(url) else {
+        return scrub(url);
+    };
+    let mut redacted = format!("{}://", parsed.scheme());
+    if !parsed.username().is_empty() {
+        redacted.push_str(parsed.username());
+        if parsed.password().is_some() {
+            redacted.push(':');
+            redacted.push_str(REDACTED);
+        }
+        redacted.push('@');
+    }
+    redacted.push_str(parsed.host_str().unwrap_or_default());
+    if let Some(port) = parsed.port() {
+        redacted.push_str(&format!(":{}", port));
+    }
+    redacted.push_str(parsed.path());
+    if parsed.query().is_some() {
+        redacted.push('?');
+        redacted.push_str(REDACTED);
+    }
+    scrub(&redacted)
+}
+
+// Replaces what follows each case-insensitive `marker`, up to the first character `end` accepts
+fn mask_after(text: &str, marker: &str, end: impl Fn(char) -> bool) -> String {
+    // ASCII lowercasing keeps the byte offsets of the original
+    let lower = text.to_ascii_lowercase();
+    let mut masked = String::with_capacity(text.len());
+    let mut copied = 0;
+    for (at, _) in lower.match_indices(marker) {
+        let start = at + marker.len();
+        if at < copied {
+            continue;
+        }
+        let length = text[start..].find(&end).unwrap_or(text.len() - start);
+        if length == 0 || text[start..].starts_with(REDACTED) {
+            continue;
+        }
+        masked.push_str(&text[copied..start]);
+        masked.push_str(REDACTED);
+        copied = start + length;
+    }
+    masked.push_str(&text[copied..]);
+    masked
+}
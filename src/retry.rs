@@ -0,0 +1,169 @@
+use crate::config::RetryPolicy;
+use crate::redact;
+use futures::future::BoxFuture;
+use log::info;
+use reqwest::{header::RETRY_AFTER, Request, RequestBuilder, Response};
+use std::{
+    error::Error,
+    sync::Arc,
+    task::{Context, Poll},
+    time::SystemTime,
+};
+use tokio::time::{sleep, Duration};
+use tower::{Layer, Service, ServiceExt};
+use tracing::{field, Instrument};
+
+// Send a request, trying again on the failures the policy deems transient. Once out of
+// attempts, the last response or error is handed back for the caller to deal with.
+pub async fn send(policy: &RetryPolicy, request: RequestBuilder) -> reqwest::Result<Response> {
+    let (client, request) = request.build_split();
+    RetryLayer::new(policy.clone())
+        .layer(client)
+        .oneshot(request?)
+        .await
+}
+
+/// Retries the requests going through it as the policy says, each attempt traced as its own span
+#[derive(Clone)]
+pub struct RetryLayer {
+    policy: Arc<RetryPolicy>,
+}
+
+impl RetryLayer {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<S> Layer<S> for RetryLayer {
+    type Service = Retry<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Retry<S> {
+    inner: S,
+    policy: Arc<RetryPolicy>,
+}
+
+impl<S> Service<Request> for Retry<S>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = Response;
+    type Error = reqwest::Error;
+    type Future = BoxFuture<'static, reqwest::Result<Response>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<reqwest::Result<()>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        let policy = self.policy.clone();
+        // The ready service takes the request, its clone waits for the next one
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let mut attempt = 1;
+            loop {
+                // Streaming bodies can't be replayed, those only get the one attempt
+                let Some(current) = request
+                    .try_clone()
+                    .filter(|_| attempt < policy.max_attempts)
+                else {
+                    return execute(&mut inner, request, attempt).await;
+                };
+
+                let (url, reason, wait) = match execute(&mut inner, current, attempt).await {
+                    Ok(response) if policy.retry_status.contains(&response.status().as_u16()) => {
+                        let wait = retry_after(&response)
+                            .unwrap_or_else(|| jitter(policy.backoff(attempt)));
+                        (response.url().clone(), response.status().to_string(), wait)
+                    }
+                    Err(e) if policy.retry_network_errors && is_transient(&e) => match e.url() {
+                        Some(url) => (url.clone(), root_cause(&e), jitter(policy.backoff(attempt))),
+                        None => return Err(e),
+                    },
+                    result => return result,
+                };
+
+                let wait = wait.min(Duration::from_millis(policy.max_backoff_ms));
+                info!(
+                    "Request to {} failed ({}), retrying in {}ms ({}/{})",
+                    redact::url(url.as_str()),
+                    reason,
+                    wait.as_millis(),
+                    attempt,
+                    policy.max_attempts
+                );
+                sleep(wait).await;
+                attempt += 1;
+            }
+        })
+    }
+}
+
+// One attempt, traced as its own span
+async fn execute<S>(service: &mut S, request: Request, attempt: u32) -> reqwest::Result<Response>
+where
+    S: Service<Request, Response = Response, Error = reqwest::Error>,
+{
+    let span = tracing::info_span!(
+        "http",
+        "http.request.method" = %request.method(),
+        "url.full" = %redact::url(request.url().as_str()),
+        "http.request.resend_count" = attempt - 1,
+        "http.request.id" = field::Empty,
+        "http.response.status_code" = field::Empty,
+        "cloudflare.ray" = field::Empty,
+    );
+    // Entered for the call too, for the layers below to record what they know
+    let service = service.ready().await?;
+    let result = span
+        .in_scope(|| service.call(request))
+        .instrument(span.clone())
+        .await;
+    if let Ok(response) = &result {
+        span.record("http.response.status_code", response.status().as_u16());
+    }
+    result
+}
+
+fn is_transient(e: &reqwest::Error) -> bool {
+    e.is_connect() || e.is_timeout() || e.is_request()
+}
+
+// Spread retries so clients failing together don't come back together
+fn jitter(delay: Duration) -> Duration {
+    delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+}
+
+// Either a number of seconds or an HTTP date
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    match value.trim().parse::<u64>() {
+        Ok(seconds) => Some(Duration::from_secs(seconds)),
+        Err(_) => httpdate::parse_http_date(value)
+            .ok()?
+            .duration_since(SystemTime::now())
+            .ok(),
+    }
+}
+
+// reqwest repeats its whole chain in each message, the innermost one says what happened
+fn root_cause(e: &reqwest::Error) -> String {
+    let mut cause: &dyn Error = e;
+    while let Some(source) = cause.source() {
+        cause = source;
+    }
+    cause.to_string()
+}
@@ -0,0 +1,251 @@
+use crate::config::{Config, MissedChecks};
+use crate::state::Trigger;
+use log::debug;
+use std::collections::{HashMap, HashSet};
+use tokio::time::{Duration, Instant};
+
+// Retries after a failure start here and double each time, up to the cap or the domain's
+// own interval, whichever is shorter
+const RETRY_BASE: Duration = Duration::from_secs(10);
+const RETRY_MAX: Duration = Duration::from_secs(300);
+
+// Domains are keyed by name rather than position so their schedule survives a config reload
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DomainKey {
+    pub zone_id: String,
+    pub domain: String,
+}
+
+#[derive(Debug)]
+struct Entry {
+    interval: Duration,
+    // When the last run was due rather than when it happened, so the time a run takes doesn't
+    // push back the ones after it
+    last_due: Option<Instant>,
+    // Random delay added to the interval, drawn after each run
+    jitter: Duration,
+    // Consecutive failed runs, and when the next attempt is due
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl Entry {
+    fn next_run(&self, first_run: Instant) -> Instant {
+        match (self.retry_at, self.last_due) {
+            (Some(retry_at), _) => retry_at,
+            (None, Some(last_due)) => last_due + self.interval + self.jitter,
+            (None, None) => first_run,
+        }
+    }
+
+    // Mark the run due on schedule as done, given when the run started and when it ended. A run
+    // started ahead of schedule, e.g. on request, leaves the schedule alone.
+    fn advance(&mut self, started: Instant, now: Instant, missed: MissedChecks) {
+        let Some(last_due) = self.last_due else {
+            self.last_due = Some(started);
+            return;
+        };
+        let due = last_due + self.interval;
+        if due > started {
+            return;
+        }
+        self.last_due = Some(match (due + self.interval > now, missed) {
+            (true, _) | (false, MissedChecks::Burst) => due,
+            (false, MissedChecks::Delay) => now,
+            (false, MissedChecks::Skip) => {
+                let missed = (now - due).as_nanos() / self.interval.as_nanos();
+                due + self.interval * missed as u32
+            }
+        });
+    }
+
+    // Exponential, with jitter so instances sharing a failing endpoint don't retry in lockstep
+    fn retry_delay(&self) -> Duration {
+        let exponent = self.failures.saturating_sub(1).min(16);
+        let delay = (RETRY_BASE * 2u32.pow(exponent))
+            .min(RETRY_MAX)
+            .min(self.interval);
+        delay.mul_f64(0.5 + fastrand::f64() / 2.0)
+    }
+}
+
+// Tracks when each domain is next due, so domains with different intervals share a single loop
+#[derive(Debug, Default)]
+pub struct Scheduler {
+    entries: HashMap<DomainKey, Entry>,
+    // Upper bound of the random delay added to each interval
+    jitter: Duration,
+    // Domains that never ran wait for this rather than starting right away
+    start: Option<Instant>,
+    missed: MissedChecks,
+}
+
+impl Scheduler {
+    pub fn new(config: &Config) -> Self {
+        let mut scheduler = Self::default();
+        scheduler.sync(config);
+        scheduler
+    }
+
+    // Align the schedule with a (re)loaded config: new domains are due immediately,
+    // removed or disabled ones are dropped and changed intervals apply from the last run's
+    // due time
+    pub fn sync(&mut self, config: &Config) {
+        self.jitter = Duration::from_secs(config.interval_jitter.unwrap_or(0));
+        self.missed = config.missed_checks;
+        let mut seen = HashSet::new();
+
+        for zone in config.zones.iter().filter(|zone| zone.is_enabled()) {
+            for domain in zone.domains.iter().filter(|domain| domain.is_enabled()) {
+                let key = DomainKey {
+                    zone_id: zone.id.to_string(),
+                    domain: domain.name.to_string(),
+                };
+                let interval =
+                    Duration::from_secs(config.settings(zone, domain).update_interval * 60);
+
+                self.entries
+                    .entry(key.clone())
+                    .and_modify(|entry| entry.interval = interval)
+                    .or_insert(Entry {
+                        interval,
+                        last_due: None,
+                        jitter: Duration::ZERO,
+                        failures: 0,
+                        retry_at: None,
+                    });
+                seen.insert(key);
+            }
+        }
+
+        self.entries.retain(|key, _| seen.contains(key));
+    }
+
+    pub fn covers_all(&self, keys: &HashSet<DomainKey>) -> bool {
+        self.entries.keys().all(|key| keys.contains(key))
+    }
+
+    pub fn shortest_interval(&self) -> Option<Duration> {
+        self.entries.values().map(|entry| entry.interval).min()
+    }
+
+    // Hold back the first run of every domain until the given time
+    pub fn delay_start(&mut self, start: Instant) {
+        self.start = Some(start);
+    }
+
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let first_run = self.start.unwrap_or_else(Instant::now);
+        self.entries
+            .values()
+            .map(|entry| entry.next_run(first_run))
+            .min()
+    }
+
+    pub fn due(&self, now: Instant) -> HashSet<DomainKey> {
+        let first_run = self.start.unwrap_or(now);
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.next_run(first_run) <= now)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    // Domains run together share their jitter, so they stay in the same cycle
+    pub fn complete(&mut self, keys: &HashSet<DomainKey>, started: Instant, now: Instant) {
+        let jitter = self.jitter.mul_f64(fastrand::f64());
+        for key in keys {
+            if let Some(entry) = self.entries.get_mut(key) {
+                entry.advance(started, now, self.missed);
+                entry.jitter = jitter;
+                entry.failures = 0;
+                entry.retry_at = None;
+            }
+        }
+    }
+
+    // Try domains again at the given time, without counting it as a failure
+    pub fn postpone(&mut self, keys: &HashSet<DomainKey>, until: Instant) {
+        for key in keys {
+            if let Some(entry) = self.entries.get_mut(key) {
+                entry.retry_at = Some(until);
+            }
+        }
+    }
+
+    // Schedule a retry for domains whose run failed, returning the longest delay
+    pub fn failed(&mut self, keys: &HashSet<DomainKey>, now: Instant) -> Duration {
+        let mut longest = Duration::ZERO;
+        for key in keys {
+            if let Some(entry) = self.entries.get_mut(key) {
+                entry.failures += 1;
+                let delay = entry.retry_delay();
+                entry.retry_at = Some(now + delay);
+                longest = longest.max(delay);
+            }
+        }
+        longest
+    }
+}
+
+// Requests for a check of every record, from signals, the admin API, the control socket or a
+// takeover. Those arriving within the debounce window of the first are run as one cycle, forced if
+// any of them was, and those that queued up during a cycle of every record are covered by it.
+#[derive(Debug, Default)]
+pub struct Requests {
+    // When the gathered requests are run, and how
+    due: Option<Instant>,
+    force: bool,
+    trigger: Trigger,
+    // Set from the end of a cycle of every record until the requests queued meanwhile are in,
+    // whether it was forced
+    covered: Option<bool>,
+}
+
+impl Requests {
+    pub fn add(&mut self, force: bool, trigger: Trigger, debounce: Duration) {
+        // A pushed address wasn't known to the cycle
+        let covered = self.covered.is_some_and(|forced| forced || !force);
+        if covered && trigger != Trigger::Push {
+            debug!("Already done by the cycle that just ended");
+            return;
+        }
+        match self.due {
+            Some(_) => {
+                debug!("Gathered with the check already requested");
+                if force && !self.force {
+                    self.force = true;
+                    self.trigger = trigger;
+                }
+            }
+            None => {
+                self.due = Some(Instant::now() + debounce);
+                self.force = force;
+                self.trigger = trigger;
+            }
+        }
+    }
+
+    pub fn due(&self) -> Option<Instant> {
+        self.due
+    }
+
+    // Whether to force the cycle, and what to record as its trigger
+    pub fn take(&mut self) -> (bool, Trigger) {
+        self.due = None;
+        (std::mem::take(&mut self.force), self.trigger)
+    }
+
+    pub fn covered_by(&mut self, force: bool) {
+        self.covered = Some(force);
+    }
+
+    pub fn covering(&self) -> bool {
+        self.covered.is_some()
+    }
+
+    // The requests queued during the cycle are in, later ones start a new one
+    pub fn settle(&mut self) {
+        self.covered = None;
+    }
+}
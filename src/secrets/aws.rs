@@ -0,0 +1,77 @@
+use anyhow::{Context, Result};
+
+#[cfg(feature = "aws")]
+async fn sdk_config() -> aws_config::SdkConfig {
+    // Region and credentials come from the usual environment, profile or instance metadata chain
+    aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await
+}
+
+#[cfg(feature = "aws")]
+pub async fn fetch_secret(secret_id: &str, key: Option<&str>) -> Result<String> {
+    log::debug!("Reading secret from AWS Secrets Manager: {}", secret_id);
+    let client = aws_sdk_secretsmanager::Client::new(&sdk_config().await);
+    let response = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .send()
+        .await
+        .with_context(|| format!("Failed to read AWS secret: {}", secret_id))?;
+
+    let value = response
+        .secret_string()
+        .with_context(|| format!("AWS secret {} has no string value", secret_id))?;
+
+    match key {
+        Some(key) => extract_key(value, key)
+            .with_context(|| format!("Invalid value for AWS secret: {}", secret_id)),
+        None => Ok(value.to_string()),
+    }
+}
+
+#[cfg(feature = "aws")]
+pub async fn fetch_parameter(name: &str) -> Result<String> {
+    log::debug!("Reading parameter from AWS SSM Parameter Store: {}", name);
+    let client = aws_sdk_ssm::Client::new(&sdk_config().await);
+    let response = client
+        .get_parameter()
+        .name(name)
+        .with_decryption(true)
+        .send()
+        .await
+        .with_context(|| format!("Failed to read AWS parameter: {}", name))?;
+
+    response
+        .parameter()
+        .and_then(|parameter| parameter.value())
+        .map(|value| value.to_string())
+        .with_context(|| format!("AWS parameter {} has no value", name))
+}
+
+#[cfg(not(feature = "aws"))]
+pub async fn fetch_secret(secret_id: &str, _key: Option<&str>) -> Result<String> {
+    Err(anyhow::anyhow!(
+        "Can't read AWS secret {}: clouddns was built without the `aws` feature",
+        secret_id
+    ))
+}
+
+#[cfg(not(feature = "aws"))]
+pub async fn fetch_parameter(name: &str) -> Result<String> {
+    Err(anyhow::anyhow!(
+        "Can't read AWS parameter {}: clouddns was built without the `aws` feature",
+        name
+    ))
+}
+
+// Secrets Manager values are often JSON objects holding several keys
+#[cfg_attr(not(feature = "aws"), allow(dead_code))]
+fn extract_key(value: &str, key: &str) -> Result<String> {
+    let object: serde_json::Map<String, serde_json::Value> =
+        serde_json::from_str(value).context("Secret value is not a JSON object")?;
+
+    match object.get(key) {
+        Some(serde_json::Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(anyhow::anyhow!("Key `{}` is not a string", key)),
+        None => Err(anyhow::anyhow!("Key `{}` not found", key)),
+    }
+}
@@ -0,0 +1,33 @@
+use crate::config::secret::is_docker_secret_name;
+use anyhow::{Context, Result};
+use log::debug;
+use std::{env, path::PathBuf};
+
+// Where Docker Swarm and Compose mount the `secrets:` of a service
+const SECRETS_DIR: &str = "/run/secrets";
+
+// Under systemd, the files passed with LoadCredential= take their place
+fn secrets_dir() -> PathBuf {
+    env::var_os("CREDENTIALS_DIRECTORY")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(SECRETS_DIR))
+}
+
+pub async fn fetch(name: &str) -> Result<String> {
+    if !is_docker_secret_name(name) {
+        return Err(anyhow::anyhow!("Invalid Docker secret name: {}", name));
+    }
+    let path = secrets_dir().join(name);
+    debug!("Reading secret from {}", path.display());
+
+    let value = tokio::fs::read_to_string(&path)
+        .await
+        .with_context(|| format!("Failed to read secret: {}", path.display()))?;
+
+    // Secret files usually end with a newline that isn't part of the value
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(anyhow::anyhow!("Secret {} is empty", path.display()));
+    }
+    Ok(value.to_string())
+}
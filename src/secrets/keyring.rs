@@ -0,0 +1,61 @@
+use anyhow::Result;
+
+// Every entry lives under this service name, the account tells them apart
+#[cfg(feature = "keyring")]
+const SERVICE: &str = "clouddns";
+
+#[cfg(feature = "keyring")]
+async fn entry<T, F>(account: &str, operation: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce(keyring::Entry) -> keyring::Result<T> + Send + 'static,
+{
+    use anyhow::Context;
+
+    let account = account.to_string();
+    // The platform APIs are blocking, keep them off the runtime threads
+    tokio::task::spawn_blocking(move || {
+        let entry = keyring::Entry::new(SERVICE, &account)?;
+        operation(entry)
+    })
+    .await?
+    .context("Keyring access failed")
+}
+
+#[cfg(feature = "keyring")]
+pub async fn get(account: &str) -> Result<String> {
+    entry(account, |entry| entry.get_password()).await
+}
+
+#[cfg(feature = "keyring")]
+pub async fn set(account: &str, secret: &str) -> Result<()> {
+    let secret = secret.to_string();
+    entry(account, move |entry| entry.set_password(&secret)).await
+}
+
+#[cfg(feature = "keyring")]
+pub async fn delete(account: &str) -> Result<()> {
+    entry(account, |entry| entry.delete_credential()).await
+}
+
+#[cfg(not(feature = "keyring"))]
+fn unsupported<T>() -> Result<T> {
+    Err(anyhow::anyhow!(
+        "Keyring support is not available: clouddns was built without the `keyring` feature"
+    ))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub async fn get(_account: &str) -> Result<String> {
+    unsupported()
+}
+
+#[cfg(not(feature = "keyring"))]
+pub async fn set(_account: &str, _secret: &str) -> Result<()> {
+    unsupported()
+}
+
+#[cfg(not(feature = "keyring"))]
+pub async fn delete(_account: &str) -> Result<()> {
+    unsupported()
+}
@@ -0,0 +1,35 @@
+mod aws;
+mod docker;
+pub mod keyring;
+mod vault;
+
+use crate::config::SecretSource;
+use crate::redact;
+use anyhow::Result;
+use secrecy::SecretString;
+
+// Turn a configured secret into its value, fetching it from its backend if needed. Backends hand
+// their value over as is, it is only wiped once wrapped. Every value is masked in the logs.
+pub async fn resolve(source: &SecretSource) -> Result<SecretString> {
+    let secret = fetch(source).await?;
+    redact::register(&secret);
+    Ok(secret)
+}
+
+async fn fetch(source: &SecretSource) -> Result<SecretString> {
+    if let Some(name) = source.docker_secret() {
+        return docker::fetch(name).await.map(SecretString::from);
+    }
+
+    let value = match source {
+        SecretSource::Plain(value) => return Ok(value.secret()),
+        SecretSource::Vault { vault } => vault::fetch(vault).await,
+        SecretSource::AwsSecret {
+            aws_secret,
+            aws_secret_key,
+        } => aws::fetch_secret(aws_secret, aws_secret_key.as_deref()).await,
+        SecretSource::AwsParameter { aws_ssm } => aws::fetch_parameter(aws_ssm).await,
+        SecretSource::Keyring { keyring } => keyring::get(keyring.account()).await,
+    };
+    value.map(SecretString::from)
+}
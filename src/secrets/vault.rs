@@ -0,0 +1,123 @@
+use crate::config::VaultSecret;
+use crate::http;
+use anyhow::{Context, Result};
+use log::debug;
+use secrecy::{ExposeSecret, SecretString};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+
+#[derive(Debug, Deserialize)]
+struct LoginResponse {
+    auth: LoginAuth,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginAuth {
+    client_token: SecretString,
+}
+
+#[derive(Debug, Deserialize)]
+struct KvResponse {
+    data: KvData,
+}
+
+#[derive(Debug, Deserialize)]
+struct KvData {
+    data: serde_json::Map<String, Value>,
+}
+
+struct VaultClient {
+    client: reqwest::Client,
+    address: String,
+    namespace: Option<String>,
+}
+
+impl VaultClient {
+    fn from_env() -> Result<Self> {
+        let address = env::var("VAULT_ADDR").context("VAULT_ADDR is not set")?;
+
+        Ok(Self {
+            client: http::client(),
+            address: address.trim_end_matches('/').to_string(),
+            namespace: env::var("VAULT_NAMESPACE").ok(),
+        })
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}/v1/{}", self.address, path));
+        match &self.namespace {
+            Some(namespace) => request.header("X-Vault-Namespace", namespace),
+            None => request,
+        }
+    }
+
+    // VAULT_TOKEN wins, otherwise log in with AppRole credentials
+    async fn token(&self) -> Result<SecretString> {
+        if let Ok(token) = env::var("VAULT_TOKEN") {
+            return Ok(token.into());
+        }
+
+        let (role_id, secret_id) = match (env::var("VAULT_ROLE_ID"), env::var("VAULT_SECRET_ID")) {
+            (Ok(role_id), Ok(secret_id)) => (role_id, secret_id),
+            _ => {
+                return Err(anyhow::anyhow!(
+                "Set VAULT_TOKEN, or VAULT_ROLE_ID and VAULT_SECRET_ID, to authenticate with Vault"
+            ))
+            }
+        };
+        let mount = env::var("VAULT_APPROLE_MOUNT").unwrap_or_else(|_| "approle".to_string());
+
+        debug!("Logging in to Vault with AppRole");
+        let response: LoginResponse = self
+            .request(reqwest::Method::POST, &format!("auth/{}/login", mount))
+            .json(&json!({ "role_id": role_id, "secret_id": secret_id }))
+            .send()
+            .await?
+            .error_for_status()
+            .context("Vault AppRole login failed")?
+            .json()
+            .await?;
+
+        Ok(response.auth.client_token)
+    }
+}
+
+pub async fn fetch(secret: &VaultSecret) -> Result<String> {
+    let vault = VaultClient::from_env()?;
+    let token = vault.token().await?;
+
+    let path = format!(
+        "{}/data/{}",
+        secret.mount.trim_matches('/'),
+        secret.path.trim_matches('/')
+    );
+    debug!("Reading secret from Vault: {}", &path);
+
+    let response: KvResponse = vault
+        .request(reqwest::Method::GET, &path)
+        .header("X-Vault-Token", token.expose_secret())
+        .send()
+        .await?
+        .error_for_status()
+        .with_context(|| format!("Failed to read Vault secret: {}", &path))?
+        .json()
+        .await
+        .with_context(|| format!("Unexpected response for Vault secret: {}", &path))?;
+
+    match response.data.data.get(&secret.key) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(_) => Err(anyhow::anyhow!(
+            "Key `{}` of Vault secret {} is not a string",
+            &secret.key,
+            &path
+        )),
+        None => Err(anyhow::anyhow!(
+            "Key `{}` not found in Vault secret {}",
+            &secret.key,
+            &path
+        )),
+    }
+}
@@ -0,0 +1,251 @@
+// Reports to Sentry, or anything speaking its protocol such as GlitchTip: panics as they happen,
+// and cycles once they have failed `failures` times in a row. Events go to the store endpoint of
+// the project, https://KEY@HOST/42 giving https://HOST/api/42/store/.
+
+use crate::config::Config;
+use crate::error::ErrorKind;
+use crate::{correlation, http, secrets};
+use anyhow::{bail, Context, Result};
+use chrono::{SecondsFormat, Utc};
+use log::{debug, warn};
+use reqwest::{Client, Url};
+use secrecy::{ExposeSecret, SecretString};
+use serde_json::{json, Value};
+use std::{
+    backtrace::Backtrace,
+    panic::{self, PanicHookInfo},
+    sync::{Arc, Once, RwLock},
+    thread,
+    time::Duration,
+};
+
+// The longest a panicking thread waits for its report to go out
+const PANIC_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Of the thread sending a panic, whose own panics aren't reported
+const REPORTER_THREAD: &str = "sentry-reporter";
+
+// What the panic hook reports to, following the configuration
+static PANICS: RwLock<Option<Arc<Sentry>>> = RwLock::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+pub(crate) struct Sentry {
+    store: Url,
+    auth: SecretString,
+    environment: Option<String>,
+    failures: u32,
+    server_name: Option<String>,
+    provider: &'static str,
+    // Panics are sent from a runtime of their own, which can't reuse the connections of another
+    panic_client: Client,
+}
+
+impl Sentry {
+    pub async fn new(config: &Config) -> Result<Option<Arc<Self>>> {
+        let Some(sentry) = &config.sentry else {
+            return Ok(None);
+        };
+        let dsn = secrets::resolve(&sentry.dsn)
+            .await
+            .context("Failed to read the Sentry DSN")?;
+        let (store, key) = parse_dsn(dsn.expose_secret())?;
+        let auth = format!(
+            "Sentry sentry_version=7, sentry_client=clouddns/{}, sentry_key={}",
+            env!("CARGO_PKG_VERSION"),
+            key
+        );
+        let panic_client = http::builder(config)
+            .await?
+            .pool_max_idle_per_host(0)
+            .build()
+            .context("Failed to set up the HTTP client")?;
+        Ok(Some(Arc::new(Self {
+            store,
+            auth: SecretString::from(auth),
+            environment: sentry.environment.clone(),
+            failures: sentry.failures,
+            server_name: hostname::get()
+                .ok()
+                .and_then(|name| name.into_string().ok()),
+            provider: config.provider.r#type.as_str(),
+            panic_client,
+        })))
+    }
+
+    // Failed cycles in a row worth a report
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    pub async fn capture_failures(&self, error: &anyhow::Error, failures: u32, domains: &[String]) {
+        let kind = error
+            .downcast_ref::<ErrorKind>()
+            .map_or_else(|| "Other".to_string(), |kind| format!("{:?}", kind));
+        // Oldest first, the root cause then each error wrapping it
+        let mut chain: Vec<Value> = error
+            .chain()
+            .map(|cause| json!({ "type": "Error", "value": cause.to_string() }))
+            .collect();
+        chain.reverse();
+        let event = self.event(
+            "error",
+            json!({ "values": chain }),
+            json!({ "kind": kind }),
+            json!({ "failures": failures, "domains": domains }),
+            // One issue per kind of failure rather than one per wording of it
+            json!(["cycle-failed", kind]),
+        );
+        match send(&http::client(), &self.store, &self.auth, &event).await {
+            Ok(()) => debug!("Reported {} failed cycles to Sentry", failures),
+            Err(e) => warn!("Failed to report to Sentry: {:#}", e),
+        }
+    }
+
+    fn capture_panic(&self, info: &PanicHookInfo) {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(message) => message.to_string(),
+            None => info
+                .payload()
+                .downcast_ref::<String>()
+                .cloned()
+                .unwrap_or_else(|| "Box<dyn Any>".to_string()),
+        };
+        let location = info
+            .location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_default();
+        let exception = json!({ "values": [{
+            "type": "panic",
+            "value": message,
+            "mechanism": { "type": "panic", "handled": false },
+        }] });
+        let extra = json!({
+            "location": location,
+            "thread": thread::current().name().unwrap_or("unnamed"),
+            "backtrace": Backtrace::force_capture().to_string(),
+        });
+        let event = self.event(
+            "fatal",
+            exception,
+            json!({}),
+            extra,
+            json!(["panic", location]),
+        );
+
+        // The runtime the panic happened in may be the one unwinding, a thread of its own sends it
+        let (client, store, auth) = (
+            self.panic_client.clone(),
+            self.store.clone(),
+            self.auth.clone(),
+        );
+        let sent = thread::Builder::new()
+            .name(REPORTER_THREAD.to_string())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(async {
+                    tokio::time::timeout(PANIC_TIMEOUT, send(&client, &store, &auth, &event))
+                        .await
+                        .context("Timed out")?
+                })
+            })
+            .map_err(anyhow::Error::from)
+            .and_then(|reporter| {
+                reporter
+                    .join()
+                    .unwrap_or_else(|_| Err(anyhow::anyhow!("The reporter panicked")))
+            });
+        match sent {
+            Ok(()) => eprintln!("The panic was reported to Sentry"),
+            Err(e) => eprintln!("Failed to report the panic to Sentry: {:#}", e),
+        }
+    }
+
+    fn event(
+        &self,
+        level: &str,
+        exception: Value,
+        tags: Value,
+        extra: Value,
+        fingerprint: Value,
+    ) -> Value {
+        let mut tags = tags;
+        tags["provider"] = json!(self.provider);
+        if let Some(cycle) = correlation::current() {
+            tags["cycle_id"] = json!(cycle);
+        }
+        json!({
+            "event_id": event_id(),
+            "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            "platform": "native",
+            "level": level,
+            "logger": "clouddns",
+            "release": format!("clouddns@{}", env!("CARGO_PKG_VERSION")),
+            "environment": self.environment,
+            "server_name": self.server_name,
+            "exception": exception,
+            "tags": tags,
+            "extra": extra,
+            "fingerprint": fingerprint,
+            "contexts": {
+                "os": { "name": std::env::consts::OS },
+                "device": { "arch": std::env::consts::ARCH },
+            },
+        })
+    }
+}
+
+// Panics anywhere in the process go to `sentry` from now on, none when it's None. The hook is set
+// once, on top of the one printing the panic.
+pub(crate) fn report_panics(sentry: Option<Arc<Sentry>>) {
+    *PANICS.write().unwrap_or_else(|e| e.into_inner()) = sentry;
+    PANIC_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            previous(info);
+            if thread::current().name() == Some(REPORTER_THREAD) {
+                return;
+            }
+            let sentry = PANICS.read().unwrap_or_else(|e| e.into_inner()).clone();
+            if let Some(sentry) = sentry {
+                sentry.capture_panic(info);
+            }
+        }));
+    });
+}
+
+async fn send(client: &Client, store: &Url, auth: &SecretString, event: &Value) -> Result<()> {
+    client
+        .post(store.clone())
+        .header("X-Sentry-Auth", auth.expose_secret())
+        .json(event)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .context("Sentry didn't take the event")?;
+    Ok(())
+}
+
+// https://KEY@HOST[:PORT][/PATH]/PROJECT to the store endpoint and the key
+fn parse_dsn(dsn: &str) -> Result<(Url, String)> {
+    let url = Url::parse(dsn.trim()).context("Invalid Sentry DSN")?;
+    let key = url.username();
+    let path = url.path().trim_end_matches('/');
+    let (prefix, project) = path.rsplit_once('/').unwrap_or_default();
+    if key.is_empty() || project.is_empty() || url.host_str().is_none() {
+        bail!("Invalid Sentry DSN, expected e.g. https://KEY@o0.ingest.sentry.io/42");
+    }
+    let mut store = url.clone();
+    store.set_username("").ok();
+    store.set_password(None).ok();
+    store.set_path(&format!("{}/api/{}/store/", prefix, project));
+    Ok((store, key.to_string()))
+}
+
+// 32 hexadecimal digits, a UUID without dashes
+fn event_id() -> String {
+    let mut bytes = [0; 16];
+    getrandom::getrandom(&mut bytes).expect("the system has no random number generator");
+    hex::encode(bytes)
+}
@@ -0,0 +1,157 @@
+use crate::cli::Cli;
+use anyhow::{Context, Result};
+use clap::Parser;
+use clouddns::ddns::CloudflareDdns;
+use clouddns::{error, eventlog};
+use log::{error, info};
+use std::{env, ffi::OsString, fs, time::Duration};
+use tokio::sync::oneshot;
+use windows_service::{
+    define_windows_service,
+    service::{
+        ServiceAccess, ServiceControl, ServiceControlAccept, ServiceErrorControl, ServiceExitCode,
+        ServiceInfo, ServiceStartType, ServiceState, ServiceStatus, ServiceType,
+    },
+    service_control_handler::{self, ServiceControlHandlerResult},
+    service_dispatcher,
+    service_manager::{ServiceManager, ServiceManagerAccess},
+};
+
+const SERVICE_NAME: &str = "clouddns";
+const SERVICE_TYPE: ServiceType = ServiceType::OWN_PROCESS;
+
+// Register the current binary with the service control manager, starting automatically at boot
+// with the given configuration
+pub fn install(config_file: &str, profile: Option<&str>) -> Result<()> {
+    let config = fs::canonicalize(config_file)
+        .with_context(|| format!("Failed to locate config file: {}", config_file))?;
+    let mut launch_arguments = vec![OsString::from("--config"), config.into_os_string()];
+    if let Some(profile) = profile {
+        launch_arguments.extend([OsString::from("--profile"), OsString::from(profile)]);
+    }
+    launch_arguments.extend([OsString::from("service"), OsString::from("run")]);
+
+    let manager = ServiceManager::local_computer(
+        None::<&str>,
+        ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE,
+    )?;
+    let service = manager.create_service(
+        &ServiceInfo {
+            name: OsString::from(SERVICE_NAME),
+            display_name: OsString::from("clouddns"),
+            service_type: SERVICE_TYPE,
+            start_type: ServiceStartType::AutoStart,
+            error_control: ServiceErrorControl::Normal,
+            executable_path: env::current_exe()?,
+            launch_arguments,
+            dependencies: vec![],
+            // LocalSystem
+            account_name: None,
+            account_password: None,
+        },
+        ServiceAccess::CHANGE_CONFIG,
+    )?;
+    service.set_description("Dynamic DNS updater for Cloudflare")?;
+    eventlog::register()?;
+
+    println!(
+        "Installed the {} service, start it with `sc start {}`",
+        SERVICE_NAME, SERVICE_NAME
+    );
+    Ok(())
+}
+
+pub fn uninstall() -> Result<()> {
+    let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)?;
+    let service = manager.open_service(
+        SERVICE_NAME,
+        ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE,
+    )?;
+
+    // Deletion completes once the service has stopped
+    service.delete()?;
+    if service.query_status()?.current_state != ServiceState::Stopped {
+        service.stop()?;
+    }
+
+    eventlog::deregister()?;
+
+    println!("Removed the {} service", SERVICE_NAME);
+    Ok(())
+}
+
+// Hand the process over to the service control manager, which calls back into `service_main`.
+// Only meaningful when started by the SCM, as set up by `install`.
+pub async fn run() -> Result<()> {
+    tokio::task::spawn_blocking(|| service_dispatcher::start(SERVICE_NAME, ffi_service_main))
+        .await?
+        .context("Failed to connect to the service control manager, is this running as a service?")
+}
+
+define_windows_service!(ffi_service_main, service_main);
+
+fn service_main(_arguments: Vec<OsString>) {
+    // The SCM passes the arguments `install` registered on the command line, not here
+    let cli = Cli::parse();
+    if let Err(e) = run_service(cli) {
+        error!("Service failed: {:?}", e);
+    }
+}
+
+fn run_service(cli: Cli) -> Result<()> {
+    let (stop_tx, stop_rx) = oneshot::channel();
+    let mut stop_tx = Some(stop_tx);
+    let status_handle =
+        service_control_handler::register(SERVICE_NAME, move |control| match control {
+            ServiceControl::Stop | ServiceControl::Shutdown => {
+                if let Some(stop_tx) = stop_tx.take() {
+                    let _ = stop_tx.send(());
+                }
+                ServiceControlHandlerResult::NoError
+            }
+            ServiceControl::Interrogate => ServiceControlHandlerResult::NoError,
+            _ => ServiceControlHandlerResult::NotImplemented,
+        })?;
+
+    let set_status = |state, controls_accepted, exit_code| {
+        status_handle.set_service_status(ServiceStatus {
+            service_type: SERVICE_TYPE,
+            current_state: state,
+            controls_accepted,
+            exit_code,
+            checkpoint: 0,
+            wait_hint: Duration::default(),
+            process_id: None,
+        })
+    };
+    set_status(
+        ServiceState::Running,
+        ServiceControlAccept::STOP | ServiceControlAccept::SHUTDOWN,
+        ServiceExitCode::Win32(0),
+    )?;
+
+    let result = tokio::runtime::Runtime::new()?.block_on(async {
+        let mut ddns = CloudflareDdns::new(&cli.config, cli.profile.as_deref())
+            .await?
+            .ip_override(&cli.ip_override)?
+            .lock(cli.lock_file.as_deref())?
+            .state(cli.state_file.as_deref())?;
+        ddns.run(async {
+            let _ = stop_rx.await;
+            info!("Stop requested by the service control manager");
+        })
+        .await?;
+        anyhow::Ok(())
+    });
+
+    let exit_code = match &result {
+        Ok(()) => ServiceExitCode::Win32(0),
+        Err(e) => ServiceExitCode::ServiceSpecific(error::exit_code(e).into()),
+    };
+    set_status(
+        ServiceState::Stopped,
+        ServiceControlAccept::empty(),
+        exit_code,
+    )?;
+    result
+}
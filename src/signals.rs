@@ -0,0 +1,43 @@
+use anyhow::Result;
+#[cfg(unix)]
+use tokio::signal::unix::{signal, Signal, SignalKind};
+
+// Listens for one of the signals used to control the daemon:
+// SIGHUP reloads the configuration, SIGUSR1 checks every record right away and
+// SIGUSR2 forces an update of every record.
+// On platforms without these signals the listener simply never fires.
+pub struct SignalListener {
+    #[cfg(unix)]
+    signal: Signal,
+}
+
+impl SignalListener {
+    pub fn reload() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: signal(SignalKind::hangup())?,
+        })
+    }
+
+    pub fn update_now() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: signal(SignalKind::user_defined1())?,
+        })
+    }
+
+    pub fn force_update() -> Result<Self> {
+        Ok(Self {
+            #[cfg(unix)]
+            signal: signal(SignalKind::user_defined2())?,
+        })
+    }
+
+    pub async fn recv(&mut self) {
+        #[cfg(unix)]
+        self.signal.recv().await;
+
+        #[cfg(not(unix))]
+        std::future::pending::<()>().await;
+    }
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, net::IpAddr};
+use tokio::time::Instant;
+
+/// What the daemon knows right now, published after every step for live views
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    // Keyed by source and record type
+    pub addresses: BTreeMap<(String, &'static str), Result<IpAddr, String>>,
+    // Keyed by record name and type
+    pub records: BTreeMap<(String, &'static str), RecordStatus>,
+    pub next_run: Option<Instant>,
+    pub paused: bool,
+    pub in_maintenance: bool,
+    // End of the last cycle that went through without errors
+    pub last_success: Option<Instant>,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordStatus {
+    pub tenant: Option<String>,
+    // What the provider serves, as of the last check
+    pub content: Option<String>,
+    pub state: RecordState,
+    pub checked: DateTime<Local>,
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordState {
+    InSync,
+    Updated,
+    // Would be updated, in a dry run
+    Pending,
+    // Held back by a maintenance window
+    Deferred,
+    Failed(String),
+}
+
+impl RecordState {
+    // Every name `as_str` gives
+    pub const NAMES: [&'static str; 5] = ["in_sync", "updated", "pending", "deferred", "failed"];
+
+    // As published over MQTT, the admin API and the control socket
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RecordState::InSync => "in_sync",
+            RecordState::Updated => "updated",
+            RecordState::Pending => "pending",
+            RecordState::Deferred => "deferred",
+            RecordState::Failed(_) => "failed",
+        }
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            RecordState::Failed(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+// The snapshot as served by the admin API and the control socket
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Report {
+    pub paused: bool,
+    pub in_maintenance: bool,
+    // Seconds until the next scheduled check
+    pub next_check: Option<u64>,
+    // Seconds since the last check that went through without errors
+    pub last_success: Option<u64>,
+    pub addresses: Vec<AddressReport>,
+    pub records: Vec<RecordReport>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddressReport {
+    pub source: String,
+    pub r#type: String,
+    pub address: Option<IpAddr>,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordReport {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub record: String,
+    pub r#type: String,
+    pub state: String,
+    pub content: Option<String>,
+    pub checked: DateTime<Local>,
+    pub error: Option<String>,
+}
+
+impl From<Snapshot> for Report {
+    fn from(snapshot: Snapshot) -> Self {
+        let addresses = snapshot
+            .addresses
+            .into_iter()
+            .map(|((source, r#type), address)| {
+                let (address, error) = match address {
+                    Ok(address) => (Some(address), None),
+                    Err(error) => (None, Some(error)),
+                };
+                AddressReport {
+                    source,
+                    r#type: r#type.to_string(),
+                    address,
+                    error,
+                }
+            })
+            .collect();
+        // Grouped by tenant, those without one first
+        let mut records: Vec<RecordReport> = snapshot
+            .records
+            .into_iter()
+            .map(|((record, r#type), status)| RecordReport {
+                tenant: status.tenant,
+                record,
+                r#type: r#type.to_string(),
+                state: status.state.as_str().to_string(),
+                error: status.state.error().map(str::to_string),
+                content: status.content,
+                checked: status.checked,
+            })
+            .collect();
+        records.sort_by(|a, b| a.tenant.cmp(&b.tenant));
+
+        Report {
+            paused: snapshot.paused,
+            in_maintenance: snapshot.in_maintenance,
+            next_check: snapshot
+                .next_run
+                .map(|at| at.saturating_duration_since(Instant::now()).as_secs()),
+            last_success: snapshot.last_success.map(|at| at.elapsed().as_secs()),
+            addresses,
+            records,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use super::{decode, encode, State, StateStore};
+use crate::api::{KvClient, Limiter};
+use crate::config::Config;
+use crate::secrets;
+use anyhow::{Context, Result};
+use log::debug;
+use std::{
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+/// The state as one value of a Cloudflare Workers KV namespace, in the JSON of the state file, so
+/// that instances without storage of their own, or reinstalled, pick it up where another left it.
+/// It is reached with the API token. KV takes few writes a day on the free plan, a save that
+/// changes nothing writes nothing. No history is kept.
+pub struct KvStore {
+    key: String,
+    requests: mpsc::Sender<Request>,
+    // What the namespace holds as far as this instance knows
+    written: Mutex<Option<String>>,
+}
+
+// The store is called from synchronous code, inside a runtime or not, so a thread of its own with
+// a runtime of its own makes the requests
+enum Request {
+    Get(mpsc::Sender<Result<Option<String>>>),
+    Put(String, mpsc::Sender<Result<()>>),
+}
+
+impl KvStore {
+    pub fn new(config: &Config, key: String) -> Result<Self> {
+        let (Some(account_id), Some(namespace_id)) =
+            (&config.state.account_id, &config.state.namespace_id)
+        else {
+            anyhow::bail!("The kv state store needs an account_id and a namespace_id");
+        };
+        let (account_id, namespace_id) = (account_id.clone(), namespace_id.clone());
+        let (api_token, retry) = (config.api_token.clone(), config.retry.clone());
+        let limiter = Limiter::new(config.api_rate_limit());
+        let (requests, received) = mpsc::channel::<Request>();
+        let worker_key = key.clone();
+        thread::Builder::new()
+            .name("kv-state".to_string())
+            .spawn(move || {
+                let runtime = match tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                {
+                    Ok(runtime) => runtime,
+                    Err(e) => {
+                        let e = format!("Failed to start the KV state store: {}", e);
+                        return fail(received, &e);
+                    }
+                };
+                let client = runtime.block_on(async {
+                    let api_token = secrets::resolve(&api_token).await?;
+                    anyhow::Ok(KvClient::new(
+                        &api_token,
+                        &retry,
+                        limiter,
+                        &account_id,
+                        &namespace_id,
+                    ))
+                });
+                let client = match client {
+                    Ok(client) => client,
+                    Err(e) => return fail(received, &format!("{:#}", e)),
+                };
+                for request in received {
+                    match request {
+                        Request::Get(reply) => {
+                            reply.send(runtime.block_on(client.get(&worker_key))).ok();
+                        }
+                        Request::Put(value, reply) => {
+                            reply
+                                .send(runtime.block_on(client.put(&worker_key, value)))
+                                .ok();
+                        }
+                    }
+                }
+            })
+            .context("Failed to start the KV state store")?;
+        Ok(Self {
+            key,
+            requests,
+            written: Mutex::new(None),
+        })
+    }
+
+    fn get(&self) -> Result<Option<String>> {
+        let (reply, answer) = mpsc::channel();
+        self.requests
+            .send(Request::Get(reply))
+            .ok()
+            .and_then(|()| answer.recv().ok())
+            .context("The KV state store stopped")?
+    }
+
+    fn put(&self, value: String) -> Result<()> {
+        let (reply, answer) = mpsc::channel();
+        self.requests
+            .send(Request::Put(value, reply))
+            .ok()
+            .and_then(|()| answer.recv().ok())
+            .context("The KV state store stopped")?
+    }
+}
+
+// Answers every request with the error the thread failed to start with
+fn fail(received: mpsc::Receiver<Request>, error: &str) {
+    for request in received {
+        match request {
+            Request::Get(reply) => {
+                reply.send(Err(anyhow::anyhow!("{}", error))).ok();
+            }
+            Request::Put(_, reply) => {
+                reply.send(Err(anyhow::anyhow!("{}", error))).ok();
+            }
+        }
+    }
+}
+
+impl StateStore for KvStore {
+    fn load(&self) -> Result<State> {
+        let Some(contents) = self.get()? else {
+            debug!("No state under {} in the KV namespace yet", self.key);
+            return Ok(State::default());
+        };
+        let state = decode(&contents)
+            .with_context(|| format!("Invalid state under {} in the KV namespace", self.key))?;
+        debug!("Loaded state from {} in the KV namespace", self.key);
+        *self.written.lock().unwrap_or_else(|e| e.into_inner()) = Some(contents);
+        Ok(state)
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        let contents = encode(state)?;
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if written.as_deref() == Some(contents.as_str()) {
+            return Ok(());
+        }
+        self.put(contents.clone())?;
+        *written = Some(contents);
+        Ok(())
+    }
+}
@@ -0,0 +1,532 @@
+mod kv;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use crate::config::{Config, IpSource, RecordType, StateStoreKind};
+use crate::lock::instance_name;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    env, fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    net::IpAddr,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+pub use kv::KvStore;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteStore;
+
+/// Where the updater keeps its [`State`] across restarts, selected with the `[state]` table of
+/// the configuration or given to [`CloudflareDdns::state_store`]
+///
+/// [`CloudflareDdns::state_store`]: crate::CloudflareDdns::state_store
+pub trait StateStore: Send + Sync {
+    /// The state saved last, empty when nothing was saved yet
+    fn load(&self) -> Result<State>;
+    /// Called after every cycle, and once more when the daemon stops
+    fn save(&self, state: &State) -> Result<()>;
+    /// Keeps the changes made to the records and the updates that failed, in the order they were
+    /// made. Stores without a history drop them.
+    fn append_history(&self, _entries: &[HistoryEntry]) -> Result<()> {
+        Ok(())
+    }
+    /// The entries kept that the query matches, oldest first
+    fn history(&self, _query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        Ok(Vec::new())
+    }
+}
+
+/// What a restart should remember: the addresses last detected, for each record the content
+/// last confirmed with the provider and how its recent updates went, and whether updates were
+/// paused
+#[derive(Debug, Default, Clone)]
+pub struct State {
+    pub addresses: HashMap<(IpSource, RecordType), IpAddr>,
+    pub records: HashMap<(String, RecordType), RecordHistory>,
+    /// Records are checked but not written until updates are resumed
+    pub paused: bool,
+}
+
+/// What is known of a record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordHistory {
+    // Unknown until the record has been seen in sync or updated
+    pub content: Option<String>,
+    pub last_success: Option<DateTime<Utc>>,
+    // Consecutive failed updates
+    pub failures: u32,
+    // Created for a discovered hostname, so it is deleted once nothing asks for it. Records that
+    // were there before are never deleted.
+    pub created: bool,
+}
+
+/// A change made to a record, or an update that failed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub time: DateTime<Utc>,
+    pub record: String,
+    pub r#type: RecordType,
+    /// What it held, none for a record created
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous: Option<String>,
+    /// What it holds now, none for a record deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    pub reason: ChangeReason,
+    pub trigger: Trigger,
+    /// Why the update failed, the record then still holding `previous`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Which of the entries kept [`StateStore::history`] returns, all of them by default
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HistoryQuery {
+    /// Only those of this name and the names under it, e.g. `example.com` for the whole zone
+    pub domain: Option<String>,
+    /// Only those made since then
+    pub since: Option<DateTime<Utc>>,
+    /// Only those made before then
+    pub until: Option<DateTime<Utc>>,
+    /// Only the failed updates
+    pub failed: bool,
+    /// Only the last ones, this many
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    /// Whether the entry is one to return, `limit` aside
+    pub fn matches(&self, entry: &HistoryEntry) -> bool {
+        let domain = self.domain.as_deref().is_none_or(|domain| {
+            entry.record == domain
+                || entry
+                    .record
+                    .strip_suffix(domain)
+                    .is_some_and(|name| name.ends_with('.'))
+        });
+        domain
+            && self.since.is_none_or(|since| entry.time >= since)
+            && self.until.is_none_or(|until| entry.time < until)
+            && (!self.failed || entry.error.is_some())
+    }
+
+    // The matching entries, oldest first, cut down to the limit
+    fn limited(&self, mut entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+        if let Some(limit) = self.limit {
+            entries.drain(..entries.len().saturating_sub(limit));
+        }
+        entries
+    }
+}
+
+/// Why a record was changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChangeReason {
+    /// The address changed
+    AddressChanged,
+    /// Someone else changed the record, it was set back
+    Drift,
+    /// Rewritten as it was, by a forced update
+    Forced,
+    /// Created for a discovered hostname
+    Discovered,
+    /// Deleted, nothing asking for its hostname anymore
+    Released,
+}
+
+/// What started the check that changed a record
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Trigger {
+    /// The record's interval came round
+    #[default]
+    Schedule,
+    /// `SIGUSR1` or `SIGUSR2`
+    Signal,
+    /// A request through the admin API, the control socket or a [`Control`] channel
+    ///
+    /// [`Control`]: crate::Control
+    Request,
+    /// A request carrying the address to use
+    Push,
+    /// A maintenance window ending, with changes held back
+    Maintenance,
+    /// A single pass such as `clouddns once`
+    Once,
+    /// Discovery, for records created or deleted as hostnames come and go
+    Discovery,
+    /// This instance taking the leader election lease over
+    Takeover,
+}
+
+impl ChangeReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeReason::AddressChanged => "address-changed",
+            ChangeReason::Drift => "drift",
+            ChangeReason::Forced => "forced",
+            ChangeReason::Discovered => "discovered",
+            ChangeReason::Released => "released",
+        }
+    }
+}
+
+impl fmt::Display for ChangeReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Trigger {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Trigger::Schedule => "schedule",
+            Trigger::Signal => "signal",
+            Trigger::Request => "request",
+            Trigger::Push => "push",
+            Trigger::Maintenance => "maintenance",
+            Trigger::Once => "once",
+            Trigger::Discovery => "discovery",
+            Trigger::Takeover => "takeover",
+        }
+    }
+}
+
+impl fmt::Display for Trigger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+// On disk, maps keyed by tuples don't serialize to JSON
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    #[serde(default)]
+    addresses: Vec<AddressEntry>,
+    #[serde(default)]
+    records: Vec<RecordEntry>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    paused: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct AddressEntry {
+    source: IpSource,
+    r#type: RecordType,
+    address: IpAddr,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecordEntry {
+    record: String,
+    r#type: RecordType,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_success: Option<DateTime<Utc>>,
+    #[serde(default)]
+    failures: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    created: bool,
+}
+
+// The state as the JSON of the state file, in a stable order
+fn encode(state: &State) -> Result<String> {
+    let mut addresses: Vec<AddressEntry> = state
+        .addresses
+        .iter()
+        .map(|((source, record_type), address)| AddressEntry {
+            source: source.clone(),
+            r#type: *record_type,
+            address: *address,
+        })
+        .collect();
+    addresses.sort_by_key(|entry| (entry.source.to_string(), entry.r#type.as_str()));
+    let mut records: Vec<RecordEntry> = state
+        .records
+        .iter()
+        .map(|((record, record_type), history)| RecordEntry {
+            record: record.clone(),
+            r#type: *record_type,
+            content: history.content.clone(),
+            last_success: history.last_success,
+            failures: history.failures,
+            created: history.created,
+        })
+        .collect();
+    records.sort_by(|a, b| (&a.record, a.r#type.as_str()).cmp(&(&b.record, b.r#type.as_str())));
+    Ok(serde_json::to_string_pretty(&StateFile {
+        addresses,
+        records,
+        paused: state.paused,
+    })?)
+}
+
+fn decode(contents: &str) -> Result<State> {
+    let file: StateFile = serde_json::from_str(contents)?;
+    Ok(State {
+        addresses: file
+            .addresses
+            .into_iter()
+            .map(|entry| ((entry.source, entry.r#type), entry.address))
+            .collect(),
+        records: file
+            .records
+            .into_iter()
+            .map(|entry| {
+                let history = RecordHistory {
+                    content: entry.content,
+                    last_success: entry.last_success,
+                    failures: entry.failures,
+                    created: entry.created,
+                };
+                ((entry.record, entry.r#type), history)
+            })
+            .collect(),
+        paused: file.paused,
+    })
+}
+
+impl State {
+    // Whether the record was confirmed to hold this content since the given time
+    pub(crate) fn confirmed_since(
+        &self,
+        record: &str,
+        record_type: RecordType,
+        content: &str,
+        since: DateTime<Utc>,
+    ) -> bool {
+        self.records
+            .get(&(record.to_string(), record_type))
+            .is_some_and(|history| {
+                history.content.as_deref() == Some(content)
+                    && history.last_success.is_some_and(|last| last >= since)
+            })
+    }
+
+    // Returns how many failures in a row this ends
+    pub(crate) fn succeeded(
+        &mut self,
+        record: &str,
+        record_type: RecordType,
+        content: String,
+    ) -> u32 {
+        let history = self.history(record, record_type);
+        history.content = Some(content);
+        history.last_success = Some(Utc::now());
+        std::mem::take(&mut history.failures)
+    }
+
+    pub(crate) fn created(&mut self, record: &str, record_type: RecordType) {
+        self.history(record, record_type).created = true;
+    }
+
+    // Returns how many failures in a row there have been, this one included
+    pub(crate) fn failed(&mut self, record: &str, record_type: RecordType) -> u32 {
+        let history = self.history(record, record_type);
+        history.failures += 1;
+        history.failures
+    }
+
+    fn history(&mut self, record: &str, record_type: RecordType) -> &mut RecordHistory {
+        self.records
+            .entry((record.to_string(), record_type))
+            .or_insert(RecordHistory {
+                content: None,
+                last_success: None,
+                failures: 0,
+                created: false,
+            })
+    }
+}
+
+/// The state as a JSON file, written next to its target and renamed over it so a crash never
+/// leaves half a file. The history goes to a JSON Lines file beside it, e.g. `state.history.jsonl`
+/// for `state.json`, only ever appended to.
+pub struct FileStore {
+    path: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn history_path(&self) -> PathBuf {
+        self.path.with_extension("history.jsonl")
+    }
+}
+
+impl StateStore for FileStore {
+    fn load(&self) -> Result<State> {
+        let path = &self.path;
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!("No state file at {} yet", path.display());
+                return Ok(State::default());
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read {}", path.display()));
+            }
+        };
+        let state =
+            decode(&contents).with_context(|| format!("Invalid state file {}", path.display()))?;
+        debug!("Loaded state from {}", path.display());
+        Ok(state)
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        let path = &self.path;
+        create_parent(path)?;
+        let temporary = path.with_extension("json.tmp");
+        let contents = encode(state)?;
+        fs::write(&temporary, contents)
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, path)
+            .with_context(|| format!("Failed to write state file {}", path.display()))
+    }
+
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let path = self.history_path();
+        let mut lines = String::new();
+        for entry in entries {
+            lines += &serde_json::to_string(entry)?;
+            lines.push('\n');
+        }
+        create_parent(&path)?;
+        // One write, so a line is never interleaved with another instance's
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all(lines.as_bytes()))
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn history(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let path = self.history_path();
+        let file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let mut entries = Vec::new();
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.with_context(|| format!("Failed to read {}", path.display()))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            // A line cut short by a crash shouldn't hide the others
+            match serde_json::from_str::<HistoryEntry>(&line) {
+                Ok(entry) if query.matches(&entry) => entries.push(entry),
+                Ok(_) => {}
+                Err(e) => debug!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+            }
+        }
+        Ok(query.limited(entries))
+    }
+}
+
+/// The state kept in memory only, lost when the process exits
+#[derive(Default)]
+pub struct MemoryStore {
+    saved: Mutex<State>,
+    history: Mutex<Vec<HistoryEntry>>,
+}
+
+impl StateStore for MemoryStore {
+    fn load(&self) -> Result<State> {
+        Ok(self.saved.lock().unwrap_or_else(|e| e.into_inner()).clone())
+    }
+
+    fn save(&self, state: &State) -> Result<()> {
+        *self.saved.lock().unwrap_or_else(|e| e.into_inner()) = state.clone();
+        Ok(())
+    }
+
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let mut history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        history.extend_from_slice(entries);
+        Ok(())
+    }
+
+    fn history(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let history = self.history.lock().unwrap_or_else(|e| e.into_inner());
+        let entries = history.iter().filter(|entry| query.matches(entry));
+        Ok(query.limited(entries.cloned().collect()))
+    }
+}
+
+// The store the configuration selects, at `path` or the default location. None when there is
+// neither, for an updater built without a configuration file.
+pub fn open(
+    config: &Config,
+    path: Option<PathBuf>,
+    config_file: Option<&str>,
+) -> Result<Option<Box<dyn StateStore>>> {
+    let extension = match config.state.store {
+        StateStoreKind::Memory => return Ok(Some(Box::new(MemoryStore::default()))),
+        StateStoreKind::Kv => return open_kv(config, config_file).map(Some),
+        StateStoreKind::File => "json",
+        StateStoreKind::Sqlite => "db",
+    };
+    let Some(path) = path.or_else(|| config_file.map(|file| default_path(file, extension))) else {
+        return Ok(None);
+    };
+    match config.state.store {
+        StateStoreKind::Sqlite => open_sqlite(path, config_file).map(Some),
+        _ => Ok(Some(Box::new(FileStore::new(path)))),
+    }
+}
+
+fn open_kv(config: &Config, config_file: Option<&str>) -> Result<Box<dyn StateStore>> {
+    let key = match (&config.state.key, config_file) {
+        (Some(key), _) => key.clone(),
+        (None, Some(config_file)) => instance_name(config_file),
+        (None, None) => "default".to_string(),
+    };
+    Ok(Box::new(KvStore::new(config, key)?))
+}
+
+// Each configuration keeps rows of its own, so several instances can share a database
+#[cfg(feature = "sqlite")]
+fn open_sqlite(path: PathBuf, config_file: Option<&str>) -> Result<Box<dyn StateStore>> {
+    let instance = config_file.map_or_else(|| "default".to_string(), instance_name);
+    Ok(Box::new(SqliteStore::open(path, &instance)?))
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn open_sqlite(path: PathBuf, _config_file: Option<&str>) -> Result<Box<dyn StateStore>> {
+    Err(anyhow::anyhow!(
+        "Can't open the state database {}: clouddns was built without the `sqlite` feature",
+        path.display()
+    ))
+}
+
+fn create_parent(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+// One state file per configuration. systemd's StateDirectory= comes first, then the usual
+// per-user locations.
+pub(crate) fn default_path(config_file: &str, extension: &str) -> PathBuf {
+    let dir = env::var_os("STATE_DIRECTORY")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("XDG_STATE_HOME").map(|dir| PathBuf::from(dir).join("clouddns")))
+        .or_else(|| {
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state/clouddns"))
+        })
+        .or_else(|| env::var_os("LOCALAPPDATA").map(|dir| PathBuf::from(dir).join("clouddns")))
+        .unwrap_or_else(|| env::temp_dir().join("clouddns"));
+    dir.join(format!("{}.{}", instance_name(config_file), extension))
+}
@@ -0,0 +1,289 @@
+use super::{create_parent, HistoryEntry, HistoryQuery, RecordHistory, State, StateStore};
+use crate::config::{IpSource, RecordType};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use log::debug;
+use rusqlite::{params, Connection};
+use std::{path::PathBuf, sync::Mutex, time::Duration};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS addresses (
+        instance TEXT NOT NULL,
+        source TEXT NOT NULL,
+        type TEXT NOT NULL,
+        address TEXT NOT NULL,
+        PRIMARY KEY (instance, source, type)
+    );
+    CREATE TABLE IF NOT EXISTS records (
+        instance TEXT NOT NULL,
+        record TEXT NOT NULL,
+        type TEXT NOT NULL,
+        content TEXT,
+        last_success TEXT,
+        failures INTEGER NOT NULL DEFAULT 0,
+        created INTEGER NOT NULL DEFAULT 0,
+        PRIMARY KEY (instance, record, type)
+    );
+    CREATE TABLE IF NOT EXISTS history (
+        instance TEXT NOT NULL,
+        time TEXT NOT NULL,
+        record TEXT NOT NULL,
+        type TEXT NOT NULL,
+        previous TEXT,
+        content TEXT,
+        reason TEXT NOT NULL,
+        trigger TEXT NOT NULL,
+        error TEXT
+    );
+    CREATE INDEX IF NOT EXISTS history_time ON history (instance, time);
+    CREATE TABLE IF NOT EXISTS paused (
+        instance TEXT PRIMARY KEY
+    );
+";
+
+// Instances writing at the same moment wait for each other rather than fail
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The state in an SQLite database, which several instances can share: the rows of each are
+/// kept apart by an instance name
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+    instance: String,
+}
+
+impl SqliteStore {
+    /// Opens the database, creating it and its tables if needed
+    pub fn open(path: impl Into<PathBuf>, instance: &str) -> Result<Self> {
+        let path = path.into();
+        create_parent(&path)?;
+        let connection = Connection::open(&path)
+            .with_context(|| format!("Failed to open the state database {}", path.display()))?;
+        connection.busy_timeout(BUSY_TIMEOUT)?;
+        connection
+            .execute_batch(SCHEMA)
+            .with_context(|| format!("Failed to set up the state database {}", path.display()))?;
+        // Databases created before failed updates, or the records created, were kept lack the
+        // columns
+        for (table, column, definition) in [
+            ("history", "error", "TEXT"),
+            ("records", "created", "INTEGER NOT NULL DEFAULT 0"),
+        ] {
+            let columns: i64 = connection.query_row(
+                "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2",
+                params![table, column],
+                |row| row.get(0),
+            )?;
+            if columns == 0 {
+                connection
+                    .execute(
+                        &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                        [],
+                    )
+                    .with_context(|| {
+                        format!("Failed to upgrade the state database {}", path.display())
+                    })?;
+            }
+        }
+        debug!("Using the state database {}", path.display());
+        Ok(Self {
+            connection: Mutex::new(connection),
+            instance: instance.to_string(),
+        })
+    }
+}
+
+impl StateStore for SqliteStore {
+    fn load(&self) -> Result<State> {
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let mut state = State::default();
+
+        let mut statement = connection
+            .prepare("SELECT source, type, address FROM addresses WHERE instance = ?1")?;
+        let mut rows = statement.query(params![self.instance])?;
+        while let Some(row) = rows.next()? {
+            let source: String = row.get(0)?;
+            let source = source.parse::<IpSource>().map_err(anyhow::Error::msg)?;
+            let address: String = row.get(2)?;
+            state.addresses.insert(
+                (source, record_type(&row.get::<_, String>(1)?)?),
+                address
+                    .parse()
+                    .context("Invalid address in the state database")?,
+            );
+        }
+
+        let mut statement = connection.prepare(
+            "SELECT record, type, content, last_success, failures, created FROM records
+             WHERE instance = ?1",
+        )?;
+        let mut rows = statement.query(params![self.instance])?;
+        while let Some(row) = rows.next()? {
+            let last_success: Option<String> = row.get(3)?;
+            let history = RecordHistory {
+                content: row.get(2)?,
+                last_success: last_success
+                    .map(|time| {
+                        DateTime::parse_from_rfc3339(&time).map(|time| time.with_timezone(&Utc))
+                    })
+                    .transpose()
+                    .context("Invalid time in the state database")?,
+                failures: row.get(4)?,
+                created: row.get(5)?,
+            };
+            let record: String = row.get(0)?;
+            let record_type = record_type(&row.get::<_, String>(1)?)?;
+            state.records.insert((record, record_type), history);
+        }
+
+        state.paused = connection.query_row(
+            "SELECT EXISTS (SELECT 1 FROM paused WHERE instance = ?1)",
+            params![self.instance],
+            |row| row.get(0),
+        )?;
+        Ok(state)
+    }
+
+    // Replaces the rows of this instance in one transaction, other instances see either the old
+    // state or the new one
+    fn save(&self, state: &State) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM addresses WHERE instance = ?1",
+            params![self.instance],
+        )?;
+        transaction.execute(
+            "DELETE FROM records WHERE instance = ?1",
+            params![self.instance],
+        )?;
+        transaction.execute(
+            "DELETE FROM paused WHERE instance = ?1",
+            params![self.instance],
+        )?;
+        if state.paused {
+            transaction.execute(
+                "INSERT INTO paused (instance) VALUES (?1)",
+                params![self.instance],
+            )?;
+        }
+        for ((source, record_type), address) in &state.addresses {
+            transaction.execute(
+                "INSERT INTO addresses (instance, source, type, address) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    self.instance,
+                    source.to_string(),
+                    record_type.as_str(),
+                    address.to_string()
+                ],
+            )?;
+        }
+        for ((record, record_type), history) in &state.records {
+            transaction.execute(
+                "INSERT INTO records
+                 (instance, record, type, content, last_success, failures, created)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    self.instance,
+                    record,
+                    record_type.as_str(),
+                    history.content,
+                    history.last_success.map(|time| time.to_rfc3339()),
+                    history.failures,
+                    history.created
+                ],
+            )?;
+        }
+        transaction
+            .commit()
+            .context("Failed to save the state to the database")
+    }
+
+    fn append_history(&self, entries: &[HistoryEntry]) -> Result<()> {
+        let mut connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let transaction = connection.transaction()?;
+        for entry in entries {
+            transaction.execute(
+                "INSERT INTO history
+                 (instance, time, record, type, previous, content, reason, trigger, error)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    self.instance,
+                    timestamp(entry.time),
+                    entry.record,
+                    entry.r#type.as_str(),
+                    entry.previous,
+                    entry.content,
+                    entry.reason.as_str(),
+                    entry.trigger.as_str(),
+                    entry.error
+                ],
+            )?;
+        }
+        transaction
+            .commit()
+            .context("Failed to save the history to the database")
+    }
+
+    // The last ones first, so the limit keeps those, then put back in order
+    fn history(&self, query: &HistoryQuery) -> Result<Vec<HistoryEntry>> {
+        let connection = self.connection.lock().unwrap_or_else(|e| e.into_inner());
+        let mut statement = connection.prepare(
+            "SELECT time, record, type, previous, content, reason, trigger, error FROM history
+             WHERE instance = ?1 AND time >= ?2 AND (?3 IS NULL OR time < ?3)
+               AND (?4 IS NULL OR record = ?4 OR substr(record, -length(?4) - 1) = '.' || ?4)
+               AND (NOT ?5 OR error IS NOT NULL)
+             ORDER BY time DESC, rowid DESC LIMIT ?6",
+        )?;
+        let since = query.since.map(timestamp).unwrap_or_default();
+        // Negative for no limit
+        let limit = query
+            .limit
+            .map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX));
+        let mut rows = statement.query(params![
+            self.instance,
+            since,
+            query.until.map(timestamp),
+            query.domain,
+            query.failed,
+            limit
+        ])?;
+        let mut entries = Vec::new();
+        while let Some(row) = rows.next()? {
+            let time: String = row.get(0)?;
+            let reason: String = row.get(5)?;
+            let trigger: String = row.get(6)?;
+            entries.push(HistoryEntry {
+                time: DateTime::parse_from_rfc3339(&time)
+                    .context("Invalid time in the state database")?
+                    .with_timezone(&Utc),
+                record: row.get(1)?,
+                r#type: record_type(&row.get::<_, String>(2)?)?,
+                previous: row.get(3)?,
+                content: row.get(4)?,
+                reason: serde_json::from_value(reason.into())
+                    .context("Invalid change reason in the state database")?,
+                trigger: serde_json::from_value(trigger.into())
+                    .context("Invalid trigger in the state database")?,
+                error: row.get(7)?,
+            });
+        }
+        entries.reverse();
+        Ok(entries)
+    }
+}
+
+// Fixed width in UTC, so times compare as text
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+fn record_type(name: &str) -> Result<RecordType> {
+    match name {
+        "A" => Ok(RecordType::A),
+        "AAAA" => Ok(RecordType::Aaaa),
+        _ => Err(anyhow::anyhow!(
+            "Invalid record type {} in the state database",
+            name
+        )),
+    }
+}
@@ -0,0 +1,129 @@
+// A small JSON document rewritten after every cycle, for monitors that can read a file but not
+// call an endpoint, e.g. a Nagios or Zabbix agent check:
+//
+//   jq -e '.healthy and (now - (.updated | fromdate) < 900)' /var/lib/clouddns/status.json
+//
+// It is replaced whole, readers never see half of it.
+
+use crate::snapshot::{AddressReport, RecordReport, Report, Snapshot};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub(crate) struct StatusFile {
+    path: PathBuf,
+    last_success: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Document<'a> {
+    version: &'static str,
+    // When the last cycle ended
+    #[serde(serialize_with = "seconds")]
+    updated: DateTime<Utc>,
+    // Whether it went through without errors
+    healthy: bool,
+    #[serde(serialize_with = "optional_seconds")]
+    last_success: Option<DateTime<Utc>>,
+    #[serde(serialize_with = "optional_seconds")]
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<&'a str>,
+    paused: bool,
+    in_maintenance: bool,
+    addresses: Vec<AddressReport>,
+    records: Vec<RecordReport>,
+}
+
+// What a previous run left, so one-shot runs carry the times over
+#[derive(Deserialize)]
+struct Previous {
+    last_success: Option<DateTime<Utc>>,
+    last_failure: Option<DateTime<Utc>>,
+    last_error: Option<String>,
+}
+
+impl StatusFile {
+    pub fn new(path: PathBuf) -> Self {
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Previous>(&contents).ok());
+        let (last_success, last_failure, last_error) = match previous {
+            Some(previous) => (
+                previous.last_success,
+                previous.last_failure,
+                previous.last_error,
+            ),
+            None => (None, None, None),
+        };
+        Self {
+            path,
+            last_success,
+            last_failure,
+            last_error,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn write(
+        &mut self,
+        now: DateTime<Utc>,
+        error: Option<&anyhow::Error>,
+        snapshot: Snapshot,
+    ) -> Result<()> {
+        match error {
+            None => self.last_success = Some(now),
+            Some(error) => {
+                self.last_failure = Some(now);
+                self.last_error = Some(format!("{:#}", error));
+            }
+        }
+        let report = Report::from(snapshot);
+        let document = Document {
+            version: env!("CARGO_PKG_VERSION"),
+            updated: now,
+            healthy: error.is_none(),
+            last_success: self.last_success,
+            last_failure: self.last_failure,
+            last_error: self.last_error.as_deref(),
+            paused: report.paused,
+            in_maintenance: report.in_maintenance,
+            addresses: report.addresses,
+            records: report.records,
+        };
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let temporary = self.path.with_extension("json.tmp");
+        let contents = serde_json::to_string_pretty(&document)?;
+        fs::write(&temporary, contents)
+            .with_context(|| format!("Failed to write {}", temporary.display()))?;
+        fs::rename(&temporary, &self.path)
+            .with_context(|| format!("Failed to write status file {}", self.path.display()))
+    }
+}
+
+// Whole seconds, which every monitoring tool's date parser takes
+fn seconds<S: serde::Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn optional_seconds<S: serde::Serializer>(
+    time: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => seconds(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
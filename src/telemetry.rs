@@ -0,0 +1,72 @@
+#[cfg(feature = "otel")]
+use anyhow::Context;
+use anyhow::Result;
+use log::warn;
+use std::env;
+
+// Traces are exported when an OTLP endpoint is set through the standard environment variables
+fn enabled() -> bool {
+    let exporter = env::var("OTEL_TRACES_EXPORTER").unwrap_or_default();
+    let disabled = env::var("OTEL_SDK_DISABLED").is_ok_and(|value| value == "true");
+    !disabled
+        && exporter != "none"
+        && (env::var_os("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+            || env::var_os("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some())
+}
+
+// Flushes the spans still buffered when dropped
+#[cfg(feature = "otel")]
+pub struct Telemetry {
+    provider: opentelemetry_sdk::trace::SdkTracerProvider,
+}
+
+#[cfg(feature = "otel")]
+pub fn init() -> Result<Option<Telemetry>> {
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{trace::SdkTracerProvider, Resource};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    if !enabled() {
+        return Ok(None);
+    }
+
+    // The endpoint, headers and timeout come from the OTEL_EXPORTER_OTLP_* variables
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .build()
+        .context("Failed to set up the OTLP exporter")?;
+    let mut resource = Resource::builder();
+    if env::var_os("OTEL_SERVICE_NAME").is_none() {
+        resource = resource.with_service_name("clouddns");
+    }
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_resource(resource.build())
+        .build();
+
+    let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("clouddns"));
+    tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer))
+        .context("Failed to install the tracing subscriber")?;
+    Ok(Some(Telemetry { provider }))
+}
+
+#[cfg(feature = "otel")]
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Failed to export the remaining traces: {}", e);
+        }
+    }
+}
+
+#[cfg(not(feature = "otel"))]
+pub struct Telemetry;
+
+#[cfg(not(feature = "otel"))]
+pub fn init() -> Result<Option<Telemetry>> {
+    if enabled() {
+        warn!("An OTLP endpoint is set, but clouddns was built without the `otel` feature");
+    }
+    Ok(None)
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::time::{sleep, Duration};
+
+// Editors and config management tools tend to write a file in several steps,
+// so wait for things to settle before reloading
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+pub struct ConfigWatcher {
+    // Kept alive for as long as we want events
+    _watcher: RecommendedWatcher,
+    events: UnboundedReceiver<()>,
+}
+
+impl ConfigWatcher {
+    pub fn new(config_file: &str) -> Result<Self> {
+        let path = Path::new(config_file);
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("Invalid config file path: {}", config_file))?
+            .to_os_string();
+        // Watch the parent directory rather than the file itself: atomic saves and
+        // Kubernetes ConfigMap updates replace the file, which drops a watch on the inode
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        let (sender, events) = unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            match event {
+                Ok(event) => {
+                    let relevant = matches!(
+                        event.kind,
+                        EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+                    ) && event.paths.iter().any(|p| {
+                        p.file_name() == Some(file_name.as_os_str())
+                            // ConfigMap volumes swap a `..data` symlink
+                            || p.file_name() == Some("..data".as_ref())
+                    });
+
+                    if relevant {
+                        let _ = sender.send(());
+                    }
+                }
+                Err(e) => warn!("Config watcher error: {}", &e),
+            }
+        })
+        .context("Failed to create config file watcher")?;
+
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch directory: {}", directory.display()))?;
+        info!("Watching {} for changes", config_file);
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+        })
+    }
+
+    // Resolves once the config file changed and stopped changing for a moment
+    pub async fn changed(&mut self) {
+        if self.events.recv().await.is_none() {
+            // The watcher thread is gone, never fire again
+            std::future::pending::<()>().await;
+        }
+
+        loop {
+            tokio::select! {
+                event = self.events.recv() => {
+                    if event.is_none() {
+                        break;
+                    }
+                }
+                _ = sleep(DEBOUNCE) => break,
+            }
+        }
+        debug!("Config file change detected");
+    }
+}
+
+// Waits on an optional watcher, so the run loop can select on it whether watching is enabled or not
+pub async fn changed(watcher: &mut Option<ConfigWatcher>) {
+    match watcher {
+        Some(watcher) => watcher.changed().await,
+        None => std::future::pending::<()>().await,
+    }
+}
@@ -0,0 +1,380 @@
+// The real Cloudflare client against a local server answering with responses recorded from the API
+use clouddns::api::{
+    CloudflareClient, CloudflareError, DnsApiClient, KvClient, Limiter, RecordContent, ZoneCache,
+};
+use clouddns::config::{RateLimit, RetryPolicy};
+use clouddns::DdnsError;
+use secrecy::SecretString;
+use std::{sync::Arc, time::Duration};
+use wiremock::matchers::{body_partial_json, body_string, header, method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+const ZONE_ID: &str = "023e105f4ecef8ad9ca31a8372d0c353";
+const RECORD_ID: &str = "372e67954025e0ba6aaa6d586b9e0b59";
+
+fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/cloudflare/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+fn json(status: u16, fixture_name: &str) -> ResponseTemplate {
+    ResponseTemplate::new(status).set_body_raw(fixture(fixture_name), "application/json")
+}
+
+fn records_path() -> String {
+    format!("/client/v4/zones/{}/dns_records", ZONE_ID)
+}
+
+// Retries are quick, and off unless a test asks for them
+fn client(server: &MockServer, max_attempts: u32) -> CloudflareClient {
+    let retry = RetryPolicy {
+        max_attempts,
+        backoff_ms: 1,
+        max_backoff_ms: 10,
+        ..RetryPolicy::default()
+    };
+    let token = SecretString::from("test-token");
+    CloudflareClient::new(&token, &retry, Limiter::new(RateLimit::CLOUDFLARE))
+        .base_url(&format!("{}/client/v4", server.uri()))
+}
+
+#[tokio::test]
+async fn gets_a_record_by_name_and_type() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .and(query_param("name", "home.example.com"))
+        .and(query_param("type", "A"))
+        .and(header("authorization", "Bearer test-token"))
+        .respond_with(json(200, "dns_record.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let record = client(&server, 1)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap();
+    assert_eq!(record.id, RECORD_ID);
+    assert_eq!(record.content, "198.51.100.4");
+    assert_eq!(record.ttl, 300);
+    assert!(!record.proxied);
+    assert_eq!(
+        record.modified_on.as_deref(),
+        Some("2024-05-01T12:34:56.789Z")
+    );
+}
+
+#[tokio::test]
+async fn a_missing_record_is_not_found() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(200, "no_records.json"))
+        .mount(&server)
+        .await;
+
+    let error = client(&server, 1)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap_err();
+    assert!(matches!(DdnsError::from(error), DdnsError::NotFound(_)));
+}
+
+#[tokio::test]
+async fn a_zone_given_by_name_is_looked_up_once() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/client/v4/zones"))
+        .respond_with(json(200, "zones.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(200, "dns_record.json"))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let zones = ZoneCache::new(Arc::new(client(&server, 1)), Duration::from_secs(3600));
+    for _ in 0..2 {
+        let record = zones
+            .get_record("example.com", "home.example.com", "A")
+            .await
+            .unwrap();
+        assert_eq!(record.id, RECORD_ID);
+    }
+}
+
+#[tokio::test]
+async fn a_rejected_zone_is_looked_up_again() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/client/v4/zones"))
+        .respond_with(json(200, "zones.json"))
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(403, "auth_error.json"))
+        .mount(&server)
+        .await;
+
+    let zones = ZoneCache::new(Arc::new(client(&server, 1)), Duration::from_secs(3600));
+    for _ in 0..2 {
+        let error = zones
+            .get_record("example.com", "home.example.com", "A")
+            .await
+            .unwrap_err();
+        assert!(matches!(DdnsError::from(error), DdnsError::Auth(_)));
+    }
+}
+
+#[tokio::test]
+async fn updates_a_record() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(200, "dns_record.json"))
+        .mount(&server)
+        .await;
+    Mock::given(method("PATCH"))
+        .and(path(format!("{}/{}", records_path(), RECORD_ID)))
+        .and(header("authorization", "Bearer test-token"))
+        .and(body_partial_json(serde_json::json!({
+            "type": "A",
+            "name": "home.example.com",
+            "content": "203.0.113.7",
+            "ttl": 300,
+            "proxied": false,
+        })))
+        .respond_with(json(200, "dns_record_updated.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = client(&server, 1);
+    let record = client
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap();
+    let content = RecordContent::A([203, 0, 113, 7].into());
+    let updated = client
+        .update_record(ZONE_ID, &record, &content, 300, false)
+        .await
+        .unwrap();
+    assert_eq!(updated.content, "203.0.113.7");
+}
+
+#[tokio::test]
+async fn follows_every_page_of_records() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .and(query_param("page", "1"))
+        .respond_with(json(200, "dns_records_page_1.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .and(query_param("page", "2"))
+        .respond_with(json(200, "dns_records_page_2.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let records = client(&server, 1).list_records(ZONE_ID).await.unwrap();
+    let names: Vec<_> = records
+        .iter()
+        .map(|record| (record.name.as_str(), record.r#type.as_str()))
+        .collect();
+    assert_eq!(
+        names,
+        [
+            ("home.example.com", "A"),
+            ("nas.example.com", "AAAA"),
+            ("example.com", "TXT"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn a_rate_limit_outlasting_the_retries_is_reported() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(429, "rate_limited.json").insert_header("retry-after", "0"))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let error = client(&server, 2)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap_err();
+    let error = DdnsError::from(error);
+    assert!(matches!(error, DdnsError::RateLimited(_)), "{:?}", error);
+    assert!(error.is_transient());
+}
+
+#[tokio::test]
+async fn a_rate_limit_is_retried() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(429, "rate_limited.json").insert_header("retry-after", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(200, "dns_record.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let record = client(&server, 3)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap();
+    assert_eq!(record.id, RECORD_ID);
+}
+
+#[tokio::test]
+async fn a_rejected_token_is_an_auth_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(403, "auth_error.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let error = client(&server, 3)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap_err();
+    let error = DdnsError::from(error);
+    assert!(matches!(error, DdnsError::Auth(_)), "{:?}", error);
+    assert!(!error.is_transient());
+    // The token never shows up in the error
+    assert!(!format!("{:?}", error).contains("test-token"));
+}
+
+#[tokio::test]
+async fn an_invalid_token_is_recognized_by_its_code() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(400, "invalid_token.json"))
+        .mount(&server)
+        .await;
+
+    let error = client(&server, 1)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap_err();
+    let cloudflare = error
+        .downcast_ref::<CloudflareError>()
+        .expect("a CloudflareError");
+    assert!(cloudflare.has(CloudflareError::INVALID_TOKEN));
+    assert!(format!("{:#}", error).contains("create a new one"));
+    assert!(matches!(DdnsError::from(error), DdnsError::Auth(_)));
+}
+
+#[tokio::test]
+async fn creating_a_record_that_exists_returns_it() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path(records_path()))
+        .respond_with(json(400, "identical_record.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .and(query_param("name", "home.example.com"))
+        .and(query_param("content", "198.51.100.4"))
+        .respond_with(json(200, "dns_record.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let content = RecordContent::A("198.51.100.4".parse().unwrap());
+    let record = client(&server, 1)
+        .create_record(ZONE_ID, "home.example.com", &content, 300, false)
+        .await
+        .unwrap();
+    assert_eq!(record.id, RECORD_ID);
+}
+
+#[tokio::test]
+async fn a_malformed_response_is_a_parse_error() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(records_path()))
+        .respond_with(json(200, "truncated.json"))
+        .mount(&server)
+        .await;
+
+    let error = client(&server, 1)
+        .get_record(ZONE_ID, "home.example.com", "A")
+        .await
+        .unwrap_err();
+    assert!(matches!(DdnsError::from(error), DdnsError::Parse(_)));
+}
+
+#[tokio::test]
+async fn keeps_a_value_in_a_kv_namespace() {
+    let server = MockServer::start().await;
+    let value_path = "/client/v4/accounts/acc/storage/kv/namespaces/ns/values/home%2Fa";
+    Mock::given(method("GET"))
+        .and(path(value_path))
+        .respond_with(json(404, "kv_key_not_found.json"))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("PUT"))
+        .and(path(value_path))
+        .and(header("authorization", "Bearer test-token"))
+        .and(body_string("{\"records\": []}"))
+        .respond_with(json(200, "kv_written.json"))
+        .expect(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(value_path))
+        .respond_with(ResponseTemplate::new(200).set_body_string("{\"records\": []}"))
+        .mount(&server)
+        .await;
+
+    let token = SecretString::from("test-token");
+    let retry = RetryPolicy {
+        max_attempts: 1,
+        ..RetryPolicy::default()
+    };
+    let kv = KvClient::new(
+        &token,
+        &retry,
+        Limiter::new(RateLimit::CLOUDFLARE),
+        "acc",
+        "ns",
+    )
+    .base_url(&format!("{}/client/v4", server.uri()));
+    assert_eq!(kv.get("home/a").await.unwrap(), None);
+    kv.put("home/a", "{\"records\": []}".to_string())
+        .await
+        .unwrap();
+    assert_eq!(
+        kv.get("home/a").await.unwrap().as_deref(),
+        Some("{\"records\": []}")
+    );
+}
@@ -0,0 +1,40 @@
+// `clouddns config example` prints a configuration that validates as it is
+use clouddns::config::{self, Config, SecretSource};
+
+#[test]
+fn the_example_is_a_valid_configuration() {
+    let example = config::example();
+    let config: Config = toml::from_str(&example).unwrap();
+    assert_eq!(config::problems(&config), Vec::<String>::new());
+}
+
+#[test]
+fn the_example_comments_options_with_their_defaults() {
+    let example = config::example();
+    let defaults = Config::new(SecretSource::Plain(String::new().into()), Vec::new());
+    for line in [
+        format!("# concurrency = {}", defaults.concurrency),
+        format!("# shutdown_timeout = {}", defaults.shutdown_timeout),
+        format!("# debounce_ms = {}", defaults.debounce_ms),
+        "# missed_checks = \"skip\"".to_string(),
+    ] {
+        assert!(
+            example.lines().any(|l| l == line),
+            "No `{}` in\n{}",
+            line,
+            example
+        );
+    }
+}
+
+// Their examples name things that may not exist on the host, or in the configuration
+#[test]
+fn options_with_examples_stay_commented() {
+    let example = config::example();
+    for option in ["tenant", "control_socket", "ca_certificates", "status_file"] {
+        let live = format!("{} = ", option);
+        let commented = format!("# {}", live);
+        assert!(example.lines().any(|line| line.starts_with(&commented)));
+        assert!(!example.lines().any(|line| line.starts_with(&live)));
+    }
+}
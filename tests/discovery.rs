@@ -0,0 +1,136 @@
+// Only the records clouddns created for a container are deleted once it stops, not those it found
+#![cfg(unix)]
+use clouddns::config::Config;
+use clouddns::{CloudflareDdns, DdnsEvent, FixedDetector, MemoryDnsProvider, RecordContent};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, watch};
+
+const CONFIG: &str = r#"
+api_token = "unused"
+
+[state]
+store = "memory"
+
+[discovery.docker]
+socket = "SOCKET"
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+"#;
+
+const CONTAINERS: &str = r#"[
+  {"Names": ["/app"], "Labels": {"clouddns.hostname": "app.example.com"}},
+  {"Names": ["/new"], "Labels": {"clouddns.hostname": "new.example.com"}}
+]"#;
+
+// Docker's API over its socket: the containers listed, and an event whenever they change
+fn docker(socket: &PathBuf, containers: watch::Receiver<&'static str>) {
+    let _ = std::fs::remove_file(socket);
+    let listener = UnixListener::bind(socket).unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut containers = containers.clone();
+            tokio::spawn(async move {
+                let mut stream = BufReader::new(stream);
+                let mut request = String::new();
+                stream.read_line(&mut request).await.unwrap();
+                let mut header = String::new();
+                while stream.read_line(&mut header).await.unwrap() > 2 {
+                    header.clear();
+                }
+                let stream = stream.get_mut();
+                stream.write_all(b"HTTP/1.0 200 OK\r\n\r\n").await.unwrap();
+                if request.starts_with("GET /containers/json") {
+                    let body = *containers.borrow_and_update();
+                    stream.write_all(body.as_bytes()).await.unwrap();
+                    return;
+                }
+                while containers.changed().await.is_ok() {
+                    let event = b"{\"status\":\"die\"}\n";
+                    if stream.write_all(event).await.is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+}
+
+async fn next_event(
+    events: &mut broadcast::Receiver<DdnsEvent>,
+    wanted: impl Fn(&DdnsEvent) -> bool,
+) {
+    tokio::time::timeout(Duration::from_secs(30), async {
+        while !wanted(&events.recv().await.unwrap()) {}
+    })
+    .await
+    .expect("The event never came");
+}
+
+#[tokio::test]
+async fn a_record_found_rather_than_created_survives_its_container() {
+    let socket = std::env::temp_dir().join(format!("clouddns-docker-{}.sock", std::process::id()));
+    let (containers, listed) = watch::channel(CONTAINERS);
+    docker(&socket, listed);
+
+    let provider = MemoryDnsProvider::new()
+        .zone("zone", "example.com")
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::A([192, 0, 2, 7].into()),
+        )
+        .record(
+            "zone",
+            "app.example.com",
+            RecordContent::A([192, 0, 2, 1].into()),
+        );
+    let config = CONFIG.replace("SOCKET", &socket.to_string_lossy());
+    let config: Config = toml::from_str(&config).unwrap();
+    let mut ddns = CloudflareDdns::builder(config)
+        .api_client(Box::new(provider.clone()))
+        .detector(Box::new(FixedDetector::new().ipv4([192, 0, 2, 7].into())))
+        .build()
+        .await
+        .unwrap();
+    let mut events = ddns.subscribe();
+    let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+    let stopping = async move {
+        next_event(&mut events, |event| {
+            matches!(event, DdnsEvent::RecordCreated { record, .. } if record == "new.example.com")
+        })
+        .await;
+        containers.send("[]").unwrap();
+        next_event(&mut events, |event| {
+            matches!(event, DdnsEvent::RecordDeleted { record, .. } if record == "new.example.com")
+        })
+        .await;
+        stop.send(()).unwrap();
+    };
+    let shutdown = async {
+        let _ = stopped.await;
+    };
+    let (result, ()) = tokio::join!(ddns.run(shutdown), stopping);
+    result.unwrap();
+
+    let records: Vec<_> = provider
+        .records("zone")
+        .into_iter()
+        .map(|record| (record.name, record.content))
+        .collect();
+    assert!(
+        records.contains(&("app.example.com".to_string(), "192.0.2.7".to_string())),
+        "{:?}",
+        records
+    );
+    assert!(!records.iter().any(|(name, _)| name == "new.example.com"));
+    let _ = std::fs::remove_file(&socket);
+}
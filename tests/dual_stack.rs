@@ -0,0 +1,58 @@
+// A dual-stack name whose IPv6 address can't be detected still has its A record updated
+use clouddns::config::Config;
+use clouddns::{CloudflareDdns, FixedDetector, MemoryDnsProvider, RecordContent};
+
+const CONFIG: &str = r#"
+api_token = "unused"
+
+[state]
+store = "memory"
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+record_type = "both"
+"#;
+
+#[tokio::test]
+async fn one_family_failing_doesnt_hold_back_the_other() {
+    let provider = MemoryDnsProvider::new()
+        .zone("zone", "example.com")
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::A([192, 0, 2, 1].into()),
+        )
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::Aaaa("2001:db8::1".parse().unwrap()),
+        );
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let mut ddns = CloudflareDdns::builder(config)
+        .api_client(Box::new(provider.clone()))
+        .detector(Box::new(FixedDetector::new().ipv4([192, 0, 2, 7].into())))
+        .build()
+        .await
+        .unwrap();
+
+    let error = ddns.run_once(false).await.unwrap_err();
+    assert!(format!("{:#}", error).contains("No AAAA address to detect"));
+
+    let mut records: Vec<_> = provider
+        .records("zone")
+        .into_iter()
+        .map(|record| (record.r#type, record.content))
+        .collect();
+    records.sort();
+    assert_eq!(
+        records,
+        [
+            ("A".to_string(), "192.0.2.7".to_string()),
+            ("AAAA".to_string(), "2001:db8::1".to_string()),
+        ]
+    );
+}
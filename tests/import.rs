@@ -0,0 +1,86 @@
+// Other clients' configuration files, converted without touching the network
+use clouddns::config::import::{ddclient, inadyn, placeholder_id};
+use clouddns::config::{self, IpSource, RecordTypes};
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("{}: {}", path, e))
+}
+
+#[test]
+fn ddclient_cloudflare_hosts_are_converted() {
+    let imported = ddclient::parse(&fixture("ddclient/ddclient.conf")).unwrap();
+    let config = imported.config;
+    config::validate(&config).unwrap();
+
+    assert_eq!(config.defaults.update_interval, Some(5));
+    assert_eq!(config.zones.len(), 2);
+
+    let zone = &config.zones[0];
+    assert_eq!(zone.id, placeholder_id("example.com"));
+    assert_eq!(zone.name.as_deref(), Some("example.com"));
+    assert_eq!(zone.domains.len(), 1);
+    assert_eq!(zone.domains[0].records, ["home", "@"]);
+    assert_eq!(zone.domains[0].ttl, Some(1));
+    assert_eq!(zone.domains[0].record_type, None);
+    assert_eq!(zone.domains[0].ip_source, None);
+
+    let domain = &config.zones[1].domains[0];
+    assert_eq!(domain.records, ["nas"]);
+    assert_eq!(domain.ttl, Some(120));
+    assert_eq!(domain.record_type, Some(RecordTypes::Both));
+    assert_eq!(
+        domain.ip_source,
+        Some(IpSource::Url("https://ip6.example.net/".to_string()))
+    );
+}
+
+#[test]
+fn ddclient_hosts_of_other_protocols_are_reported() {
+    let imported = ddclient::parse(&fixture("ddclient/ddclient.conf")).unwrap();
+    assert!(imported
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("me.dyndns.org") && warning.contains("dyndns2")));
+}
+
+#[test]
+fn ddclient_without_cloudflare_hosts_is_rejected() {
+    let contents = "protocol=dyndns2\nlogin=me\npassword=secret\nme.dyndns.org\n";
+    assert!(ddclient::parse(contents).is_err());
+}
+
+#[test]
+fn inadyn_cloudflare_providers_are_converted() {
+    let imported = inadyn::parse(&fixture("inadyn/inadyn.conf")).unwrap();
+    let config = imported.config;
+    config::validate(&config).unwrap();
+
+    assert_eq!(config.defaults.update_interval, Some(10));
+    assert_eq!(config.defaults.ttl, Some(1));
+    assert_eq!(config.defaults.proxied, Some(false));
+    assert_eq!(config.zones.len(), 1);
+    assert_eq!(config.zones[0].id, placeholder_id("example.com"));
+
+    // Listed in both the IPv4 and the IPv6 provider
+    let domains = &config.zones[0].domains;
+    assert_eq!(domains[0].records, ["home"]);
+    assert_eq!(domains[0].record_type, Some(RecordTypes::Both));
+    assert_eq!(
+        domains[0].ip_source,
+        Some(IpSource::Url("https://ip6.example.net/plain".to_string()))
+    );
+    assert_eq!(domains[1].records, ["@"]);
+    assert_eq!(domains[1].record_type, None);
+
+    assert!(imported
+        .warnings
+        .iter()
+        .any(|warning| warning.contains("default@dyndns.org")));
+}
+
+#[test]
+fn inadyn_syntax_errors_are_reported() {
+    let contents = "provider cloudflare.com {\n    username = example.com\n";
+    assert!(inadyn::parse(contents).is_err());
+}
@@ -0,0 +1,96 @@
+// Each record has its own gauges, enough for an alert on a record gone stale
+use clouddns::config::Config;
+use clouddns::metrics::PrometheusMetrics;
+use clouddns::{CloudflareDdns, FixedDetector, MemoryDnsProvider, RecordContent};
+use std::sync::Arc;
+
+const CONFIG: &str = r#"
+api_token = "unused"
+
+[state]
+store = "memory"
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home", "nas"]
+"#;
+
+#[tokio::test]
+async fn records_have_a_status_and_the_time_of_their_last_success() {
+    let provider = MemoryDnsProvider::new()
+        .zone("zone", "example.com")
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::A([192, 0, 2, 7].into()),
+        )
+        .record(
+            "zone",
+            "nas.example.com",
+            RecordContent::A([192, 0, 2, 1].into()),
+        );
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let metrics = PrometheusMetrics::new();
+    let mut ddns = CloudflareDdns::builder(config)
+        .api_client(Box::new(provider))
+        .detector(Box::new(FixedDetector::new().ipv4([192, 0, 2, 7].into())))
+        .build()
+        .await
+        .unwrap()
+        .metrics(Arc::new(metrics.clone()));
+
+    ddns.run_once(false).await.unwrap();
+
+    let rendered = metrics.render();
+    let value = |series: &str| {
+        rendered
+            .lines()
+            .find_map(|line| line.strip_prefix(series))
+            .map(str::trim)
+            .unwrap_or_else(|| panic!("No {} in\n{}", series, rendered))
+            .to_string()
+    };
+    let home = r#"{record="home.example.com",type="A""#;
+    let nas = r#"{record="nas.example.com",type="A""#;
+    assert_eq!(
+        value(&format!(
+            r#"clouddns_record_status{},status="in_sync"}}"#,
+            home
+        )),
+        "1"
+    );
+    assert_eq!(
+        value(&format!(
+            r#"clouddns_record_status{},status="updated"}}"#,
+            home
+        )),
+        "0"
+    );
+    assert_eq!(
+        value(&format!(
+            r#"clouddns_record_status{},status="updated"}}"#,
+            nas
+        )),
+        "1"
+    );
+    let success = value(&format!(
+        "clouddns_record_last_success_timestamp_seconds{}}}",
+        home
+    ));
+    assert!(success.parse::<f64>().unwrap() > 0.0);
+    assert!(rendered.contains(&format!(
+        "clouddns_record_last_change_timestamp_seconds{}}}",
+        nas
+    )));
+    assert!(!rendered.contains(&format!(
+        "clouddns_record_last_change_timestamp_seconds{}}}",
+        home
+    )));
+    assert!(rendered.contains(&format!(
+        "clouddns_build_info{{version=\"{}\",git_sha=",
+        env!("CARGO_PKG_VERSION")
+    )));
+}
@@ -0,0 +1,84 @@
+// A provider that stays down keeps failing cycles, even once they stop calling it
+use clouddns::api::memory::{Failure, Operation};
+use clouddns::config::Config;
+use clouddns::{CloudflareDdns, ErrorKind, FixedDetector, MemoryDnsProvider, RecordContent};
+use std::time::Duration;
+
+const CONFIG: &str = r#"
+api_token = "unused"
+
+[state]
+store = "memory"
+
+[retry]
+max_attempts = 1
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+"#;
+
+fn down() -> MemoryDnsProvider {
+    let provider = MemoryDnsProvider::new().zone("zone", "example.com").record(
+        "zone",
+        "home.example.com",
+        RecordContent::A([192, 0, 2, 1].into()),
+    );
+    for operation in [Operation::GetRecord, Operation::ListRecords] {
+        provider.fail(operation, Failure::Unavailable, usize::MAX);
+    }
+    provider
+}
+
+#[tokio::test]
+async fn cycles_skipped_by_the_circuit_breaker_still_fail() {
+    let provider = down();
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let mut ddns = CloudflareDdns::builder(config)
+        .api_client(Box::new(provider.clone()))
+        .detector(Box::new(FixedDetector::new().ipv4([192, 0, 2, 7].into())))
+        .build()
+        .await
+        .unwrap();
+
+    for _ in 0..3 {
+        let error = ddns.run_once(false).await.unwrap_err();
+        assert!(format!("{:#}", error.inner()).contains("injected failure"));
+    }
+    let calls = provider.calls(Operation::GetRecord) + provider.calls(Operation::ListRecords);
+    for _ in 0..2 {
+        let error = ddns.run_once(false).await.unwrap_err();
+        assert!(format!("{:#}", error.inner()).contains("API circuit open"));
+    }
+    assert_eq!(
+        provider.calls(Operation::GetRecord) + provider.calls(Operation::ListRecords),
+        calls,
+        "the open circuit should keep the provider from being called"
+    );
+}
+
+// The breaker opens after three failures, the watchdog waits for more
+#[tokio::test(start_paused = true)]
+async fn the_watchdog_trips_during_a_long_outage() {
+    let mut config: Config = toml::from_str(CONFIG).unwrap();
+    config.watchdog = toml::from_str("failures = 6\nexit = true").unwrap();
+    let mut ddns = CloudflareDdns::builder(config)
+        .api_client(Box::new(down()))
+        .detector(Box::new(FixedDetector::new().ipv4([192, 0, 2, 7].into())))
+        .build()
+        .await
+        .unwrap();
+
+    let run = ddns.run(std::future::pending());
+    let error = tokio::time::timeout(Duration::from_secs(3600), run)
+        .await
+        .expect("The watchdog never tripped")
+        .unwrap_err();
+    assert_eq!(
+        error.inner().downcast_ref::<ErrorKind>(),
+        Some(&ErrorKind::Watchdog)
+    );
+}
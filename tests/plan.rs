@@ -0,0 +1,229 @@
+// The planner alone, from records made up on the spot
+use clouddns::api::models::DnsRecordUpdate;
+use clouddns::api::{DnsApiClient, MemoryDnsProvider};
+use clouddns::plan::{Action, DesiredRecord, UpdatePlanner};
+use clouddns::RecordContent;
+
+fn record(id: &str, name: &str, record_type: &str, content: &str) -> DnsRecordUpdate {
+    DnsRecordUpdate {
+        id: id.to_string(),
+        name: name.to_string(),
+        content: content.to_string(),
+        ttl: 300,
+        proxied: false,
+        r#type: record_type.to_string(),
+        modified_on: None,
+    }
+}
+
+fn a(name: &str, address: [u8; 4]) -> DesiredRecord {
+    DesiredRecord::new(name, RecordContent::A(address.into()))
+}
+
+#[test]
+fn a_matching_record_is_left_alone() {
+    let current = [record("1", "home.example.com", "A", "192.0.2.1")];
+    let plan = UpdatePlanner::new().plan(&[a("home.example.com", [192, 0, 2, 1])], &current);
+    assert_eq!(
+        plan.actions,
+        [Action::NoOp {
+            record: current[0].clone()
+        }]
+    );
+    assert!(!plan.has_changes());
+}
+
+#[test]
+fn forcing_updates_a_matching_record() {
+    let current = [record("1", "home.example.com", "A", "192.0.2.1")];
+    let plan = UpdatePlanner::new()
+        .force(true)
+        .plan(&[a("home.example.com", [192, 0, 2, 1])], &current);
+    assert!(matches!(&plan.actions[..], [Action::Update { changes, .. }] if changes.is_empty()));
+}
+
+#[test]
+fn lists_every_field_that_changes() {
+    let current = [record("1", "home.example.com", "A", "192.0.2.1")];
+    let desired = a("home.example.com", [192, 0, 2, 7])
+        .ttl(Some(60))
+        .proxied(Some(true));
+    let plan = UpdatePlanner::new().plan(&[desired], &current);
+    let [Action::Update {
+        changes,
+        ttl,
+        proxied,
+        ..
+    }] = &plan.actions[..]
+    else {
+        panic!("{:?}", plan);
+    };
+    assert_eq!(
+        changes,
+        &[
+            "192.0.2.1 → 192.0.2.7",
+            "ttl 300 → 60",
+            "proxied false → true"
+        ]
+    );
+    assert_eq!((*ttl, *proxied), (60, true));
+}
+
+#[test]
+fn unset_settings_keep_what_the_provider_has() {
+    let mut current = record("1", "home.example.com", "A", "192.0.2.1");
+    current.ttl = 1;
+    current.proxied = true;
+    let plan = UpdatePlanner::new().plan(&[a("home.example.com", [192, 0, 2, 1])], &[current]);
+    assert!(!plan.has_changes());
+}
+
+// The API reports a proxied record's origin address and an automatic TTL, while public DNS answers
+// with Cloudflare's edge addresses
+#[test]
+fn proxied_records_are_compared_with_what_the_api_reports() {
+    let mut current = [record("1", "home.example.com", "A", "192.0.2.1")];
+    current[0].ttl = 1;
+    current[0].proxied = true;
+    let desired = [a("home.example.com", [192, 0, 2, 1])
+        .ttl(Some(300))
+        .proxied(Some(true))];
+    let plan = UpdatePlanner::new().plan(&desired, &current);
+    assert!(!plan.has_changes(), "{:?}", plan);
+
+    current[0].content = "192.0.2.9".to_string();
+    let plan = UpdatePlanner::new().plan(&desired, &current);
+    let [Action::Update { changes, .. }] = &plan.actions[..] else {
+        panic!("{:?}", plan);
+    };
+    assert_eq!(changes, &["192.0.2.9 → 192.0.2.1"]);
+
+    // Unproxied, the TTL applies again
+    let [desired] = desired;
+    let plan = UpdatePlanner::new().plan(&[desired.proxied(Some(false))], &current);
+    let [Action::Update { changes, .. }] = &plan.actions[..] else {
+        panic!("{:?}", plan);
+    };
+    assert_eq!(
+        changes,
+        &[
+            "192.0.2.9 → 192.0.2.1",
+            "ttl 1 → 300",
+            "proxied true → false"
+        ]
+    );
+}
+
+#[test]
+fn addresses_are_compared_as_addresses() {
+    let current = [record("1", "home.example.com", "AAAA", "2001:0db8:0:0::1")];
+    let desired = DesiredRecord::new(
+        "home.example.com",
+        RecordContent::Aaaa("2001:db8::1".parse().unwrap()),
+    );
+    let plan = UpdatePlanner::new().plan(&[desired], &current);
+    assert!(!plan.has_changes(), "{:?}", plan);
+}
+
+#[test]
+fn a_missing_record_is_created() {
+    let current = [record("1", "home.example.com", "AAAA", "2001:db8::1")];
+    let plan = UpdatePlanner::new().plan(&[a("home.example.com", [192, 0, 2, 1])], &current);
+    assert_eq!(
+        plan.actions,
+        [Action::Create {
+            name: "home.example.com".to_string(),
+            content: RecordContent::A([192, 0, 2, 1].into()),
+            ttl: 1,
+            proxied: false,
+        }]
+    );
+}
+
+#[test]
+fn duplicates_are_deleted_keeping_the_right_one() {
+    let current = [
+        record("1", "home.example.com", "A", "192.0.2.1"),
+        record("2", "home.example.com", "A", "192.0.2.7"),
+        record("3", "other.example.com", "A", "192.0.2.1"),
+    ];
+    let plan = UpdatePlanner::new().plan(&[a("home.example.com", [192, 0, 2, 7])], &current);
+    assert_eq!(
+        plan.actions,
+        [
+            Action::NoOp {
+                record: current[1].clone()
+            },
+            Action::Delete {
+                record: current[0].clone()
+            },
+        ]
+    );
+    assert_eq!(
+        plan.to_string(),
+        "  home.example.com A\n- home.example.com A 192.0.2.1\n"
+    );
+}
+
+#[test]
+fn actions_survive_being_saved() {
+    let current = [record("1", "home.example.com", "A", "192.0.2.1")];
+    let desired = [
+        a("home.example.com", [192, 0, 2, 7]),
+        a("new.example.com", [192, 0, 2, 7]),
+    ];
+    let plan = UpdatePlanner::new().plan(&desired, &current);
+    let saved = serde_json::to_value(&plan.actions).unwrap();
+    assert_eq!(saved[0]["action"], "update");
+    assert_eq!(
+        saved[1]["content"],
+        serde_json::json!({ "type": "A", "content": "192.0.2.7" })
+    );
+    let loaded: Vec<Action> = serde_json::from_value(saved).unwrap();
+    assert_eq!(loaded, plan.actions);
+}
+
+#[tokio::test]
+async fn applying_a_plan_changes_the_provider() {
+    let provider = MemoryDnsProvider::new()
+        .zone("zone", "example.com")
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::A([192, 0, 2, 1].into()),
+        )
+        .record(
+            "zone",
+            "home.example.com",
+            RecordContent::A([192, 0, 2, 2].into()),
+        );
+    let current: Vec<DnsRecordUpdate> = provider
+        .list_records("zone")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(Into::into)
+        .collect();
+    let desired = [
+        a("home.example.com", [192, 0, 2, 7]),
+        a("nas.example.com", [192, 0, 2, 7]),
+    ];
+    let plan = UpdatePlanner::new().plan(&desired, &current);
+    assert_eq!(plan.changes().count(), 3);
+
+    let results = plan.apply(&provider, "zone").await;
+    assert!(results.iter().all(Result::is_ok));
+    let mut records: Vec<_> = provider
+        .records("zone")
+        .into_iter()
+        .map(|record| (record.name, record.content))
+        .collect();
+    records.sort();
+    assert_eq!(
+        records,
+        [
+            ("home.example.com".to_string(), "192.0.2.7".to_string()),
+            ("nas.example.com".to_string(), "192.0.2.7".to_string()),
+        ]
+    );
+}
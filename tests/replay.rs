@@ -0,0 +1,56 @@
+// The whole update, from the configuration to the records written, against a recorded cassette
+use clouddns::api::cassette;
+use clouddns::config::{Config, RecordType};
+use clouddns::{CloudflareDdns, DdnsEvent};
+
+const CONFIG: &str = r#"
+api_token = "replayed-token"
+
+[[zones]]
+id = "023e105f4ecef8ad9ca31a8372d0c353"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+record_type = "A"
+"#;
+
+#[tokio::test]
+async fn updates_a_record_from_a_cassette() {
+    cassette::replay(format!(
+        "{}/tests/fixtures/cassettes/update.json",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap();
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    let mut ddns = CloudflareDdns::builder(config)
+        .build()
+        .await
+        .unwrap()
+        .ip_override(&["203.0.113.7".parse().unwrap()])
+        .unwrap();
+    let mut events = ddns.subscribe();
+
+    ddns.run_once(false).await.unwrap();
+
+    let mut updated = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let DdnsEvent::RecordUpdated {
+            record,
+            record_type,
+            previous,
+            ..
+        } = event
+        {
+            updated.push((record, record_type, previous));
+        }
+    }
+    assert_eq!(
+        updated,
+        [(
+            "home.example.com".to_string(),
+            RecordType::A,
+            "198.51.100.4".to_string()
+        )]
+    );
+}
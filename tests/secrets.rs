@@ -0,0 +1,37 @@
+// A Docker secret is a file in the secrets directory, its name can't lead anywhere else
+use clouddns::config::{self, Config};
+
+const CONFIG: &str = r#"
+api_token = "secret://api_token"
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+"#;
+
+#[test]
+fn a_docker_secret_is_named_by_its_file_name() {
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    assert_eq!(config::problems(&config), Vec::<String>::new());
+}
+
+#[test]
+fn a_docker_secret_name_leading_out_of_the_secrets_directory_is_rejected() {
+    for name in [
+        "../../etc/shadow",
+        "..",
+        "/etc/shadow",
+        "nested/token",
+        "..\\token",
+        "",
+    ] {
+        let config = CONFIG.replace("api_token\"", &format!("{}\"", name.replace('\\', "\\\\")));
+        let config: Config = toml::from_str(&config).unwrap();
+        let problems = config::problems(&config);
+        assert_eq!(problems.len(), 1, "{}: {:?}", name, problems);
+        assert!(problems[0].contains("Docker secret name must be a plain file name"));
+    }
+}
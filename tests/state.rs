@@ -0,0 +1,186 @@
+use chrono::{Duration, SubsecRound, Utc};
+use clouddns::config::RecordType;
+use clouddns::state::{
+    ChangeReason, FileStore, HistoryEntry, HistoryQuery, MemoryStore, RecordHistory, State,
+    StateStore, Trigger,
+};
+use std::{fs, io::Write};
+
+#[test]
+fn the_history_is_appended_and_read_back_since_a_time() {
+    let dir = std::env::temp_dir().join(format!("clouddns-history-{}", std::process::id()));
+    let store = FileStore::new(dir.join("state.json"));
+    let now = Utc::now();
+    let entry = |hours_ago: i64, content: &str| HistoryEntry {
+        time: now - Duration::hours(hours_ago),
+        record: "home.example.com".to_string(),
+        r#type: RecordType::A,
+        previous: Some("203.0.113.1".to_string()),
+        content: Some(content.to_string()),
+        reason: ChangeReason::AddressChanged,
+        trigger: Trigger::Schedule,
+        error: None,
+    };
+
+    store.append_history(&[entry(48, "203.0.113.2")]).unwrap();
+    // A line cut short, as a crash would leave it
+    fs::OpenOptions::new()
+        .append(true)
+        .open(dir.join("state.history.jsonl"))
+        .unwrap()
+        .write_all(b"{\"time\":\n")
+        .unwrap();
+    store.append_history(&[entry(1, "203.0.113.3")]).unwrap();
+
+    let all = store.history(&HistoryQuery::default()).unwrap();
+    let recent = store
+        .history(&HistoryQuery {
+            since: Some(now - Duration::days(1)),
+            ..HistoryQuery::default()
+        })
+        .unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(all, vec![entry(48, "203.0.113.2"), entry(1, "203.0.113.3")]);
+    assert_eq!(recent, vec![entry(1, "203.0.113.3")]);
+}
+
+#[test]
+fn the_history_is_queried_by_domain_and_time_range() {
+    let dir = std::env::temp_dir().join(format!("clouddns-query-{}", std::process::id()));
+    let stores: Vec<Box<dyn StateStore>> = vec![
+        Box::new(MemoryStore::default()),
+        Box::new(FileStore::new(dir.join("state.json"))),
+    ];
+    #[cfg(feature = "sqlite")]
+    let stores = {
+        let mut stores = stores;
+        let store = clouddns::state::SqliteStore::open(dir.join("state.db"), "test").unwrap();
+        stores.push(Box::new(store));
+        stores
+    };
+
+    // Whole seconds, which every store keeps as they are
+    let now = Utc::now().trunc_subsecs(0);
+    let entry = |hours_ago: i64, record: &str, error: Option<&str>| HistoryEntry {
+        time: now - Duration::hours(hours_ago),
+        record: record.to_string(),
+        r#type: RecordType::A,
+        previous: Some("203.0.113.1".to_string()),
+        content: Some("203.0.113.2".to_string()),
+        reason: ChangeReason::AddressChanged,
+        trigger: Trigger::Schedule,
+        error: error.map(str::to_string),
+    };
+    let entries = [
+        entry(72, "example.com", None),
+        entry(48, "home.example.com", Some("403 Forbidden")),
+        entry(24, "home.example.com", None),
+        entry(12, "home.otherexample.com", None),
+        entry(1, "vpn.example.com", None),
+    ];
+    let query = |domain: &str, since: i64, until: i64| HistoryQuery {
+        domain: Some(domain.to_string()),
+        since: Some(now - Duration::hours(since)),
+        until: Some(now - Duration::hours(until)),
+        ..HistoryQuery::default()
+    };
+
+    for store in &stores {
+        store.append_history(&entries).unwrap();
+        let zone = store.history(&query("example.com", 100, 0)).unwrap();
+        let window = store.history(&query("example.com", 50, 2)).unwrap();
+        let host = store.history(&query("home.example.com", 100, 0)).unwrap();
+        let failed = store
+            .history(&HistoryQuery {
+                failed: true,
+                ..HistoryQuery::default()
+            })
+            .unwrap();
+        let last = store
+            .history(&HistoryQuery {
+                limit: Some(2),
+                ..HistoryQuery::default()
+            })
+            .unwrap();
+
+        assert_eq!(
+            zone,
+            [&entries[..3], &entries[4..]].concat(),
+            "not a suffix match"
+        );
+        assert_eq!(window, entries[1..3]);
+        assert_eq!(host, entries[1..3]);
+        assert_eq!(failed, entries[1..2]);
+        assert_eq!(last, entries[3..]);
+    }
+    drop(stores);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn a_pause_outlasts_a_restart() {
+    let dir = std::env::temp_dir().join(format!("clouddns-paused-{}", std::process::id()));
+    let stores: Vec<Box<dyn StateStore>> = vec![
+        Box::new(MemoryStore::default()),
+        Box::new(FileStore::new(dir.join("state.json"))),
+    ];
+    #[cfg(feature = "sqlite")]
+    let stores = {
+        let mut stores = stores;
+        let store = clouddns::state::SqliteStore::open(dir.join("state.db"), "test").unwrap();
+        stores.push(Box::new(store));
+        stores
+    };
+
+    for store in &stores {
+        assert!(!store.load().unwrap().paused);
+        let mut state = State {
+            paused: true,
+            ..State::default()
+        };
+        store.save(&state).unwrap();
+        assert!(store.load().unwrap().paused);
+        state.paused = false;
+        store.save(&state).unwrap();
+        assert!(!store.load().unwrap().paused);
+    }
+    drop(stores);
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+// Deleting a record once nothing asks for it depends on knowing it was created
+#[test]
+fn the_records_created_are_known_after_a_restart() {
+    let dir = std::env::temp_dir().join(format!("clouddns-created-{}", std::process::id()));
+    let stores: Vec<Box<dyn StateStore>> = vec![
+        Box::new(MemoryStore::default()),
+        Box::new(FileStore::new(dir.join("state.json"))),
+    ];
+    #[cfg(feature = "sqlite")]
+    let stores = {
+        let mut stores = stores;
+        let store = clouddns::state::SqliteStore::open(dir.join("state.db"), "test").unwrap();
+        stores.push(Box::new(store));
+        stores
+    };
+
+    let history = |created: bool| RecordHistory {
+        content: Some("203.0.113.1".to_string()),
+        last_success: None,
+        failures: 0,
+        created,
+    };
+    let mut state = State::default();
+    for (record, created) in [("app.example.com", true), ("www.example.com", false)] {
+        state
+            .records
+            .insert((record.to_string(), RecordType::A), history(created));
+    }
+    for store in &stores {
+        store.save(&state).unwrap();
+        assert_eq!(store.load().unwrap().records, state.records);
+    }
+    drop(stores);
+    fs::remove_dir_all(&dir).unwrap();
+}
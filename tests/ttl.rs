@@ -0,0 +1,34 @@
+// Cloudflare gives proxied records an automatic TTL, so theirs is ignored rather than rejected
+use clouddns::config::{self, Config};
+
+const CONFIG: &str = r#"
+api_token = "unused"
+
+[[zones]]
+id = "zone"
+name = "example.com"
+
+[[zones.domains]]
+records = ["home"]
+proxied = true
+ttl = 30
+"#;
+
+#[test]
+fn a_short_ttl_of_a_proxied_record_is_only_a_warning() {
+    let config: Config = toml::from_str(CONFIG).unwrap();
+    assert_eq!(config::problems(&config), Vec::<String>::new());
+    assert_eq!(
+        config.ttl_warnings(),
+        ["example.com is proxied, its TTL of 30 seconds is ignored"]
+    );
+}
+
+#[test]
+fn a_short_ttl_of_a_record_not_proxied_is_rejected() {
+    let config = CONFIG.replace("proxied = true", "proxied = false");
+    let config: Config = toml::from_str(&config).unwrap();
+    let problems = config::problems(&config);
+    assert_eq!(problems.len(), 1, "{:?}", problems);
+    assert!(problems[0].starts_with("zones[0].domains[0].ttl: Cloudflare doesn't take a TTL of 30"));
+}